@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+CREATE INDEX idx_messages_chat_id ON messages(chat_id);
+DROP INDEX IF EXISTS idx_messages_chat_created_id;
@@ -0,0 +1,4 @@
+-- 为聊天记录的游标分页创建复合索引
+-- 查询按 (created_at, id) 排序并以此作为游标，单列的 chat_id 索引被该索引的前缀覆盖
+CREATE INDEX idx_messages_chat_created_id ON messages(chat_id, created_at, id);
+DROP INDEX IF EXISTS idx_messages_chat_id;
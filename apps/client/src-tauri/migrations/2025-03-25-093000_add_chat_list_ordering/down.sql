@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_chats_last_message_time;
+ALTER TABLE chat_participants DROP COLUMN is_pinned;
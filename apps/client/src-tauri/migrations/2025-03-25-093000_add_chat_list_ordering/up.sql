@@ -0,0 +1,5 @@
+-- 聊天置顶标记，按参与者单独保存
+ALTER TABLE chat_participants ADD COLUMN is_pinned BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- 聊天列表按最后消息时间排序
+CREATE INDEX idx_chats_last_message_time ON chats(last_message_time);
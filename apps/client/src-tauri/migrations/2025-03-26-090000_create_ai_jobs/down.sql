@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_ai_jobs_status;
+DROP INDEX IF EXISTS idx_ai_jobs_chat_status_created;
+DROP TRIGGER IF EXISTS update_ai_jobs_updated_at;
+DROP TABLE IF EXISTS ai_jobs;
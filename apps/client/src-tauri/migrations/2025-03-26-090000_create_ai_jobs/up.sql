@@ -0,0 +1,29 @@
+-- AI 回复任务表，同一聊天内的任务按创建顺序依次处理
+CREATE TABLE ai_jobs (
+  id TEXT PRIMARY KEY NOT NULL,
+  chat_id TEXT NOT NULL,
+  agent_user_id TEXT NOT NULL,
+  trigger_message_id TEXT,
+  reply_message_id TEXT,
+  status TEXT NOT NULL DEFAULT 'pending', -- pending / processing / completed / error / cancelled
+  error_message TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  started_at TIMESTAMP,
+  finished_at TIMESTAMP,
+  FOREIGN KEY (chat_id) REFERENCES chats (id),
+  FOREIGN KEY (agent_user_id) REFERENCES users (id),
+  FOREIGN KEY (trigger_message_id) REFERENCES messages (id),
+  FOREIGN KEY (reply_message_id) REFERENCES messages (id)
+);
+
+CREATE TRIGGER update_ai_jobs_updated_at
+AFTER UPDATE ON ai_jobs
+BEGIN
+  UPDATE ai_jobs SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+-- 按聊天取下一个待处理任务
+CREATE INDEX idx_ai_jobs_chat_status_created ON ai_jobs(chat_id, status, created_at);
+-- 启动时按状态恢复任务
+CREATE INDEX idx_ai_jobs_status ON ai_jobs(status);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS update_chat_last_message_on_update;
+DROP INDEX IF EXISTS idx_messages_streaming;
+ALTER TABLE messages DROP COLUMN error_message;
+ALTER TABLE messages DROP COLUMN status;
@@ -0,0 +1,21 @@
+-- 消息状态，流式生成的 AI 回复在生成过程中即写入，结束后更新为最终状态
+ALTER TABLE messages ADD COLUMN status TEXT NOT NULL DEFAULT 'complete'; -- streaming / complete / error / cancelled
+ALTER TABLE messages ADD COLUMN error_message TEXT;
+
+-- 启动时查找未结束的流式消息
+CREATE INDEX idx_messages_streaming ON messages(status) WHERE status = 'streaming';
+
+-- 流式消息的内容在插入后持续更新，聊天的最后消息需要随之更新
+-- 只有聊天中最新的一条消息被更新时才同步
+CREATE TRIGGER update_chat_last_message_on_update
+AFTER UPDATE OF content ON messages
+WHEN NOT EXISTS (
+  SELECT 1 FROM messages
+  WHERE chat_id = NEW.chat_id
+    AND (created_at > NEW.created_at OR (created_at = NEW.created_at AND id > NEW.id))
+)
+BEGIN
+  UPDATE chats
+  SET last_message = NEW.content
+  WHERE id = NEW.chat_id;
+END;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE agents DROP COLUMN context_keep_first;
+ALTER TABLE agents DROP COLUMN context_strategy;
+ALTER TABLE agents DROP COLUMN context_window;
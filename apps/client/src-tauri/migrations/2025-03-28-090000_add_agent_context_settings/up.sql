@@ -0,0 +1,4 @@
+-- 组装对话上下文的设置
+ALTER TABLE agents ADD COLUMN context_window INTEGER; -- 模型上下文长度（token），为空时使用默认值
+ALTER TABLE agents ADD COLUMN context_strategy TEXT NOT NULL DEFAULT 'sliding_window'; -- sliding_window / keep_first_and_recent / summary_and_recent
+ALTER TABLE agents ADD COLUMN context_keep_first INTEGER; -- keep_first_and_recent 策略保留的最早消息条数
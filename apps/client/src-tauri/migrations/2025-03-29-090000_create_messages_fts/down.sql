@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS messages_fts_after_update;
+DROP TRIGGER IF EXISTS messages_fts_after_delete;
+DROP TRIGGER IF EXISTS messages_fts_after_insert;
+DROP TABLE IF EXISTS messages_fts;
+DROP VIEW IF EXISTS message_search_content;
+DROP TABLE IF EXISTS message_search_ids;
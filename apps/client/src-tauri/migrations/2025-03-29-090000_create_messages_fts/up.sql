@@ -0,0 +1,52 @@
+-- 消息全文检索索引
+-- 使用 trigram 分词器：中文没有空格分词，按三字符切分后可以检索任意位置的子串；
+-- 少于三个字符的检索词无法使用 MATCH，由查询方改用 LIKE（同样走该索引）
+
+-- messages 的主键为文本，rowid 在 VACUUM 时可能重新编号，索引改用这张映射表中稳定的整数主键
+CREATE TABLE message_search_ids (
+  id INTEGER PRIMARY KEY,
+  message_id TEXT NOT NULL UNIQUE
+);
+
+-- 索引的外部内容，按映射表的整数主键读取消息内容
+CREATE VIEW message_search_content AS
+SELECT message_search_ids.id AS id, messages.content AS content
+FROM message_search_ids
+JOIN messages ON messages.id = message_search_ids.message_id;
+
+CREATE VIRTUAL TABLE messages_fts USING fts5(
+  content,
+  content = 'message_search_content',
+  content_rowid = 'id',
+  tokenize = 'trigram'
+);
+
+-- 为已有消息建立索引
+INSERT INTO message_search_ids (message_id) SELECT id FROM messages;
+INSERT INTO messages_fts(messages_fts) VALUES ('rebuild');
+
+-- 通过触发器与 messages 表保持同步
+CREATE TRIGGER messages_fts_after_insert
+AFTER INSERT ON messages
+BEGIN
+  INSERT INTO message_search_ids (message_id) VALUES (NEW.id);
+  INSERT INTO messages_fts(rowid, content)
+  SELECT id, NEW.content FROM message_search_ids WHERE message_id = NEW.id;
+END;
+
+CREATE TRIGGER messages_fts_after_delete
+AFTER DELETE ON messages
+BEGIN
+  INSERT INTO messages_fts(messages_fts, rowid, content)
+  SELECT 'delete', id, OLD.content FROM message_search_ids WHERE message_id = OLD.id;
+  DELETE FROM message_search_ids WHERE message_id = OLD.id;
+END;
+
+CREATE TRIGGER messages_fts_after_update
+AFTER UPDATE OF content ON messages
+BEGIN
+  INSERT INTO messages_fts(messages_fts, rowid, content)
+  SELECT 'delete', id, OLD.content FROM message_search_ids WHERE message_id = OLD.id;
+  INSERT INTO messages_fts(rowid, content)
+  SELECT id, NEW.content FROM message_search_ids WHERE message_id = NEW.id;
+END;
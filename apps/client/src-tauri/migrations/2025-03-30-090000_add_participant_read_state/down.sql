@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+CREATE TRIGGER update_chat_participants_updated_at
+AFTER UPDATE ON chat_participants
+BEGIN
+  UPDATE chat_participants SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+ALTER TABLE chats ADD COLUMN unread_count INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE chat_participants DROP COLUMN last_read_at;
+ALTER TABLE chat_participants DROP COLUMN last_read_message_id;
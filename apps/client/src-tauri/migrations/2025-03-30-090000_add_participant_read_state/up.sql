@@ -0,0 +1,26 @@
+-- 按参与者记录已读位置，未读数按参与者计算，替代 chats.unread_count
+ALTER TABLE chat_participants ADD COLUMN last_read_message_id TEXT; -- 已读到的最后一条消息
+ALTER TABLE chat_participants ADD COLUMN last_read_at TIMESTAMP;    -- 该消息的创建时间，与消息 ID 一起作为已读游标
+
+-- chat_participants 表没有 updated_at 列，该触发器会使所有对该表的更新失败
+DROP TRIGGER IF EXISTS update_chat_participants_updated_at;
+
+-- 迁移原有未读数：按时间倒序排在第 unread_count + 1 位的消息作为所有参与者的已读位置；
+-- 未读数不少于消息总数时没有对应消息，已读位置保持为空，即全部未读
+CREATE TEMP TABLE chat_read_positions AS
+SELECT ranked.chat_id, ranked.id, ranked.created_at
+FROM (
+  SELECT id, chat_id, created_at,
+         ROW_NUMBER() OVER (PARTITION BY chat_id ORDER BY created_at DESC, id DESC) AS position
+  FROM messages
+) ranked
+JOIN chats c ON c.id = ranked.chat_id
+WHERE ranked.position = c.unread_count + 1;
+
+UPDATE chat_participants
+SET last_read_message_id = (SELECT p.id FROM chat_read_positions p WHERE p.chat_id = chat_participants.chat_id),
+    last_read_at = (SELECT p.created_at FROM chat_read_positions p WHERE p.chat_id = chat_participants.chat_id);
+
+DROP TABLE chat_read_positions;
+
+ALTER TABLE chats DROP COLUMN unread_count;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_messages_reply_to_id;
+ALTER TABLE messages DROP COLUMN reply_to_id;
@@ -0,0 +1,5 @@
+-- 消息可以回复（引用）同一聊天中的另一条消息
+ALTER TABLE messages ADD COLUMN reply_to_id TEXT REFERENCES messages (id) ON DELETE SET NULL;
+
+-- 加载某条消息下的回复串
+CREATE INDEX idx_messages_reply_to_id ON messages(reply_to_id) WHERE reply_to_id IS NOT NULL;
@@ -0,0 +1,29 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER update_chat_last_message_on_update;
+
+ALTER TABLE ai_jobs DROP COLUMN regenerated_message_id;
+
+-- 只保留当前分支上的消息
+UPDATE ai_jobs SET trigger_message_id = NULL
+WHERE trigger_message_id IN (SELECT id FROM messages WHERE NOT is_active);
+UPDATE ai_jobs SET reply_message_id = NULL
+WHERE reply_message_id IN (SELECT id FROM messages WHERE NOT is_active);
+DELETE FROM messages WHERE NOT is_active;
+
+CREATE TRIGGER update_chat_last_message_on_update
+AFTER UPDATE OF content ON messages
+WHEN NOT EXISTS (
+  SELECT 1 FROM messages
+  WHERE chat_id = NEW.chat_id
+    AND (created_at > NEW.created_at OR (created_at = NEW.created_at AND id > NEW.id))
+)
+BEGIN
+  UPDATE chats
+  SET last_message = NEW.content
+  WHERE id = NEW.chat_id;
+END;
+
+DROP INDEX IF EXISTS idx_messages_parent_id;
+ALTER TABLE messages DROP COLUMN is_active;
+ALTER TABLE messages DROP COLUMN active_child_id;
+ALTER TABLE messages DROP COLUMN parent_id;
@@ -0,0 +1,47 @@
+-- 消息组成树：每条消息指向它所接续的上一条消息，重新生成的回复与原回复互为兄弟节点
+ALTER TABLE messages ADD COLUMN parent_id TEXT REFERENCES messages (id) ON DELETE SET NULL;       -- 上一条消息，聊天的第一条消息为空
+ALTER TABLE messages ADD COLUMN active_child_id TEXT REFERENCES messages (id) ON DELETE SET NULL; -- 当前选中的下一条消息
+ALTER TABLE messages ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT 1;                             -- 是否在当前选中的分支上
+
+CREATE INDEX idx_messages_parent_id ON messages(parent_id);
+
+-- 已有消息按时间顺序连成一条分支；迁移期间暂停更新时间触发器，保留原有的 updated_at
+DROP TRIGGER update_messages_updated_at;
+
+CREATE TEMP TABLE message_chain AS
+SELECT id,
+       LAG(id) OVER (PARTITION BY chat_id ORDER BY created_at, id) AS parent_id,
+       LEAD(id) OVER (PARTITION BY chat_id ORDER BY created_at, id) AS child_id
+FROM messages;
+
+UPDATE messages
+SET parent_id = (SELECT c.parent_id FROM message_chain c WHERE c.id = messages.id),
+    active_child_id = (SELECT c.child_id FROM message_chain c WHERE c.id = messages.id);
+
+DROP TABLE message_chain;
+
+CREATE TRIGGER update_messages_updated_at
+AFTER UPDATE ON messages
+BEGIN
+  UPDATE messages SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+-- 只有当前分支上最新的一条消息被更新时才同步聊天的最后消息
+DROP TRIGGER update_chat_last_message_on_update;
+
+CREATE TRIGGER update_chat_last_message_on_update
+AFTER UPDATE OF content ON messages
+WHEN NEW.is_active AND NOT EXISTS (
+  SELECT 1 FROM messages
+  WHERE chat_id = NEW.chat_id
+    AND is_active
+    AND (created_at > NEW.created_at OR (created_at = NEW.created_at AND id > NEW.id))
+)
+BEGIN
+  UPDATE chats
+  SET last_message = NEW.content
+  WHERE id = NEW.chat_id;
+END;
+
+-- 重新生成回复的任务记录被重新生成的原回复，新回复接在原回复的上一条消息之后，而不是当前分支的最后一条消息之后
+ALTER TABLE ai_jobs ADD COLUMN regenerated_message_id TEXT;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_chats_forked_from_message_id;
+DROP INDEX IF EXISTS idx_chats_forked_from_chat_id;
+ALTER TABLE chats DROP COLUMN forked_from_message_id;
+ALTER TABLE chats DROP COLUMN forked_from_chat_id;
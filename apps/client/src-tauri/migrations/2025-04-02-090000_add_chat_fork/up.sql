@@ -0,0 +1,7 @@
+-- 从某条消息分出的聊天记录来源聊天和分出位置，来源被删除后置空
+ALTER TABLE chats ADD COLUMN forked_from_chat_id TEXT REFERENCES chats (id) ON DELETE SET NULL;
+ALTER TABLE chats ADD COLUMN forked_from_message_id TEXT REFERENCES messages (id) ON DELETE SET NULL;
+
+-- 删除消息或聊天时查找引用它的分支聊天
+CREATE INDEX idx_chats_forked_from_chat_id ON chats(forked_from_chat_id) WHERE forked_from_chat_id IS NOT NULL;
+CREATE INDEX idx_chats_forked_from_message_id ON chats(forked_from_message_id) WHERE forked_from_message_id IS NOT NULL;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_message_revisions_message_created;
+DROP TABLE IF EXISTS message_revisions;
@@ -0,0 +1,13 @@
+-- 消息修订记录表，每次修改消息内容时保存修改前的内容，消息删除时一并删除
+CREATE TABLE message_revisions (
+  id TEXT PRIMARY KEY NOT NULL,
+  message_id TEXT NOT NULL,
+  content TEXT NOT NULL,   -- 被替换掉的内容
+  editor_id TEXT,          -- 进行这次修改的用户
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP, -- 修改时间
+  FOREIGN KEY (message_id) REFERENCES messages (id) ON DELETE CASCADE,
+  FOREIGN KEY (editor_id) REFERENCES users (id) ON DELETE SET NULL
+);
+
+-- 按时间加载消息的修订记录
+CREATE INDEX idx_message_revisions_message_created ON message_revisions(message_id, created_at);
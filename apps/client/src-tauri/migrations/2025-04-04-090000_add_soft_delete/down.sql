@@ -0,0 +1,22 @@
+-- This file should undo anything in `up.sql`
+-- 回收站中的消息和聊天恢复为正常状态
+DROP TRIGGER IF EXISTS update_chat_last_message_on_update;
+
+CREATE TRIGGER update_chat_last_message_on_update
+AFTER UPDATE OF content ON messages
+WHEN NEW.is_active AND NOT EXISTS (
+  SELECT 1 FROM messages
+  WHERE chat_id = NEW.chat_id
+    AND is_active
+    AND (created_at > NEW.created_at OR (created_at = NEW.created_at AND id > NEW.id))
+)
+BEGIN
+  UPDATE chats
+  SET last_message = NEW.content
+  WHERE id = NEW.chat_id;
+END;
+
+DROP INDEX IF EXISTS idx_chats_deleted_at;
+DROP INDEX IF EXISTS idx_messages_deleted_at;
+ALTER TABLE chats DROP COLUMN deleted_at;
+ALTER TABLE messages DROP COLUMN deleted_at;
@@ -0,0 +1,25 @@
+-- 消息和聊天改为软删除：删除时记录删除时间并进入回收站，超过保留期限后再彻底清除
+ALTER TABLE messages ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE chats ADD COLUMN deleted_at TIMESTAMP;
+
+-- 查找回收站中的内容和超过保留期限的内容
+CREATE INDEX idx_messages_deleted_at ON messages(deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX idx_chats_deleted_at ON chats(deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- 聊天的最后消息只取未删除的消息
+DROP TRIGGER update_chat_last_message_on_update;
+
+CREATE TRIGGER update_chat_last_message_on_update
+AFTER UPDATE OF content ON messages
+WHEN NEW.is_active AND NEW.deleted_at IS NULL AND NOT EXISTS (
+  SELECT 1 FROM messages
+  WHERE chat_id = NEW.chat_id
+    AND is_active
+    AND deleted_at IS NULL
+    AND (created_at > NEW.created_at OR (created_at = NEW.created_at AND id > NEW.id))
+)
+BEGIN
+  UPDATE chats
+  SET last_message = NEW.content
+  WHERE id = NEW.chat_id;
+END;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE chat_participants DROP COLUMN display_name;
+ALTER TABLE chat_participants DROP COLUMN sort_order;
+ALTER TABLE chat_participants DROP COLUMN is_archived;
+ALTER TABLE chat_participants DROP COLUMN is_muted;
@@ -0,0 +1,5 @@
+-- 聊天设置，按参与者单独保存，与已有的 is_pinned 一起构成用户对聊天的个人设置
+ALTER TABLE chat_participants ADD COLUMN is_muted BOOLEAN NOT NULL DEFAULT FALSE;    -- 免打扰，未读消息不计入未读总数
+ALTER TABLE chat_participants ADD COLUMN is_archived BOOLEAN NOT NULL DEFAULT FALSE; -- 归档，默认不在聊天列表中显示
+ALTER TABLE chat_participants ADD COLUMN sort_order INTEGER;                         -- 自定义排序，数值小的在前，为空时排在设置了排序的聊天之后
+ALTER TABLE chat_participants ADD COLUMN display_name TEXT;                          -- 自定义聊天名称，为空时使用默认名称
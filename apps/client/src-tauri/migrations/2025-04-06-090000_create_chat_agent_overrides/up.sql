@@ -0,0 +1,17 @@
+-- 聊天内的 AI 配置覆盖表，按（聊天，AI 用户）保存，为空的列沿用 agents 表中的配置
+CREATE TABLE chat_agent_overrides (
+  id TEXT PRIMARY KEY NOT NULL,
+  chat_id TEXT NOT NULL,
+  agent_user_id TEXT NOT NULL,
+  system_prompt TEXT,
+  model_name TEXT,
+  temperature REAL,
+  top_p REAL,
+  max_tokens INTEGER,
+  stop_sequences TEXT, -- JSON 数组格式的停止序列，空数组表示在该聊天中不使用停止序列
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (chat_id) REFERENCES chats (id) ON DELETE CASCADE,
+  FOREIGN KEY (agent_user_id) REFERENCES users (id) ON DELETE CASCADE,
+  UNIQUE (chat_id, agent_user_id)
+);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE chats DROP COLUMN max_ai_turns;
+ALTER TABLE chats DROP COLUMN moderator_user_id;
+ALTER TABLE chats DROP COLUMN reply_policy;
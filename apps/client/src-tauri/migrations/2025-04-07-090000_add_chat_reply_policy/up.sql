@@ -0,0 +1,6 @@
+-- 聊天中由哪些 AI 参与者回复：mention / round_robin / everyone / moderator
+ALTER TABLE chats ADD COLUMN reply_policy TEXT NOT NULL DEFAULT 'everyone';
+-- moderator 策略下挑选回复者的 AI 参与者，该用户被删除后置空
+ALTER TABLE chats ADD COLUMN moderator_user_id TEXT REFERENCES users (id) ON DELETE SET NULL;
+-- 用户发言后 AI 之间最多连续回复的条数，防止 AI 之间无休止地相互回复
+ALTER TABLE chats ADD COLUMN max_ai_turns INTEGER NOT NULL DEFAULT 6;
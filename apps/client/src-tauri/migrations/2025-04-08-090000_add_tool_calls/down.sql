@@ -0,0 +1,34 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS update_chat_last_message_on_update;
+
+CREATE TRIGGER update_chat_last_message_on_update
+AFTER UPDATE OF content ON messages
+WHEN NEW.is_active AND NEW.deleted_at IS NULL AND NOT EXISTS (
+  SELECT 1 FROM messages
+  WHERE chat_id = NEW.chat_id
+    AND is_active
+    AND deleted_at IS NULL
+    AND (created_at > NEW.created_at OR (created_at = NEW.created_at AND id > NEW.id))
+)
+BEGIN
+  UPDATE chats
+  SET last_message = NEW.content
+  WHERE id = NEW.chat_id;
+END;
+
+DROP TRIGGER IF EXISTS update_chat_last_message;
+
+CREATE TRIGGER update_chat_last_message
+AFTER INSERT ON messages
+BEGIN
+  UPDATE chats
+  SET last_message = NEW.content,
+      last_message_time = NEW.created_at
+  WHERE id = NEW.chat_id;
+END;
+
+ALTER TABLE agents DROP COLUMN max_tool_iterations;
+ALTER TABLE agents DROP COLUMN tools;
+ALTER TABLE messages DROP COLUMN tool_call_id;
+ALTER TABLE messages DROP COLUMN tool_calls;
+ALTER TABLE messages DROP COLUMN kind;
@@ -0,0 +1,42 @@
+-- 消息类型：text 为普通消息，tool_call 为 AI 请求调用工具，tool_result 为工具的返回结果
+ALTER TABLE messages ADD COLUMN kind TEXT NOT NULL DEFAULT 'text';
+-- tool_call 消息请求调用的工具，JSON 数组格式：[{ "id", "name", "arguments" }]
+ALTER TABLE messages ADD COLUMN tool_calls TEXT;
+-- tool_result 消息对应的工具调用 ID
+ALTER TABLE messages ADD COLUMN tool_call_id TEXT;
+
+-- Agent 可以调用的内置工具，JSON 数组格式的工具名称，为空时不使用工具
+ALTER TABLE agents ADD COLUMN tools TEXT;
+-- 一次回复中最多调用工具的轮数，为空时使用默认值
+ALTER TABLE agents ADD COLUMN max_tool_iterations INTEGER;
+
+-- 聊天的最后消息只取普通消息，工具调用和工具结果不显示在聊天列表中
+DROP TRIGGER update_chat_last_message;
+
+CREATE TRIGGER update_chat_last_message
+AFTER INSERT ON messages
+WHEN NEW.kind = 'text'
+BEGIN
+  UPDATE chats
+  SET last_message = NEW.content,
+      last_message_time = NEW.created_at
+  WHERE id = NEW.chat_id;
+END;
+
+DROP TRIGGER update_chat_last_message_on_update;
+
+CREATE TRIGGER update_chat_last_message_on_update
+AFTER UPDATE OF content ON messages
+WHEN NEW.kind = 'text' AND NEW.is_active AND NEW.deleted_at IS NULL AND NOT EXISTS (
+  SELECT 1 FROM messages
+  WHERE chat_id = NEW.chat_id
+    AND kind = 'text'
+    AND is_active
+    AND deleted_at IS NULL
+    AND (created_at > NEW.created_at OR (created_at = NEW.created_at AND id > NEW.id))
+)
+BEGIN
+  UPDATE chats
+  SET last_message = NEW.content
+  WHERE id = NEW.chat_id;
+END;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_agent_mcp_servers_server_id;
+DROP TABLE IF EXISTS agent_mcp_servers;
+DROP TABLE IF EXISTS mcp_servers;
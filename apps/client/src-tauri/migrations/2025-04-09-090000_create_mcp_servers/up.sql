@@ -0,0 +1,25 @@
+-- 本地 MCP 服务器配置，启用的服务器以子进程方式启动，通过标准输入输出收发 JSON-RPC 消息
+CREATE TABLE mcp_servers (
+  id TEXT PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL UNIQUE, -- 服务器名称，作为其工具名称的前缀
+  command TEXT NOT NULL,
+  args TEXT NOT NULL DEFAULT '[]', -- JSON 数组格式的启动参数
+  env TEXT NOT NULL DEFAULT '{}', -- JSON 对象格式的环境变量
+  cwd TEXT, -- 工作目录，为空时使用应用的工作目录
+  enabled BOOLEAN NOT NULL DEFAULT 1,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- AI 用户启用的 MCP 服务器，回复时可调用这些服务器提供的工具
+CREATE TABLE agent_mcp_servers (
+  id TEXT PRIMARY KEY NOT NULL,
+  agent_user_id TEXT NOT NULL,
+  server_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (agent_user_id) REFERENCES users (id) ON DELETE CASCADE,
+  FOREIGN KEY (server_id) REFERENCES mcp_servers (id) ON DELETE CASCADE,
+  UNIQUE (agent_user_id, server_id)
+);
+
+CREATE INDEX idx_agent_mcp_servers_server_id ON agent_mcp_servers (server_id);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE agents DROP COLUMN embedding_model;
+DROP INDEX IF EXISTS idx_agent_knowledge_resources_resource_id;
+DROP TABLE IF EXISTS agent_knowledge_resources;
+DROP INDEX IF EXISTS idx_resource_chunks_resource_model;
+DROP TABLE IF EXISTS resource_chunks;
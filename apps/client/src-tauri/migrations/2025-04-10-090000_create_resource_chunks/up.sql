@@ -0,0 +1,31 @@
+-- 文本资源切分后的片段及其向量，用于按语义检索知识库
+CREATE TABLE resource_chunks (
+  id TEXT PRIMARY KEY NOT NULL,
+  resource_id TEXT NOT NULL,
+  embedding_provider TEXT NOT NULL, -- 生成向量的服务商，不同模型的向量不能混用
+  embedding_model TEXT NOT NULL,
+  chunk_index INTEGER NOT NULL, -- 片段在资源中的顺序
+  content TEXT NOT NULL,
+  embedding BLOB NOT NULL, -- 小端序 f32 数组
+  content_hash TEXT NOT NULL, -- 切分时整个资源内容的哈希，资源内容变化后重新切分
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (resource_id) REFERENCES resources (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_resource_chunks_resource_model ON resource_chunks (resource_id, embedding_provider, embedding_model);
+
+-- AI 用户关联的知识库资源，回复时检索其中与对话相关的片段
+CREATE TABLE agent_knowledge_resources (
+  id TEXT PRIMARY KEY NOT NULL,
+  agent_user_id TEXT NOT NULL,
+  resource_id TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (agent_user_id) REFERENCES users (id) ON DELETE CASCADE,
+  FOREIGN KEY (resource_id) REFERENCES resources (id) ON DELETE CASCADE,
+  UNIQUE (agent_user_id, resource_id)
+);
+
+CREATE INDEX idx_agent_knowledge_resources_resource_id ON agent_knowledge_resources (resource_id);
+
+-- 生成知识库向量使用的模型，使用 Agent 的服务商，为空时使用默认模型
+ALTER TABLE agents ADD COLUMN embedding_model TEXT;
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE agents DROP COLUMN memory_extraction_prompt;
+ALTER TABLE agents DROP COLUMN memory_enabled;
+ALTER TABLE chat_participants DROP COLUMN memory_extracted_at;
+ALTER TABLE chat_participants DROP COLUMN memory_extracted_message_id;
+DROP INDEX IF EXISTS idx_agent_memories_agent_user_id;
+DROP TABLE IF EXISTS agent_memories;
@@ -0,0 +1,26 @@
+-- AI 用户的长期记忆，从对话中提取的事实，回复时检索相关的记忆放入系统提示词
+CREATE TABLE agent_memories (
+  id TEXT PRIMARY KEY NOT NULL,
+  agent_user_id TEXT NOT NULL,
+  content TEXT NOT NULL,
+  source_chat_id TEXT, -- 提取自的聊天，手动添加或聊天删除后为空
+  source_message_id TEXT, -- 提取自的消息
+  importance INTEGER NOT NULL DEFAULT 3, -- 重要程度 1-5
+  is_pinned BOOLEAN NOT NULL DEFAULT 0, -- 置顶的记忆总是放入上下文，也不会被自动清理
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (agent_user_id) REFERENCES users (id) ON DELETE CASCADE,
+  FOREIGN KEY (source_chat_id) REFERENCES chats (id) ON DELETE SET NULL,
+  FOREIGN KEY (source_message_id) REFERENCES messages (id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_agent_memories_agent_user_id ON agent_memories (agent_user_id);
+
+-- AI 用户在聊天中已提取记忆的位置，按（创建时间，消息ID）比较
+ALTER TABLE chat_participants ADD COLUMN memory_extracted_message_id TEXT;
+ALTER TABLE chat_participants ADD COLUMN memory_extracted_at TIMESTAMP;
+
+-- 是否在对话后自动提取记忆并在回复时使用，默认关闭
+ALTER TABLE agents ADD COLUMN memory_enabled BOOLEAN NOT NULL DEFAULT 0;
+-- 提取记忆使用的提示词，为空时使用默认提示词
+ALTER TABLE agents ADD COLUMN memory_extraction_prompt TEXT;
@@ -0,0 +1,17 @@
+-- 聊天类型：direct 为两个用户之间的单聊，group 为群聊
+ALTER TABLE chats ADD COLUMN chat_type TEXT NOT NULL DEFAULT 'group';
+
+-- 已有的单聊：未分出、参与者恰好为两人，且与单聊的创建方式一致，以其中一人的名称命名、此人是另一人的联系人；
+-- 只有一名其他成员的群聊名称由用户填写，仍为群聊
+UPDATE chats SET chat_type = 'direct'
+WHERE forked_from_chat_id IS NULL
+  AND (SELECT COUNT(*) FROM chat_participants WHERE chat_participants.chat_id = chats.id) = 2
+  AND EXISTS (
+    SELECT 1
+    FROM chat_participants owner
+    JOIN chat_participants contact ON contact.chat_id = owner.chat_id AND contact.user_id <> owner.user_id
+    JOIN users ON users.id = contact.user_id
+    JOIN user_contacts ON user_contacts.user_id = owner.user_id AND user_contacts.contact_id = contact.user_id
+    WHERE owner.chat_id = chats.id
+      AND users.name = chats.name
+  );
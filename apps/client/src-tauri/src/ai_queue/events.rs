@@ -0,0 +1,78 @@
+// AI 回复任务的事件推送
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::models::AiJob;
+
+// 任务状态变化事件名
+pub const AI_JOB_STATUS_EVENT: &str = "ai-job-status";
+// 任务增量输出事件名
+pub const AI_JOB_DELTA_EVENT: &str = "ai-job-delta";
+
+// 任务状态变化
+#[derive(Debug, Clone, Serialize)]
+pub struct AiJobStatusEvent {
+    pub job_id: String,
+    pub chat_id: String,
+    pub agent_user_id: String,
+    pub status: String,
+    pub error_message: Option<String>,
+    pub reply_message_id: Option<String>,
+}
+
+impl From<&AiJob> for AiJobStatusEvent {
+    fn from(job: &AiJob) -> Self {
+        Self {
+            job_id: job.id.clone(),
+            chat_id: job.chat_id.clone(),
+            agent_user_id: job.agent_user_id.clone(),
+            status: job.status.clone(),
+            error_message: job.error_message.clone(),
+            reply_message_id: job.reply_message_id.clone(),
+        }
+    }
+}
+
+// 模型输出的增量文本
+#[derive(Debug, Clone, Serialize)]
+pub struct AiJobDeltaEvent {
+    pub job_id: String,
+    pub chat_id: String,
+    pub agent_user_id: String,
+    pub message_id: String,
+    pub content: String,
+}
+
+/// 任务事件的接收方
+///
+/// 桌面应用中推送为 Tauri 事件，没有窗口的运行环境可替换为其他实现
+pub trait JobEventSink: Send + Sync {
+    fn status_changed(&self, event: AiJobStatusEvent);
+
+    fn delta(&self, event: AiJobDeltaEvent);
+}
+
+// 通过 Tauri 事件推送给前端
+pub struct TauriJobEventSink {
+    app: AppHandle,
+}
+
+impl TauriJobEventSink {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+impl JobEventSink for TauriJobEventSink {
+    fn status_changed(&self, event: AiJobStatusEvent) {
+        if let Err(e) = self.app.emit(AI_JOB_STATUS_EVENT, event) {
+            eprintln!("推送AI任务状态失败: {}", e);
+        }
+    }
+
+    fn delta(&self, event: AiJobDeltaEvent) {
+        if let Err(e) = self.app.emit(AI_JOB_DELTA_EVENT, event) {
+            eprintln!("推送AI任务输出失败: {}", e);
+        }
+    }
+}
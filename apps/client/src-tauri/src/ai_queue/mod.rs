@@ -0,0 +1,610 @@
+// AI 回复任务队列
+//
+// 任务持久化在 ai_jobs 表中：同一聊天内按创建顺序逐个处理，不同聊天之间并行处理。
+// 应用退出时正在处理的任务无法继续，下次启动时标记为失败（已生成的回复内容保留），待处理的任务则继续执行。
+// MCP 服务器等其他进程只向 ai_jobs 表写入待处理任务，由应用定期检查后处理
+pub mod events;
+mod orchestrator;
+mod runner;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use futures_util::future::{AbortHandle, Abortable};
+
+use crate::db::DbPool;
+use crate::llm::ProviderRegistry;
+use crate::models::{AgentMemory, AiJob, AiJobStatus, MessageStatus};
+use crate::repositories::ai_job_repository::AiJobRepository;
+use crate::services::agent_service::AgentService;
+use crate::services::ai_job_service::AiJobService;
+use crate::services::group_chat_service::GroupChatService;
+use crate::services::memory_service::MemoryService;
+use crate::services::message_service::MessageService;
+use crate::services::ServiceResult;
+use crate::mcp::McpManager;
+use crate::tools::ToolRegistry;
+use events::{AiJobStatusEvent, JobEventSink};
+use runner::ReplyProgress;
+
+// 启动时处理中任务的错误信息
+const INTERRUPTED_MESSAGE: &str = "应用退出导致任务中断";
+// 启动时未结束的流式消息的错误信息
+const INTERRUPTED_REPLY_MESSAGE: &str = "应用退出导致回复中断";
+// 检查其他进程加入的待处理任务的间隔
+const PENDING_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+// 按 (聊天ID, AI用户ID) 依次提取记忆的锁
+type MemoryExtractionLock = Arc<tokio::sync::Mutex<()>>;
+
+#[derive(Clone)]
+pub struct AiJobQueue {
+    inner: Arc<QueueInner>,
+}
+
+struct QueueInner {
+    pool: DbPool,
+    providers: Arc<ProviderRegistry>,
+    tools: Arc<ToolRegistry>,
+    mcp: Arc<McpManager>,
+    sink: Arc<dyn JobEventSink>,
+    // 应用资源目录，检索知识库时读取文本资源
+    app_resource_path: PathBuf,
+    // 正在运行处理循环的聊天，值表示处理循环运行期间是否有新任务加入
+    workers: Mutex<HashMap<String, bool>>,
+    // 正在处理的任务，用于按任务 ID 中断
+    running: Mutex<HashMap<String, AbortHandle>>,
+    // summary_and_recent 策略下生成的摘要，按 (聊天ID, AI用户ID) 只保留最近一次
+    summaries: Mutex<HashMap<(String, String), runner::CachedSummary>>,
+    // 记忆提取的锁，按 (聊天ID, AI用户ID) 依次提取，避免同一批消息被并发提取两次
+    memory_extractions: Mutex<HashMap<(String, String), MemoryExtractionLock>>,
+}
+
+impl AiJobQueue {
+    pub fn new(
+        pool: DbPool,
+        providers: Arc<ProviderRegistry>,
+        tools: Arc<ToolRegistry>,
+        mcp: Arc<McpManager>,
+        sink: Arc<dyn JobEventSink>,
+        app_resource_path: PathBuf,
+    ) -> Self {
+        Self {
+            inner: Arc::new(QueueInner {
+                pool,
+                providers,
+                tools,
+                mcp,
+                sink,
+                app_resource_path,
+                workers: Mutex::new(HashMap::new()),
+                running: Mutex::new(HashMap::new()),
+                summaries: Mutex::new(HashMap::new()),
+                memory_extractions: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    // 恢复上次退出时遗留的任务
+    //
+    // 未结束的流式消息和处理中的任务标记为失败，仍有待处理任务的聊天重新开始处理
+    pub fn recover(&self) -> ServiceResult<()> {
+        MessageService::reconcile_streaming_messages(&self.inner.pool, INTERRUPTED_REPLY_MESSAGE)?;
+
+        let interrupted = AiJobRepository::fail_all_processing(&self.inner.pool, INTERRUPTED_MESSAGE)
+            .map_err(|e| anyhow!("恢复AI回复任务失败: {}", e))?;
+        for job in &interrupted {
+            self.inner.sink.status_changed(AiJobStatusEvent::from(job));
+        }
+
+        self.wake_pending()
+    }
+
+    // 为所有有待处理任务的聊天启动处理循环
+    pub fn wake_pending(&self) -> ServiceResult<()> {
+        let chat_ids = AiJobRepository::get_chat_ids_with_pending(&self.inner.pool)
+            .map_err(|e| anyhow!("获取待处理AI任务失败: {}", e))?;
+        for chat_id in chat_ids {
+            self.wake(&chat_id);
+        }
+        Ok(())
+    }
+
+    // 定期处理其他进程（如 MCP 服务器）加入的待处理任务
+    pub async fn run_pending_watch(self) {
+        loop {
+            tokio::time::sleep(PENDING_WATCH_INTERVAL).await;
+            if let Err(e) = self.wake_pending() {
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    // 加入一个 AI 回复任务
+    pub fn enqueue(
+        &self,
+        chat_id: &str,
+        agent_user_id: &str,
+        trigger_message_id: Option<&str>,
+    ) -> ServiceResult<AiJob> {
+        self.add_job(chat_id, agent_user_id, trigger_message_id, None)
+    }
+
+    // 创建任务并通知前端、启动处理循环，regenerated_message_id 不为空时为重新生成回复的任务
+    fn add_job(
+        &self,
+        chat_id: &str,
+        agent_user_id: &str,
+        trigger_message_id: Option<&str>,
+        regenerated_message_id: Option<&str>,
+    ) -> ServiceResult<AiJob> {
+        let job = AiJobService::create_job(
+            &self.inner.pool,
+            chat_id,
+            agent_user_id,
+            trigger_message_id,
+            regenerated_message_id,
+        )?;
+        self.inner.sink.status_changed(AiJobStatusEvent::from(&job));
+        self.wake(chat_id);
+        Ok(job)
+    }
+
+    // 按聊天的回复策略为消息安排 AI 回复，返回加入的任务
+    //
+    // 回复者按顺序依次回复，后回复的 AI 能看到先回复的内容；AI 的回复完成后会再次安排，
+    // 以便其中 @ 到的其他 AI 参与者继续回复，直到达到聊天的连续回复上限
+    pub async fn dispatch(&self, message_id: &str) -> ServiceResult<Vec<AiJob>> {
+        let plan = GroupChatService::plan_replies(&self.inner.pool, message_id)?;
+        let chat_id = plan.chat_id.clone();
+        let speakers = orchestrator::select_speakers(&self.inner, plan).await;
+
+        speakers
+            .iter()
+            .map(|speaker| self.enqueue(&chat_id, &speaker.id, Some(message_id)))
+            .collect()
+    }
+
+    // 重新生成 AI 回复
+    //
+    // 原回复保留为兄弟节点，新回复由同一 AI 用户接在原回复的上一条消息之后生成，即使任务开始前聊天中又有新消息；
+    // 加入任务失败时恢复原回复所在的分支
+    pub fn regenerate(&self, message_id: &str) -> ServiceResult<AiJob> {
+        let message = MessageService::start_regeneration(&self.inner.pool, message_id)?;
+        self.add_job(&message.chat_id, &message.sender_id, message.parent_id.as_deref(), Some(&message.id))
+            .inspect_err(|_| {
+                if let Err(e) = MessageService::switch_branch(&self.inner.pool, &message.id) {
+                    eprintln!("恢复消息分支失败 message_id={}: {}", message.id, e);
+                }
+            })
+    }
+
+    // 取消任务，待处理的任务直接取消，处理中的任务中断生成
+    //
+    // 返回是否取消成功，已结束的任务返回 false
+    pub fn cancel(&self, job_id: &str) -> ServiceResult<bool> {
+        let cancelled = AiJobRepository::cancel_pending(&self.inner.pool, job_id)
+            .map_err(|e| anyhow!("取消AI回复任务失败: {}", e))?;
+        if cancelled {
+            let job = AiJobService::get_job(&self.inner.pool, job_id)?;
+            self.inner.sink.status_changed(AiJobStatusEvent::from(&job));
+            return Ok(true);
+        }
+
+        // 处理中的任务由处理循环在中断后写入取消状态
+        let running = self.inner.running.lock().expect("无法获取任务注册表锁");
+        match running.get(job_id) {
+            Some(handle) => {
+                handle.abort();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // 取消聊天中所有未结束的任务，返回取消的任务数
+    pub fn cancel_chat(&self, chat_id: &str) -> ServiceResult<usize> {
+        let jobs = AiJobService::get_chat_jobs(&self.inner.pool, chat_id, true)?;
+        let mut count = 0;
+        // 先取消排在后面的任务，避免当前任务中断后处理循环接着开始下一个
+        for job in jobs.iter().rev() {
+            if self.cancel(&job.id)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    // 立即从聊天中提取 AI 用户的长期记忆，返回新提取的记忆
+    //
+    // 不要求 AI 用户已开启记忆，也不要求新消息达到自动提取的条数；与回复完成后的自动提取依次进行
+    pub async fn extract_memories(&self, chat_id: &str, agent_user_id: &str) -> ServiceResult<Vec<AgentMemory>> {
+        let agent = AgentService::get_effective_agent(&self.inner.pool, chat_id, agent_user_id)?;
+        let provider = self.inner.providers.for_agent(&agent)?;
+        let extraction = MemoryService::extract_from_chat(&self.inner.pool, provider.as_ref(), &agent, chat_id, 1);
+        self.serialize_memory_extraction(chat_id, agent_user_id, extraction).await
+    }
+
+    // 持有 (聊天ID, AI用户ID) 的提取锁执行提取，后执行的提取从前一次推进后的位置开始
+    async fn serialize_memory_extraction<T>(
+        &self,
+        chat_id: &str,
+        agent_user_id: &str,
+        extraction: impl Future<Output = T>,
+    ) -> T {
+        let key = (chat_id.to_string(), agent_user_id.to_string());
+        let lock = self
+            .inner
+            .memory_extractions
+            .lock()
+            .expect("无法获取记忆提取锁")
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        let result = {
+            let _guard = lock.lock().await;
+            extraction.await
+        };
+
+        // 没有其他提取在等待时移除锁
+        let mut extractions = self.inner.memory_extractions.lock().expect("无法获取记忆提取锁");
+        if Arc::strong_count(&lock) == 2 {
+            extractions.remove(&key);
+        }
+        result
+    }
+
+    // 确保聊天的处理循环在运行
+    fn wake(&self, chat_id: &str) {
+        let mut workers = self.inner.workers.lock().expect("无法获取任务队列锁");
+        match workers.get_mut(chat_id) {
+            Some(has_new_jobs) => *has_new_jobs = true,
+            None => {
+                workers.insert(chat_id.to_string(), false);
+                let queue = self.clone();
+                let chat_id = chat_id.to_string();
+                tauri::async_runtime::spawn(async move { queue.run_chat(chat_id).await });
+            }
+        }
+    }
+
+    // 依次处理聊天中的待处理任务，没有任务时退出
+    async fn run_chat(self, chat_id: String) {
+        loop {
+            let next = match AiJobRepository::get_next_pending(&self.inner.pool, &chat_id) {
+                Ok(next) => next,
+                Err(e) => {
+                    eprintln!("获取待处理AI任务失败 chat_id={}: {}", chat_id, e);
+                    None
+                }
+            };
+
+            match next {
+                Some(job) => {
+                    // 任务状态写入失败时任务可能仍为待处理，退出处理循环以免反复处理同一任务，
+                    // 由 run_pending_watch 稍后重新启动
+                    if !self.process(job).await {
+                        self.inner.workers.lock().expect("无法获取任务队列锁").remove(&chat_id);
+                        return;
+                    }
+                }
+                None => {
+                    // 查询期间有新任务加入时再检查一次，否则退出处理循环
+                    let mut workers = self.inner.workers.lock().expect("无法获取任务队列锁");
+                    match workers.get_mut(&chat_id) {
+                        Some(has_new_jobs) if *has_new_jobs => *has_new_jobs = false,
+                        _ => {
+                            workers.remove(&chat_id);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // 处理单个任务并写入最终状态，返回最终状态是否写入成功
+    async fn process(&self, job: AiJob) -> bool {
+        let (handle, registration) = AbortHandle::new_pair();
+        self.inner
+            .running
+            .lock()
+            .expect("无法获取任务注册表锁")
+            .insert(job.id.clone(), handle);
+
+        let mut progress = ReplyProgress::default();
+        let result = match AiJobRepository::mark_processing(&self.inner.pool, &job.id) {
+            // 任务在开始前已被取消
+            Ok(false) => None,
+            Ok(true) => {
+                let processing = AiJob {
+                    status: AiJobStatus::Processing.as_str().to_string(),
+                    ..job.clone()
+                };
+                self.inner.sink.status_changed(AiJobStatusEvent::from(&processing));
+                let generation = runner::generate_reply(&self.inner, &job, &mut progress);
+                Some(Abortable::new(generation, registration).await)
+            }
+            Err(e) => Some(Ok(Err(anyhow!("更新任务状态失败: {}", e)))),
+        };
+
+        self.inner.running.lock().expect("无法获取任务注册表锁").remove(&job.id);
+
+        let (status, error_message) = match result {
+            None => return true,
+            Some(Ok(Ok(()))) => (AiJobStatus::Completed, None),
+            Some(Ok(Err(e))) => (AiJobStatus::Error, Some(e.to_string())),
+            Some(Err(_aborted)) => (AiJobStatus::Cancelled, None),
+        };
+
+        // 结束回复消息，失败或取消时保留已生成的内容
+        if let Some(message_id) = &progress.message_id {
+            let (message_status, content) = match status {
+                AiJobStatus::Completed => (MessageStatus::Complete, progress.content.trim()),
+                AiJobStatus::Error => (MessageStatus::Error, progress.content.as_str()),
+                _ => (MessageStatus::Cancelled, progress.content.as_str()),
+            };
+            if let Err(e) = MessageService::finish_streaming_message(
+                &self.inner.pool,
+                message_id,
+                content,
+                message_status,
+                error_message.as_deref(),
+            ) {
+                eprintln!("结束AI回复消息失败 message_id={}: {}", message_id, e);
+            }
+        }
+
+        let finished = match AiJobRepository::finish(&self.inner.pool, &job.id, status, error_message.as_deref()) {
+            Ok(job) => {
+                self.inner.sink.status_changed(AiJobStatusEvent::from(&job));
+                // 开始处理前写入状态失败时，任务不是处理中，finish 不会修改它
+                job.status != AiJobStatus::Pending.as_str()
+            }
+            Err(e) => {
+                eprintln!("更新AI任务状态失败 job_id={}: {}", job.id, e);
+                false
+            }
+        };
+
+        // 回复完成后为其中 @ 到的其他 AI 参与者安排回复，并在后台提取长期记忆
+        if let (AiJobStatus::Completed, Some(message_id)) = (status, progress.message_id) {
+            let queue = self.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = queue.dispatch(&message_id).await {
+                    eprintln!("安排后续AI回复失败 message_id={}: {}", message_id, e);
+                }
+            });
+
+            let queue = self.clone();
+            tauri::async_runtime::spawn(async move {
+                let extraction = runner::extract_memories(&queue.inner, &job.chat_id, &job.agent_user_id);
+                let result = queue
+                    .serialize_memory_extraction(&job.chat_id, &job.agent_user_id, extraction)
+                    .await;
+                if let Err(e) = result {
+                    eprintln!("提取AI记忆失败 job_id={}: {}", job.id, e);
+                }
+            });
+        }
+        finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::llm::mock::{MockProvider, MockReply};
+    use crate::repositories::chat_repository::ChatRepository;
+    use crate::repositories::message_repository::MessageRepository;
+    use crate::test_support::{create_test_chat, create_test_chat_with_pool, create_test_database, create_test_queue, TestChat};
+
+    // 每隔一段时间检查一次，直到条件满足
+    async fn wait_until(mut condition: impl FnMut() -> bool) {
+        for _ in 0..500 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("等待超时");
+    }
+
+    fn job_status(pool: &DbPool, job_id: &str) -> String {
+        AiJobService::get_job(pool, job_id).unwrap().status
+    }
+
+    fn workers_idle(queue: &AiJobQueue) -> bool {
+        queue.inner.workers.lock().unwrap().is_empty()
+    }
+
+    #[tokio::test]
+    async fn jobs_in_a_chat_run_one_after_another() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let message = MessageService::send_message(&pool, &chat.id, &human.id, "你好", None).unwrap();
+        let provider = Arc::new(MockProvider::new());
+        provider.push_reply(MockReply::Text("第一条回复".to_string()));
+        provider.push_reply(MockReply::Text("第二条回复".to_string()));
+        let queue = create_test_queue(&pool, provider);
+
+        let first = queue.enqueue(&chat.id, &ai.id, Some(&message.id)).unwrap();
+        let second = queue.enqueue(&chat.id, &ai.id, Some(&message.id)).unwrap();
+        wait_until(|| job_status(&pool, &second.id) == AiJobStatus::Completed.as_str()).await;
+        wait_until(|| workers_idle(&queue)).await;
+
+        let first = AiJobService::get_job(&pool, &first.id).unwrap();
+        let second = AiJobService::get_job(&pool, &second.id).unwrap();
+        assert_eq!(first.status, AiJobStatus::Completed.as_str());
+        assert!(second.started_at.unwrap() >= first.finished_at.unwrap());
+        let contents: Vec<String> = MessageService::get_chat_messages(&pool, &chat.id)
+            .unwrap()
+            .into_iter()
+            .map(|message| message.content)
+            .collect();
+        assert_eq!(contents, ["你好", "第一条回复", "第二条回复"]);
+    }
+
+    #[tokio::test]
+    async fn cancels_pending_and_running_jobs() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let message = MessageService::send_message(&pool, &chat.id, &human.id, "你好", None).unwrap();
+        let provider = Arc::new(MockProvider::new());
+        provider.push_reply(MockReply::Stall("正在思考".to_string()));
+        let queue = create_test_queue(&pool, provider);
+
+        let running = queue.enqueue(&chat.id, &ai.id, Some(&message.id)).unwrap();
+        let pending = queue.enqueue(&chat.id, &ai.id, Some(&message.id)).unwrap();
+        assert!(queue.cancel(&pending.id).unwrap());
+        assert_eq!(job_status(&pool, &pending.id), AiJobStatus::Cancelled.as_str());
+
+        wait_until(|| AiJobService::get_job(&pool, &running.id).unwrap().reply_message_id.is_some()).await;
+        assert!(queue.cancel(&running.id).unwrap());
+        wait_until(|| job_status(&pool, &running.id) == AiJobStatus::Cancelled.as_str()).await;
+        wait_until(|| workers_idle(&queue)).await;
+        assert!(!queue.cancel(&running.id).unwrap());
+
+        // 已生成的内容保留在回复消息中，取消的待处理任务没有回复
+        let reply_id = AiJobService::get_job(&pool, &running.id).unwrap().reply_message_id.unwrap();
+        let reply = MessageRepository::get(&pool, &reply_id).unwrap();
+        assert_eq!(reply.status, MessageStatus::Cancelled.as_str());
+        assert_eq!(reply.content, "正在思考");
+        assert!(AiJobService::get_job(&pool, &pending.id).unwrap().reply_message_id.is_none());
+    }
+
+    #[tokio::test]
+    async fn recover_fails_interrupted_jobs_and_resumes_pending() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let message = MessageService::send_message(&pool, &chat.id, &human.id, "你好", None).unwrap();
+        let interrupted = AiJobService::create_job(&pool, &chat.id, &ai.id, Some(&message.id), None).unwrap();
+        assert!(AiJobRepository::mark_processing(&pool, &interrupted.id).unwrap());
+        let pending = AiJobService::create_job(&pool, &chat.id, &ai.id, Some(&message.id), None).unwrap();
+        let queue = create_test_queue(&pool, Arc::new(MockProvider::new()));
+
+        queue.recover().unwrap();
+        let interrupted = AiJobService::get_job(&pool, &interrupted.id).unwrap();
+        assert_eq!(interrupted.status, AiJobStatus::Error.as_str());
+        assert_eq!(interrupted.error_message.as_deref(), Some(INTERRUPTED_MESSAGE));
+        wait_until(|| job_status(&pool, &pending.id) == AiJobStatus::Completed.as_str()).await;
+    }
+
+    #[tokio::test]
+    async fn failed_reply_keeps_partial_content() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let message = MessageService::send_message(&pool, &chat.id, &human.id, "你好", None).unwrap();
+        let provider = Arc::new(MockProvider::new());
+        provider.push_reply(MockReply::ErrorAfter("说到一半".to_string(), "连接中断".to_string()));
+        let queue = create_test_queue(&pool, provider);
+
+        let job = queue.enqueue(&chat.id, &ai.id, Some(&message.id)).unwrap();
+        wait_until(|| job_status(&pool, &job.id) == AiJobStatus::Error.as_str()).await;
+
+        let job = AiJobService::get_job(&pool, &job.id).unwrap();
+        assert!(job.error_message.unwrap().contains("连接中断"));
+        let reply = MessageRepository::get(&pool, &job.reply_message_id.unwrap()).unwrap();
+        assert_eq!(reply.status, MessageStatus::Error.as_str());
+        assert_eq!(reply.content, "说到一半");
+        assert!(reply.error_message.unwrap().contains("连接中断"));
+    }
+
+    #[tokio::test]
+    async fn recover_keeps_content_of_interrupted_replies() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        MessageService::send_message(&pool, &chat.id, &human.id, "你好", None).unwrap();
+        // 上次退出时正在生成的回复，已写入部分内容
+        let reply = MessageService::start_streaming_message(&pool, &chat.id, &ai.id, None, None).unwrap();
+        MessageService::flush_streaming_message(&pool, &reply.id, "说到一半").unwrap();
+        let queue = create_test_queue(&pool, Arc::new(MockProvider::new()));
+
+        queue.recover().unwrap();
+        let reply = MessageRepository::get(&pool, &reply.id).unwrap();
+        assert_eq!(reply.status, MessageStatus::Error.as_str());
+        assert_eq!(reply.content, "说到一半");
+        assert_eq!(reply.error_message.as_deref(), Some(INTERRUPTED_REPLY_MESSAGE));
+        // 已结束的消息不再写入
+        assert!(MessageService::flush_streaming_message(&pool, &reply.id, "继续").is_err());
+        assert!(MessageService::reconcile_streaming_messages(&pool, INTERRUPTED_REPLY_MESSAGE).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn regenerated_reply_follows_the_original_parent() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let message = MessageService::send_message(&pool, &chat.id, &human.id, "你好", None).unwrap();
+        let provider = Arc::new(MockProvider::new());
+        provider.push_reply(MockReply::Text("第一次回复".to_string()));
+        provider.push_reply(MockReply::Text("第二次回复".to_string()));
+        let queue = create_test_queue(&pool, provider);
+        let job = queue.enqueue(&chat.id, &ai.id, Some(&message.id)).unwrap();
+        wait_until(|| job_status(&pool, &job.id) == AiJobStatus::Completed.as_str()).await;
+        let original_id = AiJobService::get_job(&pool, &job.id).unwrap().reply_message_id.unwrap();
+
+        // 测试运行在单线程运行时上，任务在下一次等待时才开始处理，此前发送的消息先于新回复写入
+        let job = queue.regenerate(&original_id).unwrap();
+        let interjection = MessageService::send_message(&pool, &chat.id, &human.id, "再说一遍", None).unwrap();
+        wait_until(|| job_status(&pool, &job.id) == AiJobStatus::Completed.as_str()).await;
+        wait_until(|| workers_idle(&queue)).await;
+
+        let reply = MessageRepository::get(&pool, &AiJobService::get_job(&pool, &job.id).unwrap().reply_message_id.unwrap()).unwrap();
+        assert_eq!(reply.parent_id.as_deref(), Some(message.id.as_str()));
+        assert_eq!(reply.content, "第二次回复");
+        let (siblings, active) = MessageService::get_siblings(&pool, &reply.id).unwrap();
+        let sibling_ids: Vec<&str> = siblings.iter().map(|sibling| sibling.id.as_str()).collect();
+        assert_eq!(sibling_ids, [original_id.as_str(), interjection.id.as_str(), reply.id.as_str()]);
+        assert_eq!(active, Some(2));
+        let contents: Vec<String> = MessageService::get_chat_messages(&pool, &chat.id)
+            .unwrap()
+            .into_iter()
+            .map(|message| message.content)
+            .collect();
+        assert_eq!(contents, ["你好", "第二次回复"]);
+        assert_eq!(ChatRepository::get(&pool, &chat.id).unwrap().last_message.as_deref(), Some("第二次回复"));
+
+        // 期间发送的消息仍可切换回来
+        MessageService::switch_branch(&pool, &interjection.id).unwrap();
+        let contents: Vec<String> = MessageService::get_chat_messages(&pool, &chat.id)
+            .unwrap()
+            .into_iter()
+            .map(|message| message.content)
+            .collect();
+        assert_eq!(contents, ["你好", "再说一遍"]);
+    }
+
+    #[tokio::test]
+    async fn worker_exits_when_job_status_cannot_be_written() {
+        let (path, pool) = create_test_database();
+        let TestChat { pool, human, ai, chat } = create_test_chat_with_pool(pool);
+        let message = MessageService::send_message(&pool, &chat.id, &human.id, "你好", None).unwrap();
+        let job = AiJobService::create_job(&pool, &chat.id, &ai.id, Some(&message.id), None).unwrap();
+
+        // 只读连接池能读取待处理任务，但开始和结束任务都无法写入
+        let read_pool = db::create_pool(&path, true).unwrap();
+        let queue = create_test_queue(&read_pool, Arc::new(MockProvider::new()));
+        queue.wake_pending().unwrap();
+        wait_until(|| workers_idle(&queue)).await;
+        assert_eq!(job_status(&pool, &job.id), AiJobStatus::Pending.as_str());
+    }
+
+    #[tokio::test]
+    async fn concurrent_memory_extractions_run_one_after_another() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        MessageRepository::create(&pool, "我对花生过敏".to_string(), &chat.id, &human.id).unwrap();
+
+        // 只有一次模型回复，第二次提取若在第一次推进提取位置前请求模型，会得到无法解析的回声
+        let provider = Arc::new(MockProvider::new());
+        provider.push_reply(MockReply::Text(r#"{"memories": [{"content": "用户对花生过敏"}]}"#.to_string()));
+        let queue = create_test_queue(&pool, provider);
+
+        let (first, second) = tokio::join!(
+            queue.extract_memories(&chat.id, &ai.id),
+            queue.extract_memories(&chat.id, &ai.id),
+        );
+        assert_eq!(first.unwrap().len() + second.unwrap().len(), 1);
+        assert_eq!(MemoryService::get_agent_memories(&pool, &ai.id).unwrap().len(), 1);
+        assert!(queue.inner.memory_extractions.lock().unwrap().is_empty());
+    }
+}
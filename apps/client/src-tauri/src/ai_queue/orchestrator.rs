@@ -0,0 +1,53 @@
+// 群聊回复编排：按回复安排选出回复者，需要主持人挑选时请求主持人 AI 的模型
+use crate::llm::{CompletionRequest, SamplingOptions};
+use crate::models::User;
+use crate::services::agent_service::AgentService;
+use crate::services::context_builder::ContextBuilder;
+use crate::services::group_chat_service::{
+    GroupChatService, ReplyDecision, ReplyPlan, MODERATOR_HISTORY_LIMIT, MODERATOR_MAX_TOKENS,
+};
+use crate::services::ServiceResult;
+use super::QueueInner;
+
+// 按回复安排选出回复者，主持人挑选失败或没有选出任何人时使用备选的回复者
+pub(super) async fn select_speakers(inner: &QueueInner, plan: ReplyPlan) -> Vec<User> {
+    match plan.decision {
+        ReplyDecision::Speakers(speakers) => speakers,
+        ReplyDecision::Moderate { moderator, candidates, fallback, max_speakers } => {
+            match ask_moderator(inner, &plan.chat_id, &moderator, &candidates).await {
+                Ok(chosen) if !chosen.is_empty() => chosen.into_iter().take(max_speakers).collect(),
+                Ok(_) => fallback,
+                Err(e) => {
+                    eprintln!("主持人挑选回复者失败 chat_id={}: {}", plan.chat_id, e);
+                    fallback
+                }
+            }
+        }
+    }
+}
+
+// 请求主持人根据最近的对话从候选者中挑选回复者
+async fn ask_moderator(
+    inner: &QueueInner,
+    chat_id: &str,
+    moderator: &User,
+    candidates: &[User],
+) -> ServiceResult<Vec<User>> {
+    let agent = AgentService::get_effective_agent(&inner.pool, chat_id, &moderator.id)?;
+    let provider = inner.providers.for_agent(&agent)?;
+    let history = ContextBuilder::load_history(&inner.pool, chat_id, None, true, MODERATOR_HISTORY_LIMIT)?;
+
+    let request = CompletionRequest {
+        model: agent.model_name.clone(),
+        messages: GroupChatService::moderator_messages(candidates, &history),
+        options: SamplingOptions {
+            temperature: Some(0.0),
+            max_tokens: Some(MODERATOR_MAX_TOKENS),
+            ..Default::default()
+        },
+        json_mode: true,
+        tools: Vec::new(),
+    };
+    let response = provider.complete(&request).await?;
+    Ok(GroupChatService::parse_moderator_choice(&response.content, candidates))
+}
@@ -0,0 +1,471 @@
+// 执行单个 AI 回复任务
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use futures_util::StreamExt;
+
+use super::events::{AiJobDeltaEvent, AiJobStatusEvent};
+use super::QueueInner;
+use crate::llm::{CompletionRequest, LlmMessage, LlmProvider, SamplingOptions, ToolCall};
+use crate::mcp::McpTool;
+use crate::models::{Agent, AiJob};
+use crate::repositories::ai_job_repository::AiJobRepository;
+use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+use crate::repositories::message_repository::MessageRepository;
+use crate::services::agent_service::AgentService;
+use crate::services::context_builder::{ContextBuilder, ContextStrategy, HistoryMessage};
+use crate::services::knowledge_service::KnowledgeService;
+use crate::services::mcp_service::McpService;
+use crate::services::memory_service::{MemoryService, MIN_EXTRACTION_MESSAGES};
+use crate::services::message_service::MessageService;
+use crate::services::ServiceResult;
+use crate::tools::{Tool, ToolContext, ToolSet};
+
+// 参与上下文组装的最近消息条数，实际放入的条数由上下文预算决定
+const HISTORY_LIMIT: i64 = 200;
+// 生成过程中写入数据库的间隔
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+// 生成中的回复
+//
+// 任务被中断时生成过程直接停止，由处理循环根据这里记录的内容结束回复消息
+#[derive(Default)]
+pub(super) struct ReplyProgress {
+    pub message_id: Option<String>,
+    pub content: String,
+}
+
+// 调用 Agent 配置的模型生成回复
+//
+// 开始生成时即以 AI 用户身份创建流式消息，生成过程中定期写入已生成的内容。
+// 模型请求调用工具时，该消息保存为工具调用消息，执行结果逐条保存为工具结果消息并返回给模型，
+// 再以新的流式消息继续生成；达到 Agent 配置的最大轮数后不再提供工具，模型需直接给出回复
+pub(super) async fn generate_reply(
+    inner: &QueueInner,
+    job: &AiJob,
+    progress: &mut ReplyProgress,
+) -> ServiceResult<()> {
+    let agent = AgentService::get_effective_agent(&inner.pool, &job.chat_id, &job.agent_user_id)?;
+    let provider = inner.providers.for_agent(&agent)?;
+
+    // 1. 按上下文预算组装系统提示词、长期记忆、知识库资料和聊天记录；触发消息不是最新消息时，回复针对该消息并引用它；
+    //    多人聊天中其他成员的消息标注发送者名称
+    let speaker_name = ContextBuilder::load_group_speaker_name(&inner.pool, &job.chat_id, &job.agent_user_id)?;
+    let attribute_names = speaker_name.is_some();
+    let history = ContextBuilder::load_history(
+        &inner.pool,
+        &job.chat_id,
+        Some(&job.agent_user_id),
+        attribute_names,
+        HISTORY_LIMIT,
+    )?;
+    let reply_target = match &job.trigger_message_id {
+        Some(message_id) => ContextBuilder::load_reply_target(&inner.pool, &job.chat_id, message_id, attribute_names)?,
+        None => None,
+    };
+    let reply_to_id = reply_target.as_ref().and(job.trigger_message_id.as_deref());
+
+    // 以回复的目标消息（没有时为其他成员的最新消息）检索 Agent 关联的知识库；检索失败时不使用知识库
+    let query = match &reply_target {
+        Some(target) => Some(target.content.as_str()),
+        None => history.iter().rev().find(|message| !message.from_self).map(|message| message.content.as_str()),
+    };
+    let knowledge = match query {
+        Some(query) => KnowledgeService::retrieve(&inner.pool, provider.as_ref(), &agent, &inner.app_resource_path, query)
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!("检索知识库失败 job_id={}: {}", job.id, e);
+                Vec::new()
+            }),
+        None => Vec::new(),
+    };
+
+    // 开启记忆时挑选与该消息相关的长期记忆；读取失败时不使用记忆
+    let memories = if agent.memory_enabled {
+        MemoryService::retrieve(&inner.pool, &agent.user_id, query).unwrap_or_else(|e| {
+            eprintln!("读取AI记忆失败 job_id={}: {}", job.id, e);
+            Vec::new()
+        })
+    } else {
+        Vec::new()
+    };
+
+    let options = AgentService::sampling_options(&agent);
+    let mut builder = ContextBuilder::for_agent(&agent)
+        .with_reply_target(reply_target)
+        .with_speaker_name(speaker_name)
+        .with_knowledge(knowledge)
+        .with_memories(memories);
+    let mut context = builder.build(&history);
+
+    // 有消息因超出预算被省略时，使用这些消息的摘要；概括失败时不使用摘要
+    if builder.strategy() == ContextStrategy::SummaryAndRecent && !context.omitted.is_empty() {
+        // 该策略放入的历史消息是最新的连续部分，省略的是其之前的全部消息
+        let oldest_kept_id = history[context.omitted.len()].id.clone();
+        let summary = summarize_omitted(
+            inner,
+            job,
+            &agent,
+            provider.as_ref(),
+            &builder,
+            &context.omitted,
+            oldest_kept_id,
+            &options,
+        )
+        .await;
+        if let Some(summary) = summary {
+            builder = builder.with_summary(Some(summary));
+            context = builder.build(&history);
+        }
+    }
+
+    let (tools, tool_context) = load_tools(inner, job, &agent, provider.as_ref()).await?;
+    let max_iterations = AgentService::max_tool_iterations(&agent);
+    let mut messages = context.messages;
+    let mut iteration = 0;
+    // 重新生成的回复接在原回复的上一条消息（触发消息）之后，工具调用之后的消息接在当前分支的最后一条消息之后
+    let mut parent_id = job.regenerated_message_id.as_ref().and(job.trigger_message_id.as_deref());
+
+    loop {
+        let offer_tools = !tools.is_empty() && iteration < max_iterations;
+        let request = CompletionRequest {
+            model: agent.model_name.clone(),
+            messages: messages.clone(),
+            options: options.clone(),
+            json_mode: false,
+            tools: if offer_tools { tools.definitions() } else { Vec::new() },
+        };
+
+        // 2. 创建流式消息并关联到任务
+        let message = MessageService::start_streaming_message(
+            &inner.pool,
+            &job.chat_id,
+            &job.agent_user_id,
+            reply_to_id,
+            parent_id.take(),
+        )?;
+        progress.message_id = Some(message.id.clone());
+        progress.content.clear();
+        let job = AiJobRepository::set_reply_message(&inner.pool, &job.id, &message.id)
+            .map_err(|e| anyhow!("更新任务回复消息失败: {}", e))?;
+        inner.sink.status_changed(AiJobStatusEvent::from(&job));
+
+        // 3. 流式生成
+        let tool_calls = stream_reply(inner, &job, &message.id, provider.as_ref(), &request, progress).await?;
+        let Some(tool_context) = tool_context.as_ref().filter(|_| offer_tools && !tool_calls.is_empty()) else {
+            break;
+        };
+
+        // 4. 保存工具调用，依次执行工具并保存结果，结果返回给模型后继续生成
+        let content = std::mem::take(&mut progress.content).trim().to_string();
+        MessageService::finish_tool_call_message(&inner.pool, &message.id, &content, &tool_calls)?;
+        progress.message_id = None;
+        messages.push(LlmMessage::tool_calls(content, tool_calls.clone()));
+
+        for call in &tool_calls {
+            let output = tools.execute(tool_context, call).await;
+            MessageService::add_tool_result(&inner.pool, &job.chat_id, &job.agent_user_id, &call.id, &output)?;
+            messages.push(LlmMessage::tool_result(call.id.clone(), output));
+        }
+        iteration += 1;
+    }
+
+    if progress.content.trim().is_empty() {
+        return Err(anyhow!("模型返回了空回复"));
+    }
+    Ok(())
+}
+
+// 生成的对话摘要及其对应的最早放入上下文的消息
+pub(super) struct CachedSummary {
+    oldest_kept_id: String,
+    summary: String,
+}
+
+// 请求模型概括被省略的历史消息
+//
+// 最早放入上下文的消息不变时省略的消息也不变，直接使用该聊天和 AI 用户上次生成的摘要
+async fn summarize_omitted(
+    inner: &QueueInner,
+    job: &AiJob,
+    agent: &Agent,
+    provider: &dyn LlmProvider,
+    builder: &ContextBuilder,
+    omitted: &[HistoryMessage],
+    oldest_kept_id: String,
+    options: &SamplingOptions,
+) -> Option<String> {
+    let key = (job.chat_id.clone(), job.agent_user_id.clone());
+    let cached = inner
+        .summaries
+        .lock()
+        .expect("无法获取摘要缓存锁")
+        .get(&key)
+        .filter(|cached| cached.oldest_kept_id == oldest_kept_id)
+        .map(|cached| cached.summary.clone());
+    if cached.is_some() {
+        return cached;
+    }
+
+    let request = CompletionRequest {
+        model: agent.model_name.clone(),
+        messages: builder.summary_messages(omitted),
+        options: SamplingOptions {
+            max_tokens: Some(builder.summary_max_tokens() as i32),
+            ..options.clone()
+        },
+        json_mode: false,
+        tools: Vec::new(),
+    };
+    match provider.complete(&request).await {
+        Ok(response) => {
+            let cached = CachedSummary { oldest_kept_id, summary: response.content.clone() };
+            inner.summaries.lock().expect("无法获取摘要缓存锁").insert(key, cached);
+            Some(response.content)
+        }
+        Err(e) => {
+            eprintln!("生成对话摘要失败 job_id={}: {}", job.id, e);
+            None
+        }
+    }
+}
+
+// 回复完成后从聊天的新消息中提取长期记忆，Agent 未开启记忆或新消息不足时跳过；由队列按聊天和 AI 用户依次调用
+pub(super) async fn extract_memories(inner: &QueueInner, chat_id: &str, agent_user_id: &str) -> ServiceResult<()> {
+    let agent = AgentService::get_effective_agent(&inner.pool, chat_id, agent_user_id)?;
+    if !agent.memory_enabled {
+        return Ok(());
+    }
+    let provider = inner.providers.for_agent(&agent)?;
+    MemoryService::extract_from_chat(&inner.pool, provider.as_ref(), &agent, chat_id, MIN_EXTRACTION_MESSAGES).await?;
+    Ok(())
+}
+
+// 流式生成一条回复，增量输出推送给前端，已生成的内容定期写入数据库；返回模型请求调用的工具
+async fn stream_reply(
+    inner: &QueueInner,
+    job: &AiJob,
+    message_id: &str,
+    provider: &dyn LlmProvider,
+    request: &CompletionRequest,
+    progress: &mut ReplyProgress,
+) -> ServiceResult<Vec<ToolCall>> {
+    let mut stream = provider.complete_stream(request).await?;
+    let mut last_flush = Instant::now();
+    let mut tool_calls = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        tool_calls.extend(chunk.tool_calls);
+        if chunk.delta.is_empty() {
+            continue;
+        }
+        progress.content.push_str(&chunk.delta);
+        inner.sink.delta(AiJobDeltaEvent {
+            job_id: job.id.clone(),
+            chat_id: job.chat_id.clone(),
+            agent_user_id: job.agent_user_id.clone(),
+            message_id: message_id.to_string(),
+            content: chunk.delta,
+        });
+
+        if last_flush.elapsed() >= FLUSH_INTERVAL {
+            MessageService::flush_streaming_message(&inner.pool, message_id, &progress.content)?;
+            last_flush = Instant::now();
+        }
+    }
+    Ok(tool_calls)
+}
+
+// Agent 可以调用的工具和执行工具的上下文，包括启用的内置工具和启用的 MCP 服务器提供的工具
+//
+// Agent 未启用工具、模型不支持工具调用（查询能力失败时同样按不支持处理）或聊天中没有真人用户时不提供工具；
+// 工具按触发消息的发送者的权限执行，触发消息由 AI 发送时按最早加入聊天的真人用户
+async fn load_tools(
+    inner: &QueueInner,
+    job: &AiJob,
+    agent: &Agent,
+    provider: &dyn LlmProvider,
+) -> ServiceResult<(ToolSet, Option<ToolContext>)> {
+    let builtin_names = AgentService::decode_tools(agent.tools.as_deref());
+    let mcp_servers = McpService::get_active_agent_servers(&inner.pool, &job.agent_user_id)?;
+    if builtin_names.is_empty() && mcp_servers.is_empty() {
+        return Ok((ToolSet::default(), None));
+    }
+    match provider.capabilities(&agent.model_name).await {
+        Ok(capabilities) if capabilities.tools => {}
+        Ok(_) => return Ok((ToolSet::default(), None)),
+        Err(e) => {
+            eprintln!("查询模型能力失败，不提供工具 job_id={}: {}", job.id, e);
+            return Ok((ToolSet::default(), None));
+        }
+    }
+
+    let mut conn = inner.pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+    let humans: Vec<String> = ChatParticipantRepository::get_users_by_chat_id_with_conn(&mut conn, &job.chat_id)
+        .map_err(|e| anyhow!("获取聊天参与者失败: {}", e))?
+        .into_iter()
+        .filter(|user| !user.is_ai)
+        .map(|user| user.id)
+        .collect();
+    let trigger_sender = match &job.trigger_message_id {
+        Some(message_id) => Some(
+            MessageRepository::get_with_conn(&mut conn, message_id)
+                .map_err(|e| anyhow!("获取触发消息失败: {}", e))?
+                .sender_id,
+        ),
+        None => None,
+    };
+    let user_id = trigger_sender
+        .filter(|sender_id| humans.contains(sender_id))
+        .or_else(|| humans.into_iter().next());
+
+    let Some(user_id) = user_id else {
+        return Ok((ToolSet::default(), None));
+    };
+
+    // MCP 服务器启动或列出工具失败时跳过该服务器，不影响回复
+    let mut tools = inner.tools.select(&builtin_names);
+    for server in &mcp_servers {
+        let listed = match inner.mcp.connect(server).await {
+            Ok(client) => client.list_tools().await.map(|infos| (client, infos)),
+            Err(e) => Err(e),
+        };
+        match listed {
+            Ok((client, infos)) => tools.extend(
+                infos
+                    .into_iter()
+                    .map(|info| Arc::new(McpTool::new(&server.name, client.clone(), info)) as Arc<dyn Tool>),
+            ),
+            Err(e) => eprintln!("获取MCP服务器 {} 的工具失败 job_id={}: {}", server.name, job.id, e),
+        }
+    }
+    let tools = ToolSet::new(tools);
+    if tools.is_empty() {
+        return Ok((tools, None));
+    }
+
+    let tool_context = ToolContext {
+        pool: inner.pool.clone(),
+        chat_id: job.chat_id.clone(),
+        user_id,
+    };
+    Ok((tools, Some(tool_context)))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::ai_queue::AiJobQueue;
+    use crate::llm::mock::{MockProvider, MockReply};
+    use crate::models::{MessageKind, MessageStatus};
+    use crate::repositories::chat_repository::ChatRepository;
+    use crate::services::agent_service::AgentConfig;
+    use crate::services::ai_job_service::AiJobService;
+    use crate::test_support::{create_test_chat, create_test_queue, mock_agent_config, TestChat};
+    use crate::tools::builtin::CURRENT_TIME;
+
+    struct Fixture {
+        queue: AiJobQueue,
+        provider: Arc<MockProvider>,
+        chat_id: String,
+        job: AiJob,
+    }
+
+    // 真人用户向启用了 current_time 工具的 AI 用户发送一条消息，并为 AI 用户创建回复任务
+    fn fixture() -> Fixture {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let config = AgentConfig {
+            tools: vec![CURRENT_TIME.to_string()],
+            max_tool_iterations: Some(2),
+            ..mock_agent_config()
+        };
+        AgentService::update_agent_config(&pool, &ai.id, config).unwrap();
+
+        let message = MessageRepository::create(&pool, "现在几点了？".to_string(), &chat.id, &human.id).unwrap();
+        let job = AiJobService::create_job(&pool, &chat.id, &ai.id, Some(&message.id), None).unwrap();
+
+        let provider = Arc::new(MockProvider::new());
+        let queue = create_test_queue(&pool, provider.clone());
+        Fixture { queue, provider, chat_id: chat.id, job }
+    }
+
+    fn tool_call(id: &str, name: &str) -> ToolCall {
+        ToolCall { id: id.to_string(), name: name.to_string(), arguments: json!({}) }
+    }
+
+    #[tokio::test]
+    async fn tool_calls_are_saved_and_results_returned_to_model() {
+        let Fixture { queue, provider, chat_id, job } = fixture();
+        let inner = &queue.inner;
+        provider.push_reply(MockReply::ToolCalls(vec![tool_call("call-1", CURRENT_TIME)]));
+        provider.push_reply(MockReply::Text("现在是下午三点。".to_string()));
+
+        let mut progress = ReplyProgress::default();
+        generate_reply(inner, &job, &mut progress).await.unwrap();
+        assert_eq!(progress.content, "现在是下午三点。");
+        let reply_id = progress.message_id.clone().unwrap();
+        MessageService::finish_streaming_message(&inner.pool, &reply_id, &progress.content, MessageStatus::Complete, None)
+            .unwrap();
+
+        let messages = MessageService::get_chat_messages(&inner.pool, &chat_id).unwrap();
+        let kinds: Vec<&str> = messages.iter().map(|message| message.kind.as_str()).collect();
+        assert_eq!(
+            kinds,
+            [
+                MessageKind::Text.as_str(),
+                MessageKind::ToolCall.as_str(),
+                MessageKind::ToolResult.as_str(),
+                MessageKind::Text.as_str(),
+            ]
+        );
+        assert!(messages[1].tool_calls.as_deref().unwrap().contains(CURRENT_TIME));
+        assert_eq!(messages[2].tool_call_id.as_deref(), Some("call-1"));
+        assert!(messages[2].content.contains("local_time"));
+        assert_eq!(messages[3].id, reply_id);
+
+        // 工具调用和工具结果不作为聊天的最后消息
+        let chat = ChatRepository::get(&inner.pool, &chat_id).unwrap();
+        assert_eq!(chat.last_message.as_deref(), Some("现在是下午三点。"));
+    }
+
+    #[tokio::test]
+    async fn tool_errors_are_returned_to_model() {
+        let Fixture { queue, provider, chat_id, job } = fixture();
+        let inner = &queue.inner;
+        provider.push_reply(MockReply::ToolCalls(vec![tool_call("call-1", "delete_everything")]));
+
+        // 脚本用完后模拟模型回显最后一条工具结果
+        let mut progress = ReplyProgress::default();
+        generate_reply(inner, &job, &mut progress).await.unwrap();
+        assert!(progress.content.starts_with("[mock] 错误："));
+        assert!(progress.content.contains("delete_everything"));
+
+        let messages = MessageService::get_chat_messages(&inner.pool, &chat_id).unwrap();
+        assert_eq!(messages.len(), 4);
+        assert!(messages[2].content.starts_with("错误："));
+    }
+
+    #[tokio::test]
+    async fn tools_are_withheld_after_max_iterations() {
+        let Fixture { queue, provider, chat_id, job } = fixture();
+        let inner = &queue.inner;
+        for index in 0..3 {
+            provider.push_reply(MockReply::ToolCalls(vec![tool_call(&format!("call-{}", index), CURRENT_TIME)]));
+        }
+
+        // 第三次请求不再提供工具，模型仍请求调用工具时没有文本回复
+        let mut progress = ReplyProgress::default();
+        let error = generate_reply(inner, &job, &mut progress).await.unwrap_err();
+        assert!(error.to_string().contains("空回复"));
+
+        let tool_results = MessageService::get_chat_messages(&inner.pool, &chat_id)
+            .unwrap()
+            .into_iter()
+            .filter(|message| message.kind == MessageKind::ToolResult.as_str())
+            .count();
+        assert_eq!(tool_results, 2);
+    }
+}
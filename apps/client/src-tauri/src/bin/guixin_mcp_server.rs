@@ -0,0 +1,9 @@
+// guixin 的 MCP 服务器，通过标准输入输出通信
+//
+// 在编辑器或其他 Agent 的 MCP 配置中以本程序作为命令即可读取聊天记录和资源；需先启动过桌面应用以创建数据库
+fn main() {
+    if let Err(e) = tauri_app_lib::run_mcp_server() {
+        eprintln!("MCP 服务器启动失败: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,120 @@
+// 用于调试 MCP 客户端的最小 MCP 服务器，通过标准输入输出通信
+//
+// 提供 echo、add、fail 三个工具，一个文本资源 echo://hello 和一个提示词模板 greet
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+const PROTOCOL_VERSION: &str = "2025-03-26";
+
+fn main() {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("无法解析的消息: {}", e);
+                continue;
+            }
+        };
+
+        let method = message["method"].as_str().unwrap_or_default();
+        // 没有 id 的是通知，不需要应答
+        let Some(id) = message.get("id").cloned() else { continue };
+
+        let response = match handle(method, &message["params"]) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, error)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": error } }),
+        };
+        if writeln!(stdout, "{}", response).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+    }
+}
+
+fn handle(method: &str, params: &Value) -> Result<Value, (i64, String)> {
+    match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION),
+            "capabilities": { "tools": {}, "resources": {}, "prompts": {} },
+            "serverInfo": { "name": "mcp-echo-server", "version": env!("CARGO_PKG_VERSION") },
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({
+            "tools": [
+                {
+                    "name": "echo",
+                    "description": "原样返回传入的文本",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": { "text": { "type": "string" } },
+                        "required": ["text"],
+                    },
+                },
+                {
+                    "name": "add",
+                    "description": "计算两个数的和",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": { "a": { "type": "number" }, "b": { "type": "number" } },
+                        "required": ["a", "b"],
+                    },
+                },
+                {
+                    "name": "fail",
+                    "description": "总是执行失败",
+                    "inputSchema": { "type": "object", "properties": {} },
+                },
+            ],
+        })),
+        "tools/call" => call_tool(params["name"].as_str().unwrap_or_default(), &params["arguments"]),
+        "resources/list" => Ok(json!({
+            "resources": [
+                { "uri": "echo://hello", "name": "hello", "mimeType": "text/plain" },
+            ],
+        })),
+        "resources/read" => match params["uri"].as_str() {
+            Some(uri @ "echo://hello") => Ok(json!({
+                "contents": [{ "uri": uri, "mimeType": "text/plain", "text": "Hello from echo" }],
+            })),
+            uri => Err((-32002, format!("资源不存在: {}", uri.unwrap_or_default()))),
+        },
+        "prompts/list" => Ok(json!({
+            "prompts": [{
+                "name": "greet",
+                "description": "问候某人",
+                "arguments": [{ "name": "name", "description": "被问候的人", "required": true }],
+            }],
+        })),
+        "prompts/get" => match params["arguments"]["name"].as_str() {
+            Some(name) => Ok(json!({
+                "messages": [{ "role": "user", "content": { "type": "text", "text": format!("Hello, {}!", name) } }],
+            })),
+            None => Err((-32602, "缺少参数 name".to_string())),
+        },
+        method => Err((-32601, format!("不支持的方法: {}", method))),
+    }
+}
+
+fn call_tool(name: &str, arguments: &Value) -> Result<Value, (i64, String)> {
+    let text = match name {
+        "echo" => arguments["text"].as_str().unwrap_or_default().to_string(),
+        "add" => match (arguments["a"].as_f64(), arguments["b"].as_f64()) {
+            (Some(a), Some(b)) => (a + b).to_string(),
+            _ => return Ok(tool_error("参数 a 和 b 必须是数字")),
+        },
+        "fail" => return Ok(tool_error("执行失败")),
+        name => return Err((-32602, format!("未知的工具: {}", name))),
+    };
+    Ok(json!({ "content": [{ "type": "text", "text": text }] }))
+}
+
+// 工具执行失败时通过 isError 返回错误信息，而不是 JSON-RPC 错误
+fn tool_error(message: &str) -> Value {
+    json!({ "content": [{ "type": "text", "text": message }], "isError": true })
+}
@@ -0,0 +1,248 @@
+// AI 用户模型配置相关命令
+use serde::Serialize;
+use tauri::State;
+
+use crate::AppState;
+use crate::llm::ToolDefinition;
+use crate::models::{Agent, ChatAgentOverride};
+use crate::services::agent_service::{AgentConfig, AgentService, ChatAgentOverrideConfig};
+
+#[derive(Debug, Serialize)]
+pub struct AgentResponse {
+    pub id: String,
+    pub user_id: String,
+    pub provider: String,
+    pub model_name: String,
+    pub system_prompt: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<i32>,
+    pub repeat_penalty: Option<f32>,
+    pub stop_sequences: Vec<String>,
+    pub max_tokens: Option<i32>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub context_window: Option<i32>,
+    pub context_strategy: String,
+    pub context_keep_first: Option<i32>,
+    pub tools: Vec<String>,                 // 启用的工具名称
+    pub max_tool_iterations: Option<i32>,   // 一次回复中最多调用工具的轮数，为空时使用默认值
+    pub embedding_model: Option<String>,    // 生成知识库向量的模型，为空时使用默认模型
+    pub memory_enabled: bool,               // 是否提取并使用长期记忆
+    pub memory_extraction_prompt: Option<String>, // 提取记忆的提示词，为空时使用默认提示词
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<Agent> for AgentResponse {
+    fn from(agent: Agent) -> Self {
+        Self {
+            stop_sequences: AgentService::decode_stop_sequences(agent.stop_sequences.as_deref()),
+            tools: AgentService::decode_tools(agent.tools.as_deref()),
+            id: agent.id,
+            user_id: agent.user_id,
+            provider: agent.provider,
+            model_name: agent.model_name,
+            system_prompt: agent.system_prompt,
+            temperature: agent.temperature,
+            top_p: agent.top_p,
+            top_k: agent.top_k,
+            repeat_penalty: agent.repeat_penalty,
+            max_tokens: agent.max_tokens,
+            presence_penalty: agent.presence_penalty,
+            frequency_penalty: agent.frequency_penalty,
+            context_window: agent.context_window,
+            context_strategy: agent.context_strategy,
+            context_keep_first: agent.context_keep_first,
+            max_tool_iterations: agent.max_tool_iterations,
+            embedding_model: agent.embedding_model,
+            memory_enabled: agent.memory_enabled,
+            memory_extraction_prompt: agent.memory_extraction_prompt,
+            created_at: agent.created_at.to_string(),
+            updated_at: agent.updated_at.to_string(),
+        }
+    }
+}
+
+/// 获取AI用户的模型配置
+///
+/// 早期创建的AI用户没有模型配置时，会按默认配置补建
+///
+/// ## 数据库影响
+/// - 读取操作：查询 users 表确认用户为AI用户
+/// - 读取操作：查询 agents 表获取模型配置
+/// - 写入操作：配置不存在时在 agents 表中创建默认配置
+#[tauri::command]
+pub async fn get_agent_config(
+    state: State<'_, AppState>,
+    user_id: String,
+) -> Result<AgentResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+
+    AgentService::get_agent_by_user(&pool, &user_id)
+        .map(AgentResponse::from)
+        .map_err(|e| e.to_string())
+}
+
+/// 更新AI用户的模型配置
+///
+/// 以传入的配置整体替换原有配置，未设置的可选参数使用服务端默认值；
+/// 服务商必须已注册，各采样参数需在允许范围内。
+/// 上下文策略可选 sliding_window、keep_first_and_recent、summary_and_recent，
+/// 设置上下文长度时 max_tokens 必须小于上下文长度；embedding_model 为使用同一服务商生成知识库向量的模型，
+/// 更换后关联的知识库在下次检索时按新模型重新生成片段；
+/// memory_enabled 开启后在对话后按 memory_extraction_prompt（为空时使用默认提示词）提取长期记忆，并在回复时检索相关记忆
+///
+/// ## 数据库影响
+/// - 读取操作：查询 users 表确认用户为AI用户
+/// - 修改操作：更新 agents 表中该用户的模型配置
+/// - 写入操作：配置不存在时先在 agents 表中创建
+#[tauri::command]
+pub async fn update_agent_config(
+    state: State<'_, AppState>,
+    user_id: String,
+    config: AgentConfig,
+) -> Result<AgentResponse, String> {
+    // 检查模型服务商是否已注册
+    state.llm_providers.get(&config.provider).map_err(|e| e.to_string())?;
+
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+
+    AgentService::update_agent_config(&pool, &user_id, config)
+        .map(AgentResponse::from)
+        .map_err(|e| e.to_string())
+}
+
+/// 获取AI用户可以启用的工具
+///
+/// 返回各工具的名称、说明和参数的 JSON Schema，名称可用作智能体的 tools 配置
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn get_available_tools(state: State<'_, AppState>) -> Result<Vec<ToolDefinition>, String> {
+    Ok(state.tools.definitions())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChatAgentOverrideResponse {
+    pub id: String,
+    pub chat_id: String,
+    pub agent_user_id: String,
+    pub system_prompt: Option<String>,
+    pub model_name: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<i32>,
+    pub stop_sequences: Option<Vec<String>>, // 为空时沿用 Agent 配置，空数组表示不使用停止序列
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<ChatAgentOverride> for ChatAgentOverrideResponse {
+    fn from(item: ChatAgentOverride) -> Self {
+        let config = ChatAgentOverrideConfig::from(&item);
+        Self {
+            id: item.id,
+            chat_id: item.chat_id,
+            agent_user_id: item.agent_user_id,
+            system_prompt: config.system_prompt,
+            model_name: config.model_name,
+            temperature: config.temperature,
+            top_p: config.top_p,
+            max_tokens: config.max_tokens,
+            stop_sequences: config.stop_sequences,
+            created_at: item.created_at.to_string(),
+            updated_at: item.updated_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChatAgentConfigResponse {
+    pub effective: AgentResponse,                      // 合并聊天配置覆盖后在该聊天中生效的配置
+    pub overrides: Option<ChatAgentOverrideResponse>,  // 该聊天的配置覆盖，没有覆盖时为空
+}
+
+/// 获取AI用户在聊天中生效的模型配置
+///
+/// 返回 Agent 配置合并该聊天的配置覆盖后的结果，以及配置覆盖本身；当前用户和AI用户都需为该聊天的参与者
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户和AI用户是否为该聊天的参与者
+/// - 读取操作：查询 users 表确认用户为AI用户
+/// - 读取操作：查询 agents 表获取模型配置
+/// - 读取操作：查询 chat_agent_overrides 表获取该聊天的配置覆盖
+/// - 写入操作：配置不存在时在 agents 表中创建默认配置
+#[tauri::command]
+pub async fn get_chat_agent_config(
+    state: State<'_, AppState>,
+    chat_id: String,
+    agent_user_id: String,
+) -> Result<ChatAgentConfigResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+    let current_user = state.current_user.lock().expect("无法获取当前用户状态");
+
+    AgentService::get_chat_agent_config(&pool, &current_user.id, &chat_id, &agent_user_id)
+        .map(|(agent, overrides)| ChatAgentConfigResponse {
+            effective: AgentResponse::from(agent),
+            overrides: overrides.map(ChatAgentOverrideResponse::from),
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// 更新AI用户在聊天中的配置覆盖
+///
+/// 可覆盖 system_prompt、model_name、temperature、top_p、max_tokens 和 stop_sequences，
+/// 以传入的覆盖整体替换原有覆盖，未设置的字段沿用 Agent 配置；system_prompt 为空字符串时在该聊天中不使用系统提示词；
+/// 合并后的配置需满足与 update_agent_config 相同的校验
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户和AI用户是否为该聊天的参与者
+/// - 读取操作：查询 users 表确认用户为AI用户，查询 agents 表获取模型配置
+/// - 写入操作：配置不存在时在 agents 表中创建默认配置
+/// - 写入操作：在 chat_agent_overrides 表中创建或整体替换该聊天的配置覆盖
+/// - 使用事务确保校验失败时不保存覆盖
+#[tauri::command]
+pub async fn update_chat_agent_override(
+    state: State<'_, AppState>,
+    chat_id: String,
+    agent_user_id: String,
+    overrides: ChatAgentOverrideConfig,
+) -> Result<ChatAgentConfigResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+    let current_user = state.current_user.lock().expect("无法获取当前用户状态");
+
+    AgentService::update_chat_override(&pool, &current_user.id, &chat_id, &agent_user_id, overrides)
+        .map(|(agent, overrides)| ChatAgentConfigResponse {
+            effective: AgentResponse::from(agent),
+            overrides: overrides.map(ChatAgentOverrideResponse::from),
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// 重置AI用户在聊天中的配置覆盖
+///
+/// 删除该聊天的配置覆盖，之后在该聊天中使用 Agent 配置
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户和AI用户是否为该聊天的参与者
+/// - 删除操作：删除 chat_agent_overrides 表中该聊天的配置覆盖
+/// - 读取操作：查询 users 表确认用户为AI用户，查询 agents 表获取模型配置
+/// - 写入操作：配置不存在时在 agents 表中创建默认配置
+#[tauri::command]
+pub async fn reset_chat_agent_override(
+    state: State<'_, AppState>,
+    chat_id: String,
+    agent_user_id: String,
+) -> Result<ChatAgentConfigResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+    let current_user = state.current_user.lock().expect("无法获取当前用户状态");
+
+    AgentService::reset_chat_override(&pool, &current_user.id, &chat_id, &agent_user_id)
+        .map(|agent| ChatAgentConfigResponse {
+            effective: AgentResponse::from(agent),
+            overrides: None,
+        })
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,159 @@
+// AI 回复任务相关命令
+use serde::Serialize;
+use tauri::State;
+
+use crate::AppState;
+use crate::ai_queue::AiJobQueue;
+use crate::models::AiJob;
+use crate::services::ai_job_service::AiJobService;
+
+#[derive(Debug, Serialize)]
+pub struct AiJobResponse {
+    pub id: String,
+    pub chat_id: String,
+    pub agent_user_id: String,
+    pub trigger_message_id: Option<String>,
+    pub reply_message_id: Option<String>,
+    pub status: String,
+    pub error_message: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+}
+
+impl From<AiJob> for AiJobResponse {
+    fn from(job: AiJob) -> Self {
+        Self {
+            id: job.id,
+            chat_id: job.chat_id,
+            agent_user_id: job.agent_user_id,
+            trigger_message_id: job.trigger_message_id,
+            reply_message_id: job.reply_message_id,
+            status: job.status,
+            error_message: job.error_message,
+            created_at: job.created_at.to_string(),
+            updated_at: job.updated_at.to_string(),
+            started_at: job.started_at.map(|t| t.to_string()),
+            finished_at: job.finished_at.map(|t| t.to_string()),
+        }
+    }
+}
+
+/// 加入AI回复任务
+///
+/// 同一聊天中的任务按加入顺序依次处理，不同聊天之间并行处理。
+/// 状态变化通过 ai-job-status 事件推送，增量输出通过 ai-job-delta 事件推送。
+/// 开始生成时即以AI用户身份在聊天中创建 streaming 状态的回复消息，生成过程中定期写入已生成的内容。
+/// 触发消息不是聊天的最新消息时，回复针对该消息生成，并作为对该消息的回复（reply_to_id）
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chats、users、chat_participants 表确认AI用户在该聊天中
+/// - 读取操作：提供触发消息时检查 messages 表中该消息属于该聊天
+/// - 写入操作：在 ai_jobs 表中创建待处理任务
+/// - 任务开始时在 messages 表中创建回复消息，生成过程中更新其内容，结束时更新其状态
+/// - 任务结束时更新 ai_jobs 表中的状态
+#[tauri::command]
+pub async fn enqueue_ai_reply(
+    queue: State<'_, AiJobQueue>,
+    chat_id: String,
+    agent_user_id: String,
+    trigger_message_id: Option<String>,
+) -> Result<AiJobResponse, String> {
+    queue
+        .enqueue(&chat_id, &agent_user_id, trigger_message_id.as_deref())
+        .map(AiJobResponse::from)
+        .map_err(|e| e.to_string())
+}
+
+/// 按聊天的回复策略安排AI回复
+///
+/// 发送消息后调用，由聊天的回复策略决定哪些AI参与者回复该消息，消息中被 @ 到的AI参与者总会回复，
+/// 但一条消息最多安排 max_ai_turns 个回复者；
+/// 回复者按顺序依次回复，后回复的AI能看到先回复的内容，多人聊天中其他成员的消息会标注发送者名称。
+/// AI的回复完成后会自动为其中 @ 到的其他AI安排回复，直到达到聊天的连续回复上限 max_ai_turns。
+/// 已有针对该消息的未结束任务的AI不会重复安排；返回加入的任务，没有AI需要回复时返回空列表
+///
+/// ## 数据库影响
+/// - 读取操作：从 messages、chats、chat_participants 和 users 表中读取消息、聊天的回复策略和AI参与者
+/// - 读取操作：从 messages 表中读取该消息所在的对话路径，从 ai_jobs 表中读取未结束的任务
+/// - 读取操作：moderator 策略下读取主持人的 agents 和 chat_agent_overrides 配置及最近的聊天记录
+/// - 写入操作：为每个回复者在 ai_jobs 表中创建待处理任务，之后与 enqueue_ai_reply 相同
+#[tauri::command]
+pub async fn dispatch_ai_replies(
+    queue: State<'_, AiJobQueue>,
+    message_id: String,
+) -> Result<Vec<AiJobResponse>, String> {
+    queue
+        .dispatch(&message_id)
+        .await
+        .map(|jobs| jobs.into_iter().map(AiJobResponse::from).collect())
+        .map_err(|e| e.to_string())
+}
+
+/// 重新生成AI回复
+///
+/// 原回复不会被覆盖：原回复及其后续消息移出当前分支但保留，新回复接在原回复的上一条消息之后生成，
+/// 与原回复互为兄弟节点，可通过 switch_message_branch 在各版本之间切换。
+/// 任务开始前聊天中又发送了消息时，新回复仍接在原回复的上一条消息之后，这些消息移出当前分支。
+/// 当前分支上有正在生成的回复时不能重新生成
+///
+/// ## 数据库影响
+/// - 读取操作：从 messages 和 users 表中确认该消息为当前分支上的AI回复
+/// - 修改操作：更新 messages 表中原回复及其后续消息的 is_active，清空上一条消息的 active_child_id
+/// - 修改操作：更新 chats 表中的最后消息
+/// - 写入操作：在 ai_jobs 表中创建待处理任务并记录原回复（regenerated_message_id），之后与 enqueue_ai_reply 相同
+#[tauri::command]
+pub async fn regenerate_ai_reply(
+    queue: State<'_, AiJobQueue>,
+    message_id: String,
+) -> Result<AiJobResponse, String> {
+    queue
+        .regenerate(&message_id)
+        .map(AiJobResponse::from)
+        .map_err(|e| e.to_string())
+}
+
+/// 取消AI回复任务
+///
+/// 待处理的任务直接取消，处理中的任务会中断生成并保留已生成的内容；返回是否取消成功
+///
+/// ## 数据库影响
+/// - 修改操作：将 ai_jobs 表中该任务的状态更新为 cancelled
+/// - 修改操作：处理中的任务将 messages 表中的回复消息状态更新为 cancelled
+#[tauri::command]
+pub async fn cancel_ai_job(queue: State<'_, AiJobQueue>, job_id: String) -> Result<bool, String> {
+    queue.cancel(&job_id).map_err(|e| e.to_string())
+}
+
+/// 取消聊天中所有未结束的AI回复任务
+///
+/// 返回取消的任务数
+///
+/// ## 数据库影响
+/// - 读取操作：查询 ai_jobs 表中该聊天未结束的任务
+/// - 修改操作：将这些任务的状态更新为 cancelled
+#[tauri::command]
+pub async fn cancel_chat_ai_jobs(queue: State<'_, AiJobQueue>, chat_id: String) -> Result<usize, String> {
+    queue.cancel_chat(&chat_id).map_err(|e| e.to_string())
+}
+
+/// 获取聊天中的AI回复任务
+///
+/// active_only 为 true 时只返回待处理和处理中的任务，按加入顺序排列
+///
+/// ## 数据库影响
+/// - 读取操作：查询 ai_jobs 表
+/// - 无写入或修改操作
+#[tauri::command]
+pub async fn get_chat_ai_jobs(
+    state: State<'_, AppState>,
+    chat_id: String,
+    active_only: Option<bool>,
+) -> Result<Vec<AiJobResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+
+    AiJobService::get_chat_jobs(&pool, &chat_id, active_only.unwrap_or(false))
+        .map(|jobs| jobs.into_iter().map(AiJobResponse::from).collect())
+        .map_err(|e| e.to_string())
+}
@@ -1,99 +1,525 @@
 // 聊天相关命令
 use crate::AppState;
-use crate::models::ChatParticipant;
-use crate::repositories::{chat_participant_repository::ChatParticipantRepository, chat_repository::ChatRepository};
+use crate::ai_queue::AiJobQueue;
+use crate::models::{ChatSettingsChangeset, ReplyPolicy};
+use crate::repositories::chat_repository::ChatListEntry;
+use crate::services::chat_service::ChatService;
+use crate::services::group_chat_service::GroupChatService;
+use crate::services::trash_service::TrashService;
 use serde::{Deserialize, Serialize};
 use tauri::State;
 
+// 聊天列表默认每页条数
+const DEFAULT_CHAT_PAGE_SIZE: i64 = 50;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatParticipantPreview {
+    pub id: String,
+    pub name: String,
+    pub avatar_url: Option<String>,
+    pub is_ai: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatListItemResponse {
     pub id: String,
     pub name: String,
     pub avatar: String,
+    pub avatar_urls: Vec<String>,  // 聊天头像URL列表，群聊为多个成员头像
+    pub participants: Vec<ChatParticipantPreview>,
+    pub is_pinned: bool,
+    pub is_muted: bool,                  // 免打扰，未读消息不计入未读总数
+    pub is_archived: bool,
+    pub sort_order: Option<i32>,         // 自定义排序，数值小的在前
+    pub display_name: Option<String>,    // 当前用户设置的自定义名称，设置后作为 name 返回
     pub last_message: Option<String>,
     pub timestamp: Option<String>,
     pub created_at: Option<String>,  // 原始创建时间，ISO格式
     pub updated_at: Option<String>,  // 原始更新时间，ISO格式
-    pub unread: Option<i32>,
+    pub unread: Option<i64>,
+    pub forked_from_chat_id: Option<String>,     // 分出该聊天的来源聊天
+    pub forked_from_message_id: Option<String>,  // 在来源聊天中分出的消息
+    pub deleted_at: Option<String>,              // 移入回收站的时间，未删除时为空
+    pub reply_policy: String,                    // 由哪些 AI 参与者回复：mention / round_robin / everyone / moderator
+    pub moderator_user_id: Option<String>,       // moderator 策略下挑选回复者的 AI 参与者
+    pub max_ai_turns: i32,                       // 用户发言后 AI 之间最多连续回复的条数
+    pub chat_type: String,                       // 聊天类型：direct / group
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatListResponse {
     pub chats: Vec<ChatListItemResponse>,
     pub total: usize,
+    pub has_more: bool,
 }
 
 /// 获取当前用户的聊天列表
 /// 
-/// 返回当前用户参与的所有聊天，包括聊天基本信息和最后一条消息
+/// 返回当前用户参与的聊天，包括聊天基本信息、最后一条消息、所有参与者和当前用户的聊天设置；
+/// 置顶的聊天排在最前，同组内设置了自定义排序的聊天按排序值升序排在前面，其余按最后消息时间倒序排列，
+/// 可通过 offset 和 limit 分页（limit 默认 50，最多 200）；已归档的聊天默认不返回，include_archived 为 true 时一并返回
+///
+/// ## 数据库影响
+/// - 读取操作：联表查询 chats 和 chat_participants 表获取一页聊天
+/// - 读取操作：联表查询 chat_participants 和 users 表批量获取这一页聊天的参与者
+/// - 读取操作：联表查询 chat_participants 和 messages 表批量统计当前用户在这一页聊天中的未读消息数
+/// - 读取操作：统计 chat_participants 表中当前用户参与的聊天总数，与列表使用相同的归档过滤条件
+/// - 无写入、修改或删除操作
 #[tauri::command]
 pub fn get_current_user_chat_list(
     state: State<'_, AppState>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+    include_archived: Option<bool>,
 ) -> Result<ChatListResponse, String> {
     // 获取数据库连接池和当前用户
     let pool = state.db_pool.lock().map_err(|_| "无法获取数据库连接池".to_string())?;
     let current_user = state.current_user.lock().map_err(|_| "无法获取当前用户状态".to_string())?;
-    
-    // 获取用户参与的所有聊天
-    let participants = ChatParticipantRepository::get_by_user_id(&pool, &current_user.id)
-        .map_err(|e| format!("获取用户参与的聊天失败: {}", e))?;
-    
-    // 用于保存结果的向量
-    let mut chat_list = Vec::new();
-    
-    // 遍历所有参与的聊天
-    for participant in participants {
-        // 获取聊天基本信息
-        let chat = ChatRepository::get(&pool, &participant.chat_id)
-            .map_err(|e| format!("获取聊天信息失败: {}", e))?;
-        
-        // 获取聊天的所有参与者
-        let chat_participants = ChatParticipantRepository::get_by_chat_id(&pool, &chat.id)
-            .map_err(|e| format!("获取聊天参与者失败: {}", e))?;
-        
-        // 查找非当前用户的参与者作为聊天名称
-        let other_participants: Vec<ChatParticipant> = chat_participants
-            .into_iter()
-            .filter(|p| p.user_id != current_user.id)
-            .collect();
-        
-        // 处理聊天名称和头像
-        let (name, avatar) = if !other_participants.is_empty() {
-            // 使用第一个其他参与者的名字作为聊天名称
-            let first_participant_id = &other_participants[0].user_id;
-            
-            // 这里可以从user表获取用户信息
-            let user_repo = crate::repositories::user_repository::UserRepository::get(&pool, first_participant_id)
-                .map_err(|e| format!("获取用户信息失败: {}", e))?;
-            
-            (user_repo.name.clone(), user_repo.name.chars().next().unwrap_or('?').to_string())
-        } else {
-            // 如果没有其他参与者，使用聊天ID作为名称
-            (format!("聊天 {}", chat.id), "?".to_string())
-        };
-        
-        // 直接使用聊天对象中存储的最后消息信息
-        let formatted_time = chat.last_message_time.map(|t| t.to_string());
-        
-        // 添加到结果列表
-        chat_list.push(ChatListItemResponse {
-            id: chat.id,
-            name,
-            avatar,
-            last_message: chat.last_message, // 直接使用存储的最后消息
-            timestamp: formatted_time, // 使用存储的最后消息时间
-            created_at: Some(chat.created_at.to_string()),
-            updated_at: Some(chat.updated_at.to_string()),
-            unread: Some(chat.unread_count), // 使用存储的未读消息数
-        });
-    }
-    
-    // 计算聊天列表长度
-    let total = chat_list.len();
-    
-    // 返回结果
+
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_CHAT_PAGE_SIZE);
+
+    let (entries, total) = ChatService::get_user_chat_list(&pool, &current_user.id, include_archived.unwrap_or(false), offset, limit)
+        .map_err(|e| format!("获取聊天列表失败: {}", e))?;
+
+    let has_more = offset.max(0) + (entries.len() as i64) < total;
+    let chat_list = entries
+        .into_iter()
+        .map(|entry| build_chat_list_item(entry, &current_user.id))
+        .collect();
+
     Ok(ChatListResponse {
         chats: chat_list,
-        total,
+        total: total as usize,
+        has_more,
+    })
+}
+
+/// 创建与联系人的单聊
+///
+/// 如果当前用户与该联系人已有单聊，则直接返回已有聊天
+///
+/// ## 数据库影响
+/// - 读取操作：检查 user_contacts 表中是否存在联系人关系
+/// - 读取操作：联表查询 chats 和 chat_participants 表中是否已有两人的单聊（chat_type 为 direct）
+/// - 写入操作：不存在时在 chats 表中创建聊天，并在 chat_participants 表中添加双方
+/// - 使用事务确保聊天和参与者同时创建
+/// - 读取操作：联表查询 chats、chat_participants 和 users 表组装返回的聊天列表项
+#[tauri::command]
+pub async fn create_direct_chat(
+    state: State<'_, AppState>,
+    contact_id: String,
+) -> Result<ChatListItemResponse, String> {
+    let pool = state.db_pool.lock().map_err(|_| "无法获取数据库连接池".to_string())?;
+    let current_user = state.current_user.lock().map_err(|_| "无法获取当前用户状态".to_string())?;
+
+    let chat = ChatService::create_direct_chat(&pool, &current_user.id, &contact_id)
+        .map_err(|e| e.to_string())?;
+
+    let entry = ChatService::get_user_chat_entry(&pool, &current_user.id, &chat.id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(build_chat_list_item(entry, &current_user.id))
+}
+
+/// 创建群聊
+///
+/// 当前用户作为创建者自动加入群聊
+///
+/// ## 数据库影响
+/// - 读取操作：从 users 表中校验所有成员是否存在
+/// - 写入操作：在 chats 表中创建聊天，并在 chat_participants 表中添加创建者和所有成员
+/// - 使用事务确保聊天和参与者同时创建
+/// - 读取操作：联表查询 chats、chat_participants 和 users 表组装返回的聊天列表项
+#[tauri::command]
+pub async fn create_group_chat(
+    state: State<'_, AppState>,
+    name: String,
+    participant_ids: Vec<String>,
+) -> Result<ChatListItemResponse, String> {
+    let pool = state.db_pool.lock().map_err(|_| "无法获取数据库连接池".to_string())?;
+    let current_user = state.current_user.lock().map_err(|_| "无法获取当前用户状态".to_string())?;
+
+    let chat = ChatService::create_group_chat(&pool, &current_user.id, &name, &participant_ids)
+        .map_err(|e| e.to_string())?;
+
+    let entry = ChatService::get_user_chat_entry(&pool, &current_user.id, &chat.id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(build_chat_list_item(entry, &current_user.id))
+}
+
+/// 从指定消息分出新聊天
+///
+/// 新聊天沿用原聊天的名称、全部参与者和 AI 配置覆盖，并复制从第一条消息到指定消息的整条对话路径，
+/// 复制的消息使用新ID并保留原有时间；原聊天不受影响，新聊天记录来源聊天和分出的消息
+///
+/// ## 数据库影响
+/// - 读取操作：从 messages 表中获取指定消息及其之前的整条对话路径
+/// - 读取操作：检查 chat_participants 表中当前用户是否为该聊天的参与者
+/// - 写入操作：在 chats 表中创建新聊天，记录 forked_from_chat_id 和 forked_from_message_id
+/// - 写入操作：在 chat_participants 表中复制原聊天的参与者，已读位置为最后一条复制的消息
+/// - 写入操作：在 chat_agent_overrides 表中复制原聊天的 AI 配置覆盖
+/// - 写入操作：在 messages 表中写入复制的消息，触发器同步 messages_fts 索引和聊天的最后消息
+/// - 使用事务确保聊天、参与者和消息同时创建
+/// - 读取操作：联表查询 chats、chat_participants 和 users 表组装返回的聊天列表项
+#[tauri::command]
+pub fn fork_chat(
+    state: State<'_, AppState>,
+    message_id: String,
+) -> Result<ChatListItemResponse, String> {
+    let pool = state.db_pool.lock().map_err(|_| "无法获取数据库连接池".to_string())?;
+    let current_user = state.current_user.lock().map_err(|_| "无法获取当前用户状态".to_string())?;
+
+    let chat = ChatService::fork_chat(&pool, &current_user.id, &message_id)
+        .map_err(|e| e.to_string())?;
+
+    let entry = ChatService::get_user_chat_entry(&pool, &current_user.id, &chat.id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(build_chat_list_item(entry, &current_user.id))
+}
+
+/// 删除聊天
+///
+/// 聊天移入回收站，对所有参与者都不再显示，同时取消该聊天中未结束的AI回复任务；
+/// 超过回收站保留天数后彻底删除，此前可通过 restore_chat 恢复
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户是否为该聊天的参与者
+/// - 修改操作：更新 chats 表中该聊天的 deleted_at
+/// - 修改操作：将 ai_jobs 表中该聊天未结束的任务标记为已取消
+#[tauri::command]
+pub fn delete_chat(
+    state: State<'_, AppState>,
+    queue: State<'_, AiJobQueue>,
+    chat_id: String,
+) -> Result<(), String> {
+    let pool = state.db_pool.lock().map_err(|_| "无法获取数据库连接池".to_string())?;
+    let current_user = state.current_user.lock().map_err(|_| "无法获取当前用户状态".to_string())?;
+
+    ChatService::delete_chat(&pool, &current_user.id, &chat_id)
+        .map_err(|e| e.to_string())?;
+
+    queue.cancel_chat(&chat_id).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 从回收站恢复聊天
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户是否为该聊天的参与者
+/// - 修改操作：清除 chats 表中该聊天的 deleted_at
+/// - 读取操作：联表查询 chats、chat_participants 和 users 表组装返回的聊天列表项
+#[tauri::command]
+pub fn restore_chat(
+    state: State<'_, AppState>,
+    chat_id: String,
+) -> Result<ChatListItemResponse, String> {
+    let pool = state.db_pool.lock().map_err(|_| "无法获取数据库连接池".to_string())?;
+    let current_user = state.current_user.lock().map_err(|_| "无法获取当前用户状态".to_string())?;
+
+    ChatService::restore_chat(&pool, &current_user.id, &chat_id)
+        .map_err(|e| e.to_string())?;
+
+    let entry = ChatService::get_user_chat_entry(&pool, &current_user.id, &chat_id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(build_chat_list_item(entry, &current_user.id))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletedChatListResponse {
+    pub chats: Vec<ChatListItemResponse>,
+    pub retention_days: i64, // 回收站保留天数，删除时间超过该天数的聊天会被彻底删除
+}
+
+/// 获取回收站中的聊天
+///
+/// 返回当前用户参与的已删除聊天，按删除时间倒序排列
+///
+/// ## 数据库影响
+/// - 读取操作：联表查询 chats 和 chat_participants 表获取已删除的聊天
+/// - 读取操作：联表查询 chat_participants 和 users 表批量获取这些聊天的参与者
+/// - 无写入、修改或删除操作
+#[tauri::command]
+pub fn get_deleted_chats(state: State<'_, AppState>) -> Result<DeletedChatListResponse, String> {
+    let pool = state.db_pool.lock().map_err(|_| "无法获取数据库连接池".to_string())?;
+    let current_user = state.current_user.lock().map_err(|_| "无法获取当前用户状态".to_string())?;
+
+    let entries = ChatService::get_deleted_chats(&pool, &current_user.id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(DeletedChatListResponse {
+        chats: entries
+            .into_iter()
+            .map(|entry| build_chat_list_item(entry, &current_user.id))
+            .collect(),
+        retention_days: TrashService::retention_days(),
+    })
+}
+
+/// 置顶或取消置顶聊天
+///
+/// 聊天设置只对当前用户生效，其他参与者不受影响
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户是否为该聊天的参与者
+/// - 修改操作：更新 chat_participants 表中当前用户的 is_pinned
+/// - 读取操作：联表查询 chats、chat_participants 和 users 表组装返回的聊天列表项
+#[tauri::command]
+pub fn set_chat_pinned(
+    state: State<'_, AppState>,
+    chat_id: String,
+    pinned: bool,
+) -> Result<ChatListItemResponse, String> {
+    update_chat_settings(&state, &chat_id, ChatSettingsChangeset {
+        is_pinned: Some(pinned),
+        ..Default::default()
+    })
+}
+
+/// 开启或关闭聊天的免打扰
+///
+/// 免打扰的聊天仍显示自身的未读消息数，但不计入未读总数
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户是否为该聊天的参与者
+/// - 修改操作：更新 chat_participants 表中当前用户的 is_muted
+/// - 读取操作：联表查询 chats、chat_participants 和 users 表组装返回的聊天列表项
+#[tauri::command]
+pub fn set_chat_muted(
+    state: State<'_, AppState>,
+    chat_id: String,
+    muted: bool,
+) -> Result<ChatListItemResponse, String> {
+    update_chat_settings(&state, &chat_id, ChatSettingsChangeset {
+        is_muted: Some(muted),
+        ..Default::default()
+    })
+}
+
+/// 归档或取消归档聊天
+///
+/// 已归档的聊天不在默认的聊天列表中显示，可通过 get_current_user_chat_list 的 include_archived 获取
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户是否为该聊天的参与者
+/// - 修改操作：更新 chat_participants 表中当前用户的 is_archived
+/// - 读取操作：联表查询 chats、chat_participants 和 users 表组装返回的聊天列表项
+#[tauri::command]
+pub fn set_chat_archived(
+    state: State<'_, AppState>,
+    chat_id: String,
+    archived: bool,
+) -> Result<ChatListItemResponse, String> {
+    update_chat_settings(&state, &chat_id, ChatSettingsChangeset {
+        is_archived: Some(archived),
+        ..Default::default()
+    })
+}
+
+/// 设置聊天的自定义排序
+///
+/// 同为置顶或非置顶的聊天中，设置了排序的聊天按排序值升序排在前面；sort_order 为空时清除自定义排序
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户是否为该聊天的参与者
+/// - 修改操作：更新 chat_participants 表中当前用户的 sort_order
+/// - 读取操作：联表查询 chats、chat_participants 和 users 表组装返回的聊天列表项
+#[tauri::command]
+pub fn set_chat_sort_order(
+    state: State<'_, AppState>,
+    chat_id: String,
+    sort_order: Option<i32>,
+) -> Result<ChatListItemResponse, String> {
+    update_chat_settings(&state, &chat_id, ChatSettingsChangeset {
+        sort_order: Some(sort_order),
+        ..Default::default()
+    })
+}
+
+/// 设置聊天的自定义名称
+///
+/// 自定义名称只对当前用户显示，display_name 为空或只包含空白时恢复默认名称
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户是否为该聊天的参与者
+/// - 修改操作：更新 chat_participants 表中当前用户的 display_name
+/// - 读取操作：联表查询 chats、chat_participants 和 users 表组装返回的聊天列表项
+#[tauri::command]
+pub fn set_chat_display_name(
+    state: State<'_, AppState>,
+    chat_id: String,
+    display_name: Option<String>,
+) -> Result<ChatListItemResponse, String> {
+    update_chat_settings(&state, &chat_id, ChatSettingsChangeset {
+        display_name: Some(display_name),
+        ..Default::default()
     })
-}
\ No newline at end of file
+}
+
+// 修改当前用户对聊天的个人设置，并返回更新后的聊天列表项
+fn update_chat_settings(
+    state: &State<'_, AppState>,
+    chat_id: &str,
+    changes: ChatSettingsChangeset,
+) -> Result<ChatListItemResponse, String> {
+    let pool = state.db_pool.lock().map_err(|_| "无法获取数据库连接池".to_string())?;
+    let current_user = state.current_user.lock().map_err(|_| "无法获取当前用户状态".to_string())?;
+
+    ChatService::update_chat_settings(&pool, &current_user.id, chat_id, changes)
+        .map_err(|e| e.to_string())?;
+
+    let entry = ChatService::get_user_chat_entry(&pool, &current_user.id, chat_id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(build_chat_list_item(entry, &current_user.id))
+}
+
+/// 设置聊天的回复策略
+///
+/// 决定用户发言后由哪些AI参与者回复：mention 只有被 @ 到的AI回复，round_robin 按加入顺序轮流回复，
+/// everyone 所有AI依次回复，moderator 由 moderator_user_id 指定的主持人AI挑选回复者。
+/// 无论哪种策略，被 @ 到的AI都会回复；AI的回复只会触发其中 @ 到的其他AI，
+/// 一条用户消息最多安排 max_ai_turns 个回复者，用户发言后AI连续回复 max_ai_turns 条后不再继续，
+/// max_ai_turns 为空时保持不变。
+/// 回复策略对聊天的所有参与者生效
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户和主持人是否为该聊天的参与者
+/// - 读取操作：查询 users 表确认主持人为AI用户
+/// - 修改操作：更新 chats 表中的 reply_policy、moderator_user_id 和 max_ai_turns
+/// - 读取操作：联表查询 chats、chat_participants 和 users 表组装返回的聊天列表项
+#[tauri::command]
+pub fn update_chat_reply_policy(
+    state: State<'_, AppState>,
+    chat_id: String,
+    policy: ReplyPolicy,
+    moderator_user_id: Option<String>,
+    max_ai_turns: Option<i32>,
+) -> Result<ChatListItemResponse, String> {
+    let pool = state.db_pool.lock().map_err(|_| "无法获取数据库连接池".to_string())?;
+    let current_user = state.current_user.lock().map_err(|_| "无法获取当前用户状态".to_string())?;
+
+    GroupChatService::update_reply_policy(
+        &pool,
+        &current_user.id,
+        &chat_id,
+        policy,
+        moderator_user_id.as_deref(),
+        max_ai_turns,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let entry = ChatService::get_user_chat_entry(&pool, &current_user.id, &chat_id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(build_chat_list_item(entry, &current_user.id))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MarkChatReadResponse {
+    pub chat_id: String,
+    pub unread: i64,        // 标记后该聊天剩余的未读消息数
+    pub total_unread: i64,  // 标记后所有聊天的未读消息总数
+}
+
+/// 将聊天标记为已读
+///
+/// 把当前用户在该聊天中的已读位置推进到 up_to_message_id 指定的消息，未指定时推进到最新消息；
+/// 已读位置只前进不后退，指定更早的消息不会使已读的消息重新变为未读
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户是否为该聊天的参与者
+/// - 读取操作：从 messages 表中获取指定消息或该聊天的最新消息
+/// - 修改操作：更新 chat_participants 表中当前用户的 last_read_message_id 和 last_read_at
+/// - 读取操作：联表查询 chat_participants 和 messages 表统计剩余未读消息数和未读总数
+#[tauri::command]
+pub fn mark_chat_read(
+    state: State<'_, AppState>,
+    chat_id: String,
+    up_to_message_id: Option<String>,
+) -> Result<MarkChatReadResponse, String> {
+    let pool = state.db_pool.lock().map_err(|_| "无法获取数据库连接池".to_string())?;
+    let current_user = state.current_user.lock().map_err(|_| "无法获取当前用户状态".to_string())?;
+
+    let unread = ChatService::mark_chat_read(&pool, &current_user.id, &chat_id, up_to_message_id.as_deref())
+        .map_err(|e| e.to_string())?;
+    let total_unread = ChatService::get_total_unread(&pool, &current_user.id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(MarkChatReadResponse { chat_id, unread, total_unread })
+}
+
+/// 获取当前用户所有聊天的未读消息总数
+///
+/// 用于应用图标和导航栏上的未读角标，设置了免打扰的聊天不计入
+///
+/// ## 数据库影响
+/// - 读取操作：联表查询 chat_participants 和 messages 表统计当前用户在未免打扰的聊天中已读位置之后他人发送的消息数
+/// - 无写入、修改或删除操作
+#[tauri::command]
+pub fn get_total_unread_count(state: State<'_, AppState>) -> Result<i64, String> {
+    let pool = state.db_pool.lock().map_err(|_| "无法获取数据库连接池".to_string())?;
+    let current_user = state.current_user.lock().map_err(|_| "无法获取当前用户状态".to_string())?;
+
+    ChatService::get_total_unread(&pool, &current_user.id).map_err(|e| e.to_string())
+}
+
+// 将聊天列表项转换为响应格式
+fn build_chat_list_item(entry: ChatListEntry, current_user_id: &str) -> ChatListItemResponse {
+    let ChatListEntry { chat, settings, unread_count, participants } = entry;
+
+    // 查找非当前用户的参与者
+    let others: Vec<_> = participants.iter().filter(|p| p.id != current_user_id).collect();
+
+    // 优先使用当前用户设置的自定义名称，否则单聊使用对方的名字，群聊使用创建时设置的名称
+    let name = match (&settings.display_name, others.as_slice()) {
+        (Some(display_name), _) => display_name.clone(),
+        (None, [other]) => other.name.clone(),
+        (None, _) => chat.name.clone(),
+    };
+    let avatar = name.chars().next().unwrap_or('?').to_string();
+
+    // 头像URL：单聊优先使用对方当前的头像，否则使用聊天保存的头像列表
+    let avatar_urls = match others.as_slice() {
+        [other] if other.avatar_url.is_some() => other.avatar_url.iter().cloned().collect(),
+        _ => serde_json::from_str(&chat.avatar_urls).unwrap_or_default(),
+    };
+
+    let participants = participants
+        .into_iter()
+        .map(|user| ChatParticipantPreview {
+            id: user.id,
+            name: user.name,
+            avatar_url: user.avatar_url,
+            is_ai: user.is_ai,
+        })
+        .collect();
+
+    ChatListItemResponse {
+        id: chat.id,
+        name,
+        avatar,
+        avatar_urls,
+        participants,
+        is_pinned: settings.is_pinned,
+        is_muted: settings.is_muted,
+        is_archived: settings.is_archived,
+        sort_order: settings.sort_order,
+        display_name: settings.display_name,
+        last_message: chat.last_message, // 直接使用存储的最后消息
+        timestamp: chat.last_message_time.map(|t| t.to_string()), // 使用存储的最后消息时间
+        created_at: Some(chat.created_at.to_string()),
+        updated_at: Some(chat.updated_at.to_string()),
+        unread: Some(unread_count), // 当前用户已读位置之后他人发送的消息数
+        forked_from_chat_id: chat.forked_from_chat_id,
+        forked_from_message_id: chat.forked_from_message_id,
+        deleted_at: chat.deleted_at.map(|t| t.to_string()),
+        reply_policy: chat.reply_policy,
+        moderator_user_id: chat.moderator_user_id,
+        max_ai_turns: chat.max_ai_turns,
+        chat_type: chat.chat_type,
+    }
+}
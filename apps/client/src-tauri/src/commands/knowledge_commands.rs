@@ -0,0 +1,90 @@
+// AI 用户知识库相关命令
+use serde::Serialize;
+use tauri::State;
+
+use crate::AppState;
+use crate::services::agent_service::AgentService;
+use crate::services::knowledge_service::{KnowledgeResource, KnowledgeService};
+use super::resource_commands::ResourceResponse;
+
+#[derive(Debug, Serialize)]
+pub struct KnowledgeResourceResponse {
+    pub resource: ResourceResponse,
+    pub chunk_count: i64, // 按AI用户当前的向量模型已生成的片段数，为 0 时尚未索引
+}
+
+impl From<KnowledgeResource> for KnowledgeResourceResponse {
+    fn from(item: KnowledgeResource) -> Self {
+        Self {
+            resource: ResourceResponse::from(item.resource),
+            chunk_count: item.chunk_count,
+        }
+    }
+}
+
+/// 获取AI用户关联的知识库资源
+///
+/// ## 数据库影响
+/// - 读取操作：查询 users 表确认用户为AI用户，查询 agents 表获取向量模型
+/// - 写入操作：配置不存在时在 agents 表中创建默认配置
+/// - 读取操作：联合查询 agent_knowledge_resources 和 resources 表获取关联的资源
+/// - 读取操作：统计 resource_chunks 表中各资源已生成的片段数
+#[tauri::command]
+pub async fn get_agent_knowledge_resources(
+    state: State<'_, AppState>,
+    agent_user_id: String,
+) -> Result<Vec<KnowledgeResourceResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+
+    KnowledgeService::get_agent_resources(&pool, &agent_user_id)
+        .map(|resources| resources.into_iter().map(KnowledgeResourceResponse::from).collect())
+        .map_err(|e| e.to_string())
+}
+
+/// 设置AI用户关联的知识库资源
+///
+/// 以传入的资源整体替换原有的关联，只能关联文本资源。AI用户回复时检索其中与对话相关的片段放入上下文，
+/// 并要求模型以 [资源:资源ID] 注明引用的来源；片段在首次检索或调用 reindex_agent_knowledge 时生成
+///
+/// ## 数据库影响
+/// - 读取操作：查询 users 表确认用户为AI用户，查询 resources 表确认各资源为文本资源
+/// - 删除操作：删除 agent_knowledge_resources 表中该AI用户原有的关联
+/// - 写入操作：在 agent_knowledge_resources 表中为每个资源创建关联
+/// - 使用事务确保替换的原子性
+#[tauri::command]
+pub async fn set_agent_knowledge_resources(
+    state: State<'_, AppState>,
+    agent_user_id: String,
+    resource_ids: Vec<String>,
+) -> Result<Vec<KnowledgeResourceResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+
+    KnowledgeService::set_agent_resources(&pool, &agent_user_id, &resource_ids)
+        .map(|resources| resources.into_iter().map(KnowledgeResourceResponse::from).collect())
+        .map_err(|e| e.to_string())
+}
+
+/// 为AI用户的知识库生成片段
+///
+/// 使用AI用户的服务商和向量模型（embedding_model，为空时使用默认模型）为关联的资源切分片段并生成向量；
+/// 内容未变化的资源跳过，任一资源失败时返回错误
+///
+/// ## 数据库影响
+/// - 读取操作：查询 agents 表获取服务商和向量模型
+/// - 读取操作：联合查询 agent_knowledge_resources 和 resources 表获取关联的资源
+/// - 删除操作：删除 resource_chunks 表中内容已变化的资源的原有片段
+/// - 写入操作：在 resource_chunks 表中写入新生成的片段
+#[tauri::command]
+pub async fn reindex_agent_knowledge(
+    state: State<'_, AppState>,
+    agent_user_id: String,
+) -> Result<Vec<KnowledgeResourceResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+    let agent = AgentService::get_agent_by_user(&pool, &agent_user_id).map_err(|e| e.to_string())?;
+    let provider = state.llm_providers.for_agent(&agent).map_err(|e| e.to_string())?;
+
+    KnowledgeService::index_agent_resources(&pool, provider.as_ref(), &agent, &state.app_resource_path)
+        .await
+        .map(|resources| resources.into_iter().map(KnowledgeResourceResponse::from).collect())
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,131 @@
+// 模型服务商相关命令
+use futures_util::future::Abortable;
+use futures_util::StreamExt;
+use serde::Serialize;
+use tauri::ipc::Channel;
+use tauri::State;
+
+use crate::AppState;
+use crate::llm::{
+    CompletionRequest, CompletionResponse, LlmError, LlmResult, ModelDescriptor,
+    ProviderCapabilities, TokenUsage, ToolCall,
+};
+
+/// 流式输出事件，通过 Channel 按顺序推送给前端
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum LlmStreamEvent {
+    // 模型输出的增量文本
+    Delta { content: String },
+    // 生成结束
+    Done {
+        finish_reason: Option<String>,
+        usage: Option<TokenUsage>,
+        // 模型请求调用的工具，由调用方执行后将结果作为 tool 消息再次请求
+        tool_calls: Vec<ToolCall>,
+    },
+}
+
+/// 获取已注册的模型服务商名称
+///
+/// 返回值可用作智能体的 provider 配置
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub fn llm_list_providers(state: State<'_, AppState>) -> Vec<String> {
+    state.llm_providers.names()
+}
+
+/// 获取指定服务商的可用模型列表
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn llm_list_models(
+    state: State<'_, AppState>,
+    provider: String,
+) -> LlmResult<Vec<ModelDescriptor>> {
+    let provider = state.llm_providers.get(&provider)?;
+    provider.list_models().await
+}
+
+/// 查询模型支持的能力（视觉、工具调用、JSON 模式等）
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn llm_model_capabilities(
+    state: State<'_, AppState>,
+    provider: String,
+    model: String,
+) -> LlmResult<ProviderCapabilities> {
+    let provider = state.llm_providers.get(&provider)?;
+    provider.capabilities(&model).await
+}
+
+/// 通过指定服务商聊天（非流式）
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn llm_chat(
+    state: State<'_, AppState>,
+    provider: String,
+    request: CompletionRequest,
+) -> LlmResult<CompletionResponse> {
+    let provider = state.llm_providers.get(&provider)?;
+    provider.complete(&request).await
+}
+
+/// 通过指定服务商聊天（流式）
+///
+/// 增量输出通过 on_event 推送，与 Ollama 流式请求共用中断登记，
+/// 可使用相同的 stream_id 调用 ollama_abort_stream 中断
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn llm_chat_stream(
+    state: State<'_, AppState>,
+    provider: String,
+    stream_id: String,
+    request: CompletionRequest,
+    on_event: Channel<LlmStreamEvent>,
+) -> LlmResult<()> {
+    let provider = state.llm_providers.get(&provider)?;
+    let (generation, registration) = state.ollama_streams.register(&stream_id);
+
+    let result = async {
+        let stream = provider.complete_stream(&request).await?;
+        let mut stream = Abortable::new(stream, registration);
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            if !chunk.delta.is_empty() {
+                send_event(&on_event, LlmStreamEvent::Delta { content: chunk.delta })?;
+            }
+            if chunk.done {
+                let done = LlmStreamEvent::Done {
+                    finish_reason: chunk.finish_reason,
+                    usage: chunk.usage,
+                    tool_calls: chunk.tool_calls,
+                };
+                send_event(&on_event, done)?;
+            }
+        }
+
+        if stream.is_aborted() {
+            return Err(LlmError::StreamAborted);
+        }
+        Ok(())
+    }
+    .await;
+
+    state.ollama_streams.remove(&stream_id, generation);
+    result
+}
+
+fn send_event(channel: &Channel<LlmStreamEvent>, event: LlmStreamEvent) -> LlmResult<()> {
+    channel.send(event).map_err(|_| LlmError::StreamAborted)
+}
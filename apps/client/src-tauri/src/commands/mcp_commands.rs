@@ -0,0 +1,268 @@
+// MCP 服务器相关命令
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::State;
+
+use crate::AppState;
+use crate::mcp::types::{
+    CallToolResult, GetPromptResult, InitializeResult, McpPromptInfo, McpResourceInfo, McpToolInfo, PromptArguments,
+    ReadResourceResult,
+};
+use crate::mcp::McpClient;
+use crate::models::McpServer;
+use crate::services::mcp_service::{McpServerConfig, McpService};
+
+#[derive(Debug, Serialize)]
+pub struct McpServerResponse {
+    pub id: String,
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub cwd: Option<String>,
+    pub enabled: bool,
+    pub running: bool, // 服务器进程是否在运行
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl McpServerResponse {
+    fn new(server: McpServer, running_ids: &[String]) -> Self {
+        Self {
+            running: running_ids.contains(&server.id),
+            args: McpService::decode_args(&server.args),
+            env: McpService::decode_env(&server.env),
+            id: server.id,
+            name: server.name,
+            command: server.command,
+            cwd: server.cwd,
+            enabled: server.enabled,
+            created_at: server.created_at.to_string(),
+            updated_at: server.updated_at.to_string(),
+        }
+    }
+}
+
+// 启动或复用服务器的连接，已停用的服务器不能连接
+async fn connect(state: &AppState, server_id: &str) -> Result<Arc<McpClient>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+    let server = McpService::get_server(&pool, server_id).map_err(|e| e.to_string())?;
+    if !server.enabled {
+        return Err(format!("MCP服务器已停用: {}", server.name));
+    }
+    state.mcp.connect(&server).await.map_err(|e| e.to_string())
+}
+
+async fn to_responses(state: &AppState, servers: Vec<McpServer>) -> Vec<McpServerResponse> {
+    let running_ids = state.mcp.running_server_ids().await;
+    servers
+        .into_iter()
+        .map(|server| McpServerResponse::new(server, &running_ids))
+        .collect()
+}
+
+/// 获取全部 MCP 服务器配置
+///
+/// ## 数据库影响
+/// - 读取操作：查询 mcp_servers 表中的全部服务器，按名称排序
+#[tauri::command]
+pub async fn get_mcp_servers(state: State<'_, AppState>) -> Result<Vec<McpServerResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+    let servers = McpService::get_servers(&pool).map_err(|e| e.to_string())?;
+    Ok(to_responses(&state, servers).await)
+}
+
+/// 添加 MCP 服务器
+///
+/// 服务器以子进程方式启动，command 为可执行文件，不经过 shell 解析；
+/// 名称只能包含字母、数字、下划线和连字符，作为其工具名称的前缀，不能与已有服务器重复
+///
+/// ## 数据库影响
+/// - 读取操作：检查 mcp_servers 表中名称是否已被使用
+/// - 写入操作：在 mcp_servers 表中创建服务器配置
+#[tauri::command]
+pub async fn create_mcp_server(
+    state: State<'_, AppState>,
+    config: McpServerConfig,
+) -> Result<McpServerResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+    let server = McpService::create_server(&pool, config).map_err(|e| e.to_string())?;
+    Ok(McpServerResponse::new(server, &[]))
+}
+
+/// 更新 MCP 服务器配置
+///
+/// 以传入的配置整体替换原有配置；正在运行的服务器会被结束，下次使用时按新配置启动
+///
+/// ## 数据库影响
+/// - 读取操作：检查 mcp_servers 表中服务器存在且名称未被其他服务器使用
+/// - 修改操作：更新 mcp_servers 表中的服务器配置
+#[tauri::command]
+pub async fn update_mcp_server(
+    state: State<'_, AppState>,
+    id: String,
+    config: McpServerConfig,
+) -> Result<McpServerResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+    let server = McpService::update_server(&pool, &id, config).map_err(|e| e.to_string())?;
+    state.mcp.disconnect(&id).await;
+    Ok(McpServerResponse::new(server, &[]))
+}
+
+/// 删除 MCP 服务器
+///
+/// 正在运行的服务器会被结束，各AI用户对该服务器的启用一并删除
+///
+/// ## 数据库影响
+/// - 删除操作：删除 mcp_servers 表中的服务器配置
+/// - 删除操作：删除 agent_mcp_servers 表中对该服务器的启用记录
+/// - 使用事务确保服务器配置和启用记录一并删除
+#[tauri::command]
+pub async fn delete_mcp_server(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+    McpService::delete_server(&pool, &id).map_err(|e| e.to_string())?;
+    state.mcp.disconnect(&id).await;
+    Ok(())
+}
+
+/// 获取AI用户启用的 MCP 服务器
+///
+/// ## 数据库影响
+/// - 读取操作：查询 users 表确认用户为AI用户
+/// - 读取操作：联合查询 agent_mcp_servers 和 mcp_servers 表获取启用的服务器
+#[tauri::command]
+pub async fn get_agent_mcp_servers(
+    state: State<'_, AppState>,
+    agent_user_id: String,
+) -> Result<Vec<McpServerResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+    let servers = McpService::get_agent_servers(&pool, &agent_user_id).map_err(|e| e.to_string())?;
+    Ok(to_responses(&state, servers).await)
+}
+
+/// 设置AI用户启用的 MCP 服务器
+///
+/// 以传入的服务器整体替换原有的启用；AI用户回复时可以调用启用且未停用的服务器提供的工具，
+/// 工具名称为「服务器名称__工具名称」
+///
+/// ## 数据库影响
+/// - 读取操作：查询 users 表确认用户为AI用户，查询 mcp_servers 表确认各服务器存在
+/// - 删除操作：删除 agent_mcp_servers 表中该AI用户原有的启用记录
+/// - 写入操作：在 agent_mcp_servers 表中为每个服务器创建启用记录
+/// - 使用事务确保替换的原子性
+#[tauri::command]
+pub async fn set_agent_mcp_servers(
+    state: State<'_, AppState>,
+    agent_user_id: String,
+    server_ids: Vec<String>,
+) -> Result<Vec<McpServerResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+    let servers = McpService::set_agent_servers(&pool, &agent_user_id, &server_ids).map_err(|e| e.to_string())?;
+    Ok(to_responses(&state, servers).await)
+}
+
+/// 连接 MCP 服务器
+///
+/// 服务器未运行时启动并完成握手，返回服务器信息、协议版本和支持的能力（tools、resources、prompts）
+///
+/// ## 数据库影响
+/// - 读取操作：查询 mcp_servers 表获取服务器配置
+#[tauri::command]
+pub async fn connect_mcp_server(state: State<'_, AppState>, id: String) -> Result<InitializeResult, String> {
+    let client = connect(&state, &id).await?;
+    Ok(client.info().clone())
+}
+
+/// 结束 MCP 服务器进程
+///
+/// 返回服务器此前是否在运行；之后AI用户回复或调用其他 MCP 命令时会重新启动
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn disconnect_mcp_server(state: State<'_, AppState>, id: String) -> Result<bool, String> {
+    Ok(state.mcp.disconnect(&id).await)
+}
+
+/// 获取 MCP 服务器提供的工具
+///
+/// ## 数据库影响
+/// - 读取操作：查询 mcp_servers 表获取服务器配置
+#[tauri::command]
+pub async fn list_mcp_tools(state: State<'_, AppState>, id: String) -> Result<Vec<McpToolInfo>, String> {
+    let client = connect(&state, &id).await?;
+    client.list_tools().await.map_err(|e| e.to_string())
+}
+
+/// 调用 MCP 服务器提供的工具
+///
+/// 工具执行失败时返回的 is_error 为 true，content 中为错误信息
+///
+/// ## 数据库影响
+/// - 读取操作：查询 mcp_servers 表获取服务器配置
+#[tauri::command]
+pub async fn call_mcp_tool(
+    state: State<'_, AppState>,
+    id: String,
+    name: String,
+    arguments: Option<Value>,
+) -> Result<CallToolResult, String> {
+    let client = connect(&state, &id).await?;
+    let arguments = arguments.unwrap_or_else(|| Value::Object(Default::default()));
+    client.call_tool(&name, arguments).await.map_err(|e| e.to_string())
+}
+
+/// 获取 MCP 服务器提供的资源
+///
+/// ## 数据库影响
+/// - 读取操作：查询 mcp_servers 表获取服务器配置
+#[tauri::command]
+pub async fn list_mcp_resources(state: State<'_, AppState>, id: String) -> Result<Vec<McpResourceInfo>, String> {
+    let client = connect(&state, &id).await?;
+    client.list_resources().await.map_err(|e| e.to_string())
+}
+
+/// 读取 MCP 服务器提供的资源内容
+///
+/// ## 数据库影响
+/// - 读取操作：查询 mcp_servers 表获取服务器配置
+#[tauri::command]
+pub async fn read_mcp_resource(
+    state: State<'_, AppState>,
+    id: String,
+    uri: String,
+) -> Result<ReadResourceResult, String> {
+    let client = connect(&state, &id).await?;
+    client.read_resource(&uri).await.map_err(|e| e.to_string())
+}
+
+/// 获取 MCP 服务器提供的提示词模板
+///
+/// ## 数据库影响
+/// - 读取操作：查询 mcp_servers 表获取服务器配置
+#[tauri::command]
+pub async fn list_mcp_prompts(state: State<'_, AppState>, id: String) -> Result<Vec<McpPromptInfo>, String> {
+    let client = connect(&state, &id).await?;
+    client.list_prompts().await.map_err(|e| e.to_string())
+}
+
+/// 按参数填充 MCP 服务器的提示词模板
+///
+/// ## 数据库影响
+/// - 读取操作：查询 mcp_servers 表获取服务器配置
+#[tauri::command]
+pub async fn get_mcp_prompt(
+    state: State<'_, AppState>,
+    id: String,
+    name: String,
+    arguments: Option<PromptArguments>,
+) -> Result<GetPromptResult, String> {
+    let client = connect(&state, &id).await?;
+    client
+        .get_prompt(&name, &arguments.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,133 @@
+// AI 用户长期记忆相关命令
+use serde::Serialize;
+use tauri::State;
+
+use crate::AppState;
+use crate::ai_queue::AiJobQueue;
+use crate::models::AgentMemory;
+use crate::services::memory_service::MemoryService;
+
+#[derive(Debug, Serialize)]
+pub struct AgentMemoryResponse {
+    pub id: String,
+    pub agent_user_id: String,
+    pub content: String,
+    pub source_chat_id: Option<String>,    // 提取自的聊天，聊天删除后为空
+    pub source_message_id: Option<String>, // 提取自的消息，消息删除后为空
+    pub importance: i32,                   // 重要程度 1-5
+    pub is_pinned: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<AgentMemory> for AgentMemoryResponse {
+    fn from(memory: AgentMemory) -> Self {
+        Self {
+            id: memory.id,
+            agent_user_id: memory.agent_user_id,
+            content: memory.content,
+            source_chat_id: memory.source_chat_id,
+            source_message_id: memory.source_message_id,
+            importance: memory.importance,
+            is_pinned: memory.is_pinned,
+            created_at: memory.created_at.to_string(),
+            updated_at: memory.updated_at.to_string(),
+        }
+    }
+}
+
+/// 获取AI用户的长期记忆
+///
+/// 置顶的记忆在前，其余按重要程度和更新时间降序
+///
+/// ## 数据库影响
+/// - 读取操作：查询 agent_memories 表获取该AI用户的全部记忆
+#[tauri::command]
+pub async fn get_agent_memories(
+    state: State<'_, AppState>,
+    agent_user_id: String,
+) -> Result<Vec<AgentMemoryResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+
+    MemoryService::get_agent_memories(&pool, &agent_user_id)
+        .map(|memories| memories.into_iter().map(AgentMemoryResponse::from).collect())
+        .map_err(|e| e.to_string())
+}
+
+/// 修改AI用户的一条记忆
+///
+/// 内容不能为空且不超过 500 个字符，重要程度为 1 到 5
+///
+/// ## 数据库影响
+/// - 修改操作：更新 agent_memories 表中该记忆的内容、重要程度和更新时间
+#[tauri::command]
+pub async fn update_agent_memory(
+    state: State<'_, AppState>,
+    id: String,
+    content: String,
+    importance: i32,
+) -> Result<AgentMemoryResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+
+    MemoryService::update_memory(&pool, &id, &content, importance)
+        .map(AgentMemoryResponse::from)
+        .map_err(|e| e.to_string())
+}
+
+/// 置顶或取消置顶AI用户的一条记忆
+///
+/// 置顶的记忆在回复时总是放入系统提示词，也不会在记忆超出上限时被自动清理
+///
+/// ## 数据库影响
+/// - 修改操作：更新 agent_memories 表中该记忆的置顶状态和更新时间
+#[tauri::command]
+pub async fn set_agent_memory_pinned(
+    state: State<'_, AppState>,
+    id: String,
+    is_pinned: bool,
+) -> Result<AgentMemoryResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+
+    MemoryService::set_memory_pinned(&pool, &id, is_pinned)
+        .map(AgentMemoryResponse::from)
+        .map_err(|e| e.to_string())
+}
+
+/// 删除AI用户的一条记忆
+///
+/// ## 数据库影响
+/// - 删除操作：删除 agent_memories 表中的该记忆
+#[tauri::command]
+pub async fn delete_agent_memory(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池").clone();
+
+    MemoryService::delete_memory(&pool, &id).map_err(|e| e.to_string())
+}
+
+/// 立即从聊天中提取AI用户的长期记忆，返回新提取的记忆
+///
+/// 不要求AI用户已开启记忆，也不要求新消息达到自动提取的条数；只处理上次提取位置之后的消息，
+/// 每次最多处理 50 条，模型的输出无法解析时返回错误。回复完成后的自动提取正在进行时等待其结束后再提取
+///
+/// ## 数据库影响
+/// - 读取操作：查询 agents 表和 chat_agent_overrides 表获取AI用户在该聊天中生效的配置
+/// - 读取操作：查询 chat_participants 表确认AI用户是聊天的参与者并获取提取位置
+/// - 读取操作：查询 messages 表获取提取位置之后的消息，查询 agent_memories 表获取已有记忆
+/// - 写入操作：在 agent_memories 表中保存新提取的记忆，超出上限时删除未置顶的低重要程度记忆
+/// - 修改操作：更新 chat_participants 表中的提取位置
+/// - 使用事务确保保存记忆和推进提取位置的原子性
+#[tauri::command]
+pub async fn extract_agent_memories(
+    queue: State<'_, AiJobQueue>,
+    chat_id: String,
+    agent_user_id: String,
+) -> Result<Vec<AgentMemoryResponse>, String> {
+    queue
+        .extract_memories(&chat_id, &agent_user_id)
+        .await
+        .map(|memories| memories.into_iter().map(AgentMemoryResponse::from).collect())
+        .map_err(|e| e.to_string())
+}
@@ -1 +1,512 @@
- 
\ No newline at end of file
+// 消息相关命令
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use crate::AppState;
+use crate::db::DbPool;
+use crate::llm::ToolCall;
+use crate::models::{Message, MessageRevision, MessageSearchFilter};
+use crate::services::message_service::{
+    MessageDetail, MessageEditMode, MessagePageAnchor, MessageSearchHit, MessageService, ReplyPreview, SnippetSegment,
+    DEFAULT_PAGE_SIZE, DEFAULT_SEARCH_LIMIT,
+};
+use crate::services::trash_service::TrashService;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageResponse {
+    pub id: String,
+    pub content: String,
+    pub chat_id: String,
+    pub sender_id: String,
+    pub status: String,                // streaming / complete / error / cancelled
+    pub kind: String,                  // text / tool_call / tool_result
+    pub tool_calls: Vec<ToolCall>,     // 工具调用消息中 AI 请求调用的工具
+    pub tool_call_id: Option<String>,  // 工具结果消息对应的工具调用ID
+    pub error_message: Option<String>, // 生成失败时的错误信息
+    pub reply_to_id: Option<String>,    // 回复的消息ID，原消息被彻底删除后为空
+    pub reply_to: Option<ReplyPreview>, // 回复的消息预览
+    pub parent_id: Option<String>,      // 所接续的上一条消息
+    pub sibling_index: i64,             // 在同一条上一条消息的各版本中的位置，从 0 开始
+    pub sibling_count: i64,             // 版本数，大于 1 时可通过 switch_message_branch 切换
+    pub revision_count: i64,            // 修订次数，大于 0 时可通过 get_message_revisions 查看
+    pub deleted_at: Option<String>,     // 移入回收站的时间，未删除时为空
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<MessageDetail> for MessageResponse {
+    fn from(detail: MessageDetail) -> Self {
+        let MessageDetail { message, reply_to, sibling_index, sibling_count, revision_count } = detail;
+        Self {
+            tool_calls: MessageService::decode_tool_calls(message.tool_calls.as_deref()),
+            id: message.id,
+            content: message.content,
+            chat_id: message.chat_id,
+            sender_id: message.sender_id,
+            status: message.status,
+            kind: message.kind,
+            tool_call_id: message.tool_call_id,
+            error_message: message.error_message,
+            reply_to_id: message.reply_to_id,
+            reply_to,
+            parent_id: message.parent_id,
+            sibling_index,
+            sibling_count,
+            revision_count,
+            deleted_at: message.deleted_at.map(|t| t.to_string()),
+            created_at: message.created_at.to_string(),
+            updated_at: message.updated_at.to_string(),
+        }
+    }
+}
+
+// 附上回复预览和分支位置后转换为响应格式
+fn to_responses(pool: &DbPool, messages: Vec<Message>) -> Result<Vec<MessageResponse>, String> {
+    let messages = MessageService::attach_details(pool, messages).map_err(|e| e.to_string())?;
+    Ok(messages.into_iter().map(MessageResponse::from).collect())
+}
+
+fn to_response(pool: &DbPool, message: Message) -> Result<MessageResponse, String> {
+    to_responses(pool, vec![message])?
+        .pop()
+        .ok_or_else(|| "消息不存在".to_string())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageRevisionResponse {
+    pub id: String,
+    pub message_id: String,
+    pub content: String,           // 被替换掉的内容
+    pub editor_id: Option<String>, // 进行这次修改的用户，用户被删除后为空
+    pub created_at: String,        // 修改时间
+}
+
+impl From<MessageRevision> for MessageRevisionResponse {
+    fn from(revision: MessageRevision) -> Self {
+        Self {
+            id: revision.id,
+            message_id: revision.message_id,
+            content: revision.content,
+            editor_id: revision.editor_id,
+            created_at: revision.created_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageSiblingsResponse {
+    pub siblings: Vec<MessageResponse>, // 按创建时间升序
+    pub active_index: Option<usize>,    // 当前分支上的版本，正在重新生成时为空
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessagePageResponse {
+    pub messages: Vec<MessageResponse>,
+    pub has_more_before: bool,
+    pub has_more_after: bool,
+    pub before_cursor: Option<String>, // 继续向前加载时作为 before_id 传入
+    pub after_cursor: Option<String>,  // 继续向后加载时作为 after_id 传入
+}
+
+#[derive(Debug, Serialize)]
+pub struct MessageSearchHitResponse {
+    pub message_id: String,
+    pub chat_id: String,
+    pub chat_name: String,
+    pub sender_id: String,
+    pub sender_name: String,
+    pub sender_is_ai: bool,
+    pub created_at: String,
+    pub score: f64,                    // 相关度，越大越相关
+    pub snippet: Vec<SnippetSegment>,  // 命中位置附近的摘录，highlighted 标出命中的检索词
+}
+
+impl From<MessageSearchHit> for MessageSearchHitResponse {
+    fn from(hit: MessageSearchHit) -> Self {
+        Self {
+            message_id: hit.row.id,
+            chat_id: hit.row.chat_id,
+            chat_name: hit.row.chat_name,
+            sender_id: hit.row.sender_id,
+            sender_name: hit.row.sender_name,
+            sender_is_ai: hit.row.sender_is_ai,
+            created_at: hit.row.created_at.to_string(),
+            score: hit.row.score,
+            snippet: hit.snippet,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MessageSearchResponse {
+    pub hits: Vec<MessageSearchHitResponse>,
+    pub has_more: bool,
+    pub next_offset: Option<i64>, // 继续加载时作为 offset 传入
+}
+
+/// 以当前用户身份发送消息
+///
+/// 此命令会从应用状态中获取当前用户ID作为发送者，reply_to_id 为回复（引用）的消息，必须属于同一聊天
+///
+/// ## 数据库影响
+/// - 读取操作：检查 chat_participants 表中当前用户是否为该聊天的参与者
+/// - 读取操作：提供 reply_to_id 时检查 messages 表中该消息属于该聊天
+/// - 写入操作：在 messages 表中创建新消息记录，接在当前分支的最后一条消息之后（触发器会同步更新 chats 表的最后消息）
+/// - 修改操作：更新 messages 表中上一条消息的 active_child_id
+/// - 修改操作：将 chat_participants 表中当前用户的已读位置推进到这条消息
+/// - 使用事务确保以上操作同时成功或同时失败
+/// - 读取操作：从 messages、message_revisions 和 users 表中获取回复的消息预览、消息的版本数和修订次数
+#[tauri::command]
+pub async fn send_current_user_message(
+    state: State<'_, AppState>,
+    chat_id: String,
+    content: String,
+    reply_to_id: Option<String>,
+) -> Result<MessageResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+    let current_user = state.current_user.lock().expect("无法获取当前用户状态");
+
+    let message = MessageService::send_message(&pool, &chat_id, &current_user.id, &content, reply_to_id.as_deref())
+        .map_err(|e| e.to_string())?;
+
+    to_response(&pool, message)
+}
+
+/// 获取聊天的历史消息
+///
+/// 按创建时间升序返回指定聊天当前分支上的所有消息
+///
+/// ## 数据库影响
+/// - 读取操作：从 messages 表中查询指定聊天的所有消息
+/// - 读取操作：从 messages、message_revisions 和 users 表中批量获取回复的消息预览、各消息的版本数和修订次数
+/// - 无写入、修改或删除操作
+#[tauri::command]
+pub async fn get_chat_messages(
+    state: State<'_, AppState>,
+    chat_id: String,
+) -> Result<Vec<MessageResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+
+    let messages = MessageService::get_chat_messages(&pool, &chat_id)
+        .map_err(|e| e.to_string())?;
+
+    to_responses(&pool, messages)
+}
+
+/// 获取消息下的回复串
+///
+/// 返回指定消息及其下当前分支上的全部回复（包括回复的回复），按创建时间升序排列，第一条为该消息本身
+///
+/// ## 数据库影响
+/// - 读取操作：递归查询 messages 表中回复该消息的消息
+/// - 读取操作：从 messages、message_revisions 和 users 表中批量获取回复的消息预览、各消息的版本数和修订次数
+/// - 无写入、修改或删除操作
+#[tauri::command]
+pub async fn get_message_thread(
+    state: State<'_, AppState>,
+    message_id: String,
+) -> Result<Vec<MessageResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+
+    let thread = MessageService::get_reply_thread(&pool, &message_id)
+        .map_err(|e| e.to_string())?;
+
+    to_responses(&pool, thread)
+}
+
+/// 获取消息的所有版本
+///
+/// 重新生成的AI回复与原回复互为兄弟节点，返回接在同一条上一条消息之后的所有消息（包括该消息本身）
+///
+/// ## 数据库影响
+/// - 读取操作：从 messages 表中查询该消息及其兄弟节点
+/// - 读取操作：从 messages、message_revisions 和 users 表中批量获取回复的消息预览、各消息的版本数和修订次数
+/// - 无写入、修改或删除操作
+#[tauri::command]
+pub async fn get_message_siblings(
+    state: State<'_, AppState>,
+    message_id: String,
+) -> Result<MessageSiblingsResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+
+    let (siblings, active_index) = MessageService::get_siblings(&pool, &message_id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(MessageSiblingsResponse {
+        siblings: to_responses(&pool, siblings)?,
+        active_index,
+    })
+}
+
+/// 切换消息分支
+///
+/// 将指定消息设为其所在版本中的当前版本：原来的版本及其后续消息移出当前分支，
+/// 指定消息及其之后上次选中的后续消息成为当前分支。返回从指定消息开始的新分支，按时间升序。
+/// 上一条消息不在当前分支上、或当前分支上有正在生成的回复时不能切换
+///
+/// ## 数据库影响
+/// - 读取操作：从 messages 表中查询该消息、上一条消息和当前选中的版本
+/// - 修改操作：更新 messages 表中原分支和新分支上消息的 is_active，以及上一条消息的 active_child_id
+/// - 修改操作：更新 chats 表中的最后消息
+/// - 使用事务确保以上操作同时成功或同时失败
+/// - 读取操作：从 messages、message_revisions 和 users 表中批量获取回复的消息预览、各消息的版本数和修订次数
+#[tauri::command]
+pub async fn switch_message_branch(
+    state: State<'_, AppState>,
+    message_id: String,
+) -> Result<Vec<MessageResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+
+    let branch = MessageService::switch_branch(&pool, &message_id)
+        .map_err(|e| e.to_string())?;
+
+    to_responses(&pool, branch)
+}
+
+/// 分页获取聊天的历史消息
+///
+/// 基于 (created_at, id) 的游标分页，before_id、after_id、around_id 最多指定一个：
+/// - 都不指定：返回最新的一页
+/// - before_id：返回该消息之前的一页
+/// - after_id：返回该消息之后的一页
+/// - around_id：返回以该消息为中心的上下文（包含该消息），用于跳转到指定消息
+///
+/// 只返回当前分支上的消息，按时间升序排列
+///
+/// ## 数据库影响
+/// - 读取操作：从 messages 表中查询锚点消息及其前后的消息
+/// - 读取操作：从 messages、message_revisions 和 users 表中批量获取回复的消息预览、各消息的版本数和修订次数
+/// - 无写入、修改或删除操作
+#[tauri::command]
+pub async fn get_chat_messages_page(
+    state: State<'_, AppState>,
+    chat_id: String,
+    before_id: Option<String>,
+    after_id: Option<String>,
+    around_id: Option<String>,
+    limit: Option<i64>,
+) -> Result<MessagePageResponse, String> {
+    let anchor = match (before_id, after_id, around_id) {
+        (None, None, None) => MessagePageAnchor::Latest,
+        (Some(id), None, None) => MessagePageAnchor::Before(id),
+        (None, Some(id), None) => MessagePageAnchor::After(id),
+        (None, None, Some(id)) => MessagePageAnchor::Around(id),
+        _ => return Err("before_id、after_id 和 around_id 最多只能指定一个".to_string()),
+    };
+
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+
+    let page = MessageService::get_messages_page(&pool, &chat_id, anchor, limit.unwrap_or(DEFAULT_PAGE_SIZE))
+        .map_err(|e| e.to_string())?;
+
+    // 两端的消息ID作为继续加载的游标
+    let before_cursor = page.messages.first()
+        .filter(|_| page.has_more_before)
+        .map(|message| message.id.clone());
+    let after_cursor = page.messages.last()
+        .filter(|_| page.has_more_after)
+        .map(|message| message.id.clone());
+
+    Ok(MessagePageResponse {
+        messages: to_responses(&pool, page.messages)?,
+        has_more_before: page.has_more_before,
+        has_more_after: page.has_more_after,
+        before_cursor,
+        after_cursor,
+    })
+}
+
+/// 编辑消息
+///
+/// 修改指定消息的内容，修改前的内容保存为修订记录，正在生成中的AI回复不能编辑。
+/// 只能编辑自己发送的消息或AI用户的回复，不能编辑其他成员的消息。
+/// 编辑自己发送的消息时可通过 mode 处理其后的AI回复：
+/// - in_place（默认）：只修改内容，后续消息保持不变
+/// - truncate：修改内容并删除其后的所有消息（包括其他分支），之后可重新请求AI回复
+/// - branch：以新内容创建该消息的新版本并切换到新版本，原消息及其后续消息保留为另一个分支，
+///   返回新版本的消息
+///
+/// ## 数据库影响
+/// - 读取操作：从 messages 表中查询指定ID的消息，检查 chat_participants 表中当前用户是否为参与者，从 users 表中确认其他发送者为AI用户
+/// - 写入操作：in_place 和 truncate 时在 message_revisions 表中保存修改前的内容
+/// - 修改操作：in_place 和 truncate 时更新 messages 表中该消息的内容和更新时间（该消息为聊天最新消息时，触发器会同步更新 chats 表的最后消息）
+/// - 删除操作：truncate 时从 messages 表中删除其后的所有消息及其在 message_revisions 表中的修订记录，
+///   并清除 ai_jobs 表、agent_memories 表和其他消息的 reply_to_id 对这些消息的引用
+/// - 修改操作：branch 时将 messages 表中原消息及其后续消息标记为不在当前分支上
+/// - 写入操作：branch 时在 messages 表中写入新版本的消息，并推进当前用户在 chat_participants 表中的已读位置
+/// - 使用事务确保以上操作同时成功或同时失败
+/// - 读取操作：从 messages、message_revisions 和 users 表中获取回复的消息预览、消息的版本数和修订次数
+#[tauri::command]
+pub async fn update_message(
+    state: State<'_, AppState>,
+    id: String,
+    content: String,
+    mode: Option<MessageEditMode>,
+) -> Result<MessageResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+    let current_user = state.current_user.lock().expect("无法获取当前用户状态");
+
+    let message = MessageService::edit_message(&pool, &current_user.id, &id, &content, mode.unwrap_or_default())
+        .map_err(|e| e.to_string())?;
+
+    to_response(&pool, message)
+}
+
+/// 获取消息的修订记录
+///
+/// 每条记录为一次修改前的内容，按修改时间倒序排列；当前用户必须是消息所在聊天的参与者
+///
+/// ## 数据库影响
+/// - 读取操作：从 messages 表中查询该消息，检查 chat_participants 表中当前用户是否为参与者
+/// - 读取操作：从 message_revisions 表中查询该消息的所有修订记录
+/// - 无写入、修改或删除操作
+#[tauri::command]
+pub async fn get_message_revisions(
+    state: State<'_, AppState>,
+    message_id: String,
+) -> Result<Vec<MessageRevisionResponse>, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+    let current_user = state.current_user.lock().expect("无法获取当前用户状态");
+
+    let revisions = MessageService::get_revisions(&pool, &current_user.id, &message_id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(revisions.into_iter().map(MessageRevisionResponse::from).collect())
+}
+
+/// 将消息恢复为某条修订记录中的内容
+///
+/// 恢复本身也是一次编辑，恢复前的内容同样保存为修订记录；与编辑相同，只能恢复自己发送的消息或AI用户的回复
+///
+/// ## 数据库影响
+/// - 读取操作：从 message_revisions 表中查询修订记录，从 messages 表中查询对应的消息
+/// - 读取操作：检查 chat_participants 表中当前用户是否为参与者，从 users 表中确认其他发送者为AI用户
+/// - 写入操作：在 message_revisions 表中保存恢复前的内容
+/// - 修改操作：更新 messages 表中该消息的内容和更新时间
+/// - 使用事务确保修订记录和消息内容同时更新
+/// - 读取操作：从 messages、message_revisions 和 users 表中获取回复的消息预览、消息的版本数和修订次数
+#[tauri::command]
+pub async fn restore_message_revision(
+    state: State<'_, AppState>,
+    revision_id: String,
+) -> Result<MessageResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+    let current_user = state.current_user.lock().expect("无法获取当前用户状态");
+
+    let message = MessageService::restore_revision(&pool, &current_user.id, &revision_id)
+        .map_err(|e| e.to_string())?;
+
+    to_response(&pool, message)
+}
+
+/// 删除消息
+///
+/// 消息移入回收站，不再显示在聊天中，也不再作为AI回复的上下文，其后续消息保持不变；
+/// 超过回收站保留天数后彻底删除，此前可通过 restore_message 恢复
+///
+/// ## 数据库影响
+/// - 读取操作：从 messages 表中查询指定ID的消息，检查 chat_participants 表中当前用户是否为参与者
+/// - 修改操作：更新 messages 表中该消息的 deleted_at
+/// - 修改操作：更新 chats 表中该聊天的最后消息
+/// - 使用事务确保以上操作同时成功或同时失败
+#[tauri::command]
+pub async fn delete_message(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+    let current_user = state.current_user.lock().expect("无法获取当前用户状态");
+
+    MessageService::delete_message(&pool, &current_user.id, &id)
+        .map_err(|e| e.to_string())
+}
+
+/// 从回收站恢复消息
+///
+/// 消息所在的聊天也已删除时，需先恢复聊天
+///
+/// ## 数据库影响
+/// - 读取操作：从 messages 和 chats 表中查询消息及其所在的聊天，检查 chat_participants 表中当前用户是否为参与者
+/// - 修改操作：清除 messages 表中该消息的 deleted_at
+/// - 修改操作：更新 chats 表中该聊天的最后消息
+/// - 使用事务确保以上操作同时成功或同时失败
+/// - 读取操作：从 messages、message_revisions 和 users 表中获取回复的消息预览、消息的版本数和修订次数
+#[tauri::command]
+pub async fn restore_message(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<MessageResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+    let current_user = state.current_user.lock().expect("无法获取当前用户状态");
+
+    let message = MessageService::restore_message(&pool, &current_user.id, &id)
+        .map_err(|e| e.to_string())?;
+
+    to_response(&pool, message)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletedMessageListResponse {
+    pub messages: Vec<MessageResponse>,
+    pub retention_days: i64, // 回收站保留天数，删除时间超过该天数的消息会被彻底删除
+}
+
+/// 获取回收站中的消息
+///
+/// 返回当前用户参与的未删除聊天中已删除的消息，按删除时间倒序排列；已删除聊天中的消息随聊天一起通过 get_deleted_chats 列出
+///
+/// ## 数据库影响
+/// - 读取操作：联表查询 chats、chat_participants 和 messages 表获取已删除的消息
+/// - 读取操作：从 messages、message_revisions 和 users 表中批量获取回复的消息预览、各消息的版本数和修订次数
+/// - 无写入、修改或删除操作
+#[tauri::command]
+pub async fn get_deleted_messages(state: State<'_, AppState>) -> Result<DeletedMessageListResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+    let current_user = state.current_user.lock().expect("无法获取当前用户状态");
+
+    let messages = MessageService::get_deleted_messages(&pool, &current_user.id)
+        .map_err(|e| e.to_string())?;
+
+    Ok(DeletedMessageListResponse {
+        messages: to_responses(&pool, messages)?,
+        retention_days: TrashService::retention_days(),
+    })
+}
+
+/// 全文检索消息
+///
+/// 检索内容按空白拆分为多个检索词，返回包含全部检索词的消息（不区分大小写），按相关度排序。
+/// 中文等没有空格分词的内容可以检索任意位置的子串。
+/// filter 可按聊天、发送者、是否 AI 用户和时间范围（created_after 包含、created_before 不包含，
+/// 格式如 2025-03-01T00:00:00，UTC）过滤。
+/// 跳转到命中的消息时，以 message_id 作为 around_id 调用 get_chat_messages_page
+///
+/// ## 数据库影响
+/// - 读取操作：通过 messages_fts 全文索引和 message_search_ids 映射表检索 messages 表，并关联 users、chats 表获取发送者和聊天名称
+/// - 无写入、修改或删除操作
+#[tauri::command]
+pub async fn search_messages(
+    state: State<'_, AppState>,
+    query: String,
+    filter: Option<MessageSearchFilter>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<MessageSearchResponse, String> {
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+
+    let offset = offset.unwrap_or(0).max(0);
+    let page = MessageService::search_messages(
+        &pool,
+        &query,
+        &filter.unwrap_or_default(),
+        limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+        offset,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let next_offset = page.has_more.then_some(offset + page.hits.len() as i64);
+    Ok(MessageSearchResponse {
+        hits: page.hits.into_iter().map(MessageSearchHitResponse::from).collect(),
+        has_more: page.has_more,
+        next_offset,
+    })
+}
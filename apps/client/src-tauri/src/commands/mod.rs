@@ -5,9 +5,24 @@ pub mod user_commands;
 pub mod app_commands;
 pub mod user_contact_commands;
 pub mod resource_commands;
+pub mod ollama_commands;
+pub mod llm_commands;
+pub mod agent_commands;
+pub mod ai_job_commands;
+pub mod mcp_commands;
+pub mod knowledge_commands;
+pub mod memory_commands;
 
 pub use app_commands::*;
 pub use user_commands::*;
 pub use chat_commands::*;
+pub use message_commands::*;
 pub use user_contact_commands::*;
 pub use resource_commands::*;
+pub use ollama_commands::*;
+pub use llm_commands::*;
+pub use agent_commands::*;
+pub use ai_job_commands::*;
+pub use mcp_commands::*;
+pub use knowledge_commands::*;
+pub use memory_commands::*;
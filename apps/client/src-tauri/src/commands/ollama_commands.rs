@@ -0,0 +1,187 @@
+// Ollama 相关命令
+use futures_util::future::Abortable;
+use futures_util::StreamExt;
+use serde::Serialize;
+use tauri::ipc::Channel;
+use tauri::State;
+
+use crate::AppState;
+use crate::ollama::types::{
+    ChatRequest, ChatResponse, GenerateRequest, GenerateResponse, GenerationStats, ModelListResponse,
+    ShowRequest, ShowResponse,
+};
+use crate::ollama::{OllamaError, OllamaResult};
+
+/// 流式输出事件，通过 Channel 按顺序推送给前端
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum OllamaStreamEvent {
+    // 模型输出的增量文本
+    Delta { content: String },
+    // 生成结束
+    Done {
+        model: String,
+        done_reason: Option<String>,
+        #[serde(flatten)]
+        stats: GenerationStats,
+    },
+}
+
+/// 获取本地已安装的模型列表
+///
+/// 对应 Ollama 的 /api/tags
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn ollama_list_models(state: State<'_, AppState>) -> OllamaResult<ModelListResponse> {
+    state.ollama_client.list_models().await
+}
+
+/// 获取模型详情
+///
+/// 对应 Ollama 的 /api/show
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn ollama_show_model(
+    state: State<'_, AppState>,
+    request: ShowRequest,
+) -> OllamaResult<ShowResponse> {
+    state.ollama_client.show_model(&request).await
+}
+
+/// 聊天（非流式）
+///
+/// 对应 Ollama 的 /api/chat，一次性返回完整回复
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn ollama_chat(
+    state: State<'_, AppState>,
+    request: ChatRequest,
+) -> OllamaResult<ChatResponse> {
+    state.ollama_client.chat(&request).await
+}
+
+/// 聊天（流式）
+///
+/// 对应 Ollama 的 /api/chat，增量输出通过 on_event 推送，
+/// 可使用相同的 stream_id 调用 ollama_abort_stream 中断
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn ollama_chat_stream(
+    state: State<'_, AppState>,
+    stream_id: String,
+    request: ChatRequest,
+    on_event: Channel<OllamaStreamEvent>,
+) -> OllamaResult<()> {
+    let (generation, registration) = state.ollama_streams.register(&stream_id);
+
+    let result = async {
+        let stream = state.ollama_client.chat_stream(&request).await?;
+        let mut stream = Abortable::new(stream, registration);
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            let content = chunk.message.map(|message| message.content).unwrap_or_default();
+            send_chunk(&on_event, content, chunk.done, chunk.model, chunk.done_reason, chunk.stats)?;
+        }
+
+        if stream.is_aborted() {
+            return Err(OllamaError::StreamAborted);
+        }
+        Ok(())
+    }
+    .await;
+
+    state.ollama_streams.remove(&stream_id, generation);
+    result
+}
+
+/// 文本补全（非流式）
+///
+/// 对应 Ollama 的 /api/generate，一次性返回完整结果
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn ollama_generate(
+    state: State<'_, AppState>,
+    request: GenerateRequest,
+) -> OllamaResult<GenerateResponse> {
+    state.ollama_client.generate(&request).await
+}
+
+/// 文本补全（流式）
+///
+/// 对应 Ollama 的 /api/generate，增量输出通过 on_event 推送，
+/// 可使用相同的 stream_id 调用 ollama_abort_stream 中断
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub async fn ollama_generate_stream(
+    state: State<'_, AppState>,
+    stream_id: String,
+    request: GenerateRequest,
+    on_event: Channel<OllamaStreamEvent>,
+) -> OllamaResult<()> {
+    let (generation, registration) = state.ollama_streams.register(&stream_id);
+
+    let result = async {
+        let stream = state.ollama_client.generate_stream(&request).await?;
+        let mut stream = Abortable::new(stream, registration);
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            send_chunk(&on_event, chunk.response, chunk.done, chunk.model, chunk.done_reason, chunk.stats)?;
+        }
+
+        if stream.is_aborted() {
+            return Err(OllamaError::StreamAborted);
+        }
+        Ok(())
+    }
+    .await;
+
+    state.ollama_streams.remove(&stream_id, generation);
+    result
+}
+
+/// 中断流式请求
+///
+/// 返回是否找到对应的流式请求，被中断的请求会以 stream_aborted 错误结束
+///
+/// ## 数据库影响
+/// - 无数据库操作
+#[tauri::command]
+pub fn ollama_abort_stream(state: State<'_, AppState>, stream_id: String) -> bool {
+    state.ollama_streams.abort(&stream_id)
+}
+
+// 推送一段增量输出，最后一段同时推送结束事件
+fn send_chunk(
+    channel: &Channel<OllamaStreamEvent>,
+    content: String,
+    done: bool,
+    model: String,
+    done_reason: Option<String>,
+    stats: GenerationStats,
+) -> OllamaResult<()> {
+    if !content.is_empty() {
+        channel
+            .send(OllamaStreamEvent::Delta { content })
+            .map_err(|_| OllamaError::StreamAborted)?;
+    }
+    if done {
+        channel
+            .send(OllamaStreamEvent::Done { model, done_reason, stats })
+            .map_err(|_| OllamaError::StreamAborted)?;
+    }
+    Ok(())
+}
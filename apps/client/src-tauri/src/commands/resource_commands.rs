@@ -262,6 +262,32 @@ pub async fn read_text_resource(
     Ok(content)
 }
 
+/// 更新文本资源内容
+/// 
+/// 以传入的内容替换文本资源的内容，关联该资源的AI用户在下次检索知识库时重新生成片段
+///
+/// ## 数据库影响
+/// - 读取操作：从 resources 表中查询指定ID的资源
+/// - 修改操作：更新 resources 表中该资源的修改时间
+/// - 删除操作：删除 resource_chunks 表中该资源原内容的片段
+/// - 使用事务确保保存文本失败时不修改数据库
+#[tauri::command]
+pub async fn update_text_resource(
+    state: State<'_, AppState>,
+    id: String,
+    content: String
+) -> Result<ResourceResponse, String> {
+    // 获取数据库连接池和资源目录
+    let pool = state.db_pool.lock().expect("无法获取数据库连接池");
+    let app_resource_path = &state.app_resource_path;
+    
+    // 更新文本内容
+    let resource = ResourceService::update_text_resource_content(&pool, &id, &content, app_resource_path)
+        .map_err(|e| e.to_string())?;
+    
+    Ok(ResourceResponse::from(resource))
+}
+
 /// 删除资源
 /// 
 /// 删除指定ID的资源，包括数据库记录和文件
@@ -269,6 +295,7 @@ pub async fn read_text_resource(
 /// ## 数据库影响
 /// - 读取操作：从 resources 表中查询指定ID的资源
 /// - 删除操作：从 resources 表中删除指定ID的资源
+/// - 删除操作：删除 resource_chunks 表中该资源的片段和 agent_knowledge_resources 表中对其的关联
 /// - 无写入或修改操作
 #[tauri::command]
 pub async fn delete_resource(
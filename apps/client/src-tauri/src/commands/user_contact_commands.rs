@@ -1,6 +1,7 @@
 use crate::repositories::user_contact_repository::UserContactRepository;
 use crate::repositories::user_repository::UserRepository;
 use crate::repositories::error::RepositoryError;
+use crate::services::agent_service::AgentConfig;
 use crate::services::user_service::UserService;
 use crate::AppState;
 use tauri::State;
@@ -110,19 +111,27 @@ pub async fn remove_current_user_contact(contact_id: String, state: State<'_, Ap
 
 /// 创建AI用户并添加为当前用户的联系人
 /// 
-/// 此命令会创建一个新的AI用户，并将其添加为当前用户的联系人
+/// 此命令会创建一个新的AI用户，并将其添加为当前用户的联系人，
+/// agent_config 未提供时使用默认模型配置
 ///
 /// ## 数据库影响
 /// - 写入操作：在 users 表中创建新的AI用户记录
+/// - 写入操作：在 agents 表中创建该AI用户的模型配置
 /// - 写入操作：在 user_contacts 表中创建当前用户与新AI用户的联系人关系
-/// - 使用事务确保两个操作同时成功或同时失败
+/// - 使用事务确保三个操作同时成功或同时失败
 /// - 无修改或删除操作
 #[tauri::command]
 pub async fn create_current_user_ai_contact(
     name: String, 
     description: Option<String>, 
+    agent_config: Option<AgentConfig>,
     state: State<'_, AppState>
 ) -> Result<ContactResponse, String> {
+    // 检查模型服务商是否已注册
+    if let Some(config) = &agent_config {
+        state.llm_providers.get(&config.provider).map_err(|e| e.to_string())?;
+    }
+
     // 获取数据库连接池和当前用户
     let pool = state.db_pool.lock().expect("无法获取数据库连接池");
     let current_user = state.current_user.lock().expect("无法获取当前用户状态");
@@ -132,7 +141,8 @@ pub async fn create_current_user_ai_contact(
         &pool,
         &current_user.id,
         &name,
-        description.as_deref()
+        description.as_deref(),
+        agent_config
     ).map_err(|e| e.to_string())?;
     
     // 返回创建的AI用户信息
@@ -1,5 +1,6 @@
 use anyhow::{anyhow, Result};
-use diesel::r2d2::{self, ConnectionManager};
+use diesel::connection::{AnsiTransactionManager, SimpleConnection, TransactionManager};
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection};
 use diesel::sqlite::SqliteConnection;
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use dirs::data_dir;
@@ -15,6 +16,9 @@ pub const TEXTS_DIR_NAME: &str = "texts";
 // 嵌入迁移文件
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
+// 等待其他连接释放写锁的最长时间（毫秒）
+const BUSY_TIMEOUT_MS: u32 = 5000;
+
 // 定义连接池类型
 pub type DbPool = r2d2::Pool<ConnectionManager<SqliteConnection>>;
 pub type DbConnection = r2d2::PooledConnection<ConnectionManager<SqliteConnection>>;
@@ -69,6 +73,30 @@ pub fn get_texts_dir_path() -> Result<PathBuf> {
     Ok(texts_dir)
 }
 
+// 连接初始化设置
+//
+// AI 回复任务在多个聊天间并行写入，等待写锁而不是直接返回 database is locked；
+// 显式开启外键约束，迁移中声明的 ON DELETE 动作不依赖 SQLite 编译时的默认设置；
+// 只读连接池的连接开启 query_only，任何写入都会失败
+#[derive(Debug)]
+struct ConnectionOptions {
+    query_only: bool,
+}
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for ConnectionOptions {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> std::result::Result<(), r2d2::Error> {
+        conn.batch_execute(&format!("PRAGMA busy_timeout = {};", BUSY_TIMEOUT_MS))
+            .map_err(r2d2::Error::QueryError)?;
+        conn.batch_execute("PRAGMA foreign_keys = ON;")
+            .map_err(r2d2::Error::QueryError)?;
+        if self.query_only {
+            conn.batch_execute("PRAGMA query_only = ON;")
+                .map_err(r2d2::Error::QueryError)?;
+        }
+        Ok(())
+    }
+}
+
 // 创建数据库连接池
 pub fn establish_connection() -> Result<DbPool> {
     let database_path = get_database_path()?;
@@ -76,10 +104,7 @@ pub fn establish_connection() -> Result<DbPool> {
         .to_str()
         .ok_or_else(|| anyhow!("路径转换失败"))?;
 
-    let manager = ConnectionManager::<SqliteConnection>::new(database_url);
-    let pool = r2d2::Pool::builder()
-        .build(manager)
-        .map_err(|e| anyhow!("无法创建数据库连接池: {}", e))?;
+    let pool = create_pool(database_url, false)?;
 
     // 运行迁移
     let mut conn = pool.get()?;
@@ -88,6 +113,70 @@ pub fn establish_connection() -> Result<DbPool> {
     Ok(pool)
 }
 
+// 以只读方式连接应用已创建的数据库，供独立运行的 MCP 服务器读取聊天和资源
+//
+// 不创建数据库也不运行迁移，数据库版本与当前程序不一致时返回错误，需先启动应用完成升级
+pub fn open_existing_connection() -> Result<DbPool> {
+    open_existing(true)
+}
+
+// 以可写方式连接应用已创建的数据库，供独立运行的 MCP 服务器发送消息和加入 AI 回复任务
+pub fn open_existing_write_connection() -> Result<DbPool> {
+    open_existing(false)
+}
+
+fn open_existing(query_only: bool) -> Result<DbPool> {
+    let database_path = get_database_path()?;
+    if !database_path.exists() {
+        return Err(anyhow!("数据库不存在，请先启动应用: {}", database_path.display()));
+    }
+    let database_url = database_path
+        .to_str()
+        .ok_or_else(|| anyhow!("路径转换失败"))?;
+
+    let pool = create_pool(database_url, query_only)?;
+
+    let mut conn = pool.get()?;
+    let has_pending = conn
+        .has_pending_migration(MIGRATIONS)
+        .map_err(|e| anyhow!("无法检查数据库版本: {}", e))?;
+    if has_pending {
+        return Err(anyhow!("数据库版本与当前程序不一致，请先启动应用完成升级"));
+    }
+
+    Ok(pool)
+}
+
+// 为指定的数据库文件创建连接池，不运行迁移；query_only 为 true 时连接池只能读取
+pub fn create_pool(database_url: &str, query_only: bool) -> Result<DbPool> {
+    let manager = ConnectionManager::<SqliteConnection>::new(database_url);
+    r2d2::Pool::builder()
+        .connection_customizer(Box::new(ConnectionOptions { query_only }))
+        .build(manager)
+        .map_err(|e| anyhow!("无法创建数据库连接池: {}", e))
+}
+
+// 以 BEGIN IMMEDIATE 开始事务
+//
+// 先读后写的事务在开始时就获取写锁，避免并发写入时锁升级失败直接返回 database is locked
+pub fn immediate_transaction<T, E, F>(conn: &mut DbConnection, f: F) -> std::result::Result<T, E>
+where
+    F: FnOnce(&mut DbConnection) -> std::result::Result<T, E>,
+    E: From<diesel::result::Error>,
+{
+    AnsiTransactionManager::begin_transaction_sql(&mut **conn, "BEGIN IMMEDIATE")?;
+    match f(conn) {
+        Ok(value) => {
+            AnsiTransactionManager::commit_transaction(&mut **conn)?;
+            Ok(value)
+        }
+        Err(e) => {
+            AnsiTransactionManager::rollback_transaction(&mut **conn)?;
+            Err(e)
+        }
+    }
+}
+
 // 运行数据库迁移
 fn run_migrations(conn: &mut DbConnection) -> Result<()> {
     // 使用 diesel_migrations 运行迁移
@@ -103,3 +192,71 @@ pub fn get_connection_pool(app_handle: &AppHandle) -> DbPool {
     let pool = state.db_pool.lock().expect("无法获取数据库连接池锁");
     pool.clone()
 }
+
+#[cfg(test)]
+mod tests {
+    use diesel::prelude::*;
+
+    use super::*;
+    use crate::models::ChatType;
+    use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+    use crate::repositories::chat_repository::ChatRepository;
+    use crate::repositories::message_repository::MessageRepository;
+    use crate::repositories::message_revision_repository::MessageRevisionRepository;
+    use crate::repositories::user_repository::UserRepository;
+    use crate::schema::{messages, users};
+    use crate::services::chat_service::ChatService;
+    use crate::services::message_service::{MessageEditMode, MessageService};
+    use crate::test_support::{create_test_chat, create_test_database, create_test_users, TestChat, TestUsers};
+
+    #[test]
+    fn read_only_pool_rejects_writes() {
+        let (path, _pool) = create_test_database();
+        let read_pool = create_pool(&path, true).unwrap();
+
+        let mut conn = read_pool.get().unwrap();
+        assert!(!conn.has_pending_migration(MIGRATIONS).unwrap());
+        assert!(UserRepository::create(&read_pool, "用户".to_string(), None, false).is_err());
+    }
+
+    #[test]
+    fn chat_type_backfill_keeps_two_member_groups() {
+        let TestUsers { pool, human, ai } = create_test_users();
+        let direct = ChatService::create_direct_chat(&pool, &human.id, &ai.id).unwrap();
+        let group = ChatService::create_group_chat(&pool, &human.id, "周末", std::slice::from_ref(&ai.id)).unwrap();
+        // 以成员名称命名的两人群聊，但成员之间不是联系人
+        let stranger = UserRepository::create(&pool, "陌生人".to_string(), None, false).unwrap();
+        let named = ChatService::create_group_chat(&pool, &ai.id, "陌生人", std::slice::from_ref(&stranger.id)).unwrap();
+
+        // 回退到添加聊天类型之前，再重新运行迁移进行回填
+        let mut conn = pool.get().unwrap();
+        while !conn.revert_last_migration(MIGRATIONS).unwrap().to_string().contains("20250412") {}
+        conn.run_pending_migrations(MIGRATIONS).unwrap();
+
+        let chat_type = |chat_id: &str| ChatRepository::get(&pool, chat_id).unwrap().chat_type;
+        assert_eq!(chat_type(&direct.id), ChatType::Direct.as_str());
+        assert_eq!(chat_type(&group.id), ChatType::Group.as_str());
+        assert_eq!(chat_type(&named.id), ChatType::Group.as_str());
+        assert_eq!(ChatParticipantRepository::get_by_chat_id(&pool, &group.id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn foreign_key_actions_apply_when_a_message_is_deleted() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let question = MessageService::send_message(&pool, &chat.id, &human.id, "原来的问题", None).unwrap();
+        let answer = MessageService::send_message(&pool, &chat.id, &ai.id, "回答", Some(&question.id)).unwrap();
+        MessageService::edit_message(&pool, &human.id, &question.id, "新的问题", MessageEditMode::InPlace).unwrap();
+
+        let mut conn = pool.get().unwrap();
+        diesel::delete(messages::table.filter(messages::id.eq(&question.id)))
+            .execute(&mut conn)
+            .unwrap();
+
+        let answer = MessageRepository::get(&pool, &answer.id).unwrap();
+        assert!(answer.parent_id.is_none());
+        assert!(answer.reply_to_id.is_none());
+        assert!(MessageRevisionRepository::get_by_message_id(&pool, &question.id).unwrap().is_empty());
+        // 没有声明删除动作的引用仍会阻止删除
+        assert!(diesel::delete(users::table.filter(users::id.eq(&ai.id))).execute(&mut conn).is_err());
+    }
+}
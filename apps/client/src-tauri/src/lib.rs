@@ -1,12 +1,21 @@
+mod ai_queue;
 mod commands;
 mod db;
+mod llm;
+mod mcp;
+mod mcp_server;
 mod models;
+mod ollama;
 mod repositories;
 mod schema;
 mod services;
+mod tools;
+#[cfg(test)]
+mod test_support;
 
 use crate::models::User;
-use std::sync::Mutex;
+use tauri::Manager;
+use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
 
 // 将数据库连接池和资源路径作为应用状态
@@ -16,6 +25,11 @@ pub struct AppState {
     app_resource_path: PathBuf,  // 应用资源目录路径
     images_dir_path: PathBuf,    // 图片目录路径
     texts_dir_path: PathBuf,     // 文本目录路径
+    ollama_client: ollama::OllamaClient,                 // Ollama 客户端
+    ollama_streams: ollama::OllamaStreamRegistry,        // 进行中的流式请求，各服务商共用
+    llm_providers: Arc<llm::ProviderRegistry>,           // 已注册的模型服务商
+    tools: Arc<tools::ToolRegistry>,                     // Agent 可以调用的工具
+    mcp: Arc<mcp::McpManager>,                           // 已启动的 MCP 服务器
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -34,6 +48,18 @@ pub fn run() {
         services::user_service::UserService::get_default_user(&mut conn).expect("无法获取默认用户")
     };
 
+    let ollama_client = ollama::OllamaClient::from_env();
+    let llm_providers = Arc::new(llm::ProviderRegistry::with_defaults(ollama_client.clone()));
+
+    let queue_pool = db_pool.clone();
+    let queue_providers = llm_providers.clone();
+    let tools = Arc::new(tools::ToolRegistry::with_defaults(app_resource_path.clone()));
+    let queue_tools = tools.clone();
+    let mcp = Arc::new(mcp::McpManager::default());
+    let queue_mcp = mcp.clone();
+    let purge_pool = db_pool.clone();
+    let queue_resource_path = app_resource_path.clone();
+
     tauri::Builder::default()
         .manage(AppState {
             db_pool: Mutex::new(db_pool),
@@ -41,16 +67,108 @@ pub fn run() {
             app_resource_path,
             images_dir_path,
             texts_dir_path,
+            ollama_client,
+            ollama_streams: ollama::OllamaStreamRegistry::default(),
+            llm_providers,
+            tools,
+            mcp,
         })
         .plugin(tauri_plugin_opener::init())
+        .setup(move |app| {
+            // AI 回复任务队列，状态变化通过事件推送给前端
+            let sink = Arc::new(ai_queue::events::TauriJobEventSink::new(app.handle().clone()));
+            let queue = ai_queue::AiJobQueue::new(
+                queue_pool,
+                queue_providers,
+                queue_tools,
+                queue_mcp,
+                sink,
+                queue_resource_path,
+            );
+            if let Err(e) = queue.recover() {
+                eprintln!("{}", e);
+            }
+            // MCP 服务器只写入待处理任务，由应用负责处理
+            tauri::async_runtime::spawn(queue.clone().run_pending_watch());
+            app.manage(queue);
+
+            // 定期彻底清除回收站中超过保留天数的聊天和消息
+            let retention_days = services::trash_service::TrashService::retention_days();
+            tauri::async_runtime::spawn(services::trash_service::TrashService::run_purge_loop(
+                purge_pool,
+                retention_days,
+            ));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             commands::get_app_version,
             commands::create_ai_user,
             commands::get_current_user,
             commands::get_current_user_chat_list,
+            commands::create_direct_chat,
+            commands::create_group_chat,
+            commands::fork_chat,
+            commands::delete_chat,
+            commands::restore_chat,
+            commands::get_deleted_chats,
+            commands::set_chat_pinned,
+            commands::set_chat_muted,
+            commands::set_chat_archived,
+            commands::set_chat_sort_order,
+            commands::set_chat_display_name,
+            commands::update_chat_reply_policy,
+            commands::mark_chat_read,
+            commands::get_total_unread_count,
+            commands::send_current_user_message,
+            commands::get_chat_messages,
+            commands::get_chat_messages_page,
+            commands::get_message_thread,
+            commands::get_message_siblings,
+            commands::switch_message_branch,
+            commands::search_messages,
+            commands::update_message,
+            commands::get_message_revisions,
+            commands::restore_message_revision,
+            commands::delete_message,
+            commands::restore_message,
+            commands::get_deleted_messages,
             commands::add_current_user_contact,
             commands::remove_current_user_contact,
             commands::create_current_user_ai_contact,
+            commands::get_agent_config,
+            commands::update_agent_config,
+            commands::get_available_tools,
+            commands::get_chat_agent_config,
+            commands::update_chat_agent_override,
+            commands::reset_chat_agent_override,
+            commands::enqueue_ai_reply,
+            commands::dispatch_ai_replies,
+            commands::regenerate_ai_reply,
+            commands::cancel_ai_job,
+            commands::cancel_chat_ai_jobs,
+            commands::get_chat_ai_jobs,
+            commands::get_mcp_servers,
+            commands::create_mcp_server,
+            commands::update_mcp_server,
+            commands::delete_mcp_server,
+            commands::get_agent_mcp_servers,
+            commands::set_agent_mcp_servers,
+            commands::connect_mcp_server,
+            commands::disconnect_mcp_server,
+            commands::list_mcp_tools,
+            commands::call_mcp_tool,
+            commands::list_mcp_resources,
+            commands::read_mcp_resource,
+            commands::list_mcp_prompts,
+            commands::get_mcp_prompt,
+            commands::get_agent_knowledge_resources,
+            commands::set_agent_knowledge_resources,
+            commands::reindex_agent_knowledge,
+            commands::get_agent_memories,
+            commands::update_agent_memory,
+            commands::set_agent_memory_pinned,
+            commands::delete_agent_memory,
+            commands::extract_agent_memories,
             commands::get_current_user_contacts,
             commands::upload_current_user_image,
             commands::upload_current_user_text,
@@ -59,8 +177,26 @@ pub fn run() {
             commands::get_current_user_text_resources,
             commands::get_resource,
             commands::read_text_resource,
-            commands::delete_resource
+            commands::update_text_resource,
+            commands::delete_resource,
+            commands::ollama_list_models,
+            commands::ollama_show_model,
+            commands::ollama_chat,
+            commands::ollama_chat_stream,
+            commands::ollama_generate,
+            commands::ollama_generate_stream,
+            commands::ollama_abort_stream,
+            commands::llm_list_providers,
+            commands::llm_list_models,
+            commands::llm_model_capabilities,
+            commands::llm_chat,
+            commands::llm_chat_stream
         ])
         .run(tauri::generate_context!())
         .expect("运行应用失败");
 }
+
+// 以 MCP 服务器模式运行，通过标准输入输出对外提供聊天记录和资源，由 guixin_mcp_server 启动
+pub fn run_mcp_server() -> anyhow::Result<()> {
+    mcp_server::run()
+}
@@ -0,0 +1,81 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+use crate::ollama::OllamaError;
+
+/// 大模型调用错误类型
+///
+/// 与具体服务商无关，分类沿用前端 `errors/ollama.errors.ts` 的约定
+#[derive(Debug, Error)]
+pub enum LlmError {
+    #[error("模型服务不可用或未启动: {0}")]
+    ServiceUnavailable(String),
+
+    #[error("模型服务连接错误: {0}")]
+    Connection(String),
+
+    #[error("模型服务请求超时 ({0}ms)")]
+    Timeout(u64),
+
+    #[error("模型 {model} 不存在: {message}")]
+    ModelNotFound { model: String, message: String },
+
+    #[error("模型服务 API 错误 ({status}): {message}")]
+    Api { status: u16, message: String },
+
+    #[error("流式生成被用户中断")]
+    StreamAborted,
+
+    #[error("解析模型服务响应失败: {0}")]
+    ResponseParse(String),
+
+    #[error("未知的模型服务商: {0}")]
+    UnknownProvider(String),
+}
+
+impl LlmError {
+    /// 错误分类标识
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LlmError::ServiceUnavailable(_) => "service_unavailable",
+            LlmError::Connection(_) => "connection",
+            LlmError::Timeout(_) => "timeout",
+            LlmError::ModelNotFound { .. } => "model_not_found",
+            LlmError::Api { .. } => "api",
+            LlmError::StreamAborted => "stream_aborted",
+            LlmError::ResponseParse(_) => "response_parse",
+            LlmError::UnknownProvider(_) => "unknown_provider",
+        }
+    }
+}
+
+impl From<OllamaError> for LlmError {
+    fn from(error: OllamaError) -> Self {
+        match error {
+            OllamaError::ServiceUnavailable(message) => LlmError::ServiceUnavailable(message),
+            OllamaError::Connection(message) => LlmError::Connection(message),
+            OllamaError::Timeout(ms) => LlmError::Timeout(ms),
+            OllamaError::ModelNotFound { model, message } => LlmError::ModelNotFound { model, message },
+            OllamaError::Api { status, endpoint, message } => LlmError::Api {
+                status,
+                message: format!("[{}] {}", endpoint, message),
+            },
+            OllamaError::StreamAborted => LlmError::StreamAborted,
+            OllamaError::ResponseParse(raw) => LlmError::ResponseParse(raw),
+        }
+    }
+}
+
+// 以 { kind, message } 的形式返回给前端
+impl Serialize for LlmError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("LlmError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+/// 大模型调用结果类型
+pub type LlmResult<T> = Result<T, LlmError>;
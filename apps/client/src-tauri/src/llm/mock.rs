@@ -0,0 +1,279 @@
+// 按脚本返回固定回复的模型服务商，用于测试和离线开发
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use futures_util::stream::{self, BoxStream};
+use futures_util::StreamExt;
+
+use super::error::{LlmError, LlmResult};
+use super::types::{
+    CompletionChunk, CompletionRequest, CompletionResponse, ModelDescriptor, ProviderCapabilities,
+    Role, TokenUsage, ToolCall,
+};
+use super::LlmProvider;
+
+// 模拟模型名称
+pub const MOCK_MODEL: &str = "mock";
+
+// 流式输出时每段包含的字符数
+const STREAM_CHUNK_CHARS: usize = 4;
+// 模拟向量的维数
+pub const MOCK_EMBEDDING_DIMENSIONS: usize = 64;
+
+// 预设的一次回复
+#[derive(Debug, Clone)]
+pub enum MockReply {
+    Text(String),
+    // 请求调用工具，不输出文本
+    ToolCalls(Vec<ToolCall>),
+    Error(String),
+    // 流式输出文本后不再结束，用于测试中断生成
+    Stall(String),
+    // 流式输出文本后返回错误
+    ErrorAfter(String, String),
+}
+
+// 回复输出完文本之后的结束方式
+enum ReplyEnd {
+    Done,
+    Stall,
+    Error(String),
+}
+
+pub struct MockProvider {
+    script: Mutex<VecDeque<MockReply>>,
+    capabilities: ProviderCapabilities,
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self {
+            script: Mutex::new(VecDeque::new()),
+            capabilities: ProviderCapabilities {
+                streaming: true,
+                vision: false,
+                tools: true,
+                json_mode: false,
+            },
+        }
+    }
+
+    pub fn with_capabilities(mut self, capabilities: ProviderCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    // 追加一条回复，按追加顺序依次使用
+    pub fn push_reply(&self, reply: MockReply) {
+        self.script.lock().expect("无法获取脚本锁").push_back(reply);
+    }
+
+    // 取出下一条回复，返回回复文本、请求调用的工具和结束方式；脚本用完后回显最后一条用户消息或工具结果
+    fn next_reply(&self, request: &CompletionRequest) -> LlmResult<(String, Vec<ToolCall>, ReplyEnd)> {
+        let reply = self.script.lock().expect("无法获取脚本锁").pop_front();
+        match reply {
+            Some(MockReply::Text(text)) => Ok((text, Vec::new(), ReplyEnd::Done)),
+            Some(MockReply::ToolCalls(tool_calls)) => Ok((String::new(), tool_calls, ReplyEnd::Done)),
+            Some(MockReply::Error(message)) => Err(LlmError::Api { status: 500, message }),
+            Some(MockReply::Stall(text)) => Ok((text, Vec::new(), ReplyEnd::Stall)),
+            Some(MockReply::ErrorAfter(text, message)) => Ok((text, Vec::new(), ReplyEnd::Error(message))),
+            None => {
+                let last_user = request
+                    .messages
+                    .iter()
+                    .rev()
+                    .find(|m| matches!(m.role, Role::User | Role::Tool))
+                    .map(|m| m.content.as_str())
+                    .unwrap_or_default();
+                Ok((format!("[mock] {}", last_user), Vec::new(), ReplyEnd::Done))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    async fn capabilities(&self, _model: &str) -> LlmResult<ProviderCapabilities> {
+        Ok(self.capabilities)
+    }
+
+    async fn list_models(&self) -> LlmResult<Vec<ModelDescriptor>> {
+        Ok(vec![ModelDescriptor {
+            id: MOCK_MODEL.to_string(),
+            provider: self.name().to_string(),
+            family: None,
+            parameter_size: None,
+        }])
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+        let (content, tool_calls, end) = self.next_reply(request)?;
+        match end {
+            ReplyEnd::Done => {}
+            ReplyEnd::Stall => std::future::pending::<()>().await,
+            ReplyEnd::Error(message) => return Err(LlmError::Api { status: 500, message }),
+        }
+        Ok(CompletionResponse {
+            model: request.model.clone(),
+            usage: Some(usage_of(request, &content)),
+            content,
+            finish_reason: Some(finish_reason(&tool_calls).to_string()),
+            tool_calls,
+        })
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+    ) -> LlmResult<BoxStream<'static, LlmResult<CompletionChunk>>> {
+        let (content, tool_calls, end) = self.next_reply(request)?;
+        let usage = usage_of(request, &content);
+
+        let chars: Vec<char> = content.chars().collect();
+        let mut chunks: Vec<LlmResult<CompletionChunk>> = chars
+            .chunks(STREAM_CHUNK_CHARS)
+            .map(|part| {
+                Ok(CompletionChunk {
+                    delta: part.iter().collect(),
+                    done: false,
+                    finish_reason: None,
+                    usage: None,
+                    tool_calls: Vec::new(),
+                })
+            })
+            .collect();
+        match end {
+            ReplyEnd::Done => chunks.push(Ok(CompletionChunk {
+                delta: String::new(),
+                done: true,
+                finish_reason: Some(finish_reason(&tool_calls).to_string()),
+                usage: Some(usage),
+                tool_calls,
+            })),
+            ReplyEnd::Stall => return Ok(stream::iter(chunks).chain(stream::pending()).boxed()),
+            ReplyEnd::Error(message) => chunks.push(Err(LlmError::Api { status: 500, message })),
+        }
+
+        Ok(stream::iter(chunks).boxed())
+    }
+
+    // 按字符二元组的哈希计数生成归一化向量，相同文本的向量相同，共有字词越多的文本越相似
+    async fn embed(&self, _model: &str, inputs: &[String]) -> LlmResult<Vec<Vec<f32>>> {
+        Ok(inputs.iter().map(|input| mock_embedding(input)).collect())
+    }
+}
+
+fn mock_embedding(text: &str) -> Vec<f32> {
+    let chars: Vec<char> = text.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+    let mut vector = vec![0f32; MOCK_EMBEDDING_DIMENSIONS];
+    let grams: Vec<&[char]> = if chars.len() < 2 { vec![&chars[..]] } else { chars.windows(2).collect() };
+    for gram in grams.into_iter().filter(|gram| !gram.is_empty()) {
+        // FNV-1a
+        let hash = gram.iter().fold(0xcbf29ce484222325u64, |hash, c| {
+            (hash ^ *c as u64).wrapping_mul(0x100000001b3)
+        });
+        vector[(hash % MOCK_EMBEDDING_DIMENSIONS as u64) as usize] += 1.0;
+    }
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+fn finish_reason(tool_calls: &[ToolCall]) -> &'static str {
+    if tool_calls.is_empty() { "stop" } else { "tool_calls" }
+}
+
+// 按字符数粗略统计用量
+fn usage_of(request: &CompletionRequest, content: &str) -> TokenUsage {
+    let prompt_chars: usize = request.messages.iter().map(|m| m.content.chars().count()).sum();
+    TokenUsage {
+        prompt_tokens: prompt_chars as u64,
+        completion_tokens: content.chars().count() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::llm::LlmMessage;
+
+    fn request(content: &str) -> CompletionRequest {
+        CompletionRequest {
+            model: MOCK_MODEL.to_string(),
+            messages: vec![LlmMessage::new(Role::User, content)],
+            options: Default::default(),
+            json_mode: false,
+            tools: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn replies_follow_script_then_echo() {
+        let provider = MockProvider::new();
+        let tool_call = ToolCall { id: "call_1".to_string(), name: "get_time".to_string(), arguments: json!({}) };
+        provider.push_reply(MockReply::Text("第一条".to_string()));
+        provider.push_reply(MockReply::ToolCalls(vec![tool_call.clone()]));
+        provider.push_reply(MockReply::Error("服务异常".to_string()));
+
+        let first = provider.complete(&request("你好")).await.unwrap();
+        assert_eq!(first.content, "第一条");
+        assert_eq!(first.finish_reason.as_deref(), Some("stop"));
+
+        let second = provider.complete(&request("你好")).await.unwrap();
+        assert_eq!(second.tool_calls, vec![tool_call]);
+        assert_eq!(second.finish_reason.as_deref(), Some("tool_calls"));
+
+        assert!(matches!(provider.complete(&request("你好")).await, Err(LlmError::Api { status: 500, .. })));
+        assert_eq!(provider.complete(&request("你好")).await.unwrap().content, "[mock] 你好");
+    }
+
+    #[tokio::test]
+    async fn stream_splits_reply_into_chunks() {
+        let provider = MockProvider::default().with_capabilities(ProviderCapabilities {
+            streaming: true,
+            vision: false,
+            tools: false,
+            json_mode: true,
+        });
+        provider.push_reply(MockReply::Text("一二三四五六".to_string()));
+
+        let chunks: Vec<CompletionChunk> = provider
+            .complete_stream(&request("你好"))
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        let deltas: Vec<&str> = chunks.iter().map(|chunk| chunk.delta.as_str()).collect();
+        assert_eq!(deltas, vec!["一二三四", "五六", ""]);
+        assert!(chunks.last().unwrap().done);
+        assert!(provider.capabilities(MOCK_MODEL).await.unwrap().json_mode);
+    }
+
+    #[tokio::test]
+    async fn embeddings_are_normalized_and_deterministic() {
+        let provider = MockProvider::new();
+        let inputs = vec!["苹果手机".to_string(), "苹果手机".to_string(), "天气预报".to_string()];
+        let vectors = provider.embed(MOCK_MODEL, &inputs).await.unwrap();
+
+        assert_eq!(vectors[0].len(), MOCK_EMBEDDING_DIMENSIONS);
+        assert_eq!(vectors[0], vectors[1]);
+        let norm: f32 = vectors[0].iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+        assert_ne!(vectors[0], vectors[2]);
+    }
+}
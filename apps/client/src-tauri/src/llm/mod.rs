@@ -0,0 +1,45 @@
+// 与服务商无关的大模型调用抽象
+pub mod error;
+#[cfg(any(test, feature = "mock-provider"))]
+pub mod mock;
+pub mod ollama;
+pub mod openai;
+pub mod registry;
+pub mod types;
+
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+
+pub use error::{LlmError, LlmResult};
+pub use registry::ProviderRegistry;
+pub use types::{
+    CompletionChunk, CompletionRequest, CompletionResponse, LlmMessage, ModelDescriptor,
+    ProviderCapabilities, Role, SamplingOptions, TokenUsage, ToolCall, ToolDefinition,
+};
+
+/// 模型服务商
+///
+/// 各服务商将统一的请求转换为自身协议，AI 回复、摘要等业务只依赖该 trait
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    // 服务商名称，与 agents.provider 的取值对应
+    fn name(&self) -> &str;
+
+    // 查询模型支持的能力
+    async fn capabilities(&self, model: &str) -> LlmResult<ProviderCapabilities>;
+
+    // 获取可用模型列表
+    async fn list_models(&self) -> LlmResult<Vec<ModelDescriptor>>;
+
+    // 对话补全（非流式）
+    async fn complete(&self, request: &CompletionRequest) -> LlmResult<CompletionResponse>;
+
+    // 对话补全（流式），正常结束时最后一个元素的 done 为 true
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+    ) -> LlmResult<BoxStream<'static, LlmResult<CompletionChunk>>>;
+
+    // 生成文本向量，返回的向量与 inputs 一一对应
+    async fn embed(&self, model: &str, inputs: &[String]) -> LlmResult<Vec<Vec<f32>>>;
+}
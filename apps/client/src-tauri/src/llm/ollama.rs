@@ -0,0 +1,215 @@
+// 基于 Ollama 的模型服务商
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+
+use super::error::{LlmError, LlmResult};
+use super::types::{
+    CompletionChunk, CompletionRequest, CompletionResponse, LlmMessage, ModelDescriptor,
+    ProviderCapabilities, TokenUsage, ToolCall,
+};
+use super::LlmProvider;
+use crate::ollama::types::{
+    self, ChatMessage, ChatRequest, ChatResponse, EmbedRequest, ModelOptions, ShowRequest,
+    ToolCallFunction,
+};
+use crate::ollama::OllamaClient;
+
+pub struct OllamaProvider {
+    client: OllamaClient,
+}
+
+impl OllamaProvider {
+    pub fn new(client: OllamaClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    // 根据 /api/show 返回的 capabilities 判断，旧版本 Ollama 不返回该字段时按纯文本模型处理
+    async fn capabilities(&self, model: &str) -> LlmResult<ProviderCapabilities> {
+        let request = ShowRequest {
+            model: model.to_string(),
+            verbose: None,
+        };
+        let show = self.client.show_model(&request).await?;
+        let capabilities = show.capabilities.unwrap_or_default();
+        let has = |name: &str| capabilities.iter().any(|c| c == name);
+
+        Ok(ProviderCapabilities {
+            streaming: true,
+            vision: has("vision"),
+            tools: has("tools"),
+            json_mode: true,
+        })
+    }
+
+    async fn list_models(&self) -> LlmResult<Vec<ModelDescriptor>> {
+        let response = self.client.list_models().await?;
+        Ok(response
+            .models
+            .into_iter()
+            .map(|model| {
+                let details = model.details.unwrap_or_default();
+                ModelDescriptor {
+                    id: model.name,
+                    provider: self.name().to_string(),
+                    family: details.family,
+                    parameter_size: details.parameter_size,
+                }
+            })
+            .collect())
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+        let response = self.client.chat(&to_chat_request(request)).await?;
+        let usage = usage_of(&response);
+        let (content, tool_calls) = match response.message {
+            Some(message) => (message.content, from_tool_calls(message.tool_calls)),
+            None => (String::new(), Vec::new()),
+        };
+        Ok(CompletionResponse {
+            usage,
+            content,
+            model: response.model,
+            finish_reason: response.done_reason,
+            tool_calls,
+        })
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+    ) -> LlmResult<BoxStream<'static, LlmResult<CompletionChunk>>> {
+        // 工具调用在结束前的某个元素中整体返回，收集后放在结束元素中
+        let stream = self.client.chat_stream(&to_chat_request(request)).await?;
+        Ok(stream
+            .scan(Vec::new(), |pending: &mut Vec<ToolCall>, chunk| {
+                let item = chunk.map_err(LlmError::from).map(|chunk| {
+                    let (delta, tool_calls) = match chunk.message.clone() {
+                        Some(message) => (message.content, from_tool_calls(message.tool_calls)),
+                        None => (String::new(), Vec::new()),
+                    };
+                    pending.extend(tool_calls);
+                    CompletionChunk {
+                        usage: if chunk.done { usage_of(&chunk) } else { None },
+                        delta,
+                        done: chunk.done,
+                        finish_reason: chunk.done_reason,
+                        tool_calls: if chunk.done { std::mem::take(pending) } else { Vec::new() },
+                    }
+                });
+                futures_util::future::ready(Some(item))
+            })
+            .boxed())
+    }
+
+    async fn embed(&self, model: &str, inputs: &[String]) -> LlmResult<Vec<Vec<f32>>> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let request = EmbedRequest {
+            model: model.to_string(),
+            input: inputs.to_vec(),
+            keep_alive: None,
+        };
+        let response = self.client.embed(&request).await?;
+        if response.embeddings.len() != inputs.len() {
+            return Err(LlmError::ResponseParse(format!(
+                "返回的向量数 {} 与输入数 {} 不一致",
+                response.embeddings.len(),
+                inputs.len()
+            )));
+        }
+        Ok(response.embeddings)
+    }
+}
+
+fn to_chat_request(request: &CompletionRequest) -> ChatRequest {
+    let options = &request.options;
+    ChatRequest {
+        model: request.model.clone(),
+        messages: request.messages.iter().map(to_chat_message).collect(),
+        stream: false,
+        options: Some(ModelOptions {
+            temperature: options.temperature,
+            top_p: options.top_p,
+            top_k: options.top_k,
+            num_predict: options.max_tokens,
+            num_ctx: None,
+            stop: options.stop.clone(),
+            repeat_penalty: options.repeat_penalty,
+            presence_penalty: options.presence_penalty,
+            frequency_penalty: options.frequency_penalty,
+            seed: options.seed,
+        }),
+        format: request.json_mode.then(|| Value::String("json".to_string())),
+        keep_alive: None,
+        tools: (!request.tools.is_empty()).then(|| {
+            request
+                .tools
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": tool.name,
+                            "description": tool.description,
+                            "parameters": tool.parameters,
+                        }
+                    })
+                })
+                .collect()
+        }),
+    }
+}
+
+// Ollama 按顺序对应工具调用和结果，tool 消息不需要调用 ID
+fn to_chat_message(message: &LlmMessage) -> ChatMessage {
+    let tool_calls = message
+        .tool_calls
+        .iter()
+        .map(|call| types::ToolCall {
+            function: ToolCallFunction {
+                name: call.name.clone(),
+                arguments: call.arguments.clone(),
+            },
+        })
+        .collect::<Vec<_>>();
+    ChatMessage {
+        role: message.role.as_str().to_string(),
+        content: message.content.clone(),
+        images: (!message.images.is_empty()).then(|| message.images.clone()),
+        tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+    }
+}
+
+// Ollama 不返回调用 ID，为每个调用生成一个
+fn from_tool_calls(tool_calls: Option<Vec<types::ToolCall>>) -> Vec<ToolCall> {
+    tool_calls
+        .unwrap_or_default()
+        .into_iter()
+        .map(|call| ToolCall {
+            id: format!("call_{}", uuid::Uuid::new_v4().simple()),
+            name: call.function.name,
+            arguments: call.function.arguments,
+        })
+        .collect()
+}
+
+fn usage_of(response: &ChatResponse) -> Option<TokenUsage> {
+    let stats = &response.stats;
+    if stats.prompt_eval_count.is_none() && stats.eval_count.is_none() {
+        return None;
+    }
+    Some(TokenUsage {
+        prompt_tokens: stats.prompt_eval_count.unwrap_or(0),
+        completion_tokens: stats.eval_count.unwrap_or(0),
+    })
+}
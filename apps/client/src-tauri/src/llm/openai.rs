@@ -0,0 +1,757 @@
+// 兼容 OpenAI /v1/chat/completions 接口的模型服务商（llama.cpp server、vLLM、LM Studio 等）
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures_util::stream::{self, BoxStream};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use super::error::{LlmError, LlmResult};
+use super::types::{
+    CompletionChunk, CompletionRequest, CompletionResponse, LlmMessage, ModelDescriptor,
+    ProviderCapabilities, Role, TokenUsage, ToolCall,
+};
+use super::LlmProvider;
+
+// 默认地址，llama.cpp server 的默认端口
+pub const DEFAULT_OPENAI_BASE_URL: &str = "http://localhost:8080/v1";
+
+// 连接超时
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// 非流式请求的整体超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+// 流式响应两次数据之间允许的最长间隔
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+pub struct OpenAiCompatibleProvider {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiCompatibleProvider {
+    // 创建服务商，base_url 形如 http://localhost:8080/v1
+    pub fn new(base_url: &str, api_key: Option<String>) -> Self {
+        let http = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.filter(|key| !key.trim().is_empty()),
+        }
+    }
+
+    // 根据 OPENAI_BASE_URL 与 OPENAI_API_KEY 环境变量创建服务商
+    pub fn from_env() -> Self {
+        let base_url = std::env::var("OPENAI_BASE_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_OPENAI_BASE_URL.to_string());
+        Self::new(base_url.trim(), std::env::var("OPENAI_API_KEY").ok())
+    }
+
+    fn request(&self, method: reqwest::Method, endpoint: &str) -> reqwest::RequestBuilder {
+        let builder = self.http.request(method, format!("{}{}", self.base_url, endpoint));
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatibleProvider {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    // 该协议没有查询模型能力的接口，按常见服务端的支持情况返回
+    async fn capabilities(&self, _model: &str) -> LlmResult<ProviderCapabilities> {
+        Ok(ProviderCapabilities {
+            streaming: true,
+            vision: false,
+            tools: true,
+            json_mode: true,
+        })
+    }
+
+    async fn list_models(&self) -> LlmResult<Vec<ModelDescriptor>> {
+        let response = self
+            .request(reqwest::Method::GET, "/models")
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| map_request_error(e, REQUEST_TIMEOUT))?;
+        let response = check_status(response, "").await?;
+        let body: ModelList = parse_body(response).await?;
+
+        Ok(body
+            .data
+            .into_iter()
+            .map(|model| ModelDescriptor {
+                id: model.id,
+                provider: self.name().to_string(),
+                family: model.owned_by,
+                parameter_size: None,
+            })
+            .collect())
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+        let response = self
+            .request(reqwest::Method::POST, "/chat/completions")
+            .timeout(REQUEST_TIMEOUT)
+            .json(&build_body(request, false))
+            .send()
+            .await
+            .map_err(|e| map_request_error(e, REQUEST_TIMEOUT))?;
+        let response = check_status(response, &request.model).await?;
+        let body: Completion = parse_body(response).await?;
+
+        let choice = body.choices.into_iter().next();
+        let (content, tool_calls) = match choice.as_ref().and_then(|c| c.message.as_ref()) {
+            Some(message) => (
+                message.content.clone().unwrap_or_default(),
+                ToolCallBuilder::build_all(message.tool_calls.iter().map(ToolCallBuilder::from_raw)),
+            ),
+            None => (String::new(), Vec::new()),
+        };
+        Ok(CompletionResponse {
+            model: body.model.unwrap_or_else(|| request.model.clone()),
+            content,
+            finish_reason: choice.and_then(|c| c.finish_reason),
+            usage: body.usage.map(Into::into),
+            tool_calls,
+        })
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+    ) -> LlmResult<BoxStream<'static, LlmResult<CompletionChunk>>> {
+        let response = self
+            .request(reqwest::Method::POST, "/chat/completions")
+            .json(&build_body(request, true))
+            .send()
+            .await
+            .map_err(|e| map_request_error(e, CONNECT_TIMEOUT))?;
+        let response = check_status(response, &request.model).await?;
+        Ok(sse_stream(response))
+    }
+
+    async fn embed(&self, model: &str, inputs: &[String]) -> LlmResult<Vec<Vec<f32>>> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let response = self
+            .request(reqwest::Method::POST, "/embeddings")
+            .timeout(REQUEST_TIMEOUT)
+            .json(&json!({ "model": model, "input": inputs }))
+            .send()
+            .await
+            .map_err(|e| map_request_error(e, REQUEST_TIMEOUT))?;
+        let response = check_status(response, model).await?;
+        let mut body: EmbeddingList = parse_body(response).await?;
+
+        // 按 index 还原输入顺序，未返回 index 时按返回顺序
+        body.data.sort_by_key(|entry| entry.index);
+        if body.data.len() != inputs.len() {
+            return Err(LlmError::ResponseParse(format!(
+                "返回的向量数 {} 与输入数 {} 不一致",
+                body.data.len(),
+                inputs.len()
+            )));
+        }
+        Ok(body.data.into_iter().map(|entry| entry.embedding).collect())
+    }
+}
+
+// 构造请求体，未设置的采样参数不发送
+fn build_body(request: &CompletionRequest, stream: bool) -> Value {
+    let options = &request.options;
+    let mut body = Map::new();
+    body.insert("model".into(), json!(request.model));
+    body.insert(
+        "messages".into(),
+        Value::Array(request.messages.iter().map(to_message).collect()),
+    );
+    body.insert("stream".into(), json!(stream));
+
+    let optional = [
+        ("temperature", options.temperature.map(|v| json!(v))),
+        ("top_p", options.top_p.map(|v| json!(v))),
+        ("top_k", options.top_k.map(|v| json!(v))),
+        ("max_tokens", options.max_tokens.map(|v| json!(v))),
+        ("stop", options.stop.as_ref().map(|v| json!(v))),
+        ("repeat_penalty", options.repeat_penalty.map(|v| json!(v))),
+        ("presence_penalty", options.presence_penalty.map(|v| json!(v))),
+        ("frequency_penalty", options.frequency_penalty.map(|v| json!(v))),
+        ("seed", options.seed.map(|v| json!(v))),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            body.insert(key.into(), value);
+        }
+    }
+
+    if request.json_mode {
+        body.insert("response_format".into(), json!({ "type": "json_object" }));
+    }
+    if !request.tools.is_empty() {
+        let tools = request
+            .tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    }
+                })
+            })
+            .collect();
+        body.insert("tools".into(), Value::Array(tools));
+    }
+    Value::Object(body)
+}
+
+// 带图片的消息使用多段内容格式；工具调用的参数以 JSON 字符串发送
+fn to_message(message: &LlmMessage) -> Value {
+    if message.role == Role::Tool {
+        return json!({
+            "role": message.role.as_str(),
+            "tool_call_id": message.tool_call_id,
+            "content": message.content,
+        });
+    }
+    if !message.tool_calls.is_empty() {
+        let tool_calls: Vec<Value> = message
+            .tool_calls
+            .iter()
+            .map(|call| {
+                let arguments = match &call.arguments {
+                    Value::String(raw) => raw.clone(),
+                    arguments => arguments.to_string(),
+                };
+                json!({
+                    "id": call.id,
+                    "type": "function",
+                    "function": { "name": call.name, "arguments": arguments }
+                })
+            })
+            .collect();
+        let content = (!message.content.is_empty()).then_some(message.content.as_str());
+        return json!({ "role": message.role.as_str(), "content": content, "tool_calls": tool_calls });
+    }
+    if message.images.is_empty() {
+        return json!({ "role": message.role.as_str(), "content": message.content });
+    }
+
+    let mut parts = vec![json!({ "type": "text", "text": message.content })];
+    parts.extend(message.images.iter().map(|image| {
+        json!({
+            "type": "image_url",
+            "image_url": { "url": format!("data:image/png;base64,{}", image) }
+        })
+    }));
+    json!({ "role": message.role.as_str(), "content": parts })
+}
+
+#[derive(Deserialize)]
+struct ModelList {
+    #[serde(default)]
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+    #[serde(default)]
+    owned_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingList {
+    #[serde(default)]
+    data: Vec<EmbeddingEntry>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingEntry {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct Completion {
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    // 非流式响应使用 message，流式响应使用 delta
+    #[serde(default)]
+    message: Option<ChoiceMessage>,
+    #[serde(default)]
+    delta: Option<ChoiceMessage>,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChoiceMessage {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<RawToolCall>,
+}
+
+// 工具调用，流式响应中按 index 分段返回，除第一段外 id 和 name 通常为空
+#[derive(Deserialize)]
+struct RawToolCall {
+    #[serde(default)]
+    index: Option<usize>,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    function: Option<RawFunction>,
+}
+
+#[derive(Deserialize)]
+struct RawFunction {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    arguments: Option<String>,
+}
+
+// 拼接中的工具调用
+#[derive(Default)]
+struct ToolCallBuilder {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+impl ToolCallBuilder {
+    fn from_raw(raw: &RawToolCall) -> Self {
+        let mut builder = Self::default();
+        builder.append(raw);
+        builder
+    }
+
+    fn append(&mut self, raw: &RawToolCall) {
+        if let Some(id) = raw.id.as_deref().filter(|id| !id.is_empty()) {
+            self.id = id.to_string();
+        }
+        if let Some(function) = &raw.function {
+            if let Some(name) = function.name.as_deref().filter(|name| !name.is_empty()) {
+                self.name = name.to_string();
+            }
+            if let Some(arguments) = &function.arguments {
+                self.arguments.push_str(arguments);
+            }
+        }
+    }
+
+    // 参数不是合法 JSON 时保留原始字符串，由调用方报告参数错误；没有名称的调用被忽略
+    fn build_all(builders: impl IntoIterator<Item = ToolCallBuilder>) -> Vec<ToolCall> {
+        builders
+            .into_iter()
+            .filter(|builder| !builder.name.is_empty())
+            .map(|builder| {
+                let arguments = match builder.arguments.trim() {
+                    "" => Value::Object(Map::new()),
+                    raw => serde_json::from_str(raw).unwrap_or_else(|_| Value::String(builder.arguments.clone())),
+                };
+                ToolCall {
+                    id: if builder.id.is_empty() {
+                        format!("call_{}", uuid::Uuid::new_v4().simple())
+                    } else {
+                        builder.id
+                    },
+                    name: builder.name,
+                    arguments,
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct Usage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+impl From<Usage> for TokenUsage {
+    fn from(usage: Usage) -> Self {
+        TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+        }
+    }
+}
+
+// 将 reqwest 错误映射为对应的错误分类
+fn map_request_error(error: reqwest::Error, timeout: Duration) -> LlmError {
+    if error.is_timeout() {
+        LlmError::Timeout(timeout.as_millis() as u64)
+    } else if error.is_connect() {
+        LlmError::ServiceUnavailable(error.to_string())
+    } else {
+        LlmError::Connection(error.to_string())
+    }
+}
+
+// 从 { "error": { "message": "..." } } 或 { "error": "..." } 中取出错误信息
+fn error_message(value: &Value) -> Option<String> {
+    let error = value.get("error")?;
+    error
+        .get("message")
+        .and_then(Value::as_str)
+        .or_else(|| error.as_str())
+        .map(str::to_string)
+}
+
+// 检查响应状态码，非 2xx 时解析返回的错误信息
+async fn check_status(response: reqwest::Response, model: &str) -> LlmResult<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let value = serde_json::from_str::<Value>(&body).ok();
+    let message = value.as_ref().and_then(error_message).unwrap_or(body);
+
+    // 地址配置错误时同样返回 404，只有错误信息指明模型不存在时才视为模型不存在
+    if status == reqwest::StatusCode::NOT_FOUND && !model.is_empty() && is_model_not_found(value.as_ref(), &message) {
+        return Err(LlmError::ModelNotFound {
+            model: model.to_string(),
+            message,
+        });
+    }
+
+    Err(LlmError::Api {
+        status: status.as_u16(),
+        message,
+    })
+}
+
+// 错误码为 model_not_found，或错误信息说明模型不存在（如 vLLM 的 "The model `x` does not exist."）
+fn is_model_not_found(value: Option<&Value>, message: &str) -> bool {
+    let code = value
+        .and_then(|value| value.get("error"))
+        .and_then(|error| error.get("code"))
+        .and_then(Value::as_str);
+    if code == Some("model_not_found") {
+        return true;
+    }
+    let message = message.to_lowercase();
+    message.contains("model") && (message.contains("not found") || message.contains("does not exist"))
+}
+
+async fn parse_body<T: serde::de::DeserializeOwned>(response: reqwest::Response) -> LlmResult<T> {
+    let body = response
+        .text()
+        .await
+        .map_err(|e| LlmError::Connection(e.to_string()))?;
+    serde_json::from_str(&body).map_err(|_| LlmError::ResponseParse(body))
+}
+
+// 解析一行 SSE 数据，返回增量输出和其中分段返回的工具调用，返回 None 表示应跳过该行
+fn parse_event(line: &[u8]) -> Option<LlmResult<(CompletionChunk, Vec<RawToolCall>)>> {
+    let data = line.strip_prefix(b"data:")?.trim_ascii();
+    if data == b"[DONE]" {
+        let chunk = CompletionChunk {
+            delta: String::new(),
+            done: true,
+            finish_reason: None,
+            usage: None,
+            tool_calls: Vec::new(),
+        };
+        return Some(Ok((chunk, Vec::new())));
+    }
+
+    let raw = || String::from_utf8_lossy(data).into_owned();
+    let value: Value = match serde_json::from_slice(data) {
+        Ok(value) => value,
+        Err(_) => return Some(Err(LlmError::ResponseParse(raw()))),
+    };
+    if let Some(message) = error_message(&value) {
+        return Some(Err(LlmError::Api { status: 200, message }));
+    }
+
+    let completion: Completion = match serde_json::from_value(value) {
+        Ok(completion) => completion,
+        Err(_) => return Some(Err(LlmError::ResponseParse(raw()))),
+    };
+    let choice = completion.choices.into_iter().next();
+    let (delta, tool_calls, finish_reason) = match choice {
+        Some(choice) => {
+            let (content, tool_calls) = match choice.delta {
+                Some(delta) => (delta.content.unwrap_or_default(), delta.tool_calls),
+                None => (String::new(), Vec::new()),
+            };
+            (content, tool_calls, choice.finish_reason)
+        }
+        None => (String::new(), Vec::new(), None),
+    };
+    let chunk = CompletionChunk {
+        delta,
+        done: false,
+        finish_reason,
+        usage: completion.usage.map(Into::into),
+        tool_calls: Vec::new(),
+    };
+    Some(Ok((chunk, tool_calls)))
+}
+
+// 将响应体按行切分为 SSE 事件流
+//
+// 结束原因和用量通常在 [DONE] 之前的事件中返回，分段返回的工具调用拼接完整后，统一合并到最后的结束元素中；
+// 服务端未发送 [DONE] 时在响应结束后补发结束元素
+fn sse_stream(response: reqwest::Response) -> BoxStream<'static, LlmResult<CompletionChunk>> {
+    struct State {
+        bytes: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+        buffer: Vec<u8>,
+        finished: bool,
+        done_sent: bool,
+        finish_reason: Option<String>,
+        usage: Option<TokenUsage>,
+        tool_calls: Vec<ToolCallBuilder>,
+    }
+
+    impl State {
+        fn done_chunk(&mut self) -> CompletionChunk {
+            self.done_sent = true;
+            CompletionChunk {
+                delta: String::new(),
+                done: true,
+                finish_reason: self.finish_reason.take(),
+                usage: self.usage.take(),
+                tool_calls: ToolCallBuilder::build_all(std::mem::take(&mut self.tool_calls)),
+            }
+        }
+
+        // 按 index 拼接分段返回的工具调用，未提供 index 时视为新的调用
+        fn append_tool_calls(&mut self, raw_calls: Vec<RawToolCall>) {
+            for raw in raw_calls {
+                let index = raw.index.unwrap_or(self.tool_calls.len());
+                if index >= self.tool_calls.len() {
+                    self.tool_calls.resize_with(index + 1, ToolCallBuilder::default);
+                }
+                self.tool_calls[index].append(&raw);
+            }
+        }
+
+        fn fail(&mut self, error: LlmError) -> LlmResult<CompletionChunk> {
+            self.finished = true;
+            self.done_sent = true;
+            self.buffer.clear();
+            Err(error)
+        }
+    }
+
+    let state = State {
+        bytes: response.bytes_stream().map(|chunk| chunk.map(|b| b.to_vec())).boxed(),
+        buffer: Vec::new(),
+        finished: false,
+        done_sent: false,
+        finish_reason: None,
+        usage: None,
+        tool_calls: Vec::new(),
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if state.done_sent {
+                return None;
+            }
+
+            // 缓冲区中有完整的一行，响应结束后最后一行可能没有换行符
+            let line = match state.buffer.iter().position(|b| *b == b'\n') {
+                Some(pos) => Some(state.buffer.drain(..=pos).collect::<Vec<u8>>()),
+                None if state.finished && !state.buffer.is_empty() => Some(std::mem::take(&mut state.buffer)),
+                None => None,
+            };
+
+            if let Some(line) = line {
+                match parse_event(line.trim_ascii()) {
+                    Some(Ok((chunk, _))) if chunk.done => {
+                        state.finished = true;
+                        state.buffer.clear();
+                        let chunk = state.done_chunk();
+                        return Some((Ok(chunk), state));
+                    }
+                    Some(Ok((mut chunk, raw_calls))) => {
+                        state.append_tool_calls(raw_calls);
+                        if chunk.finish_reason.is_some() {
+                            state.finish_reason = chunk.finish_reason.take();
+                        }
+                        if chunk.usage.is_some() {
+                            state.usage = chunk.usage.take();
+                        }
+                        if chunk.delta.is_empty() {
+                            continue;
+                        }
+                        return Some((Ok(chunk), state));
+                    }
+                    Some(Err(e)) => {
+                        let item = state.fail(e);
+                        return Some((item, state));
+                    }
+                    None => continue,
+                }
+            }
+
+            if state.finished {
+                let chunk = state.done_chunk();
+                return Some((Ok(chunk), state));
+            }
+
+            match tokio::time::timeout(STREAM_IDLE_TIMEOUT, state.bytes.next()).await {
+                Ok(Some(Ok(chunk))) => state.buffer.extend_from_slice(&chunk),
+                Ok(Some(Err(e))) => {
+                    let item = state.fail(map_request_error(e, STREAM_IDLE_TIMEOUT));
+                    return Some((item, state));
+                }
+                Ok(None) => state.finished = true,
+                Err(_) => {
+                    let item = state.fail(LlmError::Timeout(STREAM_IDLE_TIMEOUT.as_millis() as u64));
+                    return Some((item, state));
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::serve_once;
+
+    fn completion_request() -> CompletionRequest {
+        CompletionRequest {
+            model: "qwen".to_string(),
+            messages: vec![LlmMessage::new(Role::User, "你好")],
+            options: Default::default(),
+            json_mode: false,
+            tools: Vec::new(),
+        }
+    }
+
+    async fn collect_stream(chunks: Vec<&'static str>) -> Vec<LlmResult<CompletionChunk>> {
+        let base_url = serve_once(200, "text/event-stream", chunks).await;
+        let provider = OpenAiCompatibleProvider::new(&base_url, None);
+        let stream = provider.complete_stream(&completion_request()).await.unwrap();
+        stream.collect().await
+    }
+
+    #[test]
+    fn parse_event_skips_non_data_lines() {
+        assert!(parse_event(b": keep-alive").is_none());
+        assert!(parse_event(b"event: message").is_none());
+        assert!(matches!(parse_event(b"data: [DONE]"), Some(Ok((ref chunk, _))) if chunk.done));
+        assert!(matches!(parse_event(b"data: {oops"), Some(Err(LlmError::ResponseParse(_)))));
+    }
+
+    #[tokio::test]
+    async fn stream_joins_events_split_across_chunks() {
+        let items = collect_stream(vec![
+            "data: {\"choices\":[{\"delta\":{\"content\":\"你\"}}]}\n\ndata: {\"choices\":[{\"del",
+            "ta\":{\"content\":\"好\"}}]}\n\n: keep-alive\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":3,\"completion_tokens\":2}}\n\ndata: [DONE]\n\n",
+        ])
+        .await;
+        let chunks: Vec<CompletionChunk> = items.into_iter().map(|item| item.unwrap()).collect();
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].delta, "你");
+        assert_eq!(chunks[1].delta, "好");
+        // 结束原因和用量合并到最后的结束元素中
+        let done = &chunks[2];
+        assert!(done.done);
+        assert_eq!(done.finish_reason.as_deref(), Some("stop"));
+        let usage = done.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (3, 2));
+    }
+
+    #[tokio::test]
+    async fn stream_assembles_tool_call_fragments() {
+        let items = collect_stream(vec![
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"function\":{\"name\":\"get_time\",\"arguments\":\"{\\\"zo\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"ne\\\":\\\"UTC\\\"}\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"tool_calls\"}]}\n\n",
+        ])
+        .await;
+        let chunks: Vec<CompletionChunk> = items.into_iter().map(|item| item.unwrap()).collect();
+
+        // 服务端未发送 [DONE] 时在响应结束后补发结束元素
+        assert_eq!(chunks.len(), 1);
+        let done = &chunks[0];
+        assert!(done.done);
+        assert_eq!(done.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(done.tool_calls, vec![ToolCall {
+            id: "call_1".to_string(),
+            name: "get_time".to_string(),
+            arguments: json!({ "zone": "UTC" }),
+        }]);
+    }
+
+    #[tokio::test]
+    async fn stream_reports_error_event() {
+        let items = collect_stream(vec![
+            "data: {\"choices\":[{\"delta\":{\"content\":\"部分\"}}]}\n\n",
+            "data: {\"error\":{\"message\":\"context length exceeded\"}}\n\n",
+        ])
+        .await;
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].as_ref().unwrap().delta, "部分");
+        assert!(matches!(&items[1], Err(LlmError::Api { status: 200, message }) if message == "context length exceeded"));
+    }
+
+    #[tokio::test]
+    async fn missing_model_maps_to_model_not_found() {
+        let base_url = serve_once(404, "application/json", vec![
+            "{\"error\":{\"message\":\"The model `qwen` does not exist.\",\"type\":\"NotFoundError\"}}",
+        ])
+        .await;
+
+        let error = OpenAiCompatibleProvider::new(&base_url, None)
+            .complete(&completion_request())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, LlmError::ModelNotFound { ref model, .. } if model == "qwen"), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn unrelated_not_found_maps_to_api_error() {
+        // 地址缺少 /v1 等情况下服务端返回的普通 404
+        let base_url = serve_once(404, "text/plain", vec!["404 page not found"]).await;
+
+        let error = OpenAiCompatibleProvider::new(&base_url, None)
+            .complete(&completion_request())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, LlmError::Api { status: 404, .. }), "{:?}", error);
+    }
+}
@@ -0,0 +1,65 @@
+// 模型服务商注册表，按 agents.provider 的取值查找服务商
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::error::{LlmError, LlmResult};
+#[cfg(any(test, feature = "mock-provider"))]
+use super::mock::MockProvider;
+use super::ollama::OllamaProvider;
+use super::openai::OpenAiCompatibleProvider;
+use super::LlmProvider;
+use crate::models::Agent;
+use crate::ollama::OllamaClient;
+
+// 未指定服务商时使用的默认值
+pub const DEFAULT_PROVIDER: &str = "ollama";
+
+#[derive(Default)]
+pub struct ProviderRegistry {
+    providers: HashMap<String, Arc<dyn LlmProvider>>,
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 注册内置服务商：ollama、openai（兼容接口），mock 只在测试和开启 mock-provider 特性时注册
+    pub fn with_defaults(ollama_client: OllamaClient) -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(OllamaProvider::new(ollama_client)));
+        registry.register(Arc::new(OpenAiCompatibleProvider::from_env()));
+        #[cfg(any(test, feature = "mock-provider"))]
+        registry.register(Arc::new(MockProvider::new()));
+        registry
+    }
+
+    // 注册服务商，同名服务商会被替换
+    pub fn register(&mut self, provider: Arc<dyn LlmProvider>) {
+        self.providers.insert(provider.name().to_string(), provider);
+    }
+
+    // 按名称查找服务商，名称为空时使用默认服务商
+    pub fn get(&self, name: &str) -> LlmResult<Arc<dyn LlmProvider>> {
+        let name = match name.trim() {
+            "" => DEFAULT_PROVIDER,
+            name => name,
+        };
+        self.providers
+            .get(name)
+            .cloned()
+            .ok_or_else(|| LlmError::UnknownProvider(name.to_string()))
+    }
+
+    // 按 agents.provider 查找 Agent 使用的服务商
+    pub fn for_agent(&self, agent: &Agent) -> LlmResult<Arc<dyn LlmProvider>> {
+        self.get(&agent.provider)
+    }
+
+    // 已注册的服务商名称，按字母排序
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.providers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
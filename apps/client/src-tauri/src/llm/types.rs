@@ -0,0 +1,176 @@
+// 与服务商无关的对话补全请求与响应结构
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// 消息角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    // 工具的返回结果
+    Tool,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        }
+    }
+}
+
+// 对话消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmMessage {
+    pub role: Role,
+    pub content: String,
+    // base64 编码的图片，仅支持视觉的模型可用
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
+    // assistant 消息中请求调用的工具
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    // tool 消息对应的工具调用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl LlmMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            images: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    // 请求调用工具的 assistant 消息，content 为模型在调用前输出的文本，可以为空
+    pub fn tool_calls(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
+        Self {
+            tool_calls,
+            ..Self::new(Role::Assistant, content)
+        }
+    }
+
+    // 工具的返回结果
+    pub fn tool_result(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::new(Role::Tool, content)
+        }
+    }
+}
+
+// 模型请求的一次工具调用
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    // 服务商未返回调用 ID 时由服务商实现生成
+    pub id: String,
+    pub name: String,
+    // 调用参数，模型输出的参数不是合法 JSON 时为原始字符串
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+// 提供给模型的工具定义
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    // 参数的 JSON Schema
+    pub parameters: Value,
+}
+
+// 采样参数，未设置的字段使用服务端默认值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SamplingOptions {
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub top_k: Option<i32>,
+    #[serde(default)]
+    pub max_tokens: Option<i32>,
+    #[serde(default)]
+    pub stop: Option<Vec<String>>,
+    #[serde(default)]
+    pub repeat_penalty: Option<f32>,
+    #[serde(default)]
+    pub presence_penalty: Option<f32>,
+    #[serde(default)]
+    pub frequency_penalty: Option<f32>,
+    #[serde(default)]
+    pub seed: Option<i32>,
+}
+
+// 对话补全请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionRequest {
+    pub model: String,
+    pub messages: Vec<LlmMessage>,
+    #[serde(default)]
+    pub options: SamplingOptions,
+    // 要求模型输出 JSON
+    #[serde(default)]
+    pub json_mode: bool,
+    // 模型可以调用的工具，为空时不提供工具
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+}
+
+// token 用量
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+// 对话补全响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionResponse {
+    pub model: String,
+    pub content: String,
+    pub finish_reason: Option<String>,
+    pub usage: Option<TokenUsage>,
+    // 模型请求调用的工具，为空时 content 即为最终回复
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+// 流式补全的增量输出
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionChunk {
+    pub delta: String,
+    pub done: bool,
+    pub finish_reason: Option<String>,
+    pub usage: Option<TokenUsage>,
+    // 模型请求调用的工具，由服务商实现拼接完整后放在结束元素中
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+// 模型信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelDescriptor {
+    pub id: String,
+    pub provider: String,
+    pub family: Option<String>,
+    pub parameter_size: Option<String>,
+}
+
+// 模型能力标记
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ProviderCapabilities {
+    pub streaming: bool,
+    pub vision: bool,
+    pub tools: bool,
+    pub json_mode: bool,
+}
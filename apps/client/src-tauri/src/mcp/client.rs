@@ -0,0 +1,339 @@
+// 通过标准输入输出与本地 MCP 服务器通信的客户端
+//
+// 每行一条 JSON-RPC 消息；后台任务读取服务器输出，按请求 ID 将响应交给等待中的请求
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{oneshot, Mutex as AsyncMutex};
+
+use super::error::{McpError, McpResult};
+use super::types::{
+    CallToolResult, GetPromptResult, InitializeResult, JsonRpcError, JsonRpcMessage, JsonRpcRequest,
+    ListPromptsResult, ListResourcesResult, ListToolsResult, McpPromptInfo, McpResourceInfo, McpToolInfo,
+    PromptArguments, ReadResourceResult,
+};
+use crate::services::mcp_service::McpServerConfig;
+
+// 客户端优先使用的协议版本，服务器可以选择其他受支持的版本
+pub const PROTOCOL_VERSION: &str = "2025-03-26";
+pub const SUPPORTED_PROTOCOL_VERSIONS: [&str; 2] = ["2025-03-26", "2024-11-05"];
+
+// 启动后完成握手的最长时间，部分服务器首次启动需要下载依赖
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(30);
+// 单个请求的最长等待时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+// 分页列表最多读取的页数，防止服务器返回循环的游标
+const MAX_LIST_PAGES: usize = 50;
+
+// JSON-RPC 的方法不存在错误码
+pub const METHOD_NOT_FOUND: i64 = -32601;
+
+type PendingRequests = Arc<Mutex<HashMap<i64, oneshot::Sender<Result<Value, JsonRpcError>>>>>;
+
+pub struct McpClient {
+    connection: Connection,
+    info: InitializeResult,
+}
+
+impl McpClient {
+    // 启动服务器进程并完成 initialize 握手
+    pub async fn connect(config: &McpServerConfig) -> McpResult<Self> {
+        let spawn_error = |message: String| McpError::Spawn { server: config.name.clone(), message };
+
+        let mut command = Command::new(&config.command);
+        command
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(cwd) = &config.cwd {
+            command.current_dir(cwd);
+        }
+        // 发布版本没有控制台窗口，避免子进程弹出新的控制台
+        #[cfg(windows)]
+        command.creation_flags(0x0800_0000);
+
+        let mut child = command.spawn().map_err(|e| spawn_error(e.to_string()))?;
+        let stdin = child.stdin.take().ok_or_else(|| spawn_error("无法获取标准输入".to_string()))?;
+        let stdout = child.stdout.take().ok_or_else(|| spawn_error("无法获取标准输出".to_string()))?;
+        if let Some(stderr) = child.stderr.take() {
+            // 服务器的日志输出到标准错误，转发到应用日志
+            let server_name = config.name.clone();
+            tauri::async_runtime::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    eprintln!("[MCP {}] {}", server_name, line);
+                }
+            });
+        }
+
+        let stdin = Arc::new(AsyncMutex::new(stdin));
+        let pending = PendingRequests::default();
+        let closed = Arc::new(AtomicBool::new(false));
+        tauri::async_runtime::spawn(read_loop(
+            config.name.clone(),
+            stdout,
+            stdin.clone(),
+            pending.clone(),
+            closed.clone(),
+        ));
+
+        let connection = Connection {
+            server_name: config.name.clone(),
+            stdin,
+            pending,
+            next_id: AtomicI64::new(1),
+            closed,
+            child: AsyncMutex::new(child),
+        };
+        match initialize(&connection).await {
+            Ok(info) => Ok(Self { connection, info }),
+            Err(e) => {
+                connection.shutdown().await;
+                Err(e)
+            }
+        }
+    }
+
+    // 服务器在握手时返回的信息和能力
+    pub fn info(&self) -> &InitializeResult {
+        &self.info
+    }
+
+    // 服务器进程是否已退出
+    pub fn is_closed(&self) -> bool {
+        self.connection.is_closed()
+    }
+
+    // 结束服务器进程，进行中的请求会收到 Closed 错误
+    pub async fn shutdown(&self) {
+        self.connection.shutdown().await
+    }
+
+    pub async fn list_tools(&self) -> McpResult<Vec<McpToolInfo>> {
+        self.require(self.info.capabilities.tools.is_some(), "tools")?;
+        self.list_paged("tools/list", |page: ListToolsResult| (page.tools, page.next_cursor))
+            .await
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> McpResult<CallToolResult> {
+        self.require(self.info.capabilities.tools.is_some(), "tools")?;
+        self.connection.request("tools/call", Some(json!({ "name": name, "arguments": arguments })), REQUEST_TIMEOUT)
+            .await
+    }
+
+    pub async fn list_resources(&self) -> McpResult<Vec<McpResourceInfo>> {
+        self.require(self.info.capabilities.resources.is_some(), "resources")?;
+        self.list_paged("resources/list", |page: ListResourcesResult| (page.resources, page.next_cursor))
+            .await
+    }
+
+    pub async fn read_resource(&self, uri: &str) -> McpResult<ReadResourceResult> {
+        self.require(self.info.capabilities.resources.is_some(), "resources")?;
+        self.connection.request("resources/read", Some(json!({ "uri": uri })), REQUEST_TIMEOUT).await
+    }
+
+    pub async fn list_prompts(&self) -> McpResult<Vec<McpPromptInfo>> {
+        self.require(self.info.capabilities.prompts.is_some(), "prompts")?;
+        self.list_paged("prompts/list", |page: ListPromptsResult| (page.prompts, page.next_cursor))
+            .await
+    }
+
+    pub async fn get_prompt(&self, name: &str, arguments: &PromptArguments) -> McpResult<GetPromptResult> {
+        self.require(self.info.capabilities.prompts.is_some(), "prompts")?;
+        self.connection.request("prompts/get", Some(json!({ "name": name, "arguments": arguments })), REQUEST_TIMEOUT)
+            .await
+    }
+
+    // 依次读取分页列表的所有页
+    async fn list_paged<P, T>(&self, method: &str, split: impl Fn(P) -> (Vec<T>, Option<String>)) -> McpResult<Vec<T>>
+    where
+        P: DeserializeOwned,
+    {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        for _ in 0..MAX_LIST_PAGES {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let (page, next_cursor) = split(self.connection.request(method, Some(params), REQUEST_TIMEOUT).await?);
+            items.extend(page);
+            match next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        Ok(items)
+    }
+
+    fn require(&self, supported: bool, capability: &str) -> McpResult<()> {
+        if supported {
+            Ok(())
+        } else {
+            Err(McpError::Unsupported {
+                server: self.connection.server_name.clone(),
+                capability: capability.to_string(),
+            })
+        }
+    }
+}
+
+// 协商协议版本并获取服务器能力，完成后发送 initialized 通知
+async fn initialize(connection: &Connection) -> McpResult<InitializeResult> {
+    let params = json!({
+        "protocolVersion": PROTOCOL_VERSION,
+        "capabilities": {},
+        "clientInfo": { "name": "guixin", "version": env!("CARGO_PKG_VERSION") },
+    });
+    let info: InitializeResult = connection.request("initialize", Some(params), INITIALIZE_TIMEOUT).await?;
+    if !SUPPORTED_PROTOCOL_VERSIONS.contains(&info.protocol_version.as_str()) {
+        return Err(McpError::Protocol(format!("不支持的协议版本: {}", info.protocol_version)));
+    }
+    connection.notify("notifications/initialized", None).await?;
+    Ok(info)
+}
+
+// 与服务器进程的连接
+struct Connection {
+    server_name: String,
+    stdin: Arc<AsyncMutex<ChildStdin>>,
+    pending: PendingRequests,
+    next_id: AtomicI64,
+    closed: Arc<AtomicBool>,
+    child: AsyncMutex<Child>,
+}
+
+impl Connection {
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    async fn shutdown(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.pending.lock().expect("无法获取请求表锁").clear();
+        let mut child = self.child.lock().await;
+        let _ = child.start_kill();
+        let _ = child.wait().await;
+    }
+
+    // 发送请求并等待响应，超时后通知服务器取消该请求
+    async fn request<T: DeserializeOwned>(&self, method: &str, params: Option<Value>, timeout: Duration) -> McpResult<T> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().expect("无法获取请求表锁").insert(id, sender);
+        // 读取任务结束时会清空请求表，之后登记的请求不会再收到响应
+        if self.is_closed() {
+            self.pending.lock().expect("无法获取请求表锁").remove(&id);
+            return Err(McpError::Closed(self.server_name.clone()));
+        }
+
+        let request = JsonRpcRequest { jsonrpc: "2.0", id: Some(id), method, params };
+        if let Err(e) = self.send(&request).await {
+            self.pending.lock().expect("无法获取请求表锁").remove(&id);
+            return Err(e);
+        }
+
+        let response = match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(McpError::Closed(self.server_name.clone())),
+            Err(_) => {
+                self.pending.lock().expect("无法获取请求表锁").remove(&id);
+                let params = json!({ "requestId": id, "reason": "timeout" });
+                let _ = self.notify("notifications/cancelled", Some(params)).await;
+                return Err(McpError::Timeout {
+                    server: self.server_name.clone(),
+                    method: method.to_string(),
+                    ms: timeout.as_millis() as u64,
+                });
+            }
+        };
+
+        let result = response.map_err(|e| McpError::Server { code: e.code, message: e.message })?;
+        serde_json::from_value(result).map_err(|e| McpError::Protocol(format!("{} 的响应格式错误: {}", method, e)))
+    }
+
+    async fn notify(&self, method: &str, params: Option<Value>) -> McpResult<()> {
+        self.send(&JsonRpcRequest { jsonrpc: "2.0", id: None, method, params }).await
+    }
+
+    async fn send(&self, message: &impl Serialize) -> McpResult<()> {
+        write_message(&self.stdin, message)
+            .await
+            .map_err(|_| McpError::Closed(self.server_name.clone()))
+    }
+}
+
+async fn write_message(stdin: &AsyncMutex<ChildStdin>, message: &impl Serialize) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(line.as_bytes()).await?;
+    stdin.flush().await
+}
+
+// 读取服务器输出直到进程退出：响应交给对应的请求，服务器发起的 ping 请求直接应答，通知忽略
+async fn read_loop(
+    server_name: String,
+    stdout: ChildStdout,
+    stdin: Arc<AsyncMutex<ChildStdin>>,
+    pending: PendingRequests,
+    closed: Arc<AtomicBool>,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: JsonRpcMessage = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("[MCP {}] 无法解析的消息: {} ({})", server_name, line, e);
+                continue;
+            }
+        };
+
+        match (message.method, message.id) {
+            (Some(method), Some(id)) => {
+                let response = if method == "ping" {
+                    json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+                } else {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": METHOD_NOT_FOUND, "message": format!("不支持的方法: {}", method) },
+                    })
+                };
+                if write_message(&stdin, &response).await.is_err() {
+                    break;
+                }
+            }
+            (Some(_), None) => {}
+            (None, Some(id)) => {
+                let Some(id) = id.as_i64() else { continue };
+                let sender = pending.lock().expect("无法获取请求表锁").remove(&id);
+                if let Some(sender) = sender {
+                    let response = match message.error {
+                        Some(error) => Err(error),
+                        None => Ok(message.result.unwrap_or(Value::Null)),
+                    };
+                    let _ = sender.send(response);
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    closed.store(true, Ordering::SeqCst);
+    pending.lock().expect("无法获取请求表锁").clear();
+}
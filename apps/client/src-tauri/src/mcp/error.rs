@@ -0,0 +1,54 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+/// MCP 错误类型
+///
+/// 序列化后通过 `kind` 字段区分，与模型服务的错误格式一致
+#[derive(Debug, Error)]
+pub enum McpError {
+    #[error("启动 MCP 服务器 {server} 失败: {message}")]
+    Spawn { server: String, message: String },
+
+    #[error("MCP 服务器 {0} 已退出")]
+    Closed(String),
+
+    #[error("MCP 服务器 {server} 请求超时 ({ms}ms): {method}")]
+    Timeout { server: String, method: String, ms: u64 },
+
+    #[error("MCP 服务器返回错误 ({code}): {message}")]
+    Server { code: i64, message: String },
+
+    #[error("MCP 协议错误: {0}")]
+    Protocol(String),
+
+    #[error("MCP 服务器 {server} 不支持 {capability}")]
+    Unsupported { server: String, capability: String },
+}
+
+impl McpError {
+    /// 错误分类标识
+    pub fn kind(&self) -> &'static str {
+        match self {
+            McpError::Spawn { .. } => "spawn",
+            McpError::Closed(_) => "closed",
+            McpError::Timeout { .. } => "timeout",
+            McpError::Server { .. } => "server",
+            McpError::Protocol(_) => "protocol",
+            McpError::Unsupported { .. } => "unsupported",
+        }
+    }
+}
+
+// 以 { kind, message } 的形式返回给前端
+impl Serialize for McpError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("McpError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+/// MCP 调用结果类型
+pub type McpResult<T> = Result<T, McpError>;
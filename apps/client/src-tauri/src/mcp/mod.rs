@@ -0,0 +1,93 @@
+// Model Context Protocol 客户端
+//
+// 配置的本地 MCP 服务器以子进程方式启动，通过标准输入输出收发 JSON-RPC 消息；
+// AI 用户启用的服务器提供的工具与内置工具一起提供给模型
+pub mod client;
+pub mod error;
+pub mod tool;
+pub mod types;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+use tokio::sync::Mutex;
+
+use crate::models::McpServer;
+use crate::services::mcp_service::McpServerConfig;
+
+pub use client::McpClient;
+pub use error::McpResult;
+pub use tool::McpTool;
+
+// 已启动的 MCP 服务器，按服务器 ID 复用连接
+#[derive(Default)]
+pub struct McpManager {
+    // 服务器 ID -> (启动时的配置版本，连接)
+    clients: Mutex<HashMap<String, (NaiveDateTime, Arc<McpClient>)>>,
+}
+
+impl McpManager {
+    // 获取服务器的连接，尚未启动、进程已退出或配置在启动后被修改时重新启动
+    //
+    // 启动和握手在锁外进行，一个服务器启动缓慢时不阻塞其他服务器；同一服务器被并发启动时保留先完成的连接
+    pub async fn connect(&self, server: &McpServer) -> McpResult<Arc<McpClient>> {
+        let stale = {
+            let mut clients = self.clients.lock().await;
+            match clients.get(&server.id) {
+                Some((version, client)) if *version == server.updated_at && !client.is_closed() => {
+                    return Ok(client.clone());
+                }
+                Some(_) => clients.remove(&server.id).map(|(_, client)| client),
+                None => None,
+            }
+        };
+        if let Some(stale) = stale {
+            stale.shutdown().await;
+        }
+
+        let client = Arc::new(McpClient::connect(&McpServerConfig::from(server)).await?);
+
+        // 并发启动时先完成的连接已在表中则使用它，结束自己启动的进程；否则记录新连接并结束被替换的连接
+        let (connected, unused) = {
+            let mut clients = self.clients.lock().await;
+            match clients.get(&server.id) {
+                Some((version, existing)) if *version == server.updated_at && !existing.is_closed() => {
+                    (existing.clone(), Some(client))
+                }
+                _ => {
+                    let replaced = clients.insert(server.id.clone(), (server.updated_at, client.clone()));
+                    (client, replaced.map(|(_, replaced)| replaced))
+                }
+            }
+        };
+        if let Some(unused) = unused {
+            unused.shutdown().await;
+        }
+        Ok(connected)
+    }
+
+    // 结束服务器进程，返回服务器是否在运行
+    pub async fn disconnect(&self, server_id: &str) -> bool {
+        let removed = self.clients.lock().await.remove(server_id);
+        match removed {
+            Some((_, client)) => {
+                let running = !client.is_closed();
+                client.shutdown().await;
+                running
+            }
+            None => false,
+        }
+    }
+
+    // 正在运行的服务器 ID
+    pub async fn running_server_ids(&self) -> Vec<String> {
+        self.clients
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, (_, client))| !client.is_closed())
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
@@ -0,0 +1,69 @@
+// 将 MCP 服务器提供的工具包装为 Agent 可以调用的工具
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+use super::client::McpClient;
+use super::types::McpToolInfo;
+use crate::tools::{Tool, ToolContext, ToolError, ToolResult};
+
+// 提供给模型的工具名称的最大长度
+const MAX_TOOL_NAME_CHARS: usize = 64;
+
+pub struct McpTool {
+    name: String,
+    client: Arc<McpClient>,
+    info: McpToolInfo,
+}
+
+impl McpTool {
+    pub fn new(server_name: &str, client: Arc<McpClient>, info: McpToolInfo) -> Self {
+        Self { name: tool_name(server_name, &info.name), client, info }
+    }
+}
+
+// 提供给模型的工具名称：服务器名称__工具名称，避免与内置工具和其他服务器的工具重名；
+// 不合法的字符替换为下划线，超长时截断
+pub fn tool_name(server_name: &str, tool_name: &str) -> String {
+    format!("{}__{}", server_name, tool_name)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .take(MAX_TOOL_NAME_CHARS)
+        .collect()
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        self.info.description.as_deref().unwrap_or_default()
+    }
+
+    fn parameters(&self) -> Value {
+        self.info.input_schema.clone()
+    }
+
+    // 服务器返回 isError 时视为执行失败，错误信息同样返回给模型
+    async fn call(&self, _context: &ToolContext, arguments: Value) -> ToolResult<String> {
+        let arguments = match arguments {
+            Value::Null => Value::Object(Map::new()),
+            Value::Object(arguments) => Value::Object(arguments),
+            arguments => {
+                return Err(ToolError::InvalidArguments(format!("参数不是合法的 JSON 对象: {}", arguments)));
+            }
+        };
+        let result = self
+            .client
+            .call_tool(&self.info.name, arguments)
+            .await
+            .map_err(|e| ToolError::Execution(e.to_string()))?;
+        if result.is_error {
+            return Err(ToolError::Execution(result.to_text()));
+        }
+        Ok(result.to_text())
+    }
+}
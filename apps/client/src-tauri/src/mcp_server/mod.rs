@@ -0,0 +1,345 @@
+// 以 MCP 服务器的方式对外提供聊天记录和资源
+//
+// 由独立的可执行文件启动，通过标准输入输出收发 JSON-RPC 消息，编辑器和其他 Agent 可以读取当前用户的聊天、
+// 消息和上传的资源，检索聊天记录，以及给 AI 联系人发消息并等待其回复。
+// 与桌面应用共用同一个数据库文件，不运行迁移：读取使用只读连接，发送消息时只写入消息和待处理的回复任务，
+// 回复由桌面应用的任务队列生成，服务器查询任务状态等待其结束
+mod resources;
+mod tools;
+
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use crate::db::{self, DbPool};
+use crate::mcp::client::{METHOD_NOT_FOUND, PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS};
+use crate::mcp::types::{
+    Implementation, InitializeResult, JsonRpcError, JsonRpcMessage, McpToolInfo, ServerCapabilities,
+};
+use crate::models::User;
+use crate::services::user_service::UserService;
+use crate::services::ServiceResult;
+use crate::tools::{ToolContext, ToolSet};
+
+// JSON-RPC 错误码
+const PARSE_ERROR: i64 = -32700;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+// MCP 约定的资源不存在错误码
+const RESOURCE_NOT_FOUND: i64 = -32002;
+
+const SERVER_INSTRUCTIONS: &str = "guixin 聊天应用。聊天和消息以 guixin://chat/{id}、guixin://message/{id} 资源提供，\
+用户上传的文本和图片以 guixin://resource/{id} 资源提供；可通过工具检索聊天记录，或给 AI 联系人发消息并获取回复";
+
+type RpcResult<T> = Result<T, JsonRpcError>;
+
+fn rpc_error(code: i64, message: impl Into<String>) -> JsonRpcError {
+    JsonRpcError { code, message: message.into(), data: None }
+}
+
+fn internal_error(e: impl std::fmt::Display) -> JsonRpcError {
+    rpc_error(INTERNAL_ERROR, e.to_string())
+}
+
+// 取出请求参数中的字符串字段
+fn string_param<'a>(params: &'a Value, name: &str) -> RpcResult<&'a str> {
+    params[name]
+        .as_str()
+        .ok_or_else(|| rpc_error(INVALID_PARAMS, format!("缺少参数 {}", name)))
+}
+
+pub struct McpServer {
+    // 只读连接池，发送消息的工具另外持有可写的连接池
+    pool: DbPool,
+    // 以应用的默认用户身份读取聊天和资源、发送消息
+    current_user: User,
+    app_resource_path: PathBuf,
+    tools: ToolSet,
+}
+
+impl McpServer {
+    // 连接应用的数据库，准备对外提供的工具
+    pub fn open() -> ServiceResult<Self> {
+        let pool = db::open_existing_connection()?;
+        let write_pool = db::open_existing_write_connection()?;
+        let app_resource_path = db::get_app_resource_path()?;
+        // 没有用户时会创建默认用户，需使用可写连接
+        let current_user = {
+            let mut conn = write_pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+            UserService::get_default_user(&mut conn)?
+        };
+        Ok(Self::new(pool, write_pool, current_user, app_resource_path))
+    }
+
+    fn new(pool: DbPool, write_pool: DbPool, current_user: User, app_resource_path: PathBuf) -> Self {
+        Self {
+            pool,
+            current_user,
+            app_resource_path,
+            tools: tools::server_tools(write_pool),
+        }
+    }
+
+    // 处理一条消息，请求返回响应，通知和响应返回 None
+    async fn handle(&self, message: JsonRpcMessage) -> Option<Value> {
+        let (Some(method), Some(id)) = (message.method, message.id) else {
+            return None;
+        };
+        let params = message.params.unwrap_or(Value::Null);
+
+        let response = match self.dispatch(&method, &params).await {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+        };
+        Some(response)
+    }
+
+    async fn dispatch(&self, method: &str, params: &Value) -> RpcResult<Value> {
+        match method {
+            "initialize" => Ok(initialize(params)),
+            "ping" => Ok(json!({})),
+            "resources/list" => resources::list(self),
+            "resources/templates/list" => Ok(resources::templates()),
+            "resources/read" => resources::read(self, string_param(params, "uri")?),
+            "tools/list" => {
+                let tools: Vec<McpToolInfo> = self
+                    .tools
+                    .definitions()
+                    .into_iter()
+                    .map(|definition| McpToolInfo {
+                        name: definition.name,
+                        description: Some(definition.description),
+                        input_schema: definition.parameters,
+                    })
+                    .collect();
+                Ok(json!({ "tools": tools }))
+            }
+            "tools/call" => self.call_tool(string_param(params, "name")?, params["arguments"].clone()).await,
+            method => Err(rpc_error(METHOD_NOT_FOUND, format!("不支持的方法: {}", method))),
+        }
+    }
+
+    // 执行工具，执行失败通过 isError 返回给调用方，而不是 JSON-RPC 错误
+    async fn call_tool(&self, name: &str, arguments: Value) -> RpcResult<Value> {
+        if !self.tools.contains(name) {
+            return Err(rpc_error(INVALID_PARAMS, format!("未知的工具: {}", name)));
+        }
+
+        // 工具不在某个聊天中调用，需要聊天的工具通过参数指定
+        let context = ToolContext {
+            pool: self.pool.clone(),
+            chat_id: String::new(),
+            user_id: self.current_user.id.clone(),
+        };
+        let (text, is_error) = match self.tools.call(&context, name, arguments).await {
+            Ok(text) => (text, false),
+            Err(e) => (e.to_string(), true),
+        };
+        Ok(json!({ "content": [{ "type": "text", "text": text }], "isError": is_error }))
+    }
+}
+
+// 使用客户端请求的协议版本，不支持时使用服务器优先的版本，由客户端决定是否继续
+fn initialize(params: &Value) -> Value {
+    let protocol_version = params["protocolVersion"]
+        .as_str()
+        .filter(|version| SUPPORTED_PROTOCOL_VERSIONS.contains(version))
+        .unwrap_or(PROTOCOL_VERSION);
+    let result = InitializeResult {
+        protocol_version: protocol_version.to_string(),
+        capabilities: ServerCapabilities {
+            tools: Some(json!({})),
+            resources: Some(json!({})),
+            prompts: None,
+        },
+        server_info: Implementation {
+            name: "guixin".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        instructions: Some(SERVER_INSTRUCTIONS.to_string()),
+    };
+    serde_json::to_value(result).unwrap_or_default()
+}
+
+fn write_message(message: &Value) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", message)?;
+    stdout.flush()
+}
+
+// 启动服务器，读取标准输入直到关闭
+//
+// 每个请求在单独的任务中处理，等待 AI 回复期间仍可响应其他请求；标准输出只用于协议消息，日志输出到标准错误
+pub fn run() -> ServiceResult<()> {
+    let server = Arc::new(McpServer::open()?);
+
+    let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+    std::thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    tauri::async_runtime::block_on(async move {
+        while let Some(line) = receiver.recv().await {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let message: JsonRpcMessage = match serde_json::from_str(&line) {
+                Ok(message) => message,
+                Err(e) => {
+                    let error = rpc_error(PARSE_ERROR, format!("无法解析的消息: {}", e));
+                    let _ = write_message(&json!({ "jsonrpc": "2.0", "id": null, "error": error }));
+                    continue;
+                }
+            };
+
+            let server = server.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Some(response) = server.handle(message).await {
+                    if let Err(e) = write_message(&response) {
+                        eprintln!("写入 MCP 响应失败: {}", e);
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TEXTS_DIR_NAME;
+    use crate::models::ChatType;
+    use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+    use crate::repositories::chat_repository::ChatRepository;
+    use crate::repositories::message_repository::MessageRepository;
+    use crate::repositories::resource_repository::ResourceRepository;
+    use crate::repositories::user_repository::UserRepository;
+    use crate::test_support::{create_test_chat_with_pool, create_test_database, TempDir, TestChat};
+
+    struct Fixture {
+        server: McpServer,
+        _app_resource_dir: TempDir,
+        chat_id: String,
+        message_id: String,
+        resource_id: String,
+        other_resource_id: String,
+        other_chat_id: String,
+    }
+
+    // 当前用户与 AI 用户的单聊中有一条消息，当前用户和另一个用户各上传了一个文本资源；服务器读取使用只读连接池
+    fn fixture() -> Fixture {
+        let (path, pool) = create_test_database();
+        let TestChat { pool, human, ai, chat } = create_test_chat_with_pool(pool);
+        let other = UserRepository::create(&pool, "其他用户".to_string(), None, false).unwrap();
+        let message = MessageRepository::create(&pool, "明天的会议改到下午".to_string(), &chat.id, &human.id).unwrap();
+
+        let other_chat = ChatRepository::create(&pool, "其他", "", ChatType::Direct).unwrap();
+        ChatParticipantRepository::create(&pool, &other_chat.id, &other.id).unwrap();
+        ChatParticipantRepository::create(&pool, &other_chat.id, &ai.id).unwrap();
+
+        let app_resource_dir = TempDir::new();
+        let app_resource_path = app_resource_dir.path().to_path_buf();
+        std::fs::create_dir_all(app_resource_path.join(TEXTS_DIR_NAME)).unwrap();
+        std::fs::write(app_resource_path.join(TEXTS_DIR_NAME).join("notes.txt"), "会议纪要").unwrap();
+        let resource = ResourceRepository::create(&pool, "纪要", "text", "", "notes.txt", None, &human.id).unwrap();
+        let other_resource =
+            ResourceRepository::create(&pool, "其他", "text", "", "notes.txt", None, &other.id).unwrap();
+
+        let read_pool = db::create_pool(&path, true).unwrap();
+        Fixture {
+            server: McpServer::new(read_pool, pool, human, app_resource_path),
+            _app_resource_dir: app_resource_dir,
+            chat_id: chat.id,
+            message_id: message.id,
+            resource_id: resource.id,
+            other_resource_id: other_resource.id,
+            other_chat_id: other_chat.id,
+        }
+    }
+
+    async fn read(server: &McpServer, uri: &str) -> RpcResult<Value> {
+        server.dispatch("resources/read", &json!({ "uri": uri })).await
+    }
+
+    fn contents_text(result: &Value) -> &str {
+        result["contents"][0]["text"].as_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_chat_message_and_resource() {
+        let fixture = fixture();
+        let server = &fixture.server;
+
+        let result = read(server, &format!("guixin://chat/{}", fixture.chat_id)).await.unwrap();
+        let chat: Value = serde_json::from_str(contents_text(&result)).unwrap();
+        assert_eq!(chat["id"], fixture.chat_id.as_str());
+        assert_eq!(chat["participants"].as_array().unwrap().len(), 2);
+        assert_eq!(chat["messages"][0]["content"], "明天的会议改到下午");
+        assert_eq!(chat["messages"][0]["sender_name"], "用户");
+
+        let result = read(server, &format!("guixin://message/{}", fixture.message_id)).await.unwrap();
+        let message: Value = serde_json::from_str(contents_text(&result)).unwrap();
+        assert_eq!(message["chat_id"], fixture.chat_id.as_str());
+        assert_eq!(message["content"], "明天的会议改到下午");
+
+        let result = read(server, &format!("guixin://resource/{}", fixture.resource_id)).await.unwrap();
+        assert_eq!(contents_text(&result), "会议纪要");
+        assert_eq!(result["contents"][0]["mimeType"], "text/plain");
+    }
+
+    #[tokio::test]
+    async fn other_users_chats_and_resources_are_not_found() {
+        let fixture = fixture();
+        let server = &fixture.server;
+
+        for uri in [
+            format!("guixin://chat/{}", fixture.other_chat_id),
+            format!("guixin://resource/{}", fixture.other_resource_id),
+            "guixin://message/missing".to_string(),
+            "file:///etc/passwd".to_string(),
+        ] {
+            let error = read(server, &uri).await.unwrap_err();
+            assert_eq!(error.code, RESOURCE_NOT_FOUND, "{}", uri);
+        }
+
+        let error = server.dispatch("resources/read", &json!({})).await.unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn calls_tools_with_read_only_pool() {
+        let fixture = fixture();
+        let server = &fixture.server;
+
+        let result = server
+            .dispatch("tools/call", &json!({ "name": "search_history", "arguments": { "query": "会议" } }))
+            .await
+            .unwrap();
+        assert_eq!(result["isError"], false);
+        let text = result["content"][0]["text"].as_str().unwrap();
+        let hits: Value = serde_json::from_str(text).unwrap();
+        assert_eq!(hits["results"][0]["message_id"], fixture.message_id.as_str());
+
+        let result = server
+            .dispatch("tools/call", &json!({ "name": "search_history", "arguments": {} }))
+            .await
+            .unwrap();
+        assert_eq!(result["isError"], true);
+
+        let error = server
+            .dispatch("tools/call", &json!({ "name": "missing", "arguments": {} }))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+    }
+}
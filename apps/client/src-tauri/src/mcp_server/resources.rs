@@ -0,0 +1,211 @@
+// 以 MCP 资源提供的聊天、消息和用户上传的资源
+//
+// guixin://chat/{id} 为聊天当前分支上的全部消息，guixin://message/{id} 为单条消息，均为 JSON；
+// guixin://resource/{id} 为文本资源的内容或 base64 编码的图片
+use std::fs;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use super::{internal_error, rpc_error, McpServer, RpcResult, RESOURCE_NOT_FOUND};
+use crate::db::IMAGES_DIR_NAME;
+use crate::mcp::types::{JsonRpcError, McpResourceInfo, ReadResourceResult, ResourceContents};
+use crate::models::{Message, Resource, User};
+use crate::repositories::chat_repository::ChatListEntry;
+use crate::repositories::error::RepositoryError;
+use crate::repositories::message_repository::MessageRepository;
+use crate::repositories::resource_repository::ResourceRepository;
+use crate::repositories::user_repository::UserRepository;
+use crate::services::chat_service::ChatService;
+use crate::services::message_service::MessageService;
+use crate::services::resource_service::ResourceService;
+
+const CHAT_URI_PREFIX: &str = "guixin://chat/";
+const MESSAGE_URI_PREFIX: &str = "guixin://message/";
+const RESOURCE_URI_PREFIX: &str = "guixin://resource/";
+
+const JSON_MIME_TYPE: &str = "application/json";
+const TEXT_MIME_TYPE: &str = "text/plain";
+
+// 资源列表中最多列出的聊天数，按聊天列表的顺序取前面的聊天
+const MAX_LISTED_CHATS: i64 = 500;
+
+fn not_found(uri: &str) -> JsonRpcError {
+    rpc_error(RESOURCE_NOT_FOUND, format!("资源不存在: {}", uri))
+}
+
+// 当前用户参与的聊天（包括已归档的）和上传的资源
+pub(super) fn list(server: &McpServer) -> RpcResult<Value> {
+    let (chats, _) = ChatService::get_user_chat_list(&server.pool, &server.current_user.id, true, 0, MAX_LISTED_CHATS)
+        .map_err(internal_error)?;
+    let uploads = ResourceRepository::get_by_user_id(&server.pool, &server.current_user.id).map_err(internal_error)?;
+
+    let mut resources: Vec<McpResourceInfo> = chats.iter().map(chat_info).collect();
+    resources.extend(uploads.iter().map(resource_info));
+    Ok(json!({ "resources": resources }))
+}
+
+pub(super) fn templates() -> Value {
+    json!({
+        "resourceTemplates": [
+            {
+                "uriTemplate": format!("{}{{id}}", CHAT_URI_PREFIX),
+                "name": "chat",
+                "description": "聊天当前分支上的全部消息",
+                "mimeType": JSON_MIME_TYPE,
+            },
+            {
+                "uriTemplate": format!("{}{{id}}", MESSAGE_URI_PREFIX),
+                "name": "message",
+                "description": "单条消息",
+                "mimeType": JSON_MIME_TYPE,
+            },
+            {
+                "uriTemplate": format!("{}{{id}}", RESOURCE_URI_PREFIX),
+                "name": "resource",
+                "description": "用户上传的文本或图片",
+            },
+        ],
+    })
+}
+
+pub(super) fn read(server: &McpServer, uri: &str) -> RpcResult<Value> {
+    let contents = if let Some(id) = uri.strip_prefix(CHAT_URI_PREFIX) {
+        read_chat(server, uri, id)?
+    } else if let Some(id) = uri.strip_prefix(MESSAGE_URI_PREFIX) {
+        read_message(server, uri, id)?
+    } else if let Some(id) = uri.strip_prefix(RESOURCE_URI_PREFIX) {
+        read_resource(server, uri, id)?
+    } else {
+        return Err(not_found(uri));
+    };
+    serde_json::to_value(ReadResourceResult { contents: vec![contents] }).map_err(internal_error)
+}
+
+fn chat_info(entry: &ChatListEntry) -> McpResourceInfo {
+    let participants: Vec<&str> = entry.participants.iter().map(|user| user.name.as_str()).collect();
+    McpResourceInfo {
+        uri: format!("{}{}", CHAT_URI_PREFIX, entry.chat.id),
+        name: entry.settings.display_name.clone().unwrap_or_else(|| entry.chat.name.clone()),
+        description: Some(format!("聊天，参与者：{}", participants.join("、"))),
+        mime_type: Some(JSON_MIME_TYPE.to_string()),
+    }
+}
+
+fn resource_info(resource: &Resource) -> McpResourceInfo {
+    McpResourceInfo {
+        uri: format!("{}{}", RESOURCE_URI_PREFIX, resource.id),
+        name: resource.name.clone(),
+        description: resource.description.clone(),
+        mime_type: Some(resource_mime_type(resource).to_string()),
+    }
+}
+
+// 图片的类型由文件扩展名判断
+fn resource_mime_type(resource: &Resource) -> &'static str {
+    if resource.type_ != "image" {
+        return TEXT_MIME_TYPE;
+    }
+    let extension = resource.file_name.rsplit('.').next().unwrap_or_default().to_ascii_lowercase();
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        _ => "image/png",
+    }
+}
+
+fn message_json(message: &Message, senders: &[User]) -> Value {
+    let sender = senders.iter().find(|user| user.id == message.sender_id);
+    json!({
+        "id": message.id,
+        "chat_id": message.chat_id,
+        "sender_id": message.sender_id,
+        "sender_name": sender.map(|user| user.name.as_str()),
+        "sender_is_ai": sender.map(|user| user.is_ai),
+        "kind": message.kind,
+        "status": message.status,
+        "content": message.content,
+        "reply_to_id": message.reply_to_id,
+        "created_at": message.created_at.to_string(),
+        "updated_at": message.updated_at.to_string(),
+    })
+}
+
+fn json_contents(uri: &str, value: &Value) -> RpcResult<ResourceContents> {
+    Ok(ResourceContents {
+        uri: uri.to_string(),
+        mime_type: Some(JSON_MIME_TYPE.to_string()),
+        text: Some(serde_json::to_string_pretty(value).map_err(internal_error)?),
+        blob: None,
+    })
+}
+
+// 只能读取当前用户参与的未删除聊天
+fn read_chat(server: &McpServer, uri: &str, chat_id: &str) -> RpcResult<ResourceContents> {
+    let entry = ChatService::get_user_chat_entry(&server.pool, &server.current_user.id, chat_id)
+        .map_err(|_| not_found(uri))?;
+    let messages = MessageService::get_chat_messages(&server.pool, chat_id).map_err(internal_error)?;
+
+    let participants: Vec<Value> = entry
+        .participants
+        .iter()
+        .map(|user| json!({ "id": user.id, "name": user.name, "is_ai": user.is_ai }))
+        .collect();
+    let messages: Vec<Value> = messages
+        .iter()
+        .map(|message| message_json(message, &entry.participants))
+        .collect();
+    json_contents(
+        uri,
+        &json!({
+            "id": entry.chat.id,
+            "name": entry.settings.display_name.as_deref().unwrap_or(&entry.chat.name),
+            "participants": participants,
+            "messages": messages,
+        }),
+    )
+}
+
+fn read_message(server: &McpServer, uri: &str, message_id: &str) -> RpcResult<ResourceContents> {
+    let message = match MessageRepository::get(&server.pool, message_id) {
+        Ok(message) if message.deleted_at.is_none() => message,
+        Ok(_) | Err(RepositoryError::NotFound) | Err(RepositoryError::DatabaseError(diesel::result::Error::NotFound)) => {
+            return Err(not_found(uri))
+        }
+        Err(e) => return Err(internal_error(e)),
+    };
+    ChatService::get_user_chat_entry(&server.pool, &server.current_user.id, &message.chat_id)
+        .map_err(|_| not_found(uri))?;
+    let senders = UserRepository::get_by_ids(&server.pool, std::slice::from_ref(&message.sender_id))
+        .map_err(internal_error)?;
+
+    json_contents(uri, &message_json(&message, &senders))
+}
+
+// 只能读取当前用户上传的资源
+fn read_resource(server: &McpServer, uri: &str, resource_id: &str) -> RpcResult<ResourceContents> {
+    let resource = match ResourceRepository::get(&server.pool, resource_id) {
+        Ok(resource) if resource.user_id == server.current_user.id => resource,
+        Ok(_) | Err(RepositoryError::NotFound) => return Err(not_found(uri)),
+        Err(e) => return Err(internal_error(e)),
+    };
+    let mime_type = Some(resource_mime_type(&resource).to_string());
+
+    match resource.type_.as_str() {
+        "text" => {
+            let text = ResourceService::read_text_resource_content(&server.pool, &resource.id, &server.app_resource_path)
+                .map_err(internal_error)?;
+            Ok(ResourceContents { uri: uri.to_string(), mime_type, text: Some(text), blob: None })
+        }
+        "image" => {
+            let path = server.app_resource_path.join(IMAGES_DIR_NAME).join(&resource.file_name);
+            let data = fs::read(&path).map_err(|e| internal_error(format!("读取图片失败: {}", e)))?;
+            Ok(ResourceContents { uri: uri.to_string(), mime_type, text: None, blob: Some(BASE64.encode(data)) })
+        }
+        _ => Err(not_found(uri)),
+    }
+}
@@ -0,0 +1,284 @@
+// MCP 服务器提供的工具
+//
+// 调用方不在某个聊天中，需要聊天的工具通过参数指定聊天，结果以 JSON 文本返回
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::db::DbPool;
+use crate::models::{AiJob, AiJobStatus, MessageSearchFilter};
+use crate::repositories::ai_job_repository::AiJobRepository;
+use crate::repositories::message_repository::MessageRepository;
+use crate::services::ai_job_service::AiJobService;
+use crate::services::chat_service::ChatService;
+use crate::services::message_service::{MessageService, MAX_SEARCH_LIMIT};
+use crate::tools::builtin::ListContactsTool;
+use crate::tools::{parse_arguments, Tool, ToolContext, ToolError, ToolResult, ToolSet};
+
+pub const SEARCH_HISTORY: &str = "search_history";
+pub const SEND_MESSAGE: &str = "send_message";
+
+// 检索聊天记录时默认返回的条数
+const DEFAULT_SEARCH_RESULTS: i64 = 20;
+// 等待 AI 回复的最长时间，超时后取消仍在排队的回复任务
+const REPLY_TIMEOUT: Duration = Duration::from_secs(300);
+// 查询回复任务状态的间隔
+const REPLY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+fn execution_error(e: impl std::fmt::Display) -> ToolError {
+    ToolError::Execution(e.to_string())
+}
+
+// 对外提供的工具：联系人列表用于查找 AI 联系人的 ID
+//
+// 工具调用上下文中的连接池是只读的，发送消息使用 write_pool
+pub(super) fn server_tools(write_pool: DbPool) -> ToolSet {
+    ToolSet::new(vec![
+        Arc::new(ListContactsTool),
+        Arc::new(SearchHistoryTool),
+        Arc::new(SendMessageTool { pool: write_pool, reply_timeout: REPLY_TIMEOUT }),
+    ])
+}
+
+#[derive(Deserialize)]
+struct SearchHistoryArguments {
+    query: String,
+    #[serde(default)]
+    chat_id: Option<String>,
+    #[serde(default)]
+    sender_id: Option<String>,
+    #[serde(default)]
+    limit: Option<i64>,
+    #[serde(default)]
+    offset: Option<i64>,
+}
+
+// 全文检索聊天记录，与应用内的检索相同
+struct SearchHistoryTool;
+
+#[async_trait]
+impl Tool for SearchHistoryTool {
+    fn name(&self) -> &str {
+        SEARCH_HISTORY
+    }
+
+    fn description(&self) -> &str {
+        "按关键词检索聊天记录，多个关键词用空格分隔，返回同时包含全部关键词的消息，按相关度排序。\
+         命中的消息可通过 guixin://message/{message_id} 或 guixin://chat/{chat_id} 资源读取"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "检索关键词，多个关键词用空格分隔" },
+                "chat_id": { "type": "string", "description": "只检索该聊天" },
+                "sender_id": { "type": "string", "description": "只检索该用户发送的消息" },
+                "limit": {
+                    "type": "integer",
+                    "description": format!("返回的最大条数，默认 {}", DEFAULT_SEARCH_RESULTS),
+                    "minimum": 1,
+                    "maximum": MAX_SEARCH_LIMIT,
+                },
+                "offset": { "type": "integer", "description": "跳过的条数，用于继续加载", "minimum": 0 },
+            },
+            "required": ["query"],
+        })
+    }
+
+    async fn call(&self, context: &ToolContext, arguments: Value) -> ToolResult<String> {
+        let arguments: SearchHistoryArguments = parse_arguments(arguments)?;
+        let filter = MessageSearchFilter {
+            chat_id: arguments.chat_id,
+            sender_id: arguments.sender_id,
+            ..Default::default()
+        };
+        let offset = arguments.offset.unwrap_or(0).max(0);
+
+        let page = MessageService::search_messages(
+            &context.pool,
+            &arguments.query,
+            &filter,
+            arguments.limit.unwrap_or(DEFAULT_SEARCH_RESULTS),
+            offset,
+        )
+        .map_err(execution_error)?;
+        let next_offset = page.has_more.then_some(offset + page.hits.len() as i64);
+        let hits: Vec<Value> = page
+            .hits
+            .into_iter()
+            .map(|hit| {
+                let row = hit.row;
+                json!({
+                    "message_id": row.id,
+                    "chat_id": row.chat_id,
+                    "chat_name": row.chat_name,
+                    "sender_id": row.sender_id,
+                    "sender_name": row.sender_name,
+                    "sender_is_ai": row.sender_is_ai,
+                    "created_at": row.created_at.to_string(),
+                    "content": row.content,
+                })
+            })
+            .collect();
+        Ok(json!({ "results": hits, "next_offset": next_offset }).to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct SendMessageArguments {
+    agent_id: String,
+    content: String,
+    #[serde(default)]
+    chat_id: Option<String>,
+}
+
+// 以当前用户身份给 AI 联系人发消息，等待其回复完成后返回回复内容
+//
+// 只写入消息和待处理的回复任务，回复由桌面应用生成，应用未运行时等待超时
+struct SendMessageTool {
+    pool: DbPool,
+    reply_timeout: Duration,
+}
+
+#[async_trait]
+impl Tool for SendMessageTool {
+    fn name(&self) -> &str {
+        SEND_MESSAGE
+    }
+
+    fn description(&self) -> &str {
+        "以用户身份给 AI 联系人发送消息，等待其回复后返回回复内容，回复由 guixin 应用生成，需保持应用运行。\
+         未指定聊天时使用与该联系人的单聊，AI 联系人的 ID 可通过 list_contacts 获取"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "agent_id": { "type": "string", "description": "回复消息的 AI 联系人的ID" },
+                "content": { "type": "string", "description": "消息内容" },
+                "chat_id": { "type": "string", "description": "发送到的聊天，该 AI 联系人需是聊天的参与者" },
+            },
+            "required": ["agent_id", "content"],
+        })
+    }
+
+    async fn call(&self, context: &ToolContext, arguments: Value) -> ToolResult<String> {
+        let arguments: SendMessageArguments = parse_arguments(arguments)?;
+        let chat_id = match arguments.chat_id {
+            Some(chat_id) => chat_id,
+            None => {
+                ChatService::create_direct_chat(&self.pool, &context.user_id, &arguments.agent_id)
+                    .map_err(|e| ToolError::InvalidArguments(e.to_string()))?
+                    .id
+            }
+        };
+
+        let message = MessageService::send_message(&self.pool, &chat_id, &context.user_id, &arguments.content, None)
+            .map_err(|e| ToolError::InvalidArguments(e.to_string()))?;
+        let job = AiJobService::create_job(&self.pool, &chat_id, &arguments.agent_id, Some(&message.id), None)
+            .map_err(|e| ToolError::InvalidArguments(e.to_string()))?;
+
+        let job = match tokio::time::timeout(self.reply_timeout, wait_finished(&self.pool, &job.id)).await {
+            Ok(job) => job?,
+            Err(_) => {
+                // 处理中的任务只能由应用中断，这里只取消仍在排队的任务
+                let _ = AiJobRepository::cancel_pending(&self.pool, &job.id);
+                return Err(execution_error(format!(
+                    "等待回复超时（{} 秒），请确认 guixin 应用正在运行",
+                    self.reply_timeout.as_secs()
+                )));
+            }
+        };
+
+        if job.status != AiJobStatus::Completed.as_str() {
+            return Err(execution_error(
+                job.error_message.unwrap_or_else(|| format!("回复未完成: {}", job.status)),
+            ));
+        }
+        let reply = match &job.reply_message_id {
+            Some(reply_message_id) => Some(MessageRepository::get(&self.pool, reply_message_id).map_err(execution_error)?),
+            None => None,
+        };
+        Ok(json!({
+            "chat_id": chat_id,
+            "message_id": message.id,
+            "reply_message_id": reply.as_ref().map(|reply| reply.id.as_str()),
+            "reply": reply.as_ref().map(|reply| reply.content.as_str()).unwrap_or_default(),
+        })
+        .to_string())
+    }
+}
+
+fn is_finished(status: &str) -> bool {
+    status != AiJobStatus::Pending.as_str() && status != AiJobStatus::Processing.as_str()
+}
+
+// 定期查询任务状态，返回结束时的任务
+async fn wait_finished(pool: &DbPool, job_id: &str) -> ToolResult<AiJob> {
+    loop {
+        let job = AiJobService::get_job(pool, job_id).map_err(execution_error)?;
+        if is_finished(&job.status) {
+            return Ok(job);
+        }
+        tokio::time::sleep(REPLY_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::MockProvider;
+    use crate::test_support::{create_test_queue, create_test_users, TestUsers};
+
+    // 真人用户和作为其联系人、使用模拟模型的 AI 用户，尚无聊天
+    fn fixture() -> (DbPool, ToolContext, String) {
+        let TestUsers { pool, human, ai } = create_test_users();
+        let context = ToolContext { pool: pool.clone(), chat_id: String::new(), user_id: human.id };
+        (pool, context, ai.id)
+    }
+
+    // 模拟桌面应用：等到任务写入后处理其他进程加入的待处理任务
+    fn run_app_queue(pool: DbPool) {
+        let queue = create_test_queue(&pool, Arc::new(MockProvider::new()));
+        tokio::spawn(async move {
+            while AiJobRepository::get_chat_ids_with_pending(&pool).unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            queue.wake_pending().unwrap();
+        });
+    }
+
+    #[tokio::test]
+    async fn send_message_waits_for_reply_generated_by_app() {
+        let (pool, context, agent_id) = fixture();
+        run_app_queue(pool.clone());
+
+        let tool = SendMessageTool { pool, reply_timeout: Duration::from_secs(30) };
+        let output = tool
+            .call(&context, json!({ "agent_id": agent_id, "content": "你好" }))
+            .await
+            .unwrap();
+        let value: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value["reply"], "[mock] 你好");
+        assert!(value["reply_message_id"].is_string());
+    }
+
+    #[tokio::test]
+    async fn send_message_cancels_pending_job_on_timeout() {
+        let (pool, context, agent_id) = fixture();
+
+        let tool = SendMessageTool { pool: pool.clone(), reply_timeout: Duration::from_secs(1) };
+        let error = tool
+            .call(&context, json!({ "agent_id": agent_id, "content": "你好" }))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("等待回复超时"));
+
+        assert!(AiJobRepository::get_chat_ids_with_pending(&pool).unwrap().is_empty());
+    }
+}
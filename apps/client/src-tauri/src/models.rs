@@ -9,6 +9,7 @@ use serde::{Deserialize, Serialize};
 pub struct User {
     pub id: String,
     pub name: String,
+    pub avatar_url: Option<String>,
     pub description: Option<String>,
     pub is_ai: bool,
     pub created_at: NaiveDateTime,
@@ -20,12 +21,232 @@ pub struct User {
 pub struct NewUser {
     pub id: String,
     pub name: String,
+    pub avatar_url: Option<String>,
     pub description: Option<String>,
     pub is_ai: bool,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
 
+// Agent 模型，AI 用户的模型配置
+#[derive(Queryable, Selectable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = agents)]
+pub struct Agent {
+    pub id: String,
+    pub provider: String,
+    pub model_name: String,
+    pub system_prompt: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<i32>,
+    pub repeat_penalty: Option<f32>,
+    pub stop_sequences: Option<String>, // JSON 数组格式的停止序列
+    pub max_tokens: Option<i32>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub user_id: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub context_window: Option<i32>,     // 模型上下文长度（token）
+    pub context_strategy: String,        // 上下文组装策略
+    pub context_keep_first: Option<i32>, // keep_first_and_recent 策略保留的最早消息条数
+    pub tools: Option<String>,               // JSON 数组格式的可用内置工具名称
+    pub max_tool_iterations: Option<i32>,    // 一次回复中最多调用工具的轮数
+    pub embedding_model: Option<String>,     // 生成知识库向量使用的模型
+    pub memory_enabled: bool,                // 是否提取并使用长期记忆
+    pub memory_extraction_prompt: Option<String>, // 提取记忆使用的提示词
+}
+
+#[derive(Insertable, Debug, Deserialize)]
+#[diesel(table_name = agents)]
+pub struct NewAgent {
+    pub id: String,
+    pub provider: String,
+    pub model_name: String,
+    pub system_prompt: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<i32>,
+    pub repeat_penalty: Option<f32>,
+    pub stop_sequences: Option<String>,
+    pub max_tokens: Option<i32>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub user_id: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub context_window: Option<i32>,
+    pub context_strategy: String,
+    pub context_keep_first: Option<i32>,
+    pub tools: Option<String>,
+    pub max_tool_iterations: Option<i32>,
+    pub embedding_model: Option<String>,
+    pub memory_enabled: bool,
+    pub memory_extraction_prompt: Option<String>,
+}
+
+// Agent 配置的整体更新，未设置的可空字段会被写为 NULL
+#[derive(AsChangeset, Debug)]
+#[diesel(table_name = agents, treat_none_as_null = true)]
+pub struct AgentChangeset {
+    pub provider: String,
+    pub model_name: String,
+    pub system_prompt: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<i32>,
+    pub repeat_penalty: Option<f32>,
+    pub stop_sequences: Option<String>,
+    pub max_tokens: Option<i32>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub context_window: Option<i32>,
+    pub context_strategy: String,
+    pub context_keep_first: Option<i32>,
+    pub tools: Option<String>,
+    pub max_tool_iterations: Option<i32>,
+    pub embedding_model: Option<String>,
+    pub memory_enabled: bool,
+    pub memory_extraction_prompt: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+// ChatAgentOverride 模型，AI 用户在某个聊天中的配置覆盖，为空的字段沿用 Agent 配置
+#[derive(Queryable, Selectable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = chat_agent_overrides)]
+pub struct ChatAgentOverride {
+    pub id: String,
+    pub chat_id: String,
+    pub agent_user_id: String,
+    pub system_prompt: Option<String>,
+    pub model_name: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<i32>,
+    pub stop_sequences: Option<String>, // JSON 数组格式的停止序列
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = chat_agent_overrides)]
+pub struct NewChatAgentOverride {
+    pub id: String,
+    pub chat_id: String,
+    pub agent_user_id: String,
+    pub system_prompt: Option<String>,
+    pub model_name: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<i32>,
+    pub stop_sequences: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+// 聊天内配置覆盖的整体更新，未设置的字段会被写为 NULL，即恢复沿用 Agent 配置
+#[derive(AsChangeset, Debug)]
+#[diesel(table_name = chat_agent_overrides, treat_none_as_null = true)]
+pub struct ChatAgentOverrideChangeset {
+    pub system_prompt: Option<String>,
+    pub model_name: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<i32>,
+    pub stop_sequences: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+// McpServer 模型，本地 MCP 服务器的启动配置
+#[derive(Queryable, Selectable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = mcp_servers)]
+pub struct McpServer {
+    pub id: String,
+    pub name: String,
+    pub command: String,
+    pub args: String, // JSON 数组格式的启动参数
+    pub env: String,  // JSON 对象格式的环境变量
+    pub cwd: Option<String>,
+    pub enabled: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = mcp_servers)]
+pub struct NewMcpServer {
+    pub id: String,
+    pub name: String,
+    pub command: String,
+    pub args: String,
+    pub env: String,
+    pub cwd: Option<String>,
+    pub enabled: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+// MCP 服务器配置的整体更新
+#[derive(AsChangeset, Debug)]
+#[diesel(table_name = mcp_servers, treat_none_as_null = true)]
+pub struct McpServerChangeset {
+    pub name: String,
+    pub command: String,
+    pub args: String,
+    pub env: String,
+    pub cwd: Option<String>,
+    pub enabled: bool,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = agent_mcp_servers)]
+pub struct NewAgentMcpServer {
+    pub id: String,
+    pub agent_user_id: String,
+    pub server_id: String,
+    pub created_at: NaiveDateTime,
+}
+
+// AgentKnowledgeResource 模型，AI 用户关联的知识库资源
+#[derive(Insertable, Debug)]
+#[diesel(table_name = agent_knowledge_resources)]
+pub struct NewAgentKnowledgeResource {
+    pub id: String,
+    pub agent_user_id: String,
+    pub resource_id: String,
+    pub created_at: NaiveDateTime,
+}
+
+// AgentMemory 模型，AI 用户从对话中提取的长期记忆
+#[derive(Queryable, Selectable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = agent_memories)]
+pub struct AgentMemory {
+    pub id: String,
+    pub agent_user_id: String,
+    pub content: String,
+    pub source_chat_id: Option<String>,    // 提取自的聊天
+    pub source_message_id: Option<String>, // 提取自的消息
+    pub importance: i32,                   // 重要程度 1-5
+    pub is_pinned: bool,                   // 置顶的记忆总是放入上下文
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = agent_memories)]
+pub struct NewAgentMemory {
+    pub id: String,
+    pub agent_user_id: String,
+    pub content: String,
+    pub source_chat_id: Option<String>,
+    pub source_message_id: Option<String>,
+    pub importance: i32,
+    pub is_pinned: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
 // UserContact 模型
 #[derive(Queryable, Selectable, Debug, Serialize, Deserialize)]
 #[diesel(table_name = user_contacts)]
@@ -76,27 +297,80 @@ pub struct NewResource {
     pub updated_at: NaiveDateTime,
 }
 
+// ResourceChunk 模型，文本资源切分后的片段及其向量
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = resource_chunks)]
+pub struct ResourceChunk {
+    pub id: String,
+    pub resource_id: String,
+    pub embedding_provider: String,
+    pub embedding_model: String,
+    pub chunk_index: i32,
+    pub content: String,
+    pub embedding: Vec<u8>,     // 小端序 f32 数组
+    pub content_hash: String,   // 切分时整个资源内容的哈希
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = resource_chunks)]
+pub struct NewResourceChunk {
+    pub id: String,
+    pub resource_id: String,
+    pub embedding_provider: String,
+    pub embedding_model: String,
+    pub chunk_index: i32,
+    pub content: String,
+    pub embedding: Vec<u8>,
+    pub content_hash: String,
+    pub created_at: NaiveDateTime,
+}
+
 // Chat 模型
 #[derive(Queryable, Selectable, Debug, Serialize, Deserialize)]
 #[diesel(table_name = chats)]
 pub struct Chat {
     pub id: String,
-    pub unread_count: i32,
+    pub name: String,
+    pub avatar_urls: String, // JSON 数组格式的头像URL列表
     pub last_message: Option<String>,
     pub last_message_time: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub forked_from_chat_id: Option<String>,    // 分出该聊天的来源聊天
+    pub forked_from_message_id: Option<String>, // 在来源聊天中分出的消息
+    pub deleted_at: Option<NaiveDateTime>,      // 移入回收站的时间，未删除时为空
+    pub reply_policy: String,                   // 由哪些 AI 参与者回复，见 ReplyPolicy
+    pub moderator_user_id: Option<String>,      // moderator 策略下挑选回复者的 AI 参与者
+    pub max_ai_turns: i32,                      // 用户发言后 AI 之间最多连续回复的条数
+    pub chat_type: String,                      // 聊天类型，见 ChatType
 }
 
 #[derive(Insertable, Debug, Deserialize)]
 #[diesel(table_name = chats)]
 pub struct NewChat {
     pub id: String,
-    pub unread_count: i32,
+    pub name: String,
+    pub avatar_urls: String, // JSON 数组格式的头像URL列表
     pub last_message: Option<String>,
     pub last_message_time: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub forked_from_chat_id: Option<String>,    // 分出该聊天的来源聊天
+    pub forked_from_message_id: Option<String>, // 在来源聊天中分出的消息
+    pub reply_policy: String,
+    pub moderator_user_id: Option<String>,
+    pub max_ai_turns: i32,
+    pub chat_type: String,
+}
+
+// 聊天回复策略的修改
+#[derive(AsChangeset, Debug)]
+#[diesel(table_name = chats, treat_none_as_null = true)]
+pub struct ChatReplyPolicyChangeset {
+    pub reply_policy: String,
+    pub moderator_user_id: Option<String>,
+    pub max_ai_turns: i32,
 }
 
 // ChatParticipant 模型
@@ -107,6 +381,8 @@ pub struct ChatParticipant {
     pub joined_at: NaiveDateTime,
     pub chat_id: String,
     pub user_id: String,
+    pub last_read_message_id: Option<String>, // 已读到的最后一条消息，为空时全部未读
+    pub last_read_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable, Debug, Deserialize)]
@@ -118,8 +394,30 @@ pub struct NewChatParticipant {
     pub user_id: String,
 }
 
-// Message 模型
+// 用户对聊天的个人设置，保存在该用户的 chat_participants 记录中
 #[derive(Queryable, Selectable, Debug, Serialize, Deserialize)]
+#[diesel(table_name = chat_participants)]
+pub struct ChatSettings {
+    pub is_pinned: bool,
+    pub is_muted: bool,     // 免打扰，未读消息不计入未读总数
+    pub is_archived: bool,  // 归档，默认不在聊天列表中显示
+    pub sort_order: Option<i32>,       // 自定义排序，数值小的在前
+    pub display_name: Option<String>,  // 自定义聊天名称，为空时使用默认名称
+}
+
+// 聊天设置的修改，为 None 的字段保持不变
+#[derive(AsChangeset, Debug, Default)]
+#[diesel(table_name = chat_participants)]
+pub struct ChatSettingsChangeset {
+    pub is_pinned: Option<bool>,
+    pub is_muted: Option<bool>,
+    pub is_archived: Option<bool>,
+    pub sort_order: Option<Option<i32>>,       // Some(None) 清除自定义排序
+    pub display_name: Option<Option<String>>,  // Some(None) 清除自定义名称
+}
+
+// Message 模型，分出聊天时整行复制插入
+#[derive(Queryable, QueryableByName, Selectable, Insertable, Debug, Serialize, Deserialize)]
 #[diesel(table_name = messages)]
 pub struct Message {
     pub id: String,
@@ -128,6 +426,16 @@ pub struct Message {
     pub updated_at: NaiveDateTime,
     pub chat_id: String,
     pub sender_id: String,
+    pub status: String,
+    pub error_message: Option<String>,
+    pub reply_to_id: Option<String>, // 回复（引用）的消息
+    pub parent_id: Option<String>,       // 所接续的上一条消息，聊天的第一条消息为空
+    pub active_child_id: Option<String>, // 当前选中的下一条消息
+    pub is_active: bool,                 // 是否在当前选中的分支上
+    pub deleted_at: Option<NaiveDateTime>, // 移入回收站的时间，未删除时为空
+    pub kind: String,                      // text / tool_call / tool_result
+    pub tool_calls: Option<String>,        // tool_call 消息请求调用的工具，JSON 数组格式
+    pub tool_call_id: Option<String>,      // tool_result 消息对应的工具调用
 }
 
 #[derive(Insertable, Debug, Deserialize)]
@@ -139,4 +447,240 @@ pub struct NewMessage {
     pub updated_at: NaiveDateTime,
     pub chat_id: String,
     pub sender_id: String,
+    pub status: String,
+    pub reply_to_id: Option<String>,
+    pub parent_id: Option<String>,
+    pub kind: String,
+    pub tool_calls: Option<String>,
+    pub tool_call_id: Option<String>,
+}
+
+// MessageRevision 模型，消息内容被修改前的版本
+#[derive(Queryable, Selectable, Debug, Serialize, Deserialize)]
+#[diesel(table_name = message_revisions)]
+pub struct MessageRevision {
+    pub id: String,
+    pub message_id: String,
+    pub content: String,           // 被替换掉的内容
+    pub editor_id: Option<String>, // 进行这次修改的用户
+    pub created_at: NaiveDateTime, // 修改时间
+}
+
+#[derive(Insertable, Debug, Deserialize)]
+#[diesel(table_name = message_revisions)]
+pub struct NewMessageRevision {
+    pub id: String,
+    pub message_id: String,
+    pub content: String,
+    pub editor_id: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+// 消息检索的过滤条件，未设置的条件不参与过滤
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MessageSearchFilter {
+    pub chat_id: Option<String>,
+    pub sender_id: Option<String>,
+    pub sender_is_ai: Option<bool>,         // 只检索 AI 用户（true）或真人用户（false）的消息
+    pub created_after: Option<NaiveDateTime>,  // 包含该时间
+    pub created_before: Option<NaiveDateTime>, // 不包含该时间
+}
+
+// 消息检索命中的一条消息
+#[derive(QueryableByName, Debug)]
+pub struct MessageSearchRow {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub id: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub chat_id: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub chat_name: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub sender_id: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub sender_name: String,
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    pub sender_is_ai: bool,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub content: String,
+    #[diesel(sql_type = diesel::sql_types::Timestamp)]
+    pub created_at: NaiveDateTime,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub score: f64, // 相关度（bm25 得分取反），越大越相关；只用 LIKE 匹配时为 0
+}
+
+// 消息状态，流式生成的 AI 回复在生成结束前为 streaming
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageStatus {
+    Streaming,
+    Complete,
+    Error,
+    Cancelled,
+}
+
+impl MessageStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageStatus::Streaming => "streaming",
+            MessageStatus::Complete => "complete",
+            MessageStatus::Error => "error",
+            MessageStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+// 消息类型，AI 回复过程中的工具调用和工具结果单独保存为消息，以便重新组装上下文时还原调用过程
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    Text,
+    ToolCall,
+    ToolResult,
+}
+
+impl MessageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageKind::Text => "text",
+            MessageKind::ToolCall => "tool_call",
+            MessageKind::ToolResult => "tool_result",
+        }
+    }
+}
+
+// 聊天回复策略，决定用户发言后由哪些 AI 参与者回复
+//
+// 无论哪种策略，消息中 @ 到的 AI 参与者都会回复；AI 的回复只会触发其中 @ 到的其他 AI 参与者
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplyPolicy {
+    // 只有被 @ 到的 AI 参与者回复
+    Mention,
+    // AI 参与者按加入顺序轮流回复
+    RoundRobin,
+    // 所有 AI 参与者按加入顺序依次回复，与 chats.reply_policy 的列默认值一致
+    #[default]
+    Everyone,
+    // 由主持人 AI 挑选回复者
+    Moderator,
+}
+
+impl ReplyPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReplyPolicy::Mention => "mention",
+            ReplyPolicy::RoundRobin => "round_robin",
+            ReplyPolicy::Everyone => "everyone",
+            ReplyPolicy::Moderator => "moderator",
+        }
+    }
+
+    // 按 chats.reply_policy 解析，未知的策略名返回 None
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "mention" => Some(ReplyPolicy::Mention),
+            "round_robin" => Some(ReplyPolicy::RoundRobin),
+            "everyone" => Some(ReplyPolicy::Everyone),
+            "moderator" => Some(ReplyPolicy::Moderator),
+            _ => None,
+        }
+    }
+}
+
+// 聊天类型，创建时确定，之后不再改变
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatType {
+    // 两个用户之间的单聊，同一对用户只有一个未分出的单聊
+    Direct,
+    // 群聊，与 chats.chat_type 的列默认值一致
+    Group,
+}
+
+impl ChatType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatType::Direct => "direct",
+            ChatType::Group => "group",
+        }
+    }
+}
+
+// 聊天默认的 AI 连续回复上限，与 chats.max_ai_turns 的列默认值一致
+pub const DEFAULT_MAX_AI_TURNS: i32 = 6;
+
+// AiJob 模型，AI 回复任务
+#[derive(Queryable, Selectable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = ai_jobs)]
+pub struct AiJob {
+    pub id: String,
+    pub chat_id: String,
+    pub agent_user_id: String,
+    pub trigger_message_id: Option<String>,
+    pub reply_message_id: Option<String>,
+    pub status: String,
+    pub error_message: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+    pub regenerated_message_id: Option<String>, // 重新生成回复的任务被重新生成的原回复
+}
+
+#[derive(Insertable, Debug, Deserialize)]
+#[diesel(table_name = ai_jobs)]
+pub struct NewAiJob {
+    pub id: String,
+    pub chat_id: String,
+    pub agent_user_id: String,
+    pub trigger_message_id: Option<String>,
+    pub regenerated_message_id: Option<String>,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+// AI 回复任务状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AiJobStatus {
+    Pending,
+    Processing,
+    Completed,
+    Error,
+    Cancelled,
+}
+
+impl AiJobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AiJobStatus::Pending => "pending",
+            AiJobStatus::Processing => "processing",
+            AiJobStatus::Completed => "completed",
+            AiJobStatus::Error => "error",
+            AiJobStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+// 用户在某个聊天中的未读消息数
+#[derive(QueryableByName, Debug)]
+pub struct ChatUnreadCount {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub chat_id: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub unread: i64,
+}
+
+// 消息在兄弟节点中的位置
+#[derive(QueryableByName, Debug)]
+pub struct MessageSiblingPosition {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub id: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub sibling_index: i64, // 按创建时间排序，从 0 开始
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub sibling_count: i64,
 }
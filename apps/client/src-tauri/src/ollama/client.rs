@@ -0,0 +1,374 @@
+// Ollama HTTP 客户端
+use std::time::Duration;
+
+use futures_util::stream::{self, BoxStream};
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::error::{OllamaError, OllamaResult};
+use super::types::{
+    ChatRequest, ChatResponse, EmbedRequest, EmbedResponse, GenerateRequest, GenerateResponse,
+    ModelListResponse, ShowRequest, ShowResponse,
+};
+
+// Ollama 默认地址
+pub const DEFAULT_OLLAMA_HOST: &str = "http://localhost:11434";
+
+// 连接超时
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// 非流式请求的整体超时，首次加载模型可能较慢
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+// 流式响应两次数据之间允许的最长间隔
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Clone)]
+pub struct OllamaClient {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl OllamaClient {
+    // 创建客户端，host 形如 http://localhost:11434
+    pub fn new(host: &str) -> Self {
+        let http = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            http,
+            base_url: format!("{}/api", host.trim_end_matches('/')),
+        }
+    }
+
+    // 根据 OLLAMA_HOST 环境变量创建客户端，未设置时使用默认地址
+    pub fn from_env() -> Self {
+        match std::env::var("OLLAMA_HOST") {
+            Ok(host) if !host.trim().is_empty() => {
+                let host = host.trim();
+                if host.starts_with("http://") || host.starts_with("https://") {
+                    Self::new(host)
+                } else {
+                    Self::new(&format!("http://{}", host))
+                }
+            }
+            _ => Self::new(DEFAULT_OLLAMA_HOST),
+        }
+    }
+
+    // 聊天（非流式）
+    pub async fn chat(&self, request: &ChatRequest) -> OllamaResult<ChatResponse> {
+        let request = ChatRequest { stream: false, ..request.clone() };
+        self.post_json("/chat", &request, &request.model).await
+    }
+
+    // 聊天（流式），每个元素为一行 NDJSON 响应
+    pub async fn chat_stream(
+        &self,
+        request: &ChatRequest,
+    ) -> OllamaResult<BoxStream<'static, OllamaResult<ChatResponse>>> {
+        let request = ChatRequest { stream: true, ..request.clone() };
+        self.post_stream("/chat", &request, &request.model).await
+    }
+
+    // 文本补全（非流式）
+    pub async fn generate(&self, request: &GenerateRequest) -> OllamaResult<GenerateResponse> {
+        let request = GenerateRequest { stream: false, ..request.clone() };
+        self.post_json("/generate", &request, &request.model).await
+    }
+
+    // 文本补全（流式），每个元素为一行 NDJSON 响应
+    pub async fn generate_stream(
+        &self,
+        request: &GenerateRequest,
+    ) -> OllamaResult<BoxStream<'static, OllamaResult<GenerateResponse>>> {
+        let request = GenerateRequest { stream: true, ..request.clone() };
+        self.post_stream("/generate", &request, &request.model).await
+    }
+
+    // 生成文本向量
+    pub async fn embed(&self, request: &EmbedRequest) -> OllamaResult<EmbedResponse> {
+        self.post_json("/embed", request, &request.model).await
+    }
+
+    // 获取本地模型列表
+    pub async fn list_models(&self) -> OllamaResult<ModelListResponse> {
+        let endpoint = "/tags";
+        let response = self.http
+            .get(self.url(endpoint))
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| map_request_error(e, REQUEST_TIMEOUT))?;
+        let response = check_status(response, endpoint, "").await?;
+        parse_body(response).await
+    }
+
+    // 获取模型详情
+    pub async fn show_model(&self, request: &ShowRequest) -> OllamaResult<ShowResponse> {
+        self.post_json("/show", request, &request.model).await
+    }
+
+    fn url(&self, endpoint: &str) -> String {
+        format!("{}{}", self.base_url, endpoint)
+    }
+
+    async fn post_json<Req: Serialize, Resp: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        body: &Req,
+        model: &str,
+    ) -> OllamaResult<Resp> {
+        let response = self.http
+            .post(self.url(endpoint))
+            .timeout(REQUEST_TIMEOUT)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| map_request_error(e, REQUEST_TIMEOUT))?;
+        let response = check_status(response, endpoint, model).await?;
+        parse_body(response).await
+    }
+
+    async fn post_stream<Req: Serialize, Resp: DeserializeOwned + Send + 'static>(
+        &self,
+        endpoint: &str,
+        body: &Req,
+        model: &str,
+    ) -> OllamaResult<BoxStream<'static, OllamaResult<Resp>>> {
+        let response = self.http
+            .post(self.url(endpoint))
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| map_request_error(e, CONNECT_TIMEOUT))?;
+        let response = check_status(response, endpoint, model).await?;
+        Ok(ndjson_stream(response, endpoint.to_string()))
+    }
+}
+
+// 将 reqwest 错误映射为对应的错误分类
+fn map_request_error(error: reqwest::Error, timeout: Duration) -> OllamaError {
+    if error.is_timeout() {
+        OllamaError::Timeout(timeout.as_millis() as u64)
+    } else if error.is_connect() {
+        OllamaError::ServiceUnavailable(error.to_string())
+    } else {
+        OllamaError::Connection(error.to_string())
+    }
+}
+
+// 检查响应状态码，非 2xx 时解析 Ollama 返回的 { "error": "..." }
+async fn check_status(
+    response: reqwest::Response,
+    endpoint: &str,
+    model: &str,
+) -> OllamaResult<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|value| value.get("error").and_then(Value::as_str).map(str::to_string))
+        .unwrap_or(body);
+
+    if status == reqwest::StatusCode::NOT_FOUND && message.contains("not found") {
+        return Err(OllamaError::ModelNotFound {
+            model: model.to_string(),
+            message,
+        });
+    }
+
+    Err(OllamaError::Api {
+        status: status.as_u16(),
+        endpoint: endpoint.to_string(),
+        message,
+    })
+}
+
+async fn parse_body<T: DeserializeOwned>(response: reqwest::Response) -> OllamaResult<T> {
+    let body = response
+        .text()
+        .await
+        .map_err(|e| OllamaError::Connection(e.to_string()))?;
+    serde_json::from_str(&body).map_err(|_| OllamaError::ResponseParse(body))
+}
+
+// 解析一行 NDJSON，流中途出错时 Ollama 会返回 { "error": "..." }
+fn parse_line<T: DeserializeOwned>(line: &[u8], endpoint: &str) -> OllamaResult<T> {
+    let raw = || String::from_utf8_lossy(line).into_owned();
+    let value: Value = serde_json::from_slice(line).map_err(|_| OllamaError::ResponseParse(raw()))?;
+
+    if let Some(message) = value.get("error").and_then(Value::as_str) {
+        return Err(OllamaError::Api {
+            status: 200,
+            endpoint: endpoint.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    serde_json::from_value(value).map_err(|_| OllamaError::ResponseParse(raw()))
+}
+
+// 将响应体按行切分为 NDJSON 流
+fn ndjson_stream<T: DeserializeOwned + Send + 'static>(
+    response: reqwest::Response,
+    endpoint: String,
+) -> BoxStream<'static, OllamaResult<T>> {
+    struct State {
+        bytes: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+        buffer: Vec<u8>,
+        finished: bool,
+        endpoint: String,
+    }
+
+    let state = State {
+        bytes: response.bytes_stream().map(|chunk| chunk.map(|b| b.to_vec())).boxed(),
+        buffer: Vec::new(),
+        finished: false,
+        endpoint,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            // 缓冲区中有完整的一行
+            if let Some(pos) = state.buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = state.buffer.drain(..=pos).collect();
+                let line = line.trim_ascii();
+                if line.is_empty() {
+                    continue;
+                }
+                let item = parse_line(line, &state.endpoint);
+                return Some((item, state));
+            }
+
+            // 响应已结束，处理最后一行没有换行符的数据
+            if state.finished {
+                let rest = std::mem::take(&mut state.buffer);
+                let line = rest.trim_ascii();
+                if line.is_empty() {
+                    return None;
+                }
+                let item = parse_line(line, &state.endpoint);
+                return Some((item, state));
+            }
+
+            match tokio::time::timeout(STREAM_IDLE_TIMEOUT, state.bytes.next()).await {
+                Ok(Some(Ok(chunk))) => state.buffer.extend_from_slice(&chunk),
+                Ok(Some(Err(e))) => {
+                    state.finished = true;
+                    state.buffer.clear();
+                    return Some((Err(map_request_error(e, STREAM_IDLE_TIMEOUT)), state));
+                }
+                Ok(None) => state.finished = true,
+                Err(_) => {
+                    state.finished = true;
+                    state.buffer.clear();
+                    let error = OllamaError::Timeout(STREAM_IDLE_TIMEOUT.as_millis() as u64);
+                    return Some((Err(error), state));
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::serve_once;
+
+    fn chat_request() -> ChatRequest {
+        ChatRequest {
+            model: "llama3".to_string(),
+            messages: Vec::new(),
+            stream: false,
+            options: None,
+            format: None,
+            keep_alive: None,
+            tools: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn chat_stream_joins_lines_split_across_chunks() {
+        let host = serve_once(200, "application/x-ndjson", vec![
+            "{\"model\":\"llama3\",\"created_at\":\"t\",\"message\":{\"role\":\"assistant\",\"content\":\"你\"},\"do",
+            "ne\":false}\n\n{\"model\":\"llama3\",\"created_at\":\"t\",\"message\":{\"role\":\"assistant\",\"content\":\"好\"},\"done\":false}\n",
+            "{\"model\":\"llama3\",\"created_at\":\"t\",\"done\":true,\"done_reason\":\"stop\",\"eval_count\":2}",
+        ]).await;
+
+        let stream = OllamaClient::new(&host).chat_stream(&chat_request()).await.unwrap();
+        let chunks: Vec<ChatResponse> = stream.map(|chunk| chunk.unwrap()).collect().await;
+
+        let content: String = chunks.iter().filter_map(|chunk| chunk.message.as_ref()).map(|m| m.content.as_str()).collect();
+        assert_eq!(content, "你好");
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[2].done);
+        assert_eq!(chunks[2].done_reason.as_deref(), Some("stop"));
+        assert_eq!(chunks[2].stats.eval_count, Some(2));
+    }
+
+    #[tokio::test]
+    async fn chat_stream_reports_error_line() {
+        let host = serve_once(200, "application/x-ndjson", vec![
+            "{\"error\":\"model runner has unexpectedly stopped\"}\n",
+        ]).await;
+
+        let mut stream = OllamaClient::new(&host).chat_stream(&chat_request()).await.unwrap();
+        match stream.next().await {
+            Some(Err(OllamaError::Api { status, endpoint, message })) => {
+                assert_eq!(status, 200);
+                assert_eq!(endpoint, "/chat");
+                assert_eq!(message, "model runner has unexpectedly stopped");
+            }
+            other => panic!("unexpected item: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_model_maps_to_model_not_found() {
+        let host = serve_once(404, "application/json", vec!["{\"error\":\"model 'llama3' not found\"}"]).await;
+
+        let error = OllamaClient::new(&host).chat(&chat_request()).await.unwrap_err();
+        assert!(matches!(error, OllamaError::ModelNotFound { ref model, .. } if model == "llama3"), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn other_error_status_maps_to_api_error() {
+        let host = serve_once(500, "application/json", vec!["{\"error\":\"out of memory\"}"]).await;
+
+        let error = OllamaClient::new(&host).chat(&chat_request()).await.unwrap_err();
+        match error {
+            OllamaError::Api { status, message, .. } => {
+                assert_eq!(status, 500);
+                assert_eq!(message, "out of memory");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_body_maps_to_response_parse() {
+        let host = serve_once(200, "application/json", vec!["not json"]).await;
+
+        let error = OllamaClient::new(&host).list_models().await.unwrap_err();
+        assert!(matches!(error, OllamaError::ResponseParse(ref body) if body == "not json"), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn closed_port_maps_to_service_unavailable() {
+        // 绑定后立即释放，得到一个没有服务监听的端口
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let error = OllamaClient::new(&format!("http://{}", address)).list_models().await.unwrap_err();
+        assert!(matches!(error, OllamaError::ServiceUnavailable(_)), "{:?}", error);
+    }
+}
@@ -0,0 +1,62 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+/// Ollama 错误类型
+///
+/// 分类与前端 `errors/ollama.errors.ts` 保持一致，序列化后通过 `kind` 字段区分
+#[derive(Debug, Error)]
+pub enum OllamaError {
+    #[error("Ollama 服务不可用或未启动: {0}")]
+    ServiceUnavailable(String),
+
+    #[error("Ollama 连接错误: {0}")]
+    Connection(String),
+
+    #[error("Ollama 请求超时 ({0}ms)")]
+    Timeout(u64),
+
+    #[error("模型 {model} 不存在: {message}")]
+    ModelNotFound { model: String, message: String },
+
+    #[error("Ollama API 错误 [{endpoint}] ({status}): {message}")]
+    Api {
+        status: u16,
+        endpoint: String,
+        message: String,
+    },
+
+    #[error("流式生成被用户中断")]
+    StreamAborted,
+
+    #[error("解析 Ollama 响应失败: {0}")]
+    ResponseParse(String),
+}
+
+impl OllamaError {
+    /// 错误分类标识，对应前端的异常类
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OllamaError::ServiceUnavailable(_) => "service_unavailable",
+            OllamaError::Connection(_) => "connection",
+            OllamaError::Timeout(_) => "timeout",
+            OllamaError::ModelNotFound { .. } => "model_not_found",
+            OllamaError::Api { .. } => "api",
+            OllamaError::StreamAborted => "stream_aborted",
+            OllamaError::ResponseParse(_) => "response_parse",
+        }
+    }
+}
+
+// 以 { kind, message } 的形式返回给前端
+impl Serialize for OllamaError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("OllamaError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+/// Ollama 结果类型
+pub type OllamaResult<T> = Result<T, OllamaError>;
@@ -0,0 +1,90 @@
+// Ollama 本地模型服务客户端
+//
+// 前端不再直接请求 Ollama，所有调用都经由后端完成，流式输出通过 Tauri Channel 推送
+pub mod client;
+pub mod error;
+pub mod types;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use futures_util::future::{AbortHandle, AbortRegistration};
+
+pub use client::OllamaClient;
+pub use error::{OllamaError, OllamaResult};
+
+// 正在进行的流式请求，用于按 stream_id 中断
+#[derive(Default)]
+pub struct OllamaStreamRegistry {
+    streams: Mutex<HashMap<String, RegisteredStream>>,
+    next_generation: AtomicU64,
+}
+
+// 登记的流式请求，generation 用于区分先后使用同一 stream_id 登记的请求
+struct RegisteredStream {
+    generation: u64,
+    handle: AbortHandle,
+}
+
+impl OllamaStreamRegistry {
+    // 登记一个流式请求，返回本次登记的编号和用于包装流的中断注册
+    pub fn register(&self, stream_id: &str) -> (u64, AbortRegistration) {
+        let (handle, registration) = AbortHandle::new_pair();
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let mut streams = self.streams.lock().expect("无法获取流注册表锁");
+        // 同一 ID 的旧请求直接中断
+        if let Some(previous) = streams.insert(stream_id.to_string(), RegisteredStream { generation, handle }) {
+            previous.handle.abort();
+        }
+        (generation, registration)
+    }
+
+    // 中断指定的流式请求，返回是否找到该请求
+    pub fn abort(&self, stream_id: &str) -> bool {
+        let stream = self.streams.lock().expect("无法获取流注册表锁").remove(stream_id);
+        match stream {
+            Some(stream) => {
+                stream.handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 流式请求结束后移除登记，该 ID 已被之后的请求重新登记时保留新的登记
+    pub fn remove(&self, stream_id: &str, generation: u64) {
+        let mut streams = self.streams.lock().expect("无法获取流注册表锁");
+        if streams.get(stream_id).is_some_and(|stream| stream.generation == generation) {
+            streams.remove(stream_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_keeps_newer_registration_with_same_id() {
+        let registry = OllamaStreamRegistry::default();
+        let (old_generation, old_registration) = registry.register("stream");
+        let (_, new_registration) = registry.register("stream");
+
+        // 旧请求被新请求顶替时中断，旧请求结束后的移除不影响新请求
+        assert!(old_registration.handle().is_aborted());
+        registry.remove("stream", old_generation);
+        assert!(!new_registration.handle().is_aborted());
+        assert!(registry.abort("stream"));
+        assert!(new_registration.handle().is_aborted());
+    }
+
+    #[test]
+    fn remove_own_registration() {
+        let registry = OllamaStreamRegistry::default();
+        let (generation, _registration) = registry.register("stream");
+
+        registry.remove("stream", generation);
+        assert!(!registry.abort("stream"));
+    }
+}
@@ -0,0 +1,117 @@
+// AI 用户长期记忆仓库
+use chrono::Utc;
+use diesel::prelude::*;
+
+use super::error::RepositoryError;
+use crate::db::DbConnection;
+use crate::models::{AgentMemory, NewAgentMemory};
+use crate::schema::agent_memories;
+
+pub struct AgentMemoryRepository;
+
+impl AgentMemoryRepository {
+    // 使用已有连接获取 AI 用户的全部记忆，置顶的在前，其余按重要程度和更新时间降序
+    pub fn get_by_agent_with_conn(
+        conn: &mut DbConnection,
+        agent_user_id: &str,
+    ) -> Result<Vec<AgentMemory>, RepositoryError> {
+        agent_memories::table
+            .filter(agent_memories::agent_user_id.eq(agent_user_id))
+            .order((
+                agent_memories::is_pinned.desc(),
+                agent_memories::importance.desc(),
+                agent_memories::updated_at.desc(),
+            ))
+            .select(AgentMemory::as_select())
+            .load(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接获取单条记忆
+    pub fn get_with_conn(conn: &mut DbConnection, id: &str) -> Result<AgentMemory, RepositoryError> {
+        agent_memories::table
+            .filter(agent_memories::id.eq(id))
+            .select(AgentMemory::as_select())
+            .first(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    RepositoryError::NotFound
+                } else {
+                    RepositoryError::DatabaseError(e)
+                }
+            })
+    }
+
+    // 使用已有连接批量写入记忆
+    pub fn create_with_conn(conn: &mut DbConnection, memories: &[NewAgentMemory]) -> Result<(), RepositoryError> {
+        diesel::insert_into(agent_memories::table)
+            .values(memories)
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+
+    // 使用已有连接修改记忆的内容和重要程度
+    pub fn update_with_conn(
+        conn: &mut DbConnection,
+        id: &str,
+        content: &str,
+        importance: i32,
+    ) -> Result<AgentMemory, RepositoryError> {
+        let updated = diesel::update(agent_memories::table.filter(agent_memories::id.eq(id)))
+            .set((
+                agent_memories::content.eq(content),
+                agent_memories::importance.eq(importance),
+                agent_memories::updated_at.eq(Utc::now().naive_utc()),
+            ))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        if updated == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Self::get_with_conn(conn, id)
+    }
+
+    // 使用已有连接置顶或取消置顶记忆
+    pub fn set_pinned_with_conn(conn: &mut DbConnection, id: &str, is_pinned: bool) -> Result<AgentMemory, RepositoryError> {
+        let updated = diesel::update(agent_memories::table.filter(agent_memories::id.eq(id)))
+            .set((
+                agent_memories::is_pinned.eq(is_pinned),
+                agent_memories::updated_at.eq(Utc::now().naive_utc()),
+            ))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        if updated == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Self::get_with_conn(conn, id)
+    }
+
+    // 使用已有连接删除记忆
+    pub fn delete_by_ids_with_conn(conn: &mut DbConnection, ids: &[String]) -> Result<usize, RepositoryError> {
+        diesel::delete(agent_memories::table.filter(agent_memories::id.eq_any(ids)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接清除记忆对聊天的引用，记忆本身保留
+    pub fn clear_chat_refs_with_conn(conn: &mut DbConnection, chat_id: &str) -> Result<(), RepositoryError> {
+        diesel::update(agent_memories::table.filter(agent_memories::source_chat_id.eq(chat_id)))
+            .set((
+                agent_memories::source_chat_id.eq(None::<String>),
+                agent_memories::source_message_id.eq(None::<String>),
+            ))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+
+    // 使用已有连接清除记忆对消息的引用，记忆本身保留
+    pub fn clear_message_refs_with_conn(conn: &mut DbConnection, message_id: &str) -> Result<(), RepositoryError> {
+        diesel::update(agent_memories::table.filter(agent_memories::source_message_id.eq(message_id)))
+            .set(agent_memories::source_message_id.eq(None::<String>))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use diesel::prelude::*;
+
+use super::error::RepositoryError;
+use crate::db::DbConnection;
+use crate::models::{Agent, AgentChangeset, NewAgent};
+use crate::schema::agents;
+
+pub struct AgentRepository;
+
+impl AgentRepository {
+    // 使用已有连接创建 Agent
+    pub fn create_with_conn(conn: &mut DbConnection, new_agent: &NewAgent) -> Result<Agent, RepositoryError> {
+        diesel::insert_into(agents::table)
+            .values(new_agent)
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        agents::table
+            .filter(agents::id.eq(&new_agent.id))
+            .select(Agent::as_select())
+            .first(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接根据 AI 用户 ID 获取 Agent
+    pub fn get_by_user_id_with_conn(conn: &mut DbConnection, user_id: &str) -> Result<Agent, RepositoryError> {
+        agents::table
+            .filter(agents::user_id.eq(user_id))
+            .select(Agent::as_select())
+            .first(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    RepositoryError::NotFound
+                } else {
+                    RepositoryError::DatabaseError(e)
+                }
+            })
+    }
+
+    // 使用已有连接更新 Agent 配置
+    pub fn update_with_conn(
+        conn: &mut DbConnection,
+        id: &str,
+        changeset: &AgentChangeset,
+    ) -> Result<Agent, RepositoryError> {
+        let updated = diesel::update(agents::table.filter(agents::id.eq(id)))
+            .set(changeset)
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        if updated == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        agents::table
+            .filter(agents::id.eq(id))
+            .select(Agent::as_select())
+            .first(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+}
@@ -0,0 +1,240 @@
+use chrono::Utc;
+use diesel::prelude::*;
+
+use super::error::RepositoryError;
+use crate::db::{DbConnection, DbPool};
+use crate::models::{AiJob, AiJobStatus, NewAiJob};
+use crate::schema::ai_jobs;
+
+pub struct AiJobRepository;
+
+impl AiJobRepository {
+    // 使用已有连接创建任务
+    pub fn create_with_conn(conn: &mut DbConnection, new_job: &NewAiJob) -> Result<AiJob, RepositoryError> {
+        diesel::insert_into(ai_jobs::table)
+            .values(new_job)
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        ai_jobs::table
+            .filter(ai_jobs::id.eq(&new_job.id))
+            .select(AiJob::as_select())
+            .first(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 获取任务
+    pub fn get(pool: &DbPool, id: &str) -> Result<AiJob, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        ai_jobs::table
+            .filter(ai_jobs::id.eq(id))
+            .select(AiJob::as_select())
+            .first(&mut conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    RepositoryError::NotFound
+                } else {
+                    RepositoryError::DatabaseError(e)
+                }
+            })
+    }
+
+    // 获取聊天中最早的待处理任务
+    pub fn get_next_pending(pool: &DbPool, chat_id: &str) -> Result<Option<AiJob>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        ai_jobs::table
+            .filter(ai_jobs::chat_id.eq(chat_id))
+            .filter(ai_jobs::status.eq(AiJobStatus::Pending.as_str()))
+            .order((ai_jobs::created_at.asc(), ai_jobs::id.asc()))
+            .select(AiJob::as_select())
+            .first(&mut conn)
+            .optional()
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 获取聊天中的任务，active_only 为 true 时只返回待处理和处理中的任务
+    pub fn get_by_chat_id(
+        pool: &DbPool,
+        chat_id: &str,
+        active_only: bool,
+    ) -> Result<Vec<AiJob>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let mut query = ai_jobs::table
+            .filter(ai_jobs::chat_id.eq(chat_id))
+            .into_boxed();
+        if active_only {
+            query = query.filter(ai_jobs::status.eq_any([
+                AiJobStatus::Pending.as_str(),
+                AiJobStatus::Processing.as_str(),
+            ]));
+        }
+
+        query
+            .order((ai_jobs::created_at.asc(), ai_jobs::id.asc()))
+            .select(AiJob::as_select())
+            .load(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 获取存在待处理任务的聊天 ID
+    pub fn get_chat_ids_with_pending(pool: &DbPool) -> Result<Vec<String>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        ai_jobs::table
+            .filter(ai_jobs::status.eq(AiJobStatus::Pending.as_str()))
+            .select(ai_jobs::chat_id)
+            .distinct()
+            .load(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 将待处理任务标记为处理中，任务已不是待处理状态时返回 false
+    pub fn mark_processing(pool: &DbPool, id: &str) -> Result<bool, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+        let now = Utc::now().naive_utc();
+
+        let updated = diesel::update(
+            ai_jobs::table
+                .filter(ai_jobs::id.eq(id))
+                .filter(ai_jobs::status.eq(AiJobStatus::Pending.as_str())),
+        )
+        .set((
+            ai_jobs::status.eq(AiJobStatus::Processing.as_str()),
+            ai_jobs::started_at.eq(now),
+            ai_jobs::updated_at.eq(now),
+        ))
+        .execute(&mut conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
+    // 记录任务的回复消息，回复消息在开始生成时创建
+    pub fn set_reply_message(pool: &DbPool, id: &str, reply_message_id: &str) -> Result<AiJob, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        diesel::update(ai_jobs::table.filter(ai_jobs::id.eq(id)))
+            .set((
+                ai_jobs::reply_message_id.eq(reply_message_id),
+                ai_jobs::updated_at.eq(Utc::now().naive_utc()),
+            ))
+            .execute(&mut conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        ai_jobs::table
+            .filter(ai_jobs::id.eq(id))
+            .select(AiJob::as_select())
+            .first(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 结束处理中的任务，写入最终状态和错误信息
+    pub fn finish(
+        pool: &DbPool,
+        id: &str,
+        status: AiJobStatus,
+        error_message: Option<&str>,
+    ) -> Result<AiJob, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+        let now = Utc::now().naive_utc();
+
+        diesel::update(
+            ai_jobs::table
+                .filter(ai_jobs::id.eq(id))
+                .filter(ai_jobs::status.eq(AiJobStatus::Processing.as_str())),
+        )
+        .set((
+            ai_jobs::status.eq(status.as_str()),
+            ai_jobs::error_message.eq(error_message),
+            ai_jobs::finished_at.eq(now),
+            ai_jobs::updated_at.eq(now),
+        ))
+        .execute(&mut conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        ai_jobs::table
+            .filter(ai_jobs::id.eq(id))
+            .select(AiJob::as_select())
+            .first(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 取消待处理任务，任务已开始或已结束时返回 false
+    pub fn cancel_pending(pool: &DbPool, id: &str) -> Result<bool, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+        let now = Utc::now().naive_utc();
+
+        let updated = diesel::update(
+            ai_jobs::table
+                .filter(ai_jobs::id.eq(id))
+                .filter(ai_jobs::status.eq(AiJobStatus::Pending.as_str())),
+        )
+        .set((
+            ai_jobs::status.eq(AiJobStatus::Cancelled.as_str()),
+            ai_jobs::finished_at.eq(now),
+            ai_jobs::updated_at.eq(now),
+        ))
+        .execute(&mut conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
+    // 使用已有连接删除聊天中的所有任务
+    pub fn delete_by_chat_id_with_conn(conn: &mut DbConnection, chat_id: &str) -> Result<(), RepositoryError> {
+        diesel::delete(ai_jobs::table.filter(ai_jobs::chat_id.eq(chat_id)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+
+    // 使用已有连接清除任务对消息的引用，用于删除消息前
+    pub fn clear_message_refs_with_conn(conn: &mut DbConnection, message_id: &str) -> Result<(), RepositoryError> {
+        diesel::update(ai_jobs::table.filter(ai_jobs::trigger_message_id.eq(message_id)))
+            .set(ai_jobs::trigger_message_id.eq(None::<String>))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        diesel::update(ai_jobs::table.filter(ai_jobs::reply_message_id.eq(message_id)))
+            .set(ai_jobs::reply_message_id.eq(None::<String>))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        diesel::update(ai_jobs::table.filter(ai_jobs::regenerated_message_id.eq(message_id)))
+            .set(ai_jobs::regenerated_message_id.eq(None::<String>))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+
+    // 将所有处理中的任务标记为失败，返回受影响的任务
+    //
+    // 用于应用启动时清理上次退出时未完成的任务
+    pub fn fail_all_processing(pool: &DbPool, error_message: &str) -> Result<Vec<AiJob>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+        let now = Utc::now().naive_utc();
+
+        conn.transaction(|conn| {
+            let ids: Vec<String> = ai_jobs::table
+                .filter(ai_jobs::status.eq(AiJobStatus::Processing.as_str()))
+                .select(ai_jobs::id)
+                .load(conn)?;
+
+            diesel::update(ai_jobs::table.filter(ai_jobs::id.eq_any(&ids)))
+                .set((
+                    ai_jobs::status.eq(AiJobStatus::Error.as_str()),
+                    ai_jobs::error_message.eq(error_message),
+                    ai_jobs::finished_at.eq(now),
+                    ai_jobs::updated_at.eq(now),
+                ))
+                .execute(conn)?;
+
+            ai_jobs::table
+                .filter(ai_jobs::id.eq_any(&ids))
+                .select(AiJob::as_select())
+                .load(conn)
+        })
+        .map_err(RepositoryError::DatabaseError)
+    }
+}
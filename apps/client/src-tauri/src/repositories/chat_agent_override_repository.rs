@@ -0,0 +1,129 @@
+// 聊天内 AI 配置覆盖仓库
+use chrono::Utc;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use super::error::RepositoryError;
+use crate::db::DbConnection;
+use crate::models::{ChatAgentOverride, ChatAgentOverrideChangeset, NewChatAgentOverride};
+use crate::schema::chat_agent_overrides;
+
+pub struct ChatAgentOverrideRepository;
+
+impl ChatAgentOverrideRepository {
+    // 使用已有连接获取 AI 用户在聊天中的配置覆盖，没有覆盖时返回 None
+    pub fn get_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+        agent_user_id: &str,
+    ) -> Result<Option<ChatAgentOverride>, RepositoryError> {
+        chat_agent_overrides::table
+            .filter(chat_agent_overrides::chat_id.eq(chat_id))
+            .filter(chat_agent_overrides::agent_user_id.eq(agent_user_id))
+            .select(ChatAgentOverride::as_select())
+            .first(conn)
+            .optional()
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接保存 AI 用户在聊天中的配置覆盖，已有覆盖时整体替换
+    pub fn upsert_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+        agent_user_id: &str,
+        changeset: &ChatAgentOverrideChangeset,
+    ) -> Result<ChatAgentOverride, RepositoryError> {
+        match Self::get_with_conn(conn, chat_id, agent_user_id)? {
+            Some(existing) => {
+                diesel::update(chat_agent_overrides::table.filter(chat_agent_overrides::id.eq(&existing.id)))
+                    .set(changeset)
+                    .execute(conn)
+                    .map_err(RepositoryError::DatabaseError)?;
+            }
+            None => {
+                let new_override = NewChatAgentOverride {
+                    id: Uuid::new_v4().to_string(),
+                    chat_id: chat_id.to_string(),
+                    agent_user_id: agent_user_id.to_string(),
+                    system_prompt: changeset.system_prompt.clone(),
+                    model_name: changeset.model_name.clone(),
+                    temperature: changeset.temperature,
+                    top_p: changeset.top_p,
+                    max_tokens: changeset.max_tokens,
+                    stop_sequences: changeset.stop_sequences.clone(),
+                    created_at: Utc::now().naive_utc(),
+                    updated_at: changeset.updated_at,
+                };
+                diesel::insert_into(chat_agent_overrides::table)
+                    .values(&new_override)
+                    .execute(conn)
+                    .map_err(RepositoryError::DatabaseError)?;
+            }
+        }
+
+        Self::get_with_conn(conn, chat_id, agent_user_id)?.ok_or(RepositoryError::NotFound)
+    }
+
+    // 使用已有连接将来源聊天的全部配置覆盖复制到新聊天
+    pub fn copy_to_chat_with_conn(
+        conn: &mut DbConnection,
+        source_chat_id: &str,
+        target_chat_id: &str,
+    ) -> Result<(), RepositoryError> {
+        let overrides = chat_agent_overrides::table
+            .filter(chat_agent_overrides::chat_id.eq(source_chat_id))
+            .select(ChatAgentOverride::as_select())
+            .load(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        let copies: Vec<NewChatAgentOverride> = overrides
+            .into_iter()
+            .map(|item| NewChatAgentOverride {
+                id: Uuid::new_v4().to_string(),
+                chat_id: target_chat_id.to_string(),
+                agent_user_id: item.agent_user_id,
+                system_prompt: item.system_prompt,
+                model_name: item.model_name,
+                temperature: item.temperature,
+                top_p: item.top_p,
+                max_tokens: item.max_tokens,
+                stop_sequences: item.stop_sequences,
+                created_at: item.created_at,
+                updated_at: item.updated_at,
+            })
+            .collect();
+
+        diesel::insert_into(chat_agent_overrides::table)
+            .values(&copies)
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // 使用已有连接删除 AI 用户在聊天中的配置覆盖，返回是否存在覆盖
+    pub fn delete_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+        agent_user_id: &str,
+    ) -> Result<bool, RepositoryError> {
+        let deleted = diesel::delete(
+            chat_agent_overrides::table
+                .filter(chat_agent_overrides::chat_id.eq(chat_id))
+                .filter(chat_agent_overrides::agent_user_id.eq(agent_user_id)),
+        )
+        .execute(conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(deleted > 0)
+    }
+
+    // 使用已有连接删除聊天中全部 AI 用户的配置覆盖，在彻底删除聊天前调用
+    pub fn delete_by_chat_id_with_conn(conn: &mut DbConnection, chat_id: &str) -> Result<(), RepositoryError> {
+        diesel::delete(chat_agent_overrides::table.filter(chat_agent_overrides::chat_id.eq(chat_id)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(())
+    }
+}
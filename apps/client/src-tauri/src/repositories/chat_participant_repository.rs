@@ -1,13 +1,26 @@
 // 聊天参与者仓库
 
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
+use diesel::sql_types::Text;
+use diesel::sqlite::Sqlite;
 use uuid::Uuid;
 
 use super::error::RepositoryError;
-use crate::db::DbPool;
-use crate::models::{ChatParticipant, NewChatParticipant};
-use crate::schema::chat_participants;
+use crate::db::{DbConnection, DbPool};
+use crate::models::{ChatParticipant, ChatSettingsChangeset, ChatType, ChatUnreadCount, NewChatParticipant, User};
+use crate::schema::{chat_participants, chats, users};
+
+// 按聊天统计用户未读消息数的查询，工具调用和工具结果不计入；调用方在其后追加过滤条件和 GROUP BY
+const UNREAD_COUNT_SQL: &str = "SELECT p.chat_id AS chat_id, COUNT(m.id) AS unread \
+     FROM chat_participants p \
+     JOIN chats c ON c.id = p.chat_id AND c.deleted_at IS NULL \
+     JOIN messages m ON m.chat_id = p.chat_id AND m.sender_id <> p.user_id AND m.deleted_at IS NULL \
+                    AND m.kind = 'text' \
+     WHERE p.user_id = ? \
+       AND (p.last_read_at IS NULL \
+            OR m.created_at > p.last_read_at \
+            OR (m.created_at = p.last_read_at AND m.id > p.last_read_message_id))";
 
 pub struct ChatParticipantRepository;
 
@@ -20,6 +33,15 @@ impl ChatParticipantRepository {
     ) -> Result<ChatParticipant, RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
 
+        Self::create_with_conn(&mut conn, chat_id, user_id)
+    }
+
+    // 使用已有连接创建聊天参与者
+    pub fn create_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+        user_id: &str,
+    ) -> Result<ChatParticipant, RepositoryError> {
         let new_participant = NewChatParticipant {
             id: Uuid::new_v4().to_string(),
             joined_at: Utc::now().naive_utc(),
@@ -29,13 +51,13 @@ impl ChatParticipantRepository {
 
         diesel::insert_into(chat_participants::table)
             .values(&new_participant)
-            .execute(&mut conn)
+            .execute(conn)
             .map_err(RepositoryError::DatabaseError)?;
 
         let participant = chat_participants::table
             .filter(chat_participants::id.eq(&new_participant.id))
             .select(ChatParticipant::as_select())
-            .first(&mut conn)
+            .first(conn)
             .map_err(RepositoryError::DatabaseError)?;
 
         Ok(participant)
@@ -58,13 +80,31 @@ impl ChatParticipantRepository {
     pub fn get_by_chat_id(pool: &DbPool, chat_id: &str) -> Result<Vec<ChatParticipant>, RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
 
-        let participants = chat_participants::table
+        Self::get_by_chat_id_with_conn(&mut conn, chat_id)
+    }
+
+    // 使用已有连接根据聊天ID获取所有参与者，按加入时间升序
+    pub fn get_by_chat_id_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+    ) -> Result<Vec<ChatParticipant>, RepositoryError> {
+        chat_participants::table
             .filter(chat_participants::chat_id.eq(chat_id))
+            .order(chat_participants::joined_at.asc())
             .select(ChatParticipant::as_select())
-            .load(&mut conn)
-            .map_err(RepositoryError::DatabaseError)?;
+            .load(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
 
-        Ok(participants)
+    // 使用已有连接获取聊天中所有参与者的用户信息，按加入时间升序
+    pub fn get_users_by_chat_id_with_conn(conn: &mut DbConnection, chat_id: &str) -> Result<Vec<User>, RepositoryError> {
+        chat_participants::table
+            .inner_join(users::table)
+            .filter(chat_participants::chat_id.eq(chat_id))
+            .order(chat_participants::joined_at.asc())
+            .select(User::as_select())
+            .load(conn)
+            .map_err(RepositoryError::DatabaseError)
     }
 
     // 根据用户ID获取所有参与的聊天
@@ -80,6 +120,195 @@ impl ChatParticipantRepository {
         Ok(participants)
     }
 
+    // 查找两个用户之间已有的单聊，分出的聊天和已删除的聊天不算在内
+    pub fn find_direct_chat_id_with_conn(
+        conn: &mut DbConnection,
+        user_id: &str,
+        other_user_id: &str,
+    ) -> Result<Option<String>, RepositoryError> {
+        let user_chat_ids = chat_participants::table
+            .inner_join(chats::table)
+            .filter(chat_participants::user_id.eq(user_id))
+            .filter(chats::chat_type.eq(ChatType::Direct.as_str()))
+            .filter(chats::forked_from_chat_id.is_null())
+            .filter(chats::deleted_at.is_null())
+            .select(chat_participants::chat_id)
+            .load::<String>(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        chat_participants::table
+            .filter(chat_participants::user_id.eq(other_user_id))
+            .filter(chat_participants::chat_id.eq_any(user_chat_ids))
+            .select(chat_participants::chat_id)
+            .first::<String>(conn)
+            .optional()
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接检查用户是否为聊天参与者
+    pub fn exists_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+        user_id: &str,
+    ) -> Result<bool, RepositoryError> {
+        use diesel::dsl::exists;
+        use diesel::select;
+
+        select(exists(
+            chat_participants::table
+                .filter(chat_participants::chat_id.eq(chat_id))
+                .filter(chat_participants::user_id.eq(user_id)),
+        ))
+        .get_result(conn)
+        .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接将用户的已读位置推进到指定消息
+    //
+    // 已读位置按（创建时间，消息ID）比较，只前进不后退；返回是否发生了更新
+    pub fn mark_read_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+        user_id: &str,
+        message_id: &str,
+        message_created_at: NaiveDateTime,
+    ) -> Result<bool, RepositoryError> {
+        let updated = diesel::update(
+            chat_participants::table
+                .filter(chat_participants::chat_id.eq(chat_id))
+                .filter(chat_participants::user_id.eq(user_id))
+                .filter(
+                    chat_participants::last_read_at
+                        .is_null()
+                        .or(chat_participants::last_read_at.lt(message_created_at))
+                        .or(chat_participants::last_read_at
+                            .eq(message_created_at)
+                            .and(chat_participants::last_read_message_id.lt(message_id))),
+                ),
+        )
+        .set((
+            chat_participants::last_read_message_id.eq(message_id),
+            chat_participants::last_read_at.eq(message_created_at),
+        ))
+        .execute(conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
+    // 使用已有连接获取 AI 用户在聊天中已提取记忆的位置（创建时间，消息ID），尚未提取过时返回 None
+    pub fn get_memory_cursor_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+        user_id: &str,
+    ) -> Result<Option<(NaiveDateTime, String)>, RepositoryError> {
+        let cursor: Option<(Option<NaiveDateTime>, Option<String>)> = chat_participants::table
+            .filter(chat_participants::chat_id.eq(chat_id))
+            .filter(chat_participants::user_id.eq(user_id))
+            .select((chat_participants::memory_extracted_at, chat_participants::memory_extracted_message_id))
+            .first(conn)
+            .optional()
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(match cursor {
+            Some((Some(created_at), Some(message_id))) => Some((created_at, message_id)),
+            _ => None,
+        })
+    }
+
+    // 使用已有连接将 AI 用户在聊天中已提取记忆的位置推进到指定消息
+    //
+    // 与已读位置相同，按（创建时间，消息ID）比较，只前进不后退；返回是否发生了更新
+    pub fn advance_memory_cursor_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+        user_id: &str,
+        message_id: &str,
+        message_created_at: NaiveDateTime,
+    ) -> Result<bool, RepositoryError> {
+        let updated = diesel::update(
+            chat_participants::table
+                .filter(chat_participants::chat_id.eq(chat_id))
+                .filter(chat_participants::user_id.eq(user_id))
+                .filter(
+                    chat_participants::memory_extracted_at
+                        .is_null()
+                        .or(chat_participants::memory_extracted_at.lt(message_created_at))
+                        .or(chat_participants::memory_extracted_at
+                            .eq(message_created_at)
+                            .and(chat_participants::memory_extracted_message_id.lt(message_id))),
+                ),
+        )
+        .set((
+            chat_participants::memory_extracted_message_id.eq(message_id),
+            chat_participants::memory_extracted_at.eq(message_created_at),
+        ))
+        .execute(conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
+    // 使用已有连接统计用户在各聊天中的未读消息数
+    //
+    // 未读消息为已读位置之后、由其他参与者发送且未删除的消息；chat_ids 为空时统计用户参与的全部未删除聊天，
+    // 没有未读消息的聊天不出现在结果中
+    pub fn count_unread_with_conn(
+        conn: &mut DbConnection,
+        user_id: &str,
+        chat_ids: Option<&[&str]>,
+    ) -> Result<Vec<ChatUnreadCount>, RepositoryError> {
+        let mut query = diesel::sql_query(UNREAD_COUNT_SQL)
+            .into_boxed::<Sqlite>()
+            .bind::<Text, _>(user_id.to_string());
+
+        if let Some(chat_ids) = chat_ids {
+            if chat_ids.is_empty() {
+                return Ok(Vec::new());
+            }
+            let placeholders = vec!["?"; chat_ids.len()].join(", ");
+            query = query.sql(format!(" AND p.chat_id IN ({})", placeholders));
+            for chat_id in chat_ids {
+                query = query.bind::<Text, _>(chat_id.to_string());
+            }
+        }
+
+        query
+            .sql(" GROUP BY p.chat_id")
+            .load::<ChatUnreadCount>(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接统计用户的未读消息总数，设置了免打扰的聊天不计入
+    pub fn count_total_unread_with_conn(conn: &mut DbConnection, user_id: &str) -> Result<i64, RepositoryError> {
+        let unread = diesel::sql_query(UNREAD_COUNT_SQL)
+            .sql(" AND p.is_muted = FALSE GROUP BY p.chat_id")
+            .bind::<Text, _>(user_id)
+            .load::<ChatUnreadCount>(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(unread.iter().map(|row| row.unread).sum())
+    }
+
+    // 使用已有连接修改用户对聊天的个人设置，返回是否找到该用户的参与者记录
+    pub fn update_settings_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+        user_id: &str,
+        changes: &ChatSettingsChangeset,
+    ) -> Result<bool, RepositoryError> {
+        let updated = diesel::update(
+            chat_participants::table
+                .filter(chat_participants::chat_id.eq(chat_id))
+                .filter(chat_participants::user_id.eq(user_id)),
+        )
+        .set(changes)
+        .execute(conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
     // 删除聊天参与者
     pub fn delete(pool: &DbPool, id: &str) -> Result<(), RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
@@ -109,4 +338,43 @@ impl ChatParticipantRepository {
 
         Ok(())
     }
-} 
\ No newline at end of file
+
+    // 使用已有连接删除聊天的所有参与者
+    pub fn delete_by_chat_id_with_conn(conn: &mut DbConnection, chat_id: &str) -> Result<(), RepositoryError> {
+        diesel::delete(chat_participants::table.filter(chat_participants::chat_id.eq(chat_id)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(())
+    }
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::chat_repository::ChatRepository;
+    use crate::repositories::message_repository::MessageRepository;
+    use crate::test_support::{create_test_chat, TestChat};
+
+    #[test]
+    fn muted_chats_are_excluded_from_total_unread() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let other = ChatRepository::create(&pool, "群聊", "", ChatType::Group).unwrap();
+        ChatParticipantRepository::create(&pool, &other.id, &human.id).unwrap();
+        ChatParticipantRepository::create(&pool, &other.id, &ai.id).unwrap();
+        for chat_id in [&chat.id, &other.id, &other.id] {
+            MessageRepository::create(&pool, "你好".to_string(), chat_id, &ai.id).unwrap();
+        }
+        // 自己发送的消息不计为未读
+        MessageRepository::create(&pool, "在吗".to_string(), &chat.id, &human.id).unwrap();
+
+        let mut conn = pool.get().unwrap();
+        assert_eq!(ChatParticipantRepository::count_total_unread_with_conn(&mut conn, &human.id).unwrap(), 3);
+
+        let muted = ChatSettingsChangeset { is_muted: Some(true), ..Default::default() };
+        ChatParticipantRepository::update_settings_with_conn(&mut conn, &other.id, &human.id, &muted).unwrap();
+        assert_eq!(ChatParticipantRepository::count_total_unread_with_conn(&mut conn, &human.id).unwrap(), 1);
+        // 免打扰只影响总数，单个聊天的未读数不变
+        let unread = ChatParticipantRepository::count_unread_with_conn(&mut conn, &human.id, Some(&[&other.id])).unwrap();
+        assert_eq!(unread[0].unread, 2);
+    }
+}
@@ -1,42 +1,95 @@
 // 聊天仓库
 
-use chrono::Utc;
+use std::collections::HashMap;
+
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 use uuid::Uuid;
 
+use super::chat_participant_repository::ChatParticipantRepository;
 use super::error::RepositoryError;
-use crate::db::DbPool;
-use crate::models::{Chat, NewChat};
-use crate::schema::chats;
+use crate::db::{DbConnection, DbPool};
+use crate::models::{
+    Chat, ChatReplyPolicyChangeset, ChatSettings, ChatType, MessageKind, NewChat, ReplyPolicy, User, DEFAULT_MAX_AI_TURNS,
+};
+use crate::schema::{chat_participants, chats, messages, users};
+
+// 聊天列表项：聊天本身、当前用户对该聊天的个人设置、未读消息数以及全部参与者
+pub struct ChatListEntry {
+    pub chat: Chat,
+    pub settings: ChatSettings,
+    pub unread_count: i64,
+    pub participants: Vec<User>,
+}
 
 pub struct ChatRepository;
 
 impl ChatRepository {
     // 创建聊天
-    pub fn create(pool: &DbPool) -> Result<Chat, RepositoryError> {
+    pub fn create(pool: &DbPool, name: &str, avatar_urls: &str, chat_type: ChatType) -> Result<Chat, RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
 
+        Self::create_with_conn(&mut conn, name, avatar_urls, chat_type)
+    }
+
+    // 使用已有连接创建聊天
+    pub fn create_with_conn(
+        conn: &mut DbConnection,
+        name: &str,
+        avatar_urls: &str,
+        chat_type: ChatType,
+    ) -> Result<Chat, RepositoryError> {
         let new_chat = NewChat {
             id: Uuid::new_v4().to_string(),
-            unread_count: 0, // 初始未读消息为0
+            name: name.to_string(),
+            avatar_urls: avatar_urls.to_string(),
             last_message: None, // 初始没有最后消息
             last_message_time: None, // 初始没有最后消息时间
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
+            forked_from_chat_id: None,
+            forked_from_message_id: None,
+            reply_policy: ReplyPolicy::default().as_str().to_string(),
+            moderator_user_id: None,
+            max_ai_turns: DEFAULT_MAX_AI_TURNS,
+            chat_type: chat_type.as_str().to_string(),
         };
 
+        Self::insert_with_conn(conn, new_chat)
+    }
+
+    // 使用已有连接创建从来源聊天的某条消息分出的聊天，名称、头像、类型和回复策略沿用来源聊天
+    pub fn create_fork_with_conn(
+        conn: &mut DbConnection,
+        source: &Chat,
+        message_id: &str,
+    ) -> Result<Chat, RepositoryError> {
+        let new_chat = NewChat {
+            id: Uuid::new_v4().to_string(),
+            name: source.name.clone(),
+            avatar_urls: source.avatar_urls.clone(),
+            last_message: None, // 由复制消息时的触发器更新
+            last_message_time: None,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            forked_from_chat_id: Some(source.id.clone()),
+            forked_from_message_id: Some(message_id.to_string()),
+            reply_policy: source.reply_policy.clone(),
+            moderator_user_id: source.moderator_user_id.clone(),
+            max_ai_turns: source.max_ai_turns,
+            chat_type: source.chat_type.clone(),
+        };
+
+        Self::insert_with_conn(conn, new_chat)
+    }
+
+    fn insert_with_conn(conn: &mut DbConnection, new_chat: NewChat) -> Result<Chat, RepositoryError> {
         diesel::insert_into(chats::table)
             .values(&new_chat)
-            .execute(&mut conn)
-            .map_err(RepositoryError::DatabaseError)?;
-
-        let chat = chats::table
-            .filter(chats::id.eq(&new_chat.id))
-            .select(Chat::as_select())
-            .first(&mut conn)
+            .execute(conn)
             .map_err(RepositoryError::DatabaseError)?;
 
-        Ok(chat)
+        Self::get_with_conn(conn, &new_chat.id)
     }
 
     // 获取聊天
@@ -52,6 +105,218 @@ impl ChatRepository {
         Ok(chat)
     }
 
+    // 使用已有连接获取聊天
+    pub fn get_with_conn(conn: &mut DbConnection, id: &str) -> Result<Chat, RepositoryError> {
+        chats::table
+            .filter(chats::id.eq(id))
+            .select(Chat::as_select())
+            .first(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接将聊天的最后消息同步为当前分支上最新的一条未删除的普通消息
+    pub fn refresh_last_message_with_conn(conn: &mut DbConnection, id: &str) -> Result<(), RepositoryError> {
+        let latest = messages::table
+            .filter(messages::chat_id.eq(id))
+            .filter(messages::kind.eq(MessageKind::Text.as_str()))
+            .filter(messages::is_active.eq(true))
+            .filter(messages::deleted_at.is_null())
+            .order((messages::created_at.desc(), messages::id.desc()))
+            .select((messages::content, messages::created_at))
+            .first::<(String, NaiveDateTime)>(conn)
+            .optional()
+            .map_err(RepositoryError::DatabaseError)?;
+        let (content, time) = latest.unzip();
+
+        diesel::update(chats::table.filter(chats::id.eq(id)))
+            .set((chats::last_message.eq(content), chats::last_message_time.eq(time)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // 分页获取用户参与的未删除的聊天列表，include_archived 为 false 时不包含已归档的聊天
+    //
+    // 置顶的聊天排在最前，同组内设置了自定义排序的聊天按排序值升序排在前面，
+    // 其余按最后消息时间倒序，没有消息的聊天按创建时间排在最后；
+    // 参与者信息和未读消息数各通过一次批量查询获得，查询次数与聊天数量无关
+    pub fn get_user_chat_list(
+        pool: &DbPool,
+        user_id: &str,
+        include_archived: bool,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<ChatListEntry>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let mut query = chats::table
+            .inner_join(chat_participants::table)
+            .filter(chat_participants::user_id.eq(user_id))
+            .filter(chats::deleted_at.is_null())
+            .into_boxed();
+        if !include_archived {
+            query = query.filter(chat_participants::is_archived.eq(false));
+        }
+
+        let chat_rows = query
+            .order((
+                chat_participants::is_pinned.desc(),
+                chat_participants::sort_order.is_null(),
+                chat_participants::sort_order.asc(),
+                chats::last_message_time.is_null(),
+                chats::last_message_time.desc(),
+                chats::created_at.desc(),
+            ))
+            .offset(offset)
+            .limit(limit)
+            .select((Chat::as_select(), ChatSettings::as_select()))
+            .load::<(Chat, ChatSettings)>(&mut conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Self::attach_participants(&mut conn, user_id, chat_rows)
+    }
+
+    // 获取用户参与的单个聊天的列表项
+    pub fn get_user_chat_entry(
+        pool: &DbPool,
+        user_id: &str,
+        chat_id: &str,
+    ) -> Result<ChatListEntry, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let chat_row = chats::table
+            .inner_join(chat_participants::table)
+            .filter(chat_participants::user_id.eq(user_id))
+            .filter(chats::id.eq(chat_id))
+            .filter(chats::deleted_at.is_null())
+            .select((Chat::as_select(), ChatSettings::as_select()))
+            .first::<(Chat, ChatSettings)>(&mut conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    RepositoryError::NotFound
+                } else {
+                    RepositoryError::DatabaseError(e)
+                }
+            })?;
+
+        Self::attach_participants(&mut conn, user_id, vec![chat_row])?
+            .pop()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    // 统计用户参与的未删除的聊天数量，include_archived 为 false 时不包含已归档的聊天
+    pub fn count_user_chats(pool: &DbPool, user_id: &str, include_archived: bool) -> Result<i64, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let mut query = chat_participants::table
+            .inner_join(chats::table)
+            .filter(chat_participants::user_id.eq(user_id))
+            .filter(chats::deleted_at.is_null())
+            .into_boxed();
+        if !include_archived {
+            query = query.filter(chat_participants::is_archived.eq(false));
+        }
+
+        query
+            .count()
+            .get_result(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 获取用户参与的已删除的聊天列表，按删除时间倒序
+    pub fn get_user_deleted_chat_list(pool: &DbPool, user_id: &str) -> Result<Vec<ChatListEntry>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let chat_rows = chats::table
+            .inner_join(chat_participants::table)
+            .filter(chat_participants::user_id.eq(user_id))
+            .filter(chats::deleted_at.is_not_null())
+            .order((chats::deleted_at.desc(), chats::id.desc()))
+            .select((Chat::as_select(), ChatSettings::as_select()))
+            .load::<(Chat, ChatSettings)>(&mut conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Self::attach_participants(&mut conn, user_id, chat_rows)
+    }
+
+    // 使用已有连接更新聊天的回复策略
+    pub fn update_reply_policy_with_conn(
+        conn: &mut DbConnection,
+        id: &str,
+        changeset: &ChatReplyPolicyChangeset,
+    ) -> Result<(), RepositoryError> {
+        diesel::update(chats::table.filter(chats::id.eq(id)))
+            .set(changeset)
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+
+    // 使用已有连接设置聊天的删除时间，为空时从回收站恢复
+    pub fn set_deleted_with_conn(
+        conn: &mut DbConnection,
+        id: &str,
+        deleted_at: Option<NaiveDateTime>,
+    ) -> Result<(), RepositoryError> {
+        diesel::update(chats::table.filter(chats::id.eq(id)))
+            .set(chats::deleted_at.eq(deleted_at))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+
+    // 使用已有连接获取删除时间早于 cutoff 的聊天ID
+    pub fn get_expired_ids_with_conn(
+        conn: &mut DbConnection,
+        cutoff: NaiveDateTime,
+    ) -> Result<Vec<String>, RepositoryError> {
+        chats::table
+            .filter(chats::deleted_at.lt(cutoff))
+            .select(chats::id)
+            .load(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 批量查询聊天的参与者和用户的未读消息数，并按原有顺序组装列表项
+    fn attach_participants(
+        conn: &mut DbConnection,
+        user_id: &str,
+        chat_rows: Vec<(Chat, ChatSettings)>,
+    ) -> Result<Vec<ChatListEntry>, RepositoryError> {
+        let chat_ids: Vec<&str> = chat_rows.iter().map(|(chat, _)| chat.id.as_str()).collect();
+
+        let participant_rows = chat_participants::table
+            .inner_join(users::table)
+            .filter(chat_participants::chat_id.eq_any(&chat_ids))
+            .order(chat_participants::joined_at.asc())
+            .select((chat_participants::chat_id, User::as_select()))
+            .load::<(String, User)>(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        let mut participants_by_chat: HashMap<String, Vec<User>> = HashMap::new();
+        for (chat_id, user) in participant_rows {
+            participants_by_chat.entry(chat_id).or_default().push(user);
+        }
+
+        let unread_by_chat: HashMap<String, i64> =
+            ChatParticipantRepository::count_unread_with_conn(conn, user_id, Some(&chat_ids))?
+                .into_iter()
+                .map(|row| (row.chat_id, row.unread))
+                .collect();
+
+        let entries = chat_rows
+            .into_iter()
+            .map(|(chat, settings)| {
+                let participants = participants_by_chat.remove(&chat.id).unwrap_or_default();
+                let unread_count = unread_by_chat.get(&chat.id).copied().unwrap_or(0);
+                ChatListEntry { chat, settings, unread_count, participants }
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
     // 获取所有聊天
     pub fn get_all(pool: &DbPool) -> Result<Vec<Chat>, RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
@@ -64,14 +329,111 @@ impl ChatRepository {
         Ok(chats_list)
     }
 
-    // 删除聊天
-    pub fn delete(pool: &DbPool, id: &str) -> Result<(), RepositoryError> {
-        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+    // 使用已有连接清除从该聊天分出的聊天对它的引用，分出的聊天本身保留
+    pub fn clear_fork_refs_with_conn(conn: &mut DbConnection, chat_id: &str) -> Result<(), RepositoryError> {
+        diesel::update(chats::table.filter(chats::forked_from_chat_id.eq(chat_id)))
+            .set((
+                chats::forked_from_chat_id.eq(None::<String>),
+                chats::forked_from_message_id.eq(None::<String>),
+            ))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // 使用已有连接清除分出的聊天对分出位置消息的引用，来源聊天的引用保留
+    pub fn clear_forked_message_refs_with_conn(conn: &mut DbConnection, message_id: &str) -> Result<(), RepositoryError> {
+        diesel::update(chats::table.filter(chats::forked_from_message_id.eq(message_id)))
+            .set(chats::forked_from_message_id.eq(None::<String>))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
 
+        Ok(())
+    }
+
+    // 使用已有连接删除聊天
+    //
+    // 外键没有级联删除，调用方需在同一事务中先删除聊天的消息、参与者和 AI 回复任务，并清除其他记录对它的引用
+    pub fn delete_with_conn(conn: &mut DbConnection, id: &str) -> Result<(), RepositoryError> {
         diesel::delete(chats::table.filter(chats::id.eq(id)))
-            .execute(&mut conn)
+            .execute(conn)
             .map_err(RepositoryError::DatabaseError)?;
 
         Ok(())
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::models::ChatSettingsChangeset;
+    use crate::repositories::user_repository::UserRepository;
+    use crate::test_support::create_test_pool;
+
+    // 用户参与的聊天，minutes_ago 为最后消息距今的分钟数，为 None 时没有消息
+    fn create_chat(pool: &DbPool, user_id: &str, name: &str, minutes_ago: Option<i64>) -> Chat {
+        let chat = ChatRepository::create(pool, name, "", ChatType::Group).unwrap();
+        ChatParticipantRepository::create(pool, &chat.id, user_id).unwrap();
+        let time = minutes_ago.map(|minutes| Utc::now().naive_utc() - TimeDelta::minutes(minutes));
+        diesel::update(chats::table.filter(chats::id.eq(&chat.id)))
+            .set(chats::last_message_time.eq(time))
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+        chat
+    }
+
+    fn update_settings(pool: &DbPool, chat_id: &str, user_id: &str, changes: ChatSettingsChangeset) {
+        let mut conn = pool.get().unwrap();
+        assert!(ChatParticipantRepository::update_settings_with_conn(&mut conn, chat_id, user_id, &changes).unwrap());
+    }
+
+    fn names(entries: &[ChatListEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.chat.name.as_str()).collect()
+    }
+
+    #[test]
+    fn chat_list_orders_pinned_then_sort_order_then_last_message_time() {
+        let pool = create_test_pool();
+        let user = UserRepository::create(&pool, "用户".to_string(), None, false).unwrap();
+        create_chat(&pool, &user.id, "无消息", None);
+        create_chat(&pool, &user.id, "较早", Some(30));
+        create_chat(&pool, &user.id, "最近", Some(1));
+        let pinned = create_chat(&pool, &user.id, "置顶", Some(60));
+        let sorted = create_chat(&pool, &user.id, "排序", Some(50));
+        let archived = create_chat(&pool, &user.id, "归档", Some(0));
+        update_settings(&pool, &pinned.id, &user.id, ChatSettingsChangeset { is_pinned: Some(true), ..Default::default() });
+        update_settings(&pool, &sorted.id, &user.id, ChatSettingsChangeset { sort_order: Some(Some(1)), ..Default::default() });
+        update_settings(&pool, &archived.id, &user.id, ChatSettingsChangeset { is_archived: Some(true), ..Default::default() });
+
+        let entries = ChatRepository::get_user_chat_list(&pool, &user.id, false, 0, 10).unwrap();
+        assert_eq!(names(&entries), ["置顶", "排序", "最近", "较早", "无消息"]);
+        assert_eq!(ChatRepository::count_user_chats(&pool, &user.id, false).unwrap(), 5);
+
+        // 包含已归档的聊天时按同样的规则排序
+        let entries = ChatRepository::get_user_chat_list(&pool, &user.id, true, 0, 10).unwrap();
+        assert_eq!(names(&entries), ["置顶", "排序", "归档", "最近", "较早", "无消息"]);
+        assert_eq!(ChatRepository::count_user_chats(&pool, &user.id, true).unwrap(), 6);
+
+        let page = ChatRepository::get_user_chat_list(&pool, &user.id, false, 2, 2).unwrap();
+        assert_eq!(names(&page), ["最近", "较早"]);
+    }
+
+    #[test]
+    fn chat_list_excludes_deleted_and_other_users_chats() {
+        let pool = create_test_pool();
+        let user = UserRepository::create(&pool, "用户".to_string(), None, false).unwrap();
+        let other = UserRepository::create(&pool, "其他用户".to_string(), None, false).unwrap();
+        create_chat(&pool, &user.id, "保留", Some(1));
+        let deleted = create_chat(&pool, &user.id, "已删除", Some(0));
+        create_chat(&pool, &other.id, "他人", Some(0));
+        ChatRepository::set_deleted_with_conn(&mut pool.get().unwrap(), &deleted.id, Some(Utc::now().naive_utc()))
+            .unwrap();
+
+        let entries = ChatRepository::get_user_chat_list(&pool, &user.id, true, 0, 10).unwrap();
+        assert_eq!(names(&entries), ["保留"]);
+        assert_eq!(entries[0].participants.len(), 1);
+        assert_eq!(ChatRepository::count_user_chats(&pool, &user.id, true).unwrap(), 1);
+    }
+}
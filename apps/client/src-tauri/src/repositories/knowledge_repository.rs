@@ -0,0 +1,171 @@
+// 知识库仓库：文本资源的片段向量和 AI 用户关联的知识库资源
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use super::error::RepositoryError;
+use crate::db::DbConnection;
+use crate::models::{NewAgentKnowledgeResource, NewResourceChunk, Resource, ResourceChunk};
+use crate::schema::{agent_knowledge_resources, resource_chunks, resources};
+
+// 批量写入片段时每批的条数，避免超出 SQLite 单条语句的参数个数限制
+const INSERT_BATCH_SIZE: usize = 100;
+
+pub struct KnowledgeRepository;
+
+impl KnowledgeRepository {
+    // 使用已有连接获取资源由指定模型生成的片段，按资源和片段顺序排列
+    pub fn get_chunks_with_conn(
+        conn: &mut DbConnection,
+        resource_ids: &[String],
+        embedding_provider: &str,
+        embedding_model: &str,
+    ) -> Result<Vec<ResourceChunk>, RepositoryError> {
+        resource_chunks::table
+            .filter(resource_chunks::resource_id.eq_any(resource_ids))
+            .filter(resource_chunks::embedding_provider.eq(embedding_provider))
+            .filter(resource_chunks::embedding_model.eq(embedding_model))
+            .order((resource_chunks::resource_id.asc(), resource_chunks::chunk_index.asc()))
+            .select(ResourceChunk::as_select())
+            .load(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接获取资源由指定模型切分时的内容哈希，尚未切分时返回 None
+    pub fn get_content_hash_with_conn(
+        conn: &mut DbConnection,
+        resource_id: &str,
+        embedding_provider: &str,
+        embedding_model: &str,
+    ) -> Result<Option<String>, RepositoryError> {
+        resource_chunks::table
+            .filter(resource_chunks::resource_id.eq(resource_id))
+            .filter(resource_chunks::embedding_provider.eq(embedding_provider))
+            .filter(resource_chunks::embedding_model.eq(embedding_model))
+            .select(resource_chunks::content_hash)
+            .first(conn)
+            .optional()
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接获取资源由指定模型切分的时间，尚未切分时返回 None
+    pub fn get_indexed_at_with_conn(
+        conn: &mut DbConnection,
+        resource_id: &str,
+        embedding_provider: &str,
+        embedding_model: &str,
+    ) -> Result<Option<NaiveDateTime>, RepositoryError> {
+        resource_chunks::table
+            .filter(resource_chunks::resource_id.eq(resource_id))
+            .filter(resource_chunks::embedding_provider.eq(embedding_provider))
+            .filter(resource_chunks::embedding_model.eq(embedding_model))
+            .select(resource_chunks::created_at)
+            .first(conn)
+            .optional()
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接统计资源由指定模型生成的片段数
+    pub fn count_chunks_with_conn(
+        conn: &mut DbConnection,
+        resource_id: &str,
+        embedding_provider: &str,
+        embedding_model: &str,
+    ) -> Result<i64, RepositoryError> {
+        resource_chunks::table
+            .filter(resource_chunks::resource_id.eq(resource_id))
+            .filter(resource_chunks::embedding_provider.eq(embedding_provider))
+            .filter(resource_chunks::embedding_model.eq(embedding_model))
+            .count()
+            .get_result(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接将资源由指定模型生成的片段整体替换为给定的片段
+    pub fn replace_chunks_with_conn(
+        conn: &mut DbConnection,
+        resource_id: &str,
+        embedding_provider: &str,
+        embedding_model: &str,
+        chunks: &[NewResourceChunk],
+    ) -> Result<(), RepositoryError> {
+        diesel::delete(
+            resource_chunks::table
+                .filter(resource_chunks::resource_id.eq(resource_id))
+                .filter(resource_chunks::embedding_provider.eq(embedding_provider))
+                .filter(resource_chunks::embedding_model.eq(embedding_model)),
+        )
+        .execute(conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        for batch in chunks.chunks(INSERT_BATCH_SIZE) {
+            diesel::insert_into(resource_chunks::table)
+                .values(batch)
+                .execute(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+        }
+        Ok(())
+    }
+
+    // 使用已有连接删除资源的全部片段
+    pub fn delete_chunks_by_resource_with_conn(conn: &mut DbConnection, resource_id: &str) -> Result<usize, RepositoryError> {
+        diesel::delete(resource_chunks::table.filter(resource_chunks::resource_id.eq(resource_id)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接获取 AI 用户关联的知识库资源，按名称排序
+    pub fn get_agent_resources_with_conn(
+        conn: &mut DbConnection,
+        agent_user_id: &str,
+    ) -> Result<Vec<Resource>, RepositoryError> {
+        agent_knowledge_resources::table
+            .inner_join(resources::table)
+            .filter(agent_knowledge_resources::agent_user_id.eq(agent_user_id))
+            .order(resources::name.asc())
+            .select(Resource::as_select())
+            .load(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接将 AI 用户关联的知识库资源整体替换为给定的资源
+    pub fn set_agent_resources_with_conn(
+        conn: &mut DbConnection,
+        agent_user_id: &str,
+        resource_ids: &[String],
+    ) -> Result<(), RepositoryError> {
+        diesel::delete(
+            agent_knowledge_resources::table.filter(agent_knowledge_resources::agent_user_id.eq(agent_user_id)),
+        )
+        .execute(conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        let now = Utc::now().naive_utc();
+        let rows: Vec<NewAgentKnowledgeResource> = resource_ids
+            .iter()
+            .map(|resource_id| NewAgentKnowledgeResource {
+                id: Uuid::new_v4().to_string(),
+                agent_user_id: agent_user_id.to_string(),
+                resource_id: resource_id.clone(),
+                created_at: now,
+            })
+            .collect();
+
+        diesel::insert_into(agent_knowledge_resources::table)
+            .values(&rows)
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // 使用已有连接删除各 AI 用户对资源的关联
+    pub fn delete_agent_links_by_resource_with_conn(
+        conn: &mut DbConnection,
+        resource_id: &str,
+    ) -> Result<usize, RepositoryError> {
+        diesel::delete(agent_knowledge_resources::table.filter(agent_knowledge_resources::resource_id.eq(resource_id)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+}
@@ -0,0 +1,140 @@
+// MCP 服务器配置仓库
+use chrono::Utc;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use super::error::RepositoryError;
+use crate::db::DbConnection;
+use crate::models::{McpServer, McpServerChangeset, NewAgentMcpServer, NewMcpServer};
+use crate::schema::{agent_mcp_servers, mcp_servers};
+
+pub struct McpServerRepository;
+
+impl McpServerRepository {
+    // 使用已有连接创建 MCP 服务器配置
+    pub fn create_with_conn(conn: &mut DbConnection, new_server: &NewMcpServer) -> Result<McpServer, RepositoryError> {
+        diesel::insert_into(mcp_servers::table)
+            .values(new_server)
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Self::get_with_conn(conn, &new_server.id)
+    }
+
+    // 使用已有连接获取 MCP 服务器配置
+    pub fn get_with_conn(conn: &mut DbConnection, id: &str) -> Result<McpServer, RepositoryError> {
+        mcp_servers::table
+            .filter(mcp_servers::id.eq(id))
+            .select(McpServer::as_select())
+            .first(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    RepositoryError::NotFound
+                } else {
+                    RepositoryError::DatabaseError(e)
+                }
+            })
+    }
+
+    // 使用已有连接按名称获取 MCP 服务器配置，不存在时返回 None
+    pub fn get_by_name_with_conn(conn: &mut DbConnection, name: &str) -> Result<Option<McpServer>, RepositoryError> {
+        mcp_servers::table
+            .filter(mcp_servers::name.eq(name))
+            .select(McpServer::as_select())
+            .first(conn)
+            .optional()
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接获取全部 MCP 服务器配置，按名称排序
+    pub fn get_all_with_conn(conn: &mut DbConnection) -> Result<Vec<McpServer>, RepositoryError> {
+        mcp_servers::table
+            .order(mcp_servers::name.asc())
+            .select(McpServer::as_select())
+            .load(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接整体更新 MCP 服务器配置
+    pub fn update_with_conn(
+        conn: &mut DbConnection,
+        id: &str,
+        changeset: &McpServerChangeset,
+    ) -> Result<McpServer, RepositoryError> {
+        let updated = diesel::update(mcp_servers::table.filter(mcp_servers::id.eq(id)))
+            .set(changeset)
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        if updated == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        Self::get_with_conn(conn, id)
+    }
+
+    // 使用已有连接删除 MCP 服务器配置及各 AI 用户对该服务器的启用记录
+    //
+    // 启用记录由外键的 ON DELETE CASCADE 删除，这里仍显式删除，不依赖外键动作；应在事务中调用
+    pub fn delete_with_conn(conn: &mut DbConnection, id: &str) -> Result<(), RepositoryError> {
+        diesel::delete(agent_mcp_servers::table.filter(agent_mcp_servers::server_id.eq(id)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        let deleted = diesel::delete(mcp_servers::table.filter(mcp_servers::id.eq(id)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        if deleted == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+
+    // 使用已有连接获取 AI 用户启用的 MCP 服务器，按名称排序；only_enabled 为 true 时排除已停用的服务器
+    pub fn get_by_agent_with_conn(
+        conn: &mut DbConnection,
+        agent_user_id: &str,
+        only_enabled: bool,
+    ) -> Result<Vec<McpServer>, RepositoryError> {
+        let mut query = agent_mcp_servers::table
+            .inner_join(mcp_servers::table)
+            .filter(agent_mcp_servers::agent_user_id.eq(agent_user_id))
+            .order(mcp_servers::name.asc())
+            .select(McpServer::as_select())
+            .into_boxed();
+        if only_enabled {
+            query = query.filter(mcp_servers::enabled.eq(true));
+        }
+        query.load(conn).map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接将 AI 用户启用的 MCP 服务器整体替换为给定的服务器
+    pub fn set_agent_servers_with_conn(
+        conn: &mut DbConnection,
+        agent_user_id: &str,
+        server_ids: &[String],
+    ) -> Result<(), RepositoryError> {
+        diesel::delete(agent_mcp_servers::table.filter(agent_mcp_servers::agent_user_id.eq(agent_user_id)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        let now = Utc::now().naive_utc();
+        let rows: Vec<NewAgentMcpServer> = server_ids
+            .iter()
+            .map(|server_id| NewAgentMcpServer {
+                id: Uuid::new_v4().to_string(),
+                agent_user_id: agent_user_id.to_string(),
+                server_id: server_id.clone(),
+                created_at: now,
+            })
+            .collect();
+
+        diesel::insert_into(agent_mcp_servers::table)
+            .values(&rows)
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(())
+    }
+}
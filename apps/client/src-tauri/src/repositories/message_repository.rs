@@ -1,13 +1,26 @@
 // 消息仓库
 
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Text, Timestamp};
+use diesel::sqlite::Sqlite;
 use uuid::Uuid;
 
 use super::error::RepositoryError;
-use crate::db::DbPool;
-use crate::models::{Message, NewMessage};
-use crate::schema::messages;
+use crate::db::{DbConnection, DbPool};
+use crate::models::{
+    Message, MessageKind, MessageSearchFilter, MessageSearchRow, MessageSiblingPosition, MessageStatus,
+    NewMessage,
+};
+use crate::schema::{chat_participants, chats, messages};
+
+// 从指定消息开始沿选中的下一条消息递归，UNION 去重，即使数据中出现环也能结束递归
+const BRANCH_CTE: &str = "WITH RECURSIVE branch(id) AS ( \
+         SELECT ? \
+         UNION \
+         SELECT m.active_child_id FROM messages m JOIN branch b ON m.id = b.id \
+         WHERE m.active_child_id IS NOT NULL \
+     )";
 
 pub struct MessageRepository;
 
@@ -21,6 +34,68 @@ impl MessageRepository {
     ) -> Result<Message, RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
 
+        Self::create_with_conn(&mut conn, content, chat_id, sender_id, None)
+    }
+
+    // 使用已有连接创建消息，reply_to_id 为回复的消息
+    pub fn create_with_conn(
+        conn: &mut DbConnection,
+        content: String,
+        chat_id: &str,
+        sender_id: &str,
+        reply_to_id: Option<&str>,
+    ) -> Result<Message, RepositoryError> {
+        Self::insert_with_conn(conn, content, chat_id, sender_id, reply_to_id, None, MessageStatus::Complete, None)
+    }
+
+    // 使用已有连接创建内容为空的流式消息，生成过程中再逐步写入内容
+    //
+    // parent_id 为空时接在当前分支的最后一条消息之后；指定时接在该消息之后，
+    // 该消息原来选中的下一条消息及其后续消息移出当前分支，用于重新生成回复
+    pub fn create_streaming_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+        sender_id: &str,
+        reply_to_id: Option<&str>,
+        parent_id: Option<&str>,
+    ) -> Result<Message, RepositoryError> {
+        Self::insert_with_conn(conn, String::new(), chat_id, sender_id, reply_to_id, parent_id, MessageStatus::Streaming, None)
+    }
+
+    // 使用已有连接创建工具结果消息，sender_id 为调用工具的 AI 用户
+    pub fn create_tool_result_with_conn(
+        conn: &mut DbConnection,
+        content: String,
+        chat_id: &str,
+        sender_id: &str,
+        tool_call_id: &str,
+    ) -> Result<Message, RepositoryError> {
+        Self::insert_with_conn(conn, content, chat_id, sender_id, None, None, MessageStatus::Complete, Some(tool_call_id))
+    }
+
+    // tool_call_id 不为空时创建工具结果消息，否则创建普通消息
+    fn insert_with_conn(
+        conn: &mut DbConnection,
+        content: String,
+        chat_id: &str,
+        sender_id: &str,
+        reply_to_id: Option<&str>,
+        parent_id: Option<&str>,
+        status: MessageStatus,
+        tool_call_id: Option<&str>,
+    ) -> Result<Message, RepositoryError> {
+        // 新消息接在指定的消息之后，未指定时接在当前分支的最后一条消息之后
+        let parent = match parent_id {
+            Some(parent_id) => {
+                let parent = Self::get_with_conn(conn, parent_id)?;
+                if let Some(active_child_id) = &parent.active_child_id {
+                    Self::set_branch_active_with_conn(conn, active_child_id, false)?;
+                }
+                Some(parent)
+            }
+            None => Self::get_latest_with_conn(conn, chat_id)?,
+        };
+
         let new_message = NewMessage {
             id: Uuid::new_v4().to_string(),
             content,
@@ -28,17 +103,32 @@ impl MessageRepository {
             updated_at: Utc::now().naive_utc(),
             chat_id: chat_id.to_string(),
             sender_id: sender_id.to_string(),
+            status: status.as_str().to_string(),
+            reply_to_id: reply_to_id.map(str::to_string),
+            parent_id: parent.as_ref().map(|parent| parent.id.clone()),
+            kind: match tool_call_id {
+                Some(_) => MessageKind::ToolResult,
+                None => MessageKind::Text,
+            }
+            .as_str()
+            .to_string(),
+            tool_calls: None,
+            tool_call_id: tool_call_id.map(str::to_string),
         };
 
         diesel::insert_into(messages::table)
             .values(&new_message)
-            .execute(&mut conn)
+            .execute(conn)
             .map_err(RepositoryError::DatabaseError)?;
 
+        if let Some(parent) = &parent {
+            Self::set_active_child_with_conn(conn, &parent.id, Some(&new_message.id))?;
+        }
+
         let message = messages::table
             .filter(messages::id.eq(&new_message.id))
             .select(Message::as_select())
-            .first(&mut conn)
+            .first(conn)
             .map_err(RepositoryError::DatabaseError)?;
 
         Ok(message)
@@ -57,20 +147,434 @@ impl MessageRepository {
         Ok(message)
     }
 
-    // 获取聊天的所有消息
+    // 使用已有连接获取消息
+    pub fn get_with_conn(conn: &mut DbConnection, id: &str) -> Result<Message, RepositoryError> {
+        messages::table
+            .filter(messages::id.eq(id))
+            .select(Message::as_select())
+            .first(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    RepositoryError::NotFound
+                } else {
+                    RepositoryError::DatabaseError(e)
+                }
+            })
+    }
+
+    // 批量获取消息，不存在或已删除的ID会被忽略
+    pub fn get_by_ids(pool: &DbPool, ids: &[String]) -> Result<Vec<Message>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        messages::table
+            .filter(messages::id.eq_any(ids))
+            .filter(messages::deleted_at.is_null())
+            .select(Message::as_select())
+            .load(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 获取消息及其下当前分支上未删除的全部回复（包括回复的回复），结果按时间升序
+    pub fn get_reply_thread(pool: &DbPool, root_id: &str) -> Result<Vec<Message>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        // UNION 去重，即使数据中出现回复环也能结束递归
+        diesel::sql_query(
+            "WITH RECURSIVE thread(id) AS ( \
+                 SELECT id FROM messages WHERE id = ? AND deleted_at IS NULL \
+                 UNION \
+                 SELECT m.id FROM messages m JOIN thread t ON m.reply_to_id = t.id \
+                 WHERE m.is_active AND m.deleted_at IS NULL \
+             ) \
+             SELECT m.* FROM messages m JOIN thread t ON t.id = m.id \
+             ORDER BY m.created_at ASC, m.id ASC",
+        )
+        .bind::<Text, _>(root_id)
+        .load::<Message>(&mut conn)
+        .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 获取消息的所有未删除的兄弟节点（包括消息本身），按创建时间升序
+    pub fn get_siblings(pool: &DbPool, message: &Message) -> Result<Vec<Message>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let mut query = messages::table
+            .filter(messages::chat_id.eq(&message.chat_id))
+            .filter(messages::deleted_at.is_null())
+            .into_boxed();
+        query = match &message.parent_id {
+            Some(parent_id) => query.filter(messages::parent_id.eq(parent_id)),
+            None => query.filter(messages::parent_id.is_null()),
+        };
+
+        query
+            .order((messages::created_at.asc(), messages::id.asc()))
+            .select(Message::as_select())
+            .load(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接获取消息的兄弟节点中当前分支上的一个
+    pub fn get_active_sibling_with_conn(
+        conn: &mut DbConnection,
+        message: &Message,
+    ) -> Result<Option<Message>, RepositoryError> {
+        let mut query = messages::table
+            .filter(messages::chat_id.eq(&message.chat_id))
+            .filter(messages::is_active.eq(true))
+            .into_boxed();
+        query = match &message.parent_id {
+            Some(parent_id) => query.filter(messages::parent_id.eq(parent_id)),
+            None => query.filter(messages::parent_id.is_null()),
+        };
+
+        query
+            .select(Message::as_select())
+            .first(conn)
+            .optional()
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 批量获取消息在未删除的兄弟节点中的位置
+    pub fn get_sibling_positions(
+        pool: &DbPool,
+        ids: &[String],
+    ) -> Result<Vec<MessageSiblingPosition>, RepositoryError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let mut query = diesel::sql_query(format!(
+            "SELECT m.id AS id, \
+                    (SELECT COUNT(*) FROM messages s \
+                     WHERE s.chat_id = m.chat_id AND s.parent_id IS m.parent_id AND s.deleted_at IS NULL \
+                       AND (s.created_at < m.created_at OR (s.created_at = m.created_at AND s.id < m.id))) AS sibling_index, \
+                    (SELECT COUNT(*) FROM messages s \
+                     WHERE s.chat_id = m.chat_id AND s.parent_id IS m.parent_id AND s.deleted_at IS NULL) AS sibling_count \
+             FROM messages m WHERE m.id IN ({})",
+            placeholders
+        ))
+        .into_boxed::<Sqlite>();
+        for id in ids {
+            query = query.bind::<Text, _>(id.clone());
+        }
+
+        query
+            .load::<MessageSiblingPosition>(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接设置消息当前选中的下一条消息
+    pub fn set_active_child_with_conn(
+        conn: &mut DbConnection,
+        id: &str,
+        active_child_id: Option<&str>,
+    ) -> Result<(), RepositoryError> {
+        diesel::update(messages::table.filter(messages::id.eq(id)))
+            .set(messages::active_child_id.eq(active_child_id))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+
+    // 使用已有连接获取从指定消息开始、沿选中的下一条消息延伸的整条分支，按时间升序
+    pub fn get_branch_with_conn(conn: &mut DbConnection, id: &str) -> Result<Vec<Message>, RepositoryError> {
+        diesel::sql_query(format!(
+            "{} SELECT m.* FROM messages m JOIN branch b ON b.id = m.id ORDER BY m.created_at ASC, m.id ASC",
+            BRANCH_CTE
+        ))
+        .bind::<Text, _>(id)
+        .load::<Message>(conn)
+        .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接获取从聊天的第一条消息沿上一条消息到指定消息的整条路径，按时间升序
+    pub fn get_ancestors_with_conn(conn: &mut DbConnection, id: &str) -> Result<Vec<Message>, RepositoryError> {
+        // UNION 去重，即使数据中出现环也能结束递归
+        diesel::sql_query(
+            "WITH RECURSIVE ancestors(id) AS ( \
+                 SELECT ? \
+                 UNION \
+                 SELECT m.parent_id FROM messages m JOIN ancestors a ON m.id = a.id \
+                 WHERE m.parent_id IS NOT NULL \
+             ) \
+             SELECT m.* FROM messages m JOIN ancestors a ON a.id = m.id \
+             ORDER BY m.created_at ASC, m.id ASC",
+        )
+        .bind::<Text, _>(id)
+        .load::<Message>(conn)
+        .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接按顺序整行插入复制出的消息，须在事务中调用
+    pub fn insert_copies_with_conn(conn: &mut DbConnection, copies: &[Message]) -> Result<(), RepositoryError> {
+        // 每条消息选中的下一条消息在它之后才插入，外键约束推迟到事务提交时检查
+        diesel::sql_query("PRAGMA defer_foreign_keys = ON")
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        // 逐条插入，聊天的最后消息由插入触发器更新为最后一条
+        for copy in copies {
+            diesel::insert_into(messages::table)
+                .values(copy)
+                .execute(conn)
+                .map_err(RepositoryError::DatabaseError)?;
+        }
+        Ok(())
+    }
+
+    // 使用已有连接获取接在指定消息之后的所有消息，包括所有分支，按时间升序
+    pub fn get_descendants_with_conn(conn: &mut DbConnection, id: &str) -> Result<Vec<Message>, RepositoryError> {
+        // UNION 去重，即使数据中出现环也能结束递归
+        diesel::sql_query(
+            "WITH RECURSIVE descendants(id) AS ( \
+                 SELECT id FROM messages WHERE parent_id = ? \
+                 UNION \
+                 SELECT m.id FROM messages m JOIN descendants d ON m.parent_id = d.id \
+             ) \
+             SELECT m.* FROM messages m JOIN descendants d ON d.id = m.id \
+             ORDER BY m.created_at ASC, m.id ASC",
+        )
+        .bind::<Text, _>(id)
+        .load::<Message>(conn)
+        .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接设置从指定消息开始、沿选中的下一条消息延伸的整条分支是否在当前分支上
+    pub fn set_branch_active_with_conn(
+        conn: &mut DbConnection,
+        id: &str,
+        is_active: bool,
+    ) -> Result<(), RepositoryError> {
+        diesel::sql_query(format!(
+            "{} UPDATE messages SET is_active = ? WHERE id IN (SELECT id FROM branch)",
+            BRANCH_CTE
+        ))
+        .bind::<Text, _>(id)
+        .bind::<Bool, _>(is_active)
+        .execute(conn)
+        .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+
+    // 使用已有连接把消息的子节点改为接在该消息的上一条消息之后，用于删除树中间的消息
+    pub fn reparent_children_with_conn(
+        conn: &mut DbConnection,
+        message: &Message,
+    ) -> Result<(), RepositoryError> {
+        diesel::update(messages::table.filter(messages::parent_id.eq(&message.id)))
+            .set(messages::parent_id.eq(&message.parent_id))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        if let Some(parent_id) = &message.parent_id {
+            diesel::update(
+                messages::table
+                    .filter(messages::id.eq(parent_id))
+                    .filter(messages::active_child_id.eq(&message.id)),
+            )
+            .set(messages::active_child_id.eq(&message.active_child_id))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        }
+        Ok(())
+    }
+
+    // 使用已有连接查找聊天当前分支上是否有正在生成的消息
+    pub fn has_active_streaming_with_conn(conn: &mut DbConnection, chat_id: &str) -> Result<bool, RepositoryError> {
+        use diesel::dsl::exists;
+        use diesel::select;
+
+        select(exists(
+            messages::table
+                .filter(messages::chat_id.eq(chat_id))
+                .filter(messages::is_active.eq(true))
+                .filter(messages::status.eq(MessageStatus::Streaming.as_str())),
+        ))
+        .get_result(conn)
+        .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接获取聊天当前分支上最新的一条消息
+    pub fn get_latest_with_conn(
+        conn: &mut DbConnection,
+        chat_id: &str,
+    ) -> Result<Option<Message>, RepositoryError> {
+        messages::table
+            .filter(messages::chat_id.eq(chat_id))
+            .filter(messages::is_active.eq(true))
+            .order((messages::created_at.desc(), messages::id.desc()))
+            .select(Message::as_select())
+            .first(conn)
+            .optional()
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 获取聊天当前分支上所有未删除的消息
     pub fn get_by_chat_id(pool: &DbPool, chat_id: &str) -> Result<Vec<Message>, RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
 
         let messages_list = messages::table
             .filter(messages::chat_id.eq(chat_id))
-            .order(messages::created_at.asc())
+            .filter(messages::is_active.eq(true))
+            .filter(messages::deleted_at.is_null())
+            .order((messages::created_at.asc(), messages::id.asc()))
+            .select(Message::as_select())
+            .load(&mut conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(messages_list)
+    }
+
+    // 获取当前分支上游标之前的一页未删除的消息（不含游标本身），结果按时间升序返回
+    //
+    // 游标为 (created_at, id)，未提供游标时从最新的消息开始
+    pub fn get_page_before(
+        pool: &DbPool,
+        chat_id: &str,
+        cursor: Option<(NaiveDateTime, &str)>,
+        limit: i64,
+    ) -> Result<Vec<Message>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let mut query = messages::table
+            .filter(messages::chat_id.eq(chat_id))
+            .filter(messages::is_active.eq(true))
+            .filter(messages::deleted_at.is_null())
+            .into_boxed();
+
+        if let Some((created_at, id)) = cursor {
+            query = query.filter(
+                messages::created_at.lt(created_at)
+                    .or(messages::created_at.eq(created_at).and(messages::id.lt(id))),
+            );
+        }
+
+        let mut messages_list = query
+            .order((messages::created_at.desc(), messages::id.desc()))
+            .limit(limit)
             .select(Message::as_select())
             .load(&mut conn)
             .map_err(RepositoryError::DatabaseError)?;
 
+        // 倒序取出后翻转为升序
+        messages_list.reverse();
+
         Ok(messages_list)
     }
 
+    // 获取当前分支上游标之后的一页未删除的消息（不含游标本身），结果按时间升序返回
+    //
+    // 游标为 (created_at, id)，未提供游标时从最早的消息开始
+    pub fn get_page_after(
+        pool: &DbPool,
+        chat_id: &str,
+        cursor: Option<(NaiveDateTime, &str)>,
+        limit: i64,
+    ) -> Result<Vec<Message>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let mut query = messages::table
+            .filter(messages::chat_id.eq(chat_id))
+            .filter(messages::is_active.eq(true))
+            .filter(messages::deleted_at.is_null())
+            .into_boxed();
+
+        if let Some((created_at, id)) = cursor {
+            query = query.filter(
+                messages::created_at.gt(created_at)
+                    .or(messages::created_at.eq(created_at).and(messages::id.gt(id))),
+            );
+        }
+
+        let messages_list = query
+            .order((messages::created_at.asc(), messages::id.asc()))
+            .limit(limit)
+            .select(Message::as_select())
+            .load(&mut conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(messages_list)
+    }
+
+    // 全文检索消息，terms 中的检索词需同时出现
+    //
+    // 不少于三个字符的检索词通过 messages_fts 的 MATCH 检索并按 bm25 排序；
+    // 较短的检索词无法由 trigram 分词匹配，改用 LIKE 匹配。结果按相关度、时间倒序排列；
+    // 只检索普通消息，工具调用和工具结果不参与检索
+    pub fn search(
+        pool: &DbPool,
+        terms: &[String],
+        filter: &MessageSearchFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<MessageSearchRow>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let (long_terms, short_terms): (Vec<&String>, Vec<&String>) =
+            terms.iter().partition(|term| term.chars().count() >= 3);
+        // bm25 得分越小越相关，取反作为相关度
+        let score = if long_terms.is_empty() { "0.0" } else { "-bm25(messages_fts)" };
+
+        let mut query = diesel::sql_query(format!(
+            "SELECT m.id AS id, m.chat_id AS chat_id, c.name AS chat_name, \
+                    m.sender_id AS sender_id, u.name AS sender_name, u.is_ai AS sender_is_ai, \
+                    m.content AS content, m.created_at AS created_at, {} AS score \
+             FROM messages_fts \
+             JOIN message_search_ids s ON s.id = messages_fts.rowid \
+             JOIN messages m ON m.id = s.message_id \
+             JOIN users u ON u.id = m.sender_id \
+             JOIN chats c ON c.id = m.chat_id \
+             WHERE m.is_active AND m.deleted_at IS NULL AND c.deleted_at IS NULL AND m.kind = 'text'",
+            score
+        ))
+        .into_boxed::<Sqlite>();
+
+        if !long_terms.is_empty() {
+            // 每个检索词作为一个短语，双引号需要转义
+            let expression = long_terms
+                .iter()
+                .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            query = query.sql(" AND messages_fts MATCH ?").bind::<Text, _>(expression);
+        }
+        for term in short_terms {
+            let pattern = format!(
+                "%{}%",
+                term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+            );
+            query = query
+                .sql(" AND messages_fts.content LIKE ? ESCAPE '\\'")
+                .bind::<Text, _>(pattern);
+        }
+
+        if let Some(chat_id) = &filter.chat_id {
+            query = query.sql(" AND m.chat_id = ?").bind::<Text, _>(chat_id.clone());
+        }
+        if let Some(sender_id) = &filter.sender_id {
+            query = query.sql(" AND m.sender_id = ?").bind::<Text, _>(sender_id.clone());
+        }
+        if let Some(sender_is_ai) = filter.sender_is_ai {
+            query = query.sql(" AND u.is_ai = ?").bind::<Bool, _>(sender_is_ai);
+        }
+        if let Some(created_after) = filter.created_after {
+            query = query.sql(" AND m.created_at >= ?").bind::<Timestamp, _>(created_after);
+        }
+        if let Some(created_before) = filter.created_before {
+            query = query.sql(" AND m.created_at < ?").bind::<Timestamp, _>(created_before);
+        }
+
+        query
+            .sql(" ORDER BY score DESC, m.created_at DESC, m.id DESC LIMIT ? OFFSET ?")
+            .bind::<BigInt, _>(limit)
+            .bind::<BigInt, _>(offset)
+            .load::<MessageSearchRow>(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
     // 获取用户的所有消息
     pub fn get_by_sender_id(pool: &DbPool, sender_id: &str) -> Result<Vec<Message>, RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
@@ -85,39 +589,363 @@ impl MessageRepository {
         Ok(messages_list)
     }
 
-    // 更新消息
-    pub fn update(
-        pool: &DbPool,
+    // 使用已有连接修改消息内容
+    pub fn update_with_conn(
+        conn: &mut DbConnection,
         id: &str,
         content: String,
     ) -> Result<Message, RepositoryError> {
-        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
-
         diesel::update(messages::table.filter(messages::id.eq(id)))
             .set((
                 messages::content.eq(content),
                 messages::updated_at.eq(Utc::now().naive_utc()),
             ))
-            .execute(&mut conn)
+            .execute(conn)
             .map_err(RepositoryError::DatabaseError)?;
 
-        let updated_message = messages::table
+        Self::get_with_conn(conn, id)
+    }
+
+    // 写入流式消息已生成的内容，消息已不是流式状态（已结束或被删除）时返回 false
+    pub fn update_streaming_content(pool: &DbPool, id: &str, content: &str) -> Result<bool, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let updated = diesel::update(
+            messages::table
+                .filter(messages::id.eq(id))
+                .filter(messages::status.eq(MessageStatus::Streaming.as_str())),
+        )
+        .set((
+            messages::content.eq(content),
+            messages::updated_at.eq(Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
+    // 结束流式消息，写入最终内容和状态
+    //
+    // 消息已不是流式状态时不做修改，返回 NotFound
+    pub fn finish_streaming(
+        pool: &DbPool,
+        id: &str,
+        content: &str,
+        status: MessageStatus,
+        error_message: Option<&str>,
+    ) -> Result<Message, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let updated = diesel::update(
+            messages::table
+                .filter(messages::id.eq(id))
+                .filter(messages::status.eq(MessageStatus::Streaming.as_str())),
+        )
+        .set((
+            messages::content.eq(content),
+            messages::status.eq(status.as_str()),
+            messages::error_message.eq(error_message),
+            messages::updated_at.eq(Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        if updated == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        messages::table
             .filter(messages::id.eq(id))
             .select(Message::as_select())
             .first(&mut conn)
-            .map_err(RepositoryError::DatabaseError)?;
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 将流式消息结束为工具调用消息，content 为模型在调用前输出的文本，tool_calls 为 JSON 数组格式
+    //
+    // 消息已不是流式状态时不做修改，返回 NotFound
+    pub fn finish_as_tool_call(
+        pool: &DbPool,
+        id: &str,
+        content: &str,
+        tool_calls: &str,
+    ) -> Result<Message, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
 
-        Ok(updated_message)
+        let updated = diesel::update(
+            messages::table
+                .filter(messages::id.eq(id))
+                .filter(messages::status.eq(MessageStatus::Streaming.as_str())),
+        )
+        .set((
+            messages::content.eq(content),
+            messages::status.eq(MessageStatus::Complete.as_str()),
+            messages::kind.eq(MessageKind::ToolCall.as_str()),
+            messages::tool_calls.eq(tool_calls),
+            messages::updated_at.eq(Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+        .map_err(RepositoryError::DatabaseError)?;
+
+        if updated == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        messages::table
+            .filter(messages::id.eq(id))
+            .select(Message::as_select())
+            .first(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
     }
 
-    // 删除消息
-    pub fn delete(pool: &DbPool, id: &str) -> Result<(), RepositoryError> {
+    // 将所有流式消息标记为失败并保留已写入的内容，返回受影响的消息
+    //
+    // 用于应用启动时清理上次退出时未结束的流式消息
+    pub fn fail_all_streaming(pool: &DbPool, error_message: &str) -> Result<Vec<Message>, RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+        let now = Utc::now().naive_utc();
+
+        conn.transaction(|conn| {
+            let ids: Vec<String> = messages::table
+                .filter(messages::status.eq(MessageStatus::Streaming.as_str()))
+                .select(messages::id)
+                .load(conn)?;
+
+            diesel::update(messages::table.filter(messages::id.eq_any(&ids)))
+                .set((
+                    messages::status.eq(MessageStatus::Error.as_str()),
+                    messages::error_message.eq(error_message),
+                    messages::updated_at.eq(now),
+                ))
+                .execute(conn)?;
 
+            messages::table
+                .filter(messages::id.eq_any(&ids))
+                .select(Message::as_select())
+                .load(conn)
+        })
+        .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接设置消息的删除时间，为空时从回收站恢复
+    pub fn set_deleted_with_conn(
+        conn: &mut DbConnection,
+        id: &str,
+        deleted_at: Option<NaiveDateTime>,
+    ) -> Result<(), RepositoryError> {
+        diesel::update(messages::table.filter(messages::id.eq(id)))
+            .set(messages::deleted_at.eq(deleted_at))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+
+    // 获取用户参与的未删除聊天中已删除的消息，按删除时间倒序
+    pub fn get_deleted_by_user(pool: &DbPool, user_id: &str) -> Result<Vec<Message>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        let chat_ids = chat_participants::table
+            .inner_join(chats::table)
+            .filter(chat_participants::user_id.eq(user_id))
+            .filter(chats::deleted_at.is_null())
+            .select(chats::id);
+
+        messages::table
+            .filter(messages::chat_id.eq_any(chat_ids))
+            .filter(messages::deleted_at.is_not_null())
+            .order((messages::deleted_at.desc(), messages::id.desc()))
+            .select(Message::as_select())
+            .load(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接获取删除时间早于 cutoff 的消息，按创建时间倒序，先清除分支末端的消息
+    pub fn get_expired_with_conn(
+        conn: &mut DbConnection,
+        cutoff: NaiveDateTime,
+    ) -> Result<Vec<Message>, RepositoryError> {
+        messages::table
+            .filter(messages::deleted_at.lt(cutoff))
+            .order((messages::created_at.desc(), messages::id.desc()))
+            .select(Message::as_select())
+            .load(conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接清除其他消息对这些消息的回复引用，在彻底删除消息前调用
+    pub fn clear_reply_refs_with_conn(conn: &mut DbConnection, ids: &[String]) -> Result<(), RepositoryError> {
+        diesel::update(messages::table.filter(messages::reply_to_id.eq_any(ids)))
+            .set(messages::reply_to_id.eq(None::<String>))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // 使用已有连接清除其他消息对该聊天中消息的回复引用，在彻底删除聊天前调用
+    pub fn clear_chat_reply_refs_with_conn(conn: &mut DbConnection, chat_id: &str) -> Result<(), RepositoryError> {
+        let chat_message_ids: Vec<String> = messages::table
+            .filter(messages::chat_id.eq(chat_id))
+            .select(messages::id)
+            .load(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Self::clear_reply_refs_with_conn(conn, &chat_message_ids)
+    }
+
+    // 使用已有连接删除消息
+    pub fn delete_with_conn(conn: &mut DbConnection, id: &str) -> Result<(), RepositoryError> {
         diesel::delete(messages::table.filter(messages::id.eq(id)))
-            .execute(&mut conn)
+            .execute(conn)
             .map_err(RepositoryError::DatabaseError)?;
 
         Ok(())
     }
-} 
\ No newline at end of file
+
+    // 使用已有连接删除聊天中的所有消息
+    pub fn delete_by_chat_id_with_conn(conn: &mut DbConnection, chat_id: &str) -> Result<(), RepositoryError> {
+        diesel::delete(messages::table.filter(messages::chat_id.eq(chat_id)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // 使用已有连接批量删除消息
+    pub fn delete_by_ids_with_conn(conn: &mut DbConnection, ids: &[String]) -> Result<(), RepositoryError> {
+        diesel::delete(messages::table.filter(messages::id.eq_any(ids)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(())
+    }
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ChatType;
+    use crate::repositories::chat_repository::ChatRepository;
+    use crate::test_support::{create_test_chat, TestChat};
+
+    fn ids(messages: &[Message]) -> Vec<String> {
+        messages.iter().map(|message| message.id.clone()).collect()
+    }
+
+    #[test]
+    fn pages_split_messages_with_equal_created_at_by_id() {
+        let TestChat { pool, human, chat, .. } = create_test_chat();
+        for index in 0..5 {
+            MessageRepository::create(&pool, format!("消息{}", index), &chat.id, &human.id).unwrap();
+        }
+        // 同一时刻写入的消息按ID排序
+        let created_at = Utc::now().naive_utc();
+        diesel::update(messages::table.filter(messages::chat_id.eq(&chat.id)))
+            .set(messages::created_at.eq(created_at))
+            .execute(&mut pool.get().unwrap())
+            .unwrap();
+        let mut sorted = ids(&MessageRepository::get_by_chat_id(&pool, &chat.id).unwrap());
+        sorted.sort();
+
+        let latest = MessageRepository::get_page_before(&pool, &chat.id, None, 2).unwrap();
+        assert_eq!(ids(&latest), sorted[3..]);
+        let before = MessageRepository::get_page_before(&pool, &chat.id, Some((created_at, &sorted[3])), 2).unwrap();
+        assert_eq!(ids(&before), sorted[1..3]);
+        let after = MessageRepository::get_page_after(&pool, &chat.id, Some((created_at, &sorted[1])), 2).unwrap();
+        assert_eq!(ids(&after), sorted[2..4]);
+
+        // 逐页向前翻完全部消息，既不重复也不遗漏
+        let mut pages = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = MessageRepository::get_page_after(
+                &pool,
+                &chat.id,
+                cursor.as_deref().map(|id| (created_at, id)),
+                2,
+            )
+            .unwrap();
+            let Some(last) = page.last() else { break };
+            cursor = Some(last.id.clone());
+            pages.extend(ids(&page));
+        }
+        assert_eq!(pages, sorted);
+    }
+
+    fn search(pool: &DbPool, terms: &[&str], filter: &MessageSearchFilter) -> Vec<String> {
+        let terms: Vec<String> = terms.iter().map(|term| term.to_string()).collect();
+        MessageRepository::search(pool, &terms, filter, 10, 0)
+            .unwrap()
+            .into_iter()
+            .map(|row| row.content)
+            .collect()
+    }
+
+    #[test]
+    fn search_uses_match_for_long_terms_and_like_for_short_terms() {
+        let TestChat { pool, human, chat, .. } = create_test_chat();
+        for content in ["明天的会议改到下午三点", "今天的天气很好", "会议纪要已发送"] {
+            MessageRepository::create(&pool, content.to_string(), &chat.id, &human.id).unwrap();
+        }
+        let filter = MessageSearchFilter::default();
+
+        assert_eq!(search(&pool, &["会议纪要"], &filter), ["会议纪要已发送"]);
+        let mut short = search(&pool, &["会议"], &filter);
+        short.sort();
+        assert_eq!(short, ["会议纪要已发送", "明天的会议改到下午三点"]);
+        // 长短检索词需同时出现
+        assert_eq!(search(&pool, &["明天", "下午三点"], &filter), ["明天的会议改到下午三点"]);
+        assert!(search(&pool, &["天气", "会议"], &filter).is_empty());
+        // LIKE 中的通配符按字面匹配
+        assert!(search(&pool, &["%"], &filter).is_empty());
+    }
+
+    #[test]
+    fn search_applies_filters_and_skips_hidden_messages() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let other = ChatRepository::create(&pool, "其他", "", ChatType::Group).unwrap();
+        MessageRepository::create(&pool, "周报已提交".to_string(), &chat.id, &human.id).unwrap();
+        let reply = MessageRepository::create(&pool, "收到周报".to_string(), &chat.id, &ai.id).unwrap();
+        MessageRepository::create(&pool, "其他聊天的周报".to_string(), &other.id, &human.id).unwrap();
+        let deleted = MessageRepository::create(&pool, "删除的周报".to_string(), &chat.id, &human.id).unwrap();
+        let mut conn = pool.get().unwrap();
+        MessageRepository::set_deleted_with_conn(&mut conn, &deleted.id, Some(Utc::now().naive_utc())).unwrap();
+        MessageRepository::create_tool_result_with_conn(&mut conn, "周报工具结果".to_string(), &chat.id, &ai.id, "call-1")
+            .unwrap();
+
+        let in_chat = MessageSearchFilter { chat_id: Some(chat.id.clone()), ..Default::default() };
+        let mut contents = search(&pool, &["周报"], &in_chat);
+        contents.sort();
+        assert_eq!(contents, ["周报已提交", "收到周报"]);
+
+        let by_ai = MessageSearchFilter { sender_is_ai: Some(true), ..Default::default() };
+        assert_eq!(search(&pool, &["周报"], &by_ai), ["收到周报"]);
+        let by_sender = MessageSearchFilter { sender_id: Some(human.id.clone()), chat_id: Some(chat.id.clone()), ..Default::default() };
+        assert_eq!(search(&pool, &["周报"], &by_sender), ["周报已提交"]);
+
+        let after = MessageSearchFilter { created_after: Some(reply.created_at), chat_id: Some(chat.id.clone()), ..Default::default() };
+        assert_eq!(search(&pool, &["周报"], &after), ["收到周报"]);
+        let before = MessageSearchFilter { created_before: Some(reply.created_at), chat_id: Some(chat.id.clone()), ..Default::default() };
+        assert_eq!(search(&pool, &["周报"], &before), ["周报已提交"]);
+    }
+
+    #[test]
+    fn search_index_follows_updates_deletes_and_vacuum() {
+        let TestChat { pool, human, chat, .. } = create_test_chat();
+        let first = MessageRepository::create(&pool, "第一条消息".to_string(), &chat.id, &human.id).unwrap();
+        let second = MessageRepository::create(&pool, "第二条消息".to_string(), &chat.id, &human.id).unwrap();
+        MessageRepository::create(&pool, "第三条消息".to_string(), &chat.id, &human.id).unwrap();
+        let filter = MessageSearchFilter::default();
+
+        let mut conn = pool.get().unwrap();
+        MessageRepository::update_with_conn(&mut conn, &second.id, "修改后的内容".to_string()).unwrap();
+        MessageRepository::delete_with_conn(&mut conn, &first.id).unwrap();
+        // 删除消息后整理数据库，messages 的 rowid 可能重新编号，检索结果仍对应原消息
+        diesel::sql_query("VACUUM").execute(&mut conn).unwrap();
+
+        assert!(search(&pool, &["第二条"], &filter).is_empty());
+        assert_eq!(search(&pool, &["修改后"], &filter), ["修改后的内容"]);
+        assert!(search(&pool, &["第一条"], &filter).is_empty());
+        assert_eq!(search(&pool, &["第三条"], &filter), ["第三条消息"]);
+    }
+}
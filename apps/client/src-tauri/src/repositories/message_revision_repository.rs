@@ -0,0 +1,100 @@
+// 消息修订记录仓库
+
+use chrono::Utc;
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use super::error::RepositoryError;
+use crate::db::{DbConnection, DbPool};
+use crate::models::{MessageRevision, NewMessageRevision};
+use crate::schema::{message_revisions, messages};
+
+pub struct MessageRevisionRepository;
+
+impl MessageRevisionRepository {
+    // 使用已有连接记录消息被修改前的内容
+    pub fn create_with_conn(
+        conn: &mut DbConnection,
+        message_id: &str,
+        content: String,
+        editor_id: &str,
+    ) -> Result<MessageRevision, RepositoryError> {
+        let new_revision = NewMessageRevision {
+            id: Uuid::new_v4().to_string(),
+            message_id: message_id.to_string(),
+            content,
+            editor_id: Some(editor_id.to_string()),
+            created_at: Utc::now().naive_utc(),
+        };
+
+        diesel::insert_into(message_revisions::table)
+            .values(&new_revision)
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Self::get_with_conn(conn, &new_revision.id)
+    }
+
+    // 使用已有连接获取修订记录
+    pub fn get_with_conn(conn: &mut DbConnection, id: &str) -> Result<MessageRevision, RepositoryError> {
+        message_revisions::table
+            .filter(message_revisions::id.eq(id))
+            .select(MessageRevision::as_select())
+            .first(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    RepositoryError::NotFound
+                } else {
+                    RepositoryError::DatabaseError(e)
+                }
+            })
+    }
+
+    // 获取消息的所有修订记录，按修改时间倒序
+    pub fn get_by_message_id(pool: &DbPool, message_id: &str) -> Result<Vec<MessageRevision>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        message_revisions::table
+            .filter(message_revisions::message_id.eq(message_id))
+            .order((message_revisions::created_at.desc(), message_revisions::id.desc()))
+            .select(MessageRevision::as_select())
+            .load(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 批量统计消息的修订次数，没有修订记录的消息不出现在结果中
+    pub fn count_by_message_ids(pool: &DbPool, message_ids: &[String]) -> Result<Vec<(String, i64)>, RepositoryError> {
+        if message_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        message_revisions::table
+            .filter(message_revisions::message_id.eq_any(message_ids))
+            .group_by(message_revisions::message_id)
+            .select((message_revisions::message_id, count_star()))
+            .load(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
+    // 使用已有连接删除消息的修订记录，在彻底删除消息前调用
+    pub fn delete_by_message_ids_with_conn(conn: &mut DbConnection, message_ids: &[String]) -> Result<(), RepositoryError> {
+        diesel::delete(message_revisions::table.filter(message_revisions::message_id.eq_any(message_ids)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+
+    // 使用已有连接删除聊天中所有消息的修订记录，在彻底删除聊天前调用
+    pub fn delete_by_chat_id_with_conn(conn: &mut DbConnection, chat_id: &str) -> Result<(), RepositoryError> {
+        let chat_message_ids = messages::table
+            .filter(messages::chat_id.eq(chat_id))
+            .select(messages::id);
+
+        diesel::delete(message_revisions::table.filter(message_revisions::message_id.eq_any(chat_message_ids)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+        Ok(())
+    }
+}
@@ -5,6 +5,13 @@ pub mod message_repository;
 pub mod user_repository;
 pub mod user_contact_repository;
 pub mod resource_repository;
+pub mod agent_repository;
+pub mod ai_job_repository;
+pub mod message_revision_repository;
+pub mod chat_agent_override_repository;
+pub mod mcp_server_repository;
+pub mod knowledge_repository;
+pub mod agent_memory_repository;
 
 // 导出错误类型
 pub mod error;
@@ -5,7 +5,7 @@ use diesel::prelude::*;
 use uuid::Uuid;
 
 use super::error::RepositoryError;
-use crate::db::DbPool;
+use crate::db::{DbConnection, DbPool};
 use crate::models::{Resource, NewResource};
 use crate::schema::resources;
 
@@ -69,6 +69,35 @@ impl ResourceRepository {
         Ok(resource)
     }
 
+    // 使用已有连接获取资源
+    pub fn get_with_conn(conn: &mut DbConnection, id: &str) -> Result<Resource, RepositoryError> {
+        resources::table
+            .filter(resources::id.eq(id))
+            .select(Resource::as_select())
+            .first(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    RepositoryError::NotFound
+                } else {
+                    RepositoryError::DatabaseError(e)
+                }
+            })
+    }
+
+    // 使用已有连接更新资源的修改时间
+    pub fn touch_with_conn(conn: &mut DbConnection, id: &str) -> Result<Resource, RepositoryError> {
+        let updated = diesel::update(resources::table.filter(resources::id.eq(id)))
+            .set(resources::updated_at.eq(Utc::now().naive_utc()))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        if updated == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+
+        Self::get_with_conn(conn, id)
+    }
+
     // 获取用户的所有资源
     pub fn get_by_user_id(pool: &DbPool, user_id: &str) -> Result<Vec<Resource>, RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
@@ -150,6 +179,15 @@ impl ResourceRepository {
         Ok(updated_resource)
     }
 
+    // 使用已有连接删除资源
+    pub fn delete_with_conn(conn: &mut DbConnection, id: &str) -> Result<(), RepositoryError> {
+        diesel::delete(resources::table.filter(resources::id.eq(id)))
+            .execute(conn)
+            .map_err(RepositoryError::DatabaseError)?;
+
+        Ok(())
+    }
+
     // 删除资源
     pub fn delete(pool: &DbPool, id: &str) -> Result<(), RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
@@ -3,7 +3,7 @@ use diesel::prelude::*;
 use uuid::Uuid;
 
 use super::error::RepositoryError;
-use crate::db::DbPool;
+use crate::db::{DbConnection, DbPool};
 use crate::models::{NewUser, User};
 use crate::schema::users;
 
@@ -22,6 +22,7 @@ impl UserRepository {
         let new_user = NewUser {
             id: Uuid::new_v4().to_string(),
             name,
+            avatar_url: None,
             description,
             is_ai,
             created_at: Utc::now().naive_utc(),
@@ -55,6 +56,32 @@ impl UserRepository {
         Ok(user)
     }
 
+    // 使用已有连接获取用户
+    pub fn get_with_conn(conn: &mut DbConnection, id: &str) -> Result<User, RepositoryError> {
+        users::table
+            .filter(users::id.eq(id))
+            .select(User::as_select())
+            .first(conn)
+            .map_err(|e| {
+                if let diesel::result::Error::NotFound = e {
+                    RepositoryError::NotFound
+                } else {
+                    RepositoryError::DatabaseError(e)
+                }
+            })
+    }
+
+    // 批量获取用户，不存在的 ID 会被忽略
+    pub fn get_by_ids(pool: &DbPool, ids: &[String]) -> Result<Vec<User>, RepositoryError> {
+        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
+
+        users::table
+            .filter(users::id.eq_any(ids))
+            .select(User::as_select())
+            .load(&mut conn)
+            .map_err(RepositoryError::DatabaseError)
+    }
+
     // 获取所有用户
     pub fn get_all(pool: &DbPool) -> Result<Vec<User>, RepositoryError> {
         let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
@@ -17,6 +17,79 @@ diesel::table! {
         user_id -> Text,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        context_window -> Nullable<Integer>,
+        context_strategy -> Text,
+        context_keep_first -> Nullable<Integer>,
+        tools -> Nullable<Text>,
+        max_tool_iterations -> Nullable<Integer>,
+        embedding_model -> Nullable<Text>,
+        memory_enabled -> Bool,
+        memory_extraction_prompt -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    agent_memories (id) {
+        id -> Text,
+        agent_user_id -> Text,
+        content -> Text,
+        source_chat_id -> Nullable<Text>,
+        source_message_id -> Nullable<Text>,
+        importance -> Integer,
+        is_pinned -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    agent_mcp_servers (id) {
+        id -> Text,
+        agent_user_id -> Text,
+        server_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    agent_knowledge_resources (id) {
+        id -> Text,
+        agent_user_id -> Text,
+        resource_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    ai_jobs (id) {
+        id -> Text,
+        chat_id -> Text,
+        agent_user_id -> Text,
+        trigger_message_id -> Nullable<Text>,
+        reply_message_id -> Nullable<Text>,
+        status -> Text,
+        error_message -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        started_at -> Nullable<Timestamp>,
+        finished_at -> Nullable<Timestamp>,
+        regenerated_message_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    chat_agent_overrides (id) {
+        id -> Text,
+        chat_id -> Text,
+        agent_user_id -> Text,
+        system_prompt -> Nullable<Text>,
+        model_name -> Nullable<Text>,
+        temperature -> Nullable<Float>,
+        top_p -> Nullable<Float>,
+        max_tokens -> Nullable<Integer>,
+        stop_sequences -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -28,6 +101,15 @@ diesel::table! {
         joined_at -> Timestamp,
         chat_id -> Text,
         user_id -> Text,
+        is_pinned -> Bool,
+        last_read_message_id -> Nullable<Text>,
+        last_read_at -> Nullable<Timestamp>,
+        is_muted -> Bool,
+        is_archived -> Bool,
+        sort_order -> Nullable<Integer>,
+        display_name -> Nullable<Text>,
+        memory_extracted_message_id -> Nullable<Text>,
+        memory_extracted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -36,11 +118,41 @@ diesel::table! {
         id -> Text,
         name -> Text,
         avatar_urls -> Text,
-        unread_count -> Integer,
         last_message -> Nullable<Text>,
         last_message_time -> Nullable<Timestamp>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        forked_from_chat_id -> Nullable<Text>,
+        forked_from_message_id -> Nullable<Text>,
+        deleted_at -> Nullable<Timestamp>,
+        reply_policy -> Text,
+        moderator_user_id -> Nullable<Text>,
+        max_ai_turns -> Integer,
+        chat_type -> Text,
+    }
+}
+
+diesel::table! {
+    mcp_servers (id) {
+        id -> Text,
+        name -> Text,
+        command -> Text,
+        args -> Text,
+        env -> Text,
+        cwd -> Nullable<Text>,
+        enabled -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    message_revisions (id) {
+        id -> Text,
+        message_id -> Text,
+        content -> Text,
+        editor_id -> Nullable<Text>,
+        created_at -> Timestamp,
     }
 }
 
@@ -52,6 +164,30 @@ diesel::table! {
         updated_at -> Timestamp,
         chat_id -> Text,
         sender_id -> Text,
+        status -> Text,
+        error_message -> Nullable<Text>,
+        reply_to_id -> Nullable<Text>,
+        parent_id -> Nullable<Text>,
+        active_child_id -> Nullable<Text>,
+        is_active -> Bool,
+        deleted_at -> Nullable<Timestamp>,
+        kind -> Text,
+        tool_calls -> Nullable<Text>,
+        tool_call_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    resource_chunks (id) {
+        id -> Text,
+        resource_id -> Text,
+        embedding_provider -> Text,
+        embedding_model -> Text,
+        chunk_index -> Integer,
+        content -> Text,
+        embedding -> Binary,
+        content_hash -> Text,
+        created_at -> Timestamp,
     }
 }
 
@@ -92,18 +228,41 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(agent_knowledge_resources -> resources (resource_id));
+diesel::joinable!(agent_knowledge_resources -> users (agent_user_id));
+diesel::joinable!(agent_memories -> chats (source_chat_id));
+diesel::joinable!(agent_memories -> messages (source_message_id));
+diesel::joinable!(agent_memories -> users (agent_user_id));
+diesel::joinable!(agent_mcp_servers -> mcp_servers (server_id));
+diesel::joinable!(agent_mcp_servers -> users (agent_user_id));
 diesel::joinable!(agents -> users (user_id));
+diesel::joinable!(ai_jobs -> chats (chat_id));
+diesel::joinable!(ai_jobs -> users (agent_user_id));
+diesel::joinable!(chat_agent_overrides -> chats (chat_id));
+diesel::joinable!(chat_agent_overrides -> users (agent_user_id));
 diesel::joinable!(chat_participants -> chats (chat_id));
+diesel::joinable!(chats -> users (moderator_user_id));
 diesel::joinable!(chat_participants -> users (user_id));
+diesel::joinable!(message_revisions -> messages (message_id));
+diesel::joinable!(message_revisions -> users (editor_id));
 diesel::joinable!(messages -> chats (chat_id));
 diesel::joinable!(messages -> users (sender_id));
+diesel::joinable!(resource_chunks -> resources (resource_id));
 diesel::joinable!(resources -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    agent_knowledge_resources,
+    agent_mcp_servers,
+    agent_memories,
     agents,
+    ai_jobs,
+    chat_agent_overrides,
     chat_participants,
     chats,
+    mcp_servers,
+    message_revisions,
     messages,
+    resource_chunks,
     resources,
     user_contacts,
     users,
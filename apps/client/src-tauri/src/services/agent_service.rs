@@ -0,0 +1,585 @@
+// AI 用户的模型配置服务
+use anyhow::anyhow;
+use chrono::Utc;
+use diesel::connection::Connection;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{DbConnection, DbPool};
+use crate::llm::registry::DEFAULT_PROVIDER;
+use crate::llm::SamplingOptions;
+use crate::models::{Agent, AgentChangeset, ChatAgentOverride, ChatAgentOverrideChangeset, NewAgent};
+use crate::repositories::agent_repository::AgentRepository;
+use crate::repositories::chat_agent_override_repository::ChatAgentOverrideRepository;
+use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+use crate::repositories::error::RepositoryError;
+use crate::repositories::user_repository::UserRepository;
+use crate::tools::builtin::BUILTIN_TOOL_NAMES;
+use super::context_builder::ContextStrategy;
+use super::ServiceResult;
+
+// 新建 AI 用户时使用的默认模型
+pub const DEFAULT_MODEL_NAME: &str = "llama3";
+// 未配置 max_tool_iterations 时一次回复中最多调用工具的轮数
+pub const DEFAULT_MAX_TOOL_ITERATIONS: i32 = 5;
+// 未配置 embedding_model 时生成知识库向量使用的模型
+pub const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+// 参数取值范围
+const TEMPERATURE_RANGE: (f32, f32) = (0.0, 2.0);
+const TOP_P_RANGE: (f32, f32) = (0.0, 1.0);
+const TOP_K_RANGE: (i32, i32) = (1, 1000);
+const REPEAT_PENALTY_RANGE: (f32, f32) = (0.0, 2.0);
+const PENALTY_RANGE: (f32, f32) = (-2.0, 2.0);
+const MAX_TOKENS_RANGE: (i32, i32) = (1, 131072);
+const MAX_SYSTEM_PROMPT_CHARS: usize = 20000;
+const MAX_STOP_SEQUENCES: usize = 8;
+const MAX_STOP_SEQUENCE_CHARS: usize = 100;
+const CONTEXT_WINDOW_RANGE: (i32, i32) = (512, 1048576);
+const CONTEXT_KEEP_FIRST_RANGE: (i32, i32) = (1, 100);
+const MAX_TOOL_ITERATIONS_RANGE: (i32, i32) = (1, 20);
+
+// Agent 配置，默认值与 agents 表的列默认值一致
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    pub provider: String,
+    pub model_name: String,
+    pub system_prompt: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<i32>,
+    pub repeat_penalty: Option<f32>,
+    pub stop_sequences: Vec<String>,
+    pub max_tokens: Option<i32>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub context_window: Option<i32>,
+    pub context_strategy: String,
+    pub context_keep_first: Option<i32>,
+    pub tools: Vec<String>, // 可以调用的内置工具名称
+    pub max_tool_iterations: Option<i32>,
+    pub embedding_model: Option<String>, // 生成知识库向量的模型，为空时使用默认模型
+    pub memory_enabled: bool,                     // 是否在对话后提取长期记忆并在回复时使用
+    pub memory_extraction_prompt: Option<String>, // 提取记忆的提示词，为空时使用默认提示词
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            provider: DEFAULT_PROVIDER.to_string(),
+            model_name: DEFAULT_MODEL_NAME.to_string(),
+            system_prompt: None,
+            temperature: Some(0.7),
+            top_p: Some(0.9),
+            top_k: Some(40),
+            repeat_penalty: Some(1.1),
+            stop_sequences: Vec::new(),
+            max_tokens: Some(2048),
+            presence_penalty: Some(0.0),
+            frequency_penalty: Some(0.0),
+            context_window: None,
+            context_strategy: ContextStrategy::SLIDING_WINDOW.to_string(),
+            context_keep_first: None,
+            tools: Vec::new(),
+            max_tool_iterations: None,
+            embedding_model: None,
+            memory_enabled: false,
+            memory_extraction_prompt: None,
+        }
+    }
+}
+
+impl From<&Agent> for AgentConfig {
+    fn from(agent: &Agent) -> Self {
+        Self {
+            provider: agent.provider.clone(),
+            model_name: agent.model_name.clone(),
+            system_prompt: agent.system_prompt.clone(),
+            temperature: agent.temperature,
+            top_p: agent.top_p,
+            top_k: agent.top_k,
+            repeat_penalty: agent.repeat_penalty,
+            stop_sequences: AgentService::decode_stop_sequences(agent.stop_sequences.as_deref()),
+            max_tokens: agent.max_tokens,
+            presence_penalty: agent.presence_penalty,
+            frequency_penalty: agent.frequency_penalty,
+            context_window: agent.context_window,
+            context_strategy: agent.context_strategy.clone(),
+            context_keep_first: agent.context_keep_first,
+            tools: AgentService::decode_tools(agent.tools.as_deref()),
+            max_tool_iterations: agent.max_tool_iterations,
+            embedding_model: agent.embedding_model.clone(),
+            memory_enabled: agent.memory_enabled,
+            memory_extraction_prompt: agent.memory_extraction_prompt.clone(),
+        }
+    }
+}
+
+// AI 用户在某个聊天中的配置覆盖，为 None 的字段沿用 Agent 配置；
+// stop_sequences 为空数组时表示在该聊天中不使用停止序列
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatAgentOverrideConfig {
+    pub system_prompt: Option<String>,
+    pub model_name: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<i32>,
+    pub stop_sequences: Option<Vec<String>>,
+}
+
+impl From<&ChatAgentOverride> for ChatAgentOverrideConfig {
+    fn from(item: &ChatAgentOverride) -> Self {
+        Self {
+            system_prompt: item.system_prompt.clone(),
+            model_name: item.model_name.clone(),
+            temperature: item.temperature,
+            top_p: item.top_p,
+            max_tokens: item.max_tokens,
+            stop_sequences: item
+                .stop_sequences
+                .as_deref()
+                .map(|raw| AgentService::decode_stop_sequences(Some(raw))),
+        }
+    }
+}
+
+pub struct AgentService;
+
+impl AgentService {
+    // 获取 AI 用户的 Agent 配置
+    //
+    // 早期创建的 AI 用户没有 Agent 记录，首次读取时按默认配置补建
+    pub fn get_agent_by_user(pool: &DbPool, user_id: &str) -> ServiceResult<Agent> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| Self::get_or_create_with_conn(conn, user_id))
+    }
+
+    // 更新 AI 用户的 Agent 配置，整体替换原有配置
+    pub fn update_agent_config(
+        pool: &DbPool,
+        user_id: &str,
+        config: AgentConfig,
+    ) -> ServiceResult<Agent> {
+        let config = Self::normalize(config);
+        Self::validate(&config)?;
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            let agent = Self::get_or_create_with_conn(conn, user_id)?;
+
+            let changeset = AgentChangeset {
+                provider: config.provider,
+                model_name: config.model_name,
+                system_prompt: config.system_prompt,
+                temperature: config.temperature,
+                top_p: config.top_p,
+                top_k: config.top_k,
+                repeat_penalty: config.repeat_penalty,
+                stop_sequences: Self::encode_stop_sequences(&config.stop_sequences),
+                max_tokens: config.max_tokens,
+                presence_penalty: config.presence_penalty,
+                frequency_penalty: config.frequency_penalty,
+                context_window: config.context_window,
+                context_strategy: config.context_strategy,
+                context_keep_first: config.context_keep_first,
+                tools: Self::encode_tools(&config.tools),
+                max_tool_iterations: config.max_tool_iterations,
+                embedding_model: config.embedding_model,
+                memory_enabled: config.memory_enabled,
+                memory_extraction_prompt: config.memory_extraction_prompt,
+                updated_at: Utc::now().naive_utc(),
+            };
+
+            AgentRepository::update_with_conn(conn, &agent.id, &changeset)
+                .map_err(|e| anyhow!("更新Agent配置失败: {}", e))
+        })
+    }
+
+    // 获取 AI 用户在聊天中生效的配置：Agent 配置合并该聊天的配置覆盖
+    pub fn get_effective_agent(pool: &DbPool, chat_id: &str, agent_user_id: &str) -> ServiceResult<Agent> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            let agent = Self::get_or_create_with_conn(conn, agent_user_id)?;
+            let chat_override = ChatAgentOverrideRepository::get_with_conn(conn, chat_id, agent_user_id)
+                .map_err(|e| anyhow!("获取聊天配置覆盖失败: {}", e))?;
+            Ok(Self::merge_override(agent, chat_override.as_ref()))
+        })
+    }
+
+    // 获取 AI 用户在聊天中生效的配置和该聊天的配置覆盖，用户需为该聊天的参与者
+    pub fn get_chat_agent_config(
+        pool: &DbPool,
+        user_id: &str,
+        chat_id: &str,
+        agent_user_id: &str,
+    ) -> ServiceResult<(Agent, Option<ChatAgentOverride>)> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            Self::check_chat_agent_with_conn(conn, user_id, chat_id, agent_user_id)?;
+            let agent = Self::get_or_create_with_conn(conn, agent_user_id)?;
+            let chat_override = ChatAgentOverrideRepository::get_with_conn(conn, chat_id, agent_user_id)
+                .map_err(|e| anyhow!("获取聊天配置覆盖失败: {}", e))?;
+            Ok((Self::merge_override(agent, chat_override.as_ref()), chat_override))
+        })
+    }
+
+    // 保存 AI 用户在聊天中的配置覆盖，整体替换原有覆盖；合并后的配置需通过与 Agent 配置相同的校验
+    pub fn update_chat_override(
+        pool: &DbPool,
+        user_id: &str,
+        chat_id: &str,
+        agent_user_id: &str,
+        config: ChatAgentOverrideConfig,
+    ) -> ServiceResult<(Agent, Option<ChatAgentOverride>)> {
+        let config = Self::normalize_override(config);
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            Self::check_chat_agent_with_conn(conn, user_id, chat_id, agent_user_id)?;
+            let agent = Self::get_or_create_with_conn(conn, agent_user_id)?;
+
+            let changeset = ChatAgentOverrideChangeset {
+                system_prompt: config.system_prompt,
+                model_name: config.model_name,
+                temperature: config.temperature,
+                top_p: config.top_p,
+                max_tokens: config.max_tokens,
+                stop_sequences: config
+                    .stop_sequences
+                    .map(|stop| serde_json::to_string(&stop).unwrap_or_else(|_| "[]".to_string())),
+                updated_at: Utc::now().naive_utc(),
+            };
+            let chat_override = ChatAgentOverrideRepository::upsert_with_conn(conn, chat_id, agent_user_id, &changeset)
+                .map_err(|e| anyhow!("保存聊天配置覆盖失败: {}", e))?;
+
+            let effective = Self::merge_override(agent, Some(&chat_override));
+            Self::validate(&AgentConfig::from(&effective))?;
+            Ok((effective, Some(chat_override)))
+        })
+    }
+
+    // 删除 AI 用户在聊天中的配置覆盖，恢复使用 Agent 配置
+    pub fn reset_chat_override(
+        pool: &DbPool,
+        user_id: &str,
+        chat_id: &str,
+        agent_user_id: &str,
+    ) -> ServiceResult<Agent> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            Self::check_chat_agent_with_conn(conn, user_id, chat_id, agent_user_id)?;
+            ChatAgentOverrideRepository::delete_with_conn(conn, chat_id, agent_user_id)
+                .map_err(|e| anyhow!("删除聊天配置覆盖失败: {}", e))?;
+            Self::get_or_create_with_conn(conn, agent_user_id)
+        })
+    }
+
+    // 使用已有连接为 AI 用户创建 Agent，未提供配置时使用默认配置
+    pub fn create_with_conn(
+        conn: &mut DbConnection,
+        user_id: &str,
+        config: Option<AgentConfig>,
+    ) -> ServiceResult<Agent> {
+        let config = Self::normalize(config.unwrap_or_default());
+        Self::validate(&config)?;
+
+        let now = Utc::now().naive_utc();
+        let new_agent = NewAgent {
+            id: Uuid::new_v4().to_string(),
+            stop_sequences: Self::encode_stop_sequences(&config.stop_sequences),
+            tools: Self::encode_tools(&config.tools),
+            provider: config.provider,
+            model_name: config.model_name,
+            system_prompt: config.system_prompt,
+            temperature: config.temperature,
+            top_p: config.top_p,
+            top_k: config.top_k,
+            repeat_penalty: config.repeat_penalty,
+            max_tokens: config.max_tokens,
+            presence_penalty: config.presence_penalty,
+            frequency_penalty: config.frequency_penalty,
+            context_window: config.context_window,
+            context_strategy: config.context_strategy,
+            context_keep_first: config.context_keep_first,
+            max_tool_iterations: config.max_tool_iterations,
+            embedding_model: config.embedding_model,
+            memory_enabled: config.memory_enabled,
+            memory_extraction_prompt: config.memory_extraction_prompt,
+            user_id: user_id.to_string(),
+            created_at: now,
+            updated_at: now,
+        };
+
+        AgentRepository::create_with_conn(conn, &new_agent)
+            .map_err(|e| anyhow!("创建Agent失败: {}", e))
+    }
+
+    // Agent 配置对应的采样参数
+    pub fn sampling_options(agent: &Agent) -> SamplingOptions {
+        let stop = Self::decode_stop_sequences(agent.stop_sequences.as_deref());
+        SamplingOptions {
+            temperature: agent.temperature,
+            top_p: agent.top_p,
+            top_k: agent.top_k,
+            max_tokens: agent.max_tokens,
+            stop: (!stop.is_empty()).then_some(stop),
+            repeat_penalty: agent.repeat_penalty,
+            presence_penalty: agent.presence_penalty,
+            frequency_penalty: agent.frequency_penalty,
+            seed: None,
+        }
+    }
+
+    // 解析 JSON 数组格式的停止序列，无法解析时将整个字符串视为一个停止序列
+    pub fn decode_stop_sequences(raw: Option<&str>) -> Vec<String> {
+        match raw.map(str::trim) {
+            None | Some("") => Vec::new(),
+            Some(raw) => serde_json::from_str(raw).unwrap_or_else(|_| vec![raw.to_string()]),
+        }
+    }
+
+    // 解析 JSON 数组格式的工具名称，无法解析时视为不使用工具
+    pub fn decode_tools(raw: Option<&str>) -> Vec<String> {
+        raw.and_then(|raw| serde_json::from_str(raw).ok()).unwrap_or_default()
+    }
+
+    // 一次回复中最多调用工具的轮数
+    pub fn max_tool_iterations(agent: &Agent) -> usize {
+        agent
+            .max_tool_iterations
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(DEFAULT_MAX_TOOL_ITERATIONS as usize)
+    }
+
+    // 生成知识库向量使用的模型
+    pub fn embedding_model(agent: &Agent) -> &str {
+        agent.embedding_model.as_deref().unwrap_or(DEFAULT_EMBEDDING_MODEL)
+    }
+
+    fn encode_tools(tools: &[String]) -> Option<String> {
+        if tools.is_empty() {
+            None
+        } else {
+            serde_json::to_string(tools).ok()
+        }
+    }
+
+    fn encode_stop_sequences(stop_sequences: &[String]) -> Option<String> {
+        if stop_sequences.is_empty() {
+            None
+        } else {
+            serde_json::to_string(stop_sequences).ok()
+        }
+    }
+
+    // 将聊天内的配置覆盖合并到 Agent 配置上，覆盖中为空的字段沿用 Agent 配置
+    fn merge_override(mut agent: Agent, chat_override: Option<&ChatAgentOverride>) -> Agent {
+        if let Some(chat_override) = chat_override {
+            // 覆盖为空字符串时不使用系统提示词
+            if let Some(system_prompt) = &chat_override.system_prompt {
+                agent.system_prompt = Some(system_prompt.clone()).filter(|prompt| !prompt.is_empty());
+            }
+            if let Some(model_name) = &chat_override.model_name {
+                agent.model_name = model_name.clone();
+            }
+            if let Some(stop_sequences) = &chat_override.stop_sequences {
+                agent.stop_sequences = Some(stop_sequences.clone());
+            }
+            agent.temperature = chat_override.temperature.or(agent.temperature);
+            agent.top_p = chat_override.top_p.or(agent.top_p);
+            agent.max_tokens = chat_override.max_tokens.or(agent.max_tokens);
+        }
+        agent
+    }
+
+    // 使用已有连接检查用户和 AI 用户都是该聊天的参与者
+    fn check_chat_agent_with_conn(
+        conn: &mut DbConnection,
+        user_id: &str,
+        chat_id: &str,
+        agent_user_id: &str,
+    ) -> ServiceResult<()> {
+        let is_participant = ChatParticipantRepository::exists_with_conn(conn, chat_id, user_id)
+            .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+        if !is_participant {
+            return Err(anyhow!("聊天不存在或您不是该聊天的参与者"));
+        }
+        let agent_is_participant = ChatParticipantRepository::exists_with_conn(conn, chat_id, agent_user_id)
+            .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+        if !agent_is_participant {
+            return Err(anyhow!("该AI用户不是该聊天的参与者"));
+        }
+        Ok(())
+    }
+
+    // 使用已有连接获取 Agent，不存在时补建，非 AI 用户返回错误
+    fn get_or_create_with_conn(conn: &mut DbConnection, user_id: &str) -> ServiceResult<Agent> {
+        let user = UserRepository::get_with_conn(conn, user_id).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("用户不存在"),
+            e => anyhow!("获取用户信息失败: {}", e),
+        })?;
+        if !user.is_ai {
+            return Err(anyhow!("该用户不是AI用户"));
+        }
+
+        match AgentRepository::get_by_user_id_with_conn(conn, user_id) {
+            Ok(agent) => Ok(agent),
+            Err(RepositoryError::NotFound) => Self::create_with_conn(conn, user_id, None),
+            Err(e) => Err(anyhow!("获取Agent配置失败: {}", e)),
+        }
+    }
+
+    // 去除首尾空白，空字符串视为未设置
+    fn normalize(mut config: AgentConfig) -> AgentConfig {
+        config.provider = config.provider.trim().to_string();
+        if config.provider.is_empty() {
+            config.provider = DEFAULT_PROVIDER.to_string();
+        }
+        config.model_name = config.model_name.trim().to_string();
+        config.system_prompt = config
+            .system_prompt
+            .map(|prompt| prompt.trim().to_string())
+            .filter(|prompt| !prompt.is_empty());
+        config.stop_sequences.retain(|stop| !stop.is_empty());
+        let mut tools: Vec<String> = Vec::new();
+        for name in config.tools.iter().map(|name| name.trim()).filter(|name| !name.is_empty()) {
+            if !tools.iter().any(|existing| existing == name) {
+                tools.push(name.to_string());
+            }
+        }
+        config.tools = tools;
+        config.embedding_model = config
+            .embedding_model
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        config.memory_extraction_prompt = config
+            .memory_extraction_prompt
+            .map(|prompt| prompt.trim().to_string())
+            .filter(|prompt| !prompt.is_empty());
+        config.context_strategy = config.context_strategy.trim().to_string();
+        if config.context_strategy.is_empty() {
+            config.context_strategy = ContextStrategy::SLIDING_WINDOW.to_string();
+        }
+        config
+    }
+
+    // 去除首尾空白；系统提示词为空字符串时保留，表示在该聊天中不使用系统提示词，模型名称为空字符串视为不覆盖；
+    // 停止序列去除空字符串后保留，空数组表示不使用停止序列
+    fn normalize_override(mut config: ChatAgentOverrideConfig) -> ChatAgentOverrideConfig {
+        config.system_prompt = config.system_prompt.map(|prompt| prompt.trim().to_string());
+        config.model_name = config
+            .model_name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        if let Some(stop_sequences) = &mut config.stop_sequences {
+            stop_sequences.retain(|stop| !stop.is_empty());
+        }
+        config
+    }
+
+    // 校验参数取值范围
+    fn validate(config: &AgentConfig) -> ServiceResult<()> {
+        if config.model_name.is_empty() {
+            return Err(anyhow!("模型名称不能为空"));
+        }
+        if let Some(prompt) = &config.system_prompt {
+            if prompt.chars().count() > MAX_SYSTEM_PROMPT_CHARS {
+                return Err(anyhow!("系统提示词不能超过{}个字符", MAX_SYSTEM_PROMPT_CHARS));
+            }
+        }
+        if let Some(prompt) = &config.memory_extraction_prompt {
+            if prompt.chars().count() > MAX_SYSTEM_PROMPT_CHARS {
+                return Err(anyhow!("记忆提取提示词不能超过{}个字符", MAX_SYSTEM_PROMPT_CHARS));
+            }
+        }
+
+        check_range("temperature", config.temperature, TEMPERATURE_RANGE)?;
+        check_range("top_p", config.top_p, TOP_P_RANGE)?;
+        check_range("top_k", config.top_k, TOP_K_RANGE)?;
+        check_range("repeat_penalty", config.repeat_penalty, REPEAT_PENALTY_RANGE)?;
+        check_range("presence_penalty", config.presence_penalty, PENALTY_RANGE)?;
+        check_range("frequency_penalty", config.frequency_penalty, PENALTY_RANGE)?;
+        check_range("max_tokens", config.max_tokens, MAX_TOKENS_RANGE)?;
+
+        check_range("context_window", config.context_window, CONTEXT_WINDOW_RANGE)?;
+        check_range("context_keep_first", config.context_keep_first, CONTEXT_KEEP_FIRST_RANGE)?;
+        if let (Some(context_window), Some(max_tokens)) = (config.context_window, config.max_tokens) {
+            if max_tokens >= context_window {
+                return Err(anyhow!("max_tokens 必须小于上下文长度 {}", context_window));
+            }
+        }
+        if ContextStrategy::parse(&config.context_strategy, config.context_keep_first).is_none() {
+            return Err(anyhow!("未知的上下文策略: {}", config.context_strategy));
+        }
+
+        if config.stop_sequences.len() > MAX_STOP_SEQUENCES {
+            return Err(anyhow!("停止序列不能超过{}个", MAX_STOP_SEQUENCES));
+        }
+        if config.stop_sequences.iter().any(|stop| stop.chars().count() > MAX_STOP_SEQUENCE_CHARS) {
+            return Err(anyhow!("单个停止序列不能超过{}个字符", MAX_STOP_SEQUENCE_CHARS));
+        }
+
+        if let Some(name) = config.tools.iter().find(|name| !BUILTIN_TOOL_NAMES.contains(&name.as_str())) {
+            return Err(anyhow!("未知的工具: {}", name));
+        }
+        check_range("max_tool_iterations", config.max_tool_iterations, MAX_TOOL_ITERATIONS_RANGE)?;
+
+        Ok(())
+    }
+}
+
+// 检查可选参数是否在闭区间内，未设置时跳过
+fn check_range<T: PartialOrd + std::fmt::Display + Copy>(
+    name: &str,
+    value: Option<T>,
+    (min, max): (T, T),
+) -> ServiceResult<()> {
+    match value {
+        // NaN 与任何值比较都为 false，同样视为越界
+        Some(value) if !(value >= min && value <= max) => {
+            Err(anyhow!("{} 必须在 {} 到 {} 之间，当前为 {}", name, min, max, value))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ChatType;
+    use crate::repositories::chat_repository::ChatRepository;
+    use crate::test_support::create_test_pool;
+
+    #[test]
+    fn empty_chat_system_prompt_disables_agent_prompt() {
+        let pool = create_test_pool();
+        let user = UserRepository::create(&pool, "用户".to_string(), None, false).unwrap();
+        let ai = UserRepository::create(&pool, "助手".to_string(), None, true).unwrap();
+        let chat = ChatRepository::create(&pool, "聊天", "", ChatType::Direct).unwrap();
+        ChatParticipantRepository::create(&pool, &chat.id, &user.id).unwrap();
+        ChatParticipantRepository::create(&pool, &chat.id, &ai.id).unwrap();
+
+        let config = AgentConfig { system_prompt: Some("你是助手".to_string()), ..Default::default() };
+        AgentService::update_agent_config(&pool, &ai.id, config).unwrap();
+
+        // 未覆盖系统提示词时沿用 Agent 配置
+        let overrides = ChatAgentOverrideConfig { temperature: Some(0.5), ..Default::default() };
+        let (effective, _) = AgentService::update_chat_override(&pool, &user.id, &chat.id, &ai.id, overrides).unwrap();
+        assert_eq!(effective.system_prompt.as_deref(), Some("你是助手"));
+
+        let overrides = ChatAgentOverrideConfig { system_prompt: Some("  ".to_string()), ..Default::default() };
+        let (effective, chat_override) =
+            AgentService::update_chat_override(&pool, &user.id, &chat.id, &ai.id, overrides).unwrap();
+        assert_eq!(effective.system_prompt, None);
+        assert_eq!(chat_override.unwrap().system_prompt.as_deref(), Some(""));
+        assert_eq!(AgentService::get_effective_agent(&pool, &chat.id, &ai.id).unwrap().system_prompt, None);
+    }
+}
@@ -0,0 +1,97 @@
+// AI 回复任务服务
+use anyhow::anyhow;
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::db::{self, DbPool};
+use crate::models::{AiJob, AiJobStatus, NewAiJob};
+use crate::repositories::ai_job_repository::AiJobRepository;
+use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+use crate::repositories::chat_repository::ChatRepository;
+use crate::repositories::error::RepositoryError;
+use crate::repositories::message_repository::MessageRepository;
+use crate::repositories::user_repository::UserRepository;
+use super::ServiceResult;
+
+pub struct AiJobService;
+
+impl AiJobService {
+    // 创建待处理的 AI 回复任务
+    //
+    // 校验聊天存在且未删除、回复者为该聊天中的 AI 用户，触发消息（如有）属于该聊天；
+    // 重新生成回复时 regenerated_message_id 为原回复，新回复接在触发消息（原回复的上一条消息）之后
+    pub fn create_job(
+        pool: &DbPool,
+        chat_id: &str,
+        agent_user_id: &str,
+        trigger_message_id: Option<&str>,
+        regenerated_message_id: Option<&str>,
+    ) -> ServiceResult<AiJob> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        db::immediate_transaction(&mut conn, |conn| {
+            // 1. 检查聊天是否存在且未删除
+            let chat = ChatRepository::get_with_conn(conn, chat_id).map_err(|e| match e {
+                RepositoryError::NotFound => anyhow!("聊天不存在"),
+                e => anyhow!("获取聊天信息失败: {}", e),
+            })?;
+            if chat.deleted_at.is_some() {
+                return Err(anyhow!("聊天已删除"));
+            }
+
+            // 2. 检查回复者是否为该聊天中的 AI 用户
+            let agent_user = UserRepository::get_with_conn(conn, agent_user_id).map_err(|e| match e {
+                RepositoryError::NotFound => anyhow!("AI用户不存在"),
+                e => anyhow!("获取用户信息失败: {}", e),
+            })?;
+            if !agent_user.is_ai {
+                return Err(anyhow!("该用户不是AI用户"));
+            }
+            let is_participant = ChatParticipantRepository::exists_with_conn(conn, chat_id, agent_user_id)
+                .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+            if !is_participant {
+                return Err(anyhow!("AI用户不是该聊天的参与者"));
+            }
+
+            // 3. 检查触发消息是否属于该聊天
+            if let Some(message_id) = trigger_message_id {
+                let message = MessageRepository::get_with_conn(conn, message_id).map_err(|e| match e {
+                    RepositoryError::NotFound => anyhow!("触发消息不存在"),
+                    e => anyhow!("获取消息失败: {}", e),
+                })?;
+                if message.chat_id != chat_id {
+                    return Err(anyhow!("触发消息不属于该聊天"));
+                }
+            }
+
+            // 4. 创建任务
+            let now = Utc::now().naive_utc();
+            let new_job = NewAiJob {
+                id: Uuid::new_v4().to_string(),
+                chat_id: chat_id.to_string(),
+                agent_user_id: agent_user_id.to_string(),
+                trigger_message_id: trigger_message_id.map(str::to_string),
+                regenerated_message_id: regenerated_message_id.map(str::to_string),
+                status: AiJobStatus::Pending.as_str().to_string(),
+                created_at: now,
+                updated_at: now,
+            };
+            AiJobRepository::create_with_conn(conn, &new_job)
+                .map_err(|e| anyhow!("创建AI回复任务失败: {}", e))
+        })
+    }
+
+    // 获取任务
+    pub fn get_job(pool: &DbPool, id: &str) -> ServiceResult<AiJob> {
+        AiJobRepository::get(pool, id).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("AI回复任务不存在"),
+            e => anyhow!("获取AI回复任务失败: {}", e),
+        })
+    }
+
+    // 获取聊天中的任务，active_only 为 true 时只返回未结束的任务
+    pub fn get_chat_jobs(pool: &DbPool, chat_id: &str, active_only: bool) -> ServiceResult<Vec<AiJob>> {
+        AiJobRepository::get_by_chat_id(pool, chat_id, active_only)
+            .map_err(|e| anyhow!("获取AI回复任务失败: {}", e))
+    }
+}
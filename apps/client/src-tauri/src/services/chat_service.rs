@@ -1,52 +1,557 @@
 // 聊天相关服务
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use chrono::Utc;
 use diesel::prelude::*;
-use crate::db::DbPool;
+use uuid::Uuid;
+use crate::db::{self, DbConnection, DbPool};
+use crate::models::{Chat, ChatSettingsChangeset, ChatType, Message, MessageStatus, User};
+use crate::repositories::chat_agent_override_repository::ChatAgentOverrideRepository;
+use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+use crate::repositories::chat_repository::{ChatListEntry, ChatRepository};
 use crate::repositories::error::RepositoryError;
-use crate::schema::chats;
+use crate::repositories::message_repository::MessageRepository;
+use crate::repositories::user_contact_repository::UserContactRepository;
+use crate::repositories::user_repository::UserRepository;
+use super::ServiceResult;
+
+// 群聊头像最多拼接的成员头像数量
+const MAX_GROUP_AVATARS: usize = 9;
+
+// 聊天列表每页的最大条数
+const MAX_CHAT_PAGE_SIZE: i64 = 200;
 
 pub struct ChatService;
 
 impl ChatService {
-    // 更新聊天的未读消息数
-    pub fn update_unread_count(
-        pool: &DbPool, 
-        chat_id: &str, 
-        increment: bool
-    ) -> Result<(), RepositoryError> {
-        let mut conn = pool.get().map_err(RepositoryError::ConnectionError)?;
-        
-        // 开始事务
+    // 将用户在聊天中的已读位置推进到指定消息，未指定时推进到最新消息
+    //
+    // 返回标记后该聊天剩余的未读消息数；已读位置只前进不后退
+    pub fn mark_chat_read(
+        pool: &DbPool,
+        user_id: &str,
+        chat_id: &str,
+        up_to_message_id: Option<&str>,
+    ) -> ServiceResult<i64> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
         conn.transaction(|conn| {
-            // 首先获取当前未读消息数
-            let current_unread: i32 = chats::table
-                .filter(chats::id.eq(chat_id))
-                .select(chats::unread_count)
-                .first(conn)?;
-            
-            // 根据increment参数决定是增加还是重置未读消息数
-            let new_unread = if increment {
-                current_unread + 1
-            } else {
-                0 // 重置为0
+            let is_participant = ChatParticipantRepository::exists_with_conn(conn, chat_id, user_id)
+                .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+            if !is_participant {
+                return Err(anyhow!("聊天不存在或您不是该聊天的参与者"));
+            }
+
+            let message = match up_to_message_id {
+                Some(message_id) => {
+                    let message = MessageRepository::get_with_conn(conn, message_id).map_err(|e| match e {
+                        RepositoryError::NotFound => anyhow!("消息不存在"),
+                        e => anyhow!("获取消息失败: {}", e),
+                    })?;
+                    if message.chat_id != chat_id {
+                        return Err(anyhow!("消息不属于该聊天"));
+                    }
+                    Some(message)
+                }
+                None => MessageRepository::get_latest_with_conn(conn, chat_id)
+                    .map_err(|e| anyhow!("获取最新消息失败: {}", e))?,
             };
-            
-            // 更新未读消息数
-            diesel::update(chats::table)
-                .filter(chats::id.eq(chat_id))
-                .set(chats::unread_count.eq(new_unread))
-                .execute(conn)?;
-            
+
+            if let Some(message) = message {
+                ChatParticipantRepository::mark_read_with_conn(conn, chat_id, user_id, &message.id, message.created_at)
+                    .map_err(|e| anyhow!("更新已读位置失败: {}", e))?;
+            }
+
+            let unread = ChatParticipantRepository::count_unread_with_conn(conn, user_id, Some(&[chat_id]))
+                .map_err(|e| anyhow!("统计未读消息数失败: {}", e))?;
+            Ok(unread.first().map(|row| row.unread).unwrap_or(0))
+        })
+    }
+
+    // 获取用户在所有聊天中的未读消息总数，设置了免打扰的聊天不计入
+    pub fn get_total_unread(pool: &DbPool, user_id: &str) -> ServiceResult<i64> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        ChatParticipantRepository::count_total_unread_with_conn(&mut conn, user_id)
+            .map_err(|e| anyhow!("统计未读消息数失败: {}", e))
+    }
+
+    // 分页获取用户的聊天列表及聊天总数，include_archived 为 false 时不包含已归档的聊天
+    pub fn get_user_chat_list(
+        pool: &DbPool,
+        user_id: &str,
+        include_archived: bool,
+        offset: i64,
+        limit: i64,
+    ) -> ServiceResult<(Vec<ChatListEntry>, i64)> {
+        let entries = ChatRepository::get_user_chat_list(pool, user_id, include_archived, offset.max(0), limit.clamp(1, MAX_CHAT_PAGE_SIZE))
+            .map_err(|e| anyhow!("获取聊天列表失败: {}", e))?;
+        let total = ChatRepository::count_user_chats(pool, user_id, include_archived)
+            .map_err(|e| anyhow!("统计聊天数量失败: {}", e))?;
+        Ok((entries, total))
+    }
+
+    // 获取用户参与的单个聊天的列表项
+    pub fn get_user_chat_entry(pool: &DbPool, user_id: &str, chat_id: &str) -> ServiceResult<ChatListEntry> {
+        ChatRepository::get_user_chat_entry(pool, user_id, chat_id).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("聊天不存在或您不是该聊天的参与者"),
+            e => anyhow!("获取聊天信息失败: {}", e),
+        })
+    }
+
+    // 创建与联系人的单聊，如果已存在则直接返回已有聊天
+    pub fn create_direct_chat(
+        pool: &DbPool,
+        user_id: &str,
+        contact_id: &str,
+    ) -> ServiceResult<Chat> {
+        if user_id == contact_id {
+            return Err(anyhow!("不能与自己创建聊天"));
+        }
+
+        // 检查是否为联系人
+        let is_contact = UserContactRepository::exists(pool, user_id, contact_id)
+            .map_err(|e| anyhow!("检查联系人关系失败: {}", e))?;
+        if !is_contact {
+            return Err(anyhow!("该用户不是您的联系人"));
+        }
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            // 1. 复用已有的单聊
+            let existing_chat_id = ChatParticipantRepository::find_direct_chat_id_with_conn(conn, user_id, contact_id)
+                .map_err(|e| anyhow!("查询已有单聊失败: {}", e))?;
+            if let Some(chat_id) = existing_chat_id {
+                return ChatRepository::get_with_conn(conn, &chat_id)
+                    .map_err(|e| anyhow!("获取聊天信息失败: {}", e));
+            }
+
+            // 2. 创建聊天，名称和头像取自联系人
+            let contact = UserRepository::get_with_conn(conn, contact_id)
+                .map_err(|e| anyhow!("获取联系人信息失败: {}", e))?;
+            let avatar_urls = Self::encode_avatar_urls(std::slice::from_ref(&contact))?;
+            let chat = ChatRepository::create_with_conn(conn, &contact.name, &avatar_urls, ChatType::Direct)
+                .map_err(|e| anyhow!("创建聊天失败: {}", e))?;
+
+            // 3. 添加双方为参与者
+            ChatParticipantRepository::create_with_conn(conn, &chat.id, user_id)
+                .map_err(|e| anyhow!("添加聊天参与者失败: {}", e))?;
+            ChatParticipantRepository::create_with_conn(conn, &chat.id, contact_id)
+                .map_err(|e| anyhow!("添加聊天参与者失败: {}", e))?;
+
+            Ok(chat)
+        })
+    }
+
+    // 创建群聊，创建者自动加入
+    pub fn create_group_chat(
+        pool: &DbPool,
+        user_id: &str,
+        name: &str,
+        participant_ids: &[String],
+    ) -> ServiceResult<Chat> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("群聊名称不能为空"));
+        }
+
+        // 去重并排除创建者自己
+        let mut member_ids: Vec<&str> = Vec::new();
+        for participant_id in participant_ids {
+            if participant_id != user_id && !member_ids.contains(&participant_id.as_str()) {
+                member_ids.push(participant_id);
+            }
+        }
+        if member_ids.is_empty() {
+            return Err(anyhow!("群聊至少需要一名其他成员"));
+        }
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            // 1. 校验成员是否存在
+            let mut members = Vec::with_capacity(member_ids.len());
+            for member_id in &member_ids {
+                let member = UserRepository::get_with_conn(conn, member_id).map_err(|e| match e {
+                    RepositoryError::NotFound => anyhow!("成员不存在: {}", member_id),
+                    e => anyhow!("获取成员信息失败: {}", e),
+                })?;
+                members.push(member);
+            }
+
+            // 2. 创建聊天
+            let avatar_urls = Self::encode_avatar_urls(&members)?;
+            let chat = ChatRepository::create_with_conn(conn, name, &avatar_urls, ChatType::Group)
+                .map_err(|e| anyhow!("创建聊天失败: {}", e))?;
+
+            // 3. 添加创建者和所有成员为参与者
+            ChatParticipantRepository::create_with_conn(conn, &chat.id, user_id)
+                .map_err(|e| anyhow!("添加聊天参与者失败: {}", e))?;
+            for member in &members {
+                ChatParticipantRepository::create_with_conn(conn, &chat.id, &member.id)
+                    .map_err(|e| anyhow!("添加聊天参与者失败: {}", e))?;
+            }
+
+            Ok(chat)
+        })
+    }
+
+    // 从指定消息分出新聊天
+    //
+    // 新聊天沿用来源聊天的名称、头像、全部参与者和 AI 配置覆盖，并复制从第一条消息到指定消息的整条路径：
+    // 复制的消息使用新ID，保留原有的发送者、内容和时间，消息之间的接续和回复关系映射到新ID，
+    // 回复路径之外的消息时不保留回复关系，路径上已删除的消息复制后仍为已删除；复制的消息对所有参与者都视为已读
+    pub fn fork_chat(pool: &DbPool, user_id: &str, message_id: &str) -> ServiceResult<Chat> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        db::immediate_transaction(&mut conn, |conn| {
+            // 1. 校验消息和参与者身份
+            let message = MessageRepository::get_with_conn(conn, message_id).map_err(|e| match e {
+                RepositoryError::NotFound => anyhow!("消息不存在"),
+                e => anyhow!("获取消息失败: {}", e),
+            })?;
+            if message.deleted_at.is_some() {
+                return Err(anyhow!("消息已删除"));
+            }
+            let is_participant = ChatParticipantRepository::exists_with_conn(conn, &message.chat_id, user_id)
+                .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+            if !is_participant {
+                return Err(anyhow!("聊天不存在或您不是该聊天的参与者"));
+            }
+
+            let path = MessageRepository::get_ancestors_with_conn(conn, &message.id)
+                .map_err(|e| anyhow!("获取消息历史失败: {}", e))?;
+            if path.iter().any(|m| m.status == MessageStatus::Streaming.as_str()) {
+                return Err(anyhow!("消息正在生成中，无法分出聊天"));
+            }
+
+            // 2. 创建聊天并复制参与者
+            let source = ChatRepository::get_with_conn(conn, &message.chat_id)
+                .map_err(|e| anyhow!("获取聊天信息失败: {}", e))?;
+            if source.deleted_at.is_some() {
+                return Err(anyhow!("聊天已删除"));
+            }
+            let fork = ChatRepository::create_fork_with_conn(conn, &source, &message.id)
+                .map_err(|e| anyhow!("创建聊天失败: {}", e))?;
+            let participants = ChatParticipantRepository::get_by_chat_id_with_conn(conn, &source.id)
+                .map_err(|e| anyhow!("获取聊天参与者失败: {}", e))?;
+            for participant in &participants {
+                ChatParticipantRepository::create_with_conn(conn, &fork.id, &participant.user_id)
+                    .map_err(|e| anyhow!("添加聊天参与者失败: {}", e))?;
+            }
+            ChatAgentOverrideRepository::copy_to_chat_with_conn(conn, &source.id, &fork.id)
+                .map_err(|e| anyhow!("复制聊天配置覆盖失败: {}", e))?;
+
+            // 3. 复制消息路径
+            let copies = Self::copy_message_path(&path, &fork.id);
+            MessageRepository::insert_copies_with_conn(conn, &copies)
+                .map_err(|e| anyhow!("复制消息失败: {}", e))?;
+
+            if let Some(last) = copies.last() {
+                for participant in &participants {
+                    ChatParticipantRepository::mark_read_with_conn(
+                        conn,
+                        &fork.id,
+                        &participant.user_id,
+                        &last.id,
+                        last.created_at,
+                    )
+                    .map_err(|e| anyhow!("更新已读位置失败: {}", e))?;
+                }
+            }
+
+            ChatRepository::get_with_conn(conn, &fork.id).map_err(|e| anyhow!("获取聊天信息失败: {}", e))
+        })
+    }
+
+    // 修改用户对聊天的个人设置，只影响当前用户，其他参与者不受影响
+    //
+    // 自定义名称去除首尾空白，为空时清除自定义名称
+    pub fn update_chat_settings(
+        pool: &DbPool,
+        user_id: &str,
+        chat_id: &str,
+        mut changes: ChatSettingsChangeset,
+    ) -> ServiceResult<()> {
+        if let Some(Some(display_name)) = &changes.display_name {
+            let display_name = display_name.trim();
+            changes.display_name = Some((!display_name.is_empty()).then(|| display_name.to_string()));
+        }
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            let chat = Self::get_participant_chat_with_conn(conn, user_id, chat_id)?;
+            if chat.deleted_at.is_some() {
+                return Err(anyhow!("聊天已删除"));
+            }
+            ChatParticipantRepository::update_settings_with_conn(conn, chat_id, user_id, &changes)
+                .map_err(|e| anyhow!("更新聊天设置失败: {}", e))?;
             Ok(())
         })
     }
-    
-    // 标记聊天为已读
-    pub fn mark_as_read(pool: &DbPool, chat_id: &str) -> Result<(), RepositoryError> {
-        Self::update_unread_count(pool, chat_id, false)
+
+    // 删除聊天，聊天移入回收站，超过保留期限后由 TrashService 彻底清除
+    //
+    // 聊天对所有参与者都不再显示，调用方应同时取消该聊天中未结束的 AI 回复任务
+    pub fn delete_chat(pool: &DbPool, user_id: &str, chat_id: &str) -> ServiceResult<()> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            let chat = Self::get_participant_chat_with_conn(conn, user_id, chat_id)?;
+            if chat.deleted_at.is_some() {
+                return Ok(());
+            }
+            ChatRepository::set_deleted_with_conn(conn, chat_id, Some(Utc::now().naive_utc()))
+                .map_err(|e| anyhow!("删除聊天失败: {}", e))
+        })
+    }
+
+    // 从回收站恢复聊天
+    pub fn restore_chat(pool: &DbPool, user_id: &str, chat_id: &str) -> ServiceResult<()> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            Self::get_participant_chat_with_conn(conn, user_id, chat_id)?;
+            ChatRepository::set_deleted_with_conn(conn, chat_id, None)
+                .map_err(|e| anyhow!("恢复聊天失败: {}", e))
+        })
+    }
+
+    // 获取用户参与的已删除的聊天，按删除时间倒序
+    pub fn get_deleted_chats(pool: &DbPool, user_id: &str) -> ServiceResult<Vec<ChatListEntry>> {
+        ChatRepository::get_user_deleted_chat_list(pool, user_id)
+            .map_err(|e| anyhow!("获取已删除的聊天失败: {}", e))
+    }
+
+    // 使用已有连接获取聊天并校验用户是该聊天的参与者
+    fn get_participant_chat_with_conn(
+        conn: &mut DbConnection,
+        user_id: &str,
+        chat_id: &str,
+    ) -> ServiceResult<Chat> {
+        let is_participant = ChatParticipantRepository::exists_with_conn(conn, chat_id, user_id)
+            .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+        if !is_participant {
+            return Err(anyhow!("聊天不存在或您不是该聊天的参与者"));
+        }
+        ChatRepository::get_with_conn(conn, chat_id).map_err(|e| anyhow!("获取聊天信息失败: {}", e))
     }
-    
-    // 增加聊天的未读消息数
-    pub fn increment_unread(pool: &DbPool, chat_id: &str) -> Result<(), RepositoryError> {
-        Self::update_unread_count(pool, chat_id, true)
+
+    // 为消息路径生成属于新聊天的副本，路径按时间升序，每条消息接在前一条之后
+    fn copy_message_path(path: &[Message], chat_id: &str) -> Vec<Message> {
+        let new_ids: HashMap<&str, String> = path
+            .iter()
+            .map(|message| (message.id.as_str(), Uuid::new_v4().to_string()))
+            .collect();
+
+        path.iter()
+            .enumerate()
+            .map(|(index, message)| Message {
+                id: new_ids[message.id.as_str()].clone(),
+                content: message.content.clone(),
+                created_at: message.created_at,
+                updated_at: message.updated_at,
+                chat_id: chat_id.to_string(),
+                sender_id: message.sender_id.clone(),
+                status: message.status.clone(),
+                error_message: message.error_message.clone(),
+                reply_to_id: message
+                    .reply_to_id
+                    .as_deref()
+                    .and_then(|reply_to_id| new_ids.get(reply_to_id).cloned()),
+                parent_id: index.checked_sub(1).map(|prev| new_ids[path[prev].id.as_str()].clone()),
+                active_child_id: path.get(index + 1).map(|next| new_ids[next.id.as_str()].clone()),
+                is_active: true,
+                deleted_at: message.deleted_at,
+                kind: message.kind.clone(),
+                tool_calls: message.tool_calls.clone(),
+                tool_call_id: message.tool_call_id.clone(),
+            })
+            .collect()
+    }
+
+    // 将成员头像编码为 chats.avatar_urls 存储的 JSON 数组
+    fn encode_avatar_urls(members: &[User]) -> ServiceResult<String> {
+        let avatar_urls: Vec<&str> = members
+            .iter()
+            .filter_map(|member| member.avatar_url.as_deref())
+            .take(MAX_GROUP_AVATARS)
+            .collect();
+        serde_json::to_string(&avatar_urls).map_err(|e| anyhow!("序列化头像列表失败: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::message_service::MessageService;
+    use crate::test_support::{create_test_chat, create_test_pool, TestChat};
+
+    // 创建包含两位真人用户和 AI 用户的群聊，返回群聊和第二位真人用户
+    fn create_group(pool: &DbPool, human: &User, ai: &User) -> (Chat, User) {
+        let other = UserRepository::create(pool, "同事".to_string(), None, false).unwrap();
+        let chat = ChatRepository::create(pool, "群聊", "", ChatType::Group).unwrap();
+        for user_id in [&human.id, &other.id, &ai.id] {
+            ChatParticipantRepository::create(pool, &chat.id, user_id).unwrap();
+        }
+        (chat, other)
+    }
+
+    fn send(pool: &DbPool, chat_id: &str, sender_id: &str, content: &str) -> Message {
+        MessageService::send_message(pool, chat_id, sender_id, content, None).unwrap()
+    }
+
+    fn unread(pool: &DbPool, chat_id: &str, user_id: &str) -> i64 {
+        let mut conn = pool.get().unwrap();
+        ChatParticipantRepository::count_unread_with_conn(&mut conn, user_id, Some(&[chat_id]))
+            .unwrap()
+            .first()
+            .map(|row| row.unread)
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn unread_counts_are_kept_per_participant() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let (group, other) = create_group(&pool, &human, &ai);
+
+        send(&pool, &group.id, &ai.id, "大家好");
+        send(&pool, &group.id, &human.id, "你好");
+        send(&pool, &group.id, &ai.id, "有什么可以帮忙的");
+        send(&pool, &chat.id, &ai.id, "单聊消息");
+
+        // 发送消息即已读此前的消息，自己发送的消息不计入未读
+        assert_eq!(unread(&pool, &group.id, &human.id), 1);
+        assert_eq!(unread(&pool, &group.id, &other.id), 3);
+        assert_eq!(ChatService::get_total_unread(&pool, &human.id).unwrap(), 2);
+
+        // 一位参与者标记已读不影响其他参与者
+        assert_eq!(ChatService::mark_chat_read(&pool, &other.id, &group.id, None).unwrap(), 0);
+        assert_eq!(unread(&pool, &group.id, &other.id), 0);
+        assert_eq!(unread(&pool, &group.id, &human.id), 1);
+        assert_eq!(ChatService::get_total_unread(&pool, &other.id).unwrap(), 0);
+
+        send(&pool, &group.id, &human.id, "谢谢");
+        assert_eq!(unread(&pool, &group.id, &other.id), 1);
+        assert_eq!(unread(&pool, &group.id, &human.id), 0);
+        assert_eq!(ChatService::get_total_unread(&pool, &human.id).unwrap(), 1);
+    }
+
+    #[test]
+    fn mark_chat_read_only_moves_forward() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let messages: Vec<Message> = (0..3).map(|index| send(&pool, &chat.id, &ai.id, &format!("消息{}", index))).collect();
+        assert_eq!(unread(&pool, &chat.id, &human.id), 3);
+
+        assert_eq!(ChatService::mark_chat_read(&pool, &human.id, &chat.id, Some(&messages[1].id)).unwrap(), 1);
+        // 已读位置不会退回到更早的消息
+        assert_eq!(ChatService::mark_chat_read(&pool, &human.id, &chat.id, Some(&messages[0].id)).unwrap(), 1);
+        assert_eq!(ChatService::mark_chat_read(&pool, &human.id, &chat.id, None).unwrap(), 0);
+
+        let participant = ChatParticipantRepository::get_by_chat_id(&pool, &chat.id)
+            .unwrap()
+            .into_iter()
+            .find(|participant| participant.user_id == human.id)
+            .unwrap();
+        assert_eq!(participant.last_read_message_id.as_deref(), Some(messages[2].id.as_str()));
+        assert_eq!(participant.last_read_at, Some(messages[2].created_at));
+    }
+
+    #[test]
+    fn mark_chat_read_rejects_foreign_messages_and_non_participants() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let (group, other) = create_group(&pool, &human, &ai);
+        let group_message = send(&pool, &group.id, &ai.id, "群聊消息");
+        send(&pool, &chat.id, &ai.id, "单聊消息");
+
+        let error = ChatService::mark_chat_read(&pool, &human.id, &chat.id, Some(&group_message.id)).unwrap_err();
+        assert!(error.to_string().contains("消息不属于该聊天"));
+        let error = ChatService::mark_chat_read(&pool, &other.id, &chat.id, None).unwrap_err();
+        assert!(error.to_string().contains("不是该聊天的参与者"));
+        let error = ChatService::mark_chat_read(&pool, &human.id, &chat.id, Some("不存在")).unwrap_err();
+        assert!(error.to_string().contains("消息不存在"));
+        assert_eq!(unread(&pool, &chat.id, &human.id), 1);
+    }
+
+    #[test]
+    fn chat_list_page_size_is_clamped() {
+        let pool = create_test_pool();
+        let user = UserRepository::create(&pool, "用户".to_string(), None, false).unwrap();
+        for name in ["甲", "乙", "丙"] {
+            let chat = ChatRepository::create(&pool, name, "", ChatType::Group).unwrap();
+            ChatParticipantRepository::create(&pool, &chat.id, &user.id).unwrap();
+        }
+
+        let (entries, total) = ChatService::get_user_chat_list(&pool, &user.id, false, 0, 0).unwrap();
+        assert_eq!((entries.len(), total), (1, 3));
+        let (entries, total) = ChatService::get_user_chat_list(&pool, &user.id, false, -5, i64::MAX).unwrap();
+        assert_eq!((entries.len(), total), (3, 3));
+    }
+
+    #[test]
+    fn fork_copies_message_path_with_new_ids_and_original_times() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let first = send(&pool, &chat.id, &human.id, "第一条");
+        send(&pool, &chat.id, &ai.id, "第二条");
+        MessageService::send_message(&pool, &chat.id, &human.id, "第三条", Some(&first.id)).unwrap();
+        send(&pool, &chat.id, &ai.id, "分出之后的消息");
+        let mut originals = MessageService::get_chat_messages(&pool, &chat.id).unwrap();
+        originals.truncate(3);
+
+        let fork = ChatService::fork_chat(&pool, &human.id, &originals[2].id).unwrap();
+        assert_ne!(fork.id, chat.id);
+        assert_eq!(fork.name, chat.name);
+        assert_eq!(fork.forked_from_chat_id.as_deref(), Some(chat.id.as_str()));
+        assert_eq!(fork.forked_from_message_id.as_deref(), Some(originals[2].id.as_str()));
+        assert_eq!(fork.last_message.as_deref(), Some("第三条"));
+
+        let copies = MessageService::get_chat_messages(&pool, &fork.id).unwrap();
+        assert_eq!(copies.len(), 3);
+        for (index, (copy, original)) in copies.iter().zip(&originals).enumerate() {
+            assert_ne!(copy.id, original.id);
+            assert_eq!(copy.chat_id, fork.id);
+            assert_eq!(copy.content, original.content);
+            assert_eq!(copy.sender_id, original.sender_id);
+            assert_eq!((copy.created_at, copy.updated_at), (original.created_at, original.updated_at));
+            assert_eq!(copy.parent_id.as_deref(), index.checked_sub(1).map(|prev| copies[prev].id.as_str()));
+            assert_eq!(copy.active_child_id.as_deref(), copies.get(index + 1).map(|next| next.id.as_str()));
+        }
+        assert_eq!(copies[2].reply_to_id.as_deref(), Some(copies[0].id.as_str()));
+
+        // 来源聊天不受影响，分出的聊天对所有参与者都已读
+        assert_eq!(MessageService::get_chat_messages(&pool, &chat.id).unwrap().len(), 4);
+        let members: Vec<String> = ChatParticipantRepository::get_by_chat_id(&pool, &fork.id)
+            .unwrap()
+            .into_iter()
+            .map(|participant| participant.user_id)
+            .collect();
+        assert_eq!(members.len(), 2);
+        for user_id in [&human.id, &ai.id] {
+            assert!(members.contains(user_id));
+            assert_eq!(unread(&pool, &fork.id, user_id), 0);
+        }
+    }
+
+    #[test]
+    fn fork_is_rolled_back_when_copying_messages_fails() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        send(&pool, &chat.id, &human.id, "你好");
+        let reply = send(&pool, &chat.id, &ai.id, "你好，有什么可以帮忙的");
+        let mut conn = pool.get().unwrap();
+        diesel::sql_query(format!(
+            "CREATE TRIGGER fail_message_copies BEFORE INSERT ON messages WHEN NEW.chat_id <> '{}' \
+             BEGIN SELECT RAISE(ABORT, '复制失败'); END",
+            chat.id
+        ))
+        .execute(&mut conn)
+        .unwrap();
+
+        assert!(ChatService::fork_chat(&pool, &human.id, &reply.id).is_err());
+        // 聊天和参与者随事务一并回滚
+        assert_eq!(ChatRepository::count_user_chats(&pool, &human.id, true).unwrap(), 1);
+        assert_eq!(ChatParticipantRepository::get_by_user_id(&pool, &human.id).unwrap().len(), 1);
     }
 }
@@ -0,0 +1,885 @@
+// 对话上下文组装服务
+//
+// 在模型上下文长度内挑选发送给模型的聊天记录：上下文长度扣除为输出预留的 max_tokens 后，
+// 先放入系统提示词（以及长期记忆、知识库资料和摘要），再按策略放入历史消息。组装过程不依赖模型，token 数由可替换的估算器给出
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use crate::db::DbPool;
+use crate::llm::{LlmMessage, Role, ToolCall};
+use crate::models::{Agent, AgentMemory, Message, MessageKind};
+use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+use crate::repositories::message_repository::MessageRepository;
+use crate::repositories::user_repository::UserRepository;
+use super::knowledge_service::RetrievedChunk;
+use super::message_service::{truncate_chars, MessageService};
+use super::ServiceResult;
+
+// 未配置上下文长度时使用的默认值
+pub const DEFAULT_CONTEXT_WINDOW: usize = 4096;
+// 未配置 max_tokens 时为输出预留的 token 数
+pub const DEFAULT_RESERVED_OUTPUT_TOKENS: usize = 512;
+// keep_first_and_recent 策略未配置条数时保留的最早消息条数
+pub const DEFAULT_KEEP_FIRST: usize = 2;
+
+// 每条消息的角色标记等格式开销
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+// summary_and_recent 策略为摘要预留的最大 token 数，不超过历史消息预算的四分之一
+const MAX_SUMMARY_TOKENS: usize = 512;
+// 知识库资料的最大 token 数，不超过历史消息预算的四分之一
+const MAX_KNOWLEDGE_TOKENS: usize = 1024;
+// 长期记忆的最大 token 数，不超过历史消息预算的八分之一
+const MAX_MEMORY_TOKENS: usize = 512;
+// 截断消息时添加的前缀
+const TRUNCATED_PREFIX: &str = "…";
+// 放入上下文的被引用消息的最大字符数
+const MAX_QUOTE_CHARS: usize = 200;
+
+/// 文本的 token 数估算器
+pub trait TokenEstimator: Send + Sync {
+    fn estimate(&self, text: &str) -> usize;
+}
+
+// 按字符粗略估算：中日韩字符约每个 1 个 token，其他字符约每 4 个 1 个 token
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeuristicTokenEstimator;
+
+impl TokenEstimator for HeuristicTokenEstimator {
+    fn estimate(&self, text: &str) -> usize {
+        let (wide, other) = text.chars().fold((0usize, 0usize), |(wide, other), c| {
+            if is_wide_char(c) {
+                (wide + 1, other)
+            } else {
+                (wide, other + 1)
+            }
+        });
+        wide + other.div_ceil(4)
+    }
+}
+
+fn is_wide_char(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x3040..=0x30FF       // 平假名、片假名
+            | 0x3400..=0x4DBF // 中日韩统一表意文字扩展 A
+            | 0x4E00..=0x9FFF // 中日韩统一表意文字
+            | 0xAC00..=0xD7AF // 韩文音节
+            | 0xF900..=0xFAFF // 中日韩兼容表意文字
+            | 0xFF00..=0xFFEF // 全角字符
+            | 0x20000..=0x2FFFF
+    )
+}
+
+// 上下文组装策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextStrategy {
+    // 从最新的消息开始向前放入，放不下时停止
+    SlidingWindow,
+    // 先保留最早的若干条消息，剩余预算从最新的消息开始向前放入
+    KeepFirstAndRecent { keep_first: usize },
+    // 为更早对话的摘要预留预算，剩余预算从最新的消息开始向前放入
+    SummaryAndRecent,
+}
+
+impl ContextStrategy {
+    pub const SLIDING_WINDOW: &'static str = "sliding_window";
+    pub const KEEP_FIRST_AND_RECENT: &'static str = "keep_first_and_recent";
+    pub const SUMMARY_AND_RECENT: &'static str = "summary_and_recent";
+
+    // 按 agents.context_strategy 和 agents.context_keep_first 解析，未知的策略名返回 None
+    pub fn parse(name: &str, keep_first: Option<i32>) -> Option<Self> {
+        match name {
+            Self::SLIDING_WINDOW => Some(Self::SlidingWindow),
+            Self::KEEP_FIRST_AND_RECENT => Some(Self::KeepFirstAndRecent {
+                keep_first: keep_first
+                    .and_then(|n| usize::try_from(n).ok())
+                    .unwrap_or(DEFAULT_KEEP_FIRST),
+            }),
+            Self::SUMMARY_AND_RECENT => Some(Self::SummaryAndRecent),
+            _ => None,
+        }
+    }
+}
+
+// 参与组装的一条历史消息
+#[derive(Debug, Clone)]
+pub struct HistoryMessage {
+    pub id: String,
+    pub sender_is_ai: bool,
+    // 是否为正在回复的 AI 用户自己的消息，只有这些消息作为 assistant 放入上下文
+    pub from_self: bool,
+    // 多人聊天中其他成员消息的发送者名称，放入上下文时标注在内容开头
+    pub sender_name: Option<String>,
+    pub content: String,
+    // 该消息回复（引用）的消息
+    pub quoted: Option<QuotedMessage>,
+    // 自己的工具调用消息中请求调用的工具
+    pub tool_calls: Vec<ToolCall>,
+    // 自己的工具结果消息对应的工具调用
+    pub tool_call_id: Option<String>,
+}
+
+// 被引用的消息，内容不超过 MAX_QUOTE_CHARS 个字符
+#[derive(Debug, Clone)]
+pub struct QuotedMessage {
+    pub sender_is_ai: bool,
+    // 多人聊天中被引用消息的发送者名称
+    pub sender_name: Option<String>,
+    pub content: String,
+}
+
+impl QuotedMessage {
+    // 发送者的称呼，多人聊天中为发送者名称
+    pub fn speaker(&self) -> &str {
+        self.sender_name.as_deref().unwrap_or(speaker_label(self.sender_is_ai))
+    }
+}
+
+impl HistoryMessage {
+    // 发送者的称呼，多人聊天中为发送者名称
+    pub fn speaker(&self) -> &str {
+        self.sender_name.as_deref().unwrap_or(speaker_label(self.sender_is_ai))
+    }
+
+    // 发送给模型的内容，多人聊天中其他成员的消息以发送者名称开头
+    pub fn prompt_content(&self) -> String {
+        match &self.sender_name {
+            Some(name) => format!("{}：{}", name, self.quoted_content()),
+            None => self.quoted_content(),
+        }
+    }
+
+    // 对话记录中的一行，以发送者的称呼开头；工具调用列出调用的工具名称
+    pub fn transcript_line(&self) -> String {
+        if self.tool_call_id.is_some() {
+            return format!("工具结果: {}", self.content);
+        }
+        if !self.tool_calls.is_empty() {
+            let names: Vec<&str> = self.tool_calls.iter().map(|call| call.name.as_str()).collect();
+            return format!("{}: [调用工具 {}] {}", self.speaker(), names.join(", "), self.content)
+                .trim_end()
+                .to_string();
+        }
+        format!("{}: {}", self.speaker(), self.quoted_content())
+    }
+
+    // 消息内容，有引用时在开头附上被引用的消息
+    fn quoted_content(&self) -> String {
+        match &self.quoted {
+            Some(quoted) => format!("「回复 {}：{}」\n{}", quoted.speaker(), quoted.content, self.content),
+            None => self.content.clone(),
+        }
+    }
+}
+
+// 组装结果
+#[derive(Debug)]
+pub struct BuiltContext {
+    pub messages: Vec<LlmMessage>,
+    // 按估算器计算的 token 数，包括每条消息的格式开销
+    pub estimated_tokens: usize,
+    // 超出预算未放入上下文的历史消息，按时间升序
+    pub omitted: Vec<HistoryMessage>,
+}
+
+pub struct ContextBuilder {
+    estimator: Arc<dyn TokenEstimator>,
+    strategy: ContextStrategy,
+    context_window: usize,
+    reserved_output_tokens: usize,
+    system_prompt: Option<String>,
+    summary: Option<String>,
+    reply_target: Option<QuotedMessage>,
+    speaker_name: Option<String>,
+    knowledge: Vec<RetrievedChunk>,
+    memories: Vec<AgentMemory>,
+}
+
+impl ContextBuilder {
+    pub fn new(context_window: usize, reserved_output_tokens: usize) -> Self {
+        Self {
+            estimator: Arc::new(HeuristicTokenEstimator),
+            strategy: ContextStrategy::SlidingWindow,
+            context_window,
+            reserved_output_tokens,
+            system_prompt: None,
+            summary: None,
+            reply_target: None,
+            speaker_name: None,
+            knowledge: Vec::new(),
+            memories: Vec::new(),
+        }
+    }
+
+    // 按 Agent 配置创建，未知的策略按滑动窗口处理
+    pub fn for_agent(agent: &Agent) -> Self {
+        let context_window = agent
+            .context_window
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(DEFAULT_CONTEXT_WINDOW);
+        let reserved_output_tokens = agent
+            .max_tokens
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(DEFAULT_RESERVED_OUTPUT_TOKENS);
+        let strategy = ContextStrategy::parse(&agent.context_strategy, agent.context_keep_first)
+            .unwrap_or(ContextStrategy::SlidingWindow);
+
+        Self::new(context_window, reserved_output_tokens)
+            .with_strategy(strategy)
+            .with_system_prompt(agent.system_prompt.clone())
+    }
+
+    pub fn with_estimator(mut self, estimator: Arc<dyn TokenEstimator>) -> Self {
+        self.estimator = estimator;
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: ContextStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_system_prompt(mut self, system_prompt: Option<String>) -> Self {
+        self.system_prompt = system_prompt.filter(|prompt| !prompt.trim().is_empty());
+        self
+    }
+
+    // 更早对话的摘要，仅 summary_and_recent 策略使用
+    pub fn with_summary(mut self, summary: Option<String>) -> Self {
+        self.summary = summary.filter(|summary| !summary.trim().is_empty());
+        self
+    }
+
+    // 指定本次回复针对的较早消息，组装时放在系统提示词之后
+    pub fn with_reply_target(mut self, reply_target: Option<QuotedMessage>) -> Self {
+        self.reply_target = reply_target;
+        self
+    }
+
+    // 多人聊天中正在回复的 AI 用户的名称，组装时说明其他成员的发言格式
+    pub fn with_speaker_name(mut self, speaker_name: Option<String>) -> Self {
+        self.speaker_name = speaker_name;
+        self
+    }
+
+    // 从知识库检索到的片段，按相关度降序；组装时在预算内依次放入，并标注所属资源供模型引用
+    pub fn with_knowledge(mut self, knowledge: Vec<RetrievedChunk>) -> Self {
+        self.knowledge = knowledge;
+        self
+    }
+
+    // AI 用户的长期记忆，按优先顺序排列；组装时在预算内依次放入，紧接在系统提示词之后
+    pub fn with_memories(mut self, memories: Vec<AgentMemory>) -> Self {
+        self.memories = memories;
+        self
+    }
+
+    pub fn strategy(&self) -> ContextStrategy {
+        self.strategy
+    }
+
+    // 历史消息可用的 token 预算，为输出预留的部分超过上下文长度时为 0
+    pub fn budget(&self) -> usize {
+        self.context_window.saturating_sub(self.reserved_output_tokens)
+    }
+
+    // 为摘要预留的 token 数，也是生成摘要时的 max_tokens
+    pub fn summary_max_tokens(&self) -> usize {
+        MAX_SUMMARY_TOKENS.min(self.budget() / 4)
+    }
+
+    // 为知识库资料预留的最大 token 数
+    pub fn knowledge_max_tokens(&self) -> usize {
+        MAX_KNOWLEDGE_TOKENS.min(self.budget() / 4)
+    }
+
+    // 为长期记忆预留的最大 token 数
+    pub fn memory_max_tokens(&self) -> usize {
+        MAX_MEMORY_TOKENS.min(self.budget() / 8)
+    }
+
+    // 按策略组装上下文，history 按时间升序
+    //
+    // 最新一条消息总会放入，超出剩余预算时截断其开头部分
+    pub fn build(&self, history: &[HistoryMessage]) -> BuiltContext {
+        let mut budget = self.budget();
+        let mut used = 0;
+        let mut head = Vec::new();
+
+        // 1. 系统提示词、长期记忆、多人聊天说明、知识库资料、摘要和回复的目标消息
+        if let Some(prompt) = &self.system_prompt {
+            used += self.message_cost(prompt);
+            head.push(LlmMessage::new(Role::System, prompt.clone()));
+        }
+        if let Some(content) = self.memory_message_content() {
+            used += self.message_cost(&content);
+            head.push(LlmMessage::new(Role::System, content));
+        }
+        if let Some(name) = &self.speaker_name {
+            let content = group_chat_message_content(name);
+            used += self.message_cost(&content);
+            head.push(LlmMessage::new(Role::System, content));
+        }
+        if let Some(content) = self.knowledge_message_content() {
+            used += self.message_cost(&content);
+            head.push(LlmMessage::new(Role::System, content));
+        }
+        if self.strategy == ContextStrategy::SummaryAndRecent {
+            match &self.summary {
+                Some(summary) => {
+                    // 摘要超出预留的 summary_max_tokens 时去掉开头部分，与尚未生成摘要时占用的预算相同
+                    let header_tokens = self.estimator.estimate(&summary_message_content(""));
+                    let summary = self.truncate_head(summary, self.summary_max_tokens().saturating_sub(header_tokens));
+                    let content = summary_message_content(&summary);
+                    used += self.message_cost(&content);
+                    head.push(LlmMessage::new(Role::System, content));
+                }
+                // 尚未生成摘要时同样预留，生成摘要后放入的历史消息不会变少
+                None => budget = budget.saturating_sub(self.summary_max_tokens() + MESSAGE_OVERHEAD_TOKENS),
+            }
+        }
+
+        if let Some(target) = &self.reply_target {
+            let content = format!("请针对{}的这条消息回复：\n{}", target.speaker(), target.content);
+            used += self.message_cost(&content);
+            head.push(LlmMessage::new(Role::System, content));
+        }
+
+        let Some(latest_index) = history.len().checked_sub(1) else {
+            return BuiltContext { messages: head, estimated_tokens: used, omitted: Vec::new() };
+        };
+
+        let mut included = vec![false; history.len()];
+
+        // 2. 最新一条消息
+        let latest = &history[latest_index];
+        let mut latest_content = latest.prompt_content();
+        let latest_cost = self.history_cost(latest);
+        if used + latest_cost > budget {
+            // 截断后不超过剩余预算，系统提示词等已占满预算时内容为空
+            let available = budget.saturating_sub(used + MESSAGE_OVERHEAD_TOKENS);
+            latest_content = self.truncate_head(&latest_content, available);
+            used += self.message_cost(&latest_content);
+        } else {
+            used += latest_cost;
+        }
+        included[latest_index] = true;
+
+        // 3. 最早的若干条消息
+        let keep_first = match self.strategy {
+            ContextStrategy::KeepFirstAndRecent { keep_first } => keep_first.min(latest_index),
+            _ => 0,
+        };
+        for (index, message) in history.iter().enumerate().take(keep_first) {
+            let cost = self.history_cost(message);
+            if used + cost > budget {
+                break;
+            }
+            used += cost;
+            included[index] = true;
+        }
+
+        // 4. 从新到旧放入剩余消息，放不下时停止，保证放入的近期消息连续
+        for index in (keep_first..latest_index).rev() {
+            let cost = self.history_cost(&history[index]);
+            if used + cost > budget {
+                break;
+            }
+            used += cost;
+            included[index] = true;
+        }
+
+        let mut messages = head;
+        let mut omitted = Vec::new();
+        for (index, message) in history.iter().enumerate() {
+            if !included[index] {
+                omitted.push(message.clone());
+                continue;
+            }
+            let content = if index == latest_index {
+                latest_content.clone()
+            } else {
+                message.prompt_content()
+            };
+            messages.push(LlmMessage {
+                tool_calls: message.tool_calls.clone(),
+                tool_call_id: message.tool_call_id.clone(),
+                ..LlmMessage::new(role_for(message), content)
+            });
+        }
+
+        BuiltContext { messages: pair_tool_messages(messages), estimated_tokens: used, omitted }
+    }
+
+    // 请求模型概括被省略的历史消息，对话记录超出预算时保留较新的部分
+    pub fn summary_messages(&self, omitted: &[HistoryMessage]) -> Vec<LlmMessage> {
+        let transcript = omitted
+            .iter()
+            .map(HistoryMessage::transcript_line)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let instruction = match &self.summary {
+            Some(previous) => format!(
+                "请结合已有摘要，用简洁的中文概括下面的对话，保留人物、事实、结论和未完成的事项，不要添加对话中没有的内容。\n已有摘要：\n{}",
+                previous
+            ),
+            None => "请用简洁的中文概括下面的对话，保留人物、事实、结论和未完成的事项，不要添加对话中没有的内容。".to_string(),
+        };
+
+        let budget = self.budget().saturating_sub(
+            self.message_cost(&instruction) + self.summary_max_tokens() + MESSAGE_OVERHEAD_TOKENS,
+        );
+        let transcript = if self.estimator.estimate(&transcript) > budget {
+            self.truncate_head(&transcript, budget)
+        } else {
+            transcript
+        };
+
+        vec![
+            LlmMessage::new(Role::System, instruction),
+            LlmMessage::new(Role::User, transcript),
+        ]
+    }
+
+    // 多人聊天（参与者超过两人）中 AI 用户的名称，单聊返回 None
+    //
+    // 多人聊天中其他成员的消息需要标注发送者名称，正在回复的 AI 也需要知道自己的名称
+    pub fn load_group_speaker_name(pool: &DbPool, chat_id: &str, agent_user_id: &str) -> ServiceResult<Option<String>> {
+        let participants = ChatParticipantRepository::get_by_chat_id(pool, chat_id)
+            .map_err(|e| anyhow!("获取聊天参与者失败: {}", e))?;
+        if participants.len() <= 2 {
+            return Ok(None);
+        }
+        let agent_user = UserRepository::get(pool, agent_user_id)
+            .map_err(|e| anyhow!("获取AI用户信息失败: {}", e))?;
+        Ok(Some(agent_user.name))
+    }
+
+    // 读取聊天最近的消息及发送者信息，结果按时间升序
+    //
+    // agent_user_id 为正在回复的 AI 用户，为空时所有消息都视为其他成员的消息；
+    // attribute_names 为 true 时为其他成员的消息标注发送者名称；
+    // 内容为空的消息（中断时未生成任何内容的回复）不参与组装；工具调用和工具结果只保留该 AI 用户自己的；
+    // 回复其他消息的消息附上被引用的消息，被引用的消息不在读取范围内时单独批量读取
+    pub fn load_history(
+        pool: &DbPool,
+        chat_id: &str,
+        agent_user_id: Option<&str>,
+        attribute_names: bool,
+        limit: i64,
+    ) -> ServiceResult<Vec<HistoryMessage>> {
+        let messages = MessageRepository::get_page_before(pool, chat_id, None, limit)
+            .map_err(|e| anyhow!("获取聊天记录失败: {}", e))?;
+
+        let loaded_ids: HashSet<&str> = messages.iter().map(|message| message.id.as_str()).collect();
+        let missing_ids: Vec<String> = messages
+            .iter()
+            .filter_map(|message| message.reply_to_id.clone())
+            .filter(|id| !loaded_ids.contains(id.as_str()))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let older_quoted = if missing_ids.is_empty() {
+            Vec::new()
+        } else {
+            MessageRepository::get_by_ids(pool, &missing_ids)
+                .map_err(|e| anyhow!("获取引用的消息失败: {}", e))?
+        };
+        let by_id: HashMap<&str, &Message> = messages
+            .iter()
+            .chain(older_quoted.iter())
+            .map(|message| (message.id.as_str(), message))
+            .collect();
+
+        let sender_ids: Vec<String> = by_id
+            .values()
+            .map(|message| message.sender_id.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let senders: HashMap<String, (bool, String)> = UserRepository::get_by_ids(pool, &sender_ids)
+            .map_err(|e| anyhow!("获取消息发送者失败: {}", e))?
+            .into_iter()
+            .map(|user| (user.id, (user.is_ai, user.name)))
+            .collect();
+        let sender_is_ai = |sender_id: &str| senders.get(sender_id).is_some_and(|(is_ai, _)| *is_ai);
+        let sender_name = |sender_id: &str| {
+            senders
+                .get(sender_id)
+                .filter(|_| attribute_names)
+                .map(|(_, name)| name.clone())
+        };
+
+        Ok(messages
+            .iter()
+            .filter_map(|message| {
+                let from_self = agent_user_id == Some(message.sender_id.as_str());
+                if message.kind == MessageKind::ToolCall.as_str() {
+                    let tool_calls = MessageService::decode_tool_calls(message.tool_calls.as_deref());
+                    return (from_self && !tool_calls.is_empty()).then(|| HistoryMessage {
+                        id: message.id.clone(),
+                        sender_is_ai: true,
+                        from_self,
+                        sender_name: None,
+                        content: message.content.clone(),
+                        quoted: None,
+                        tool_calls,
+                        tool_call_id: None,
+                    });
+                }
+                if message.kind == MessageKind::ToolResult.as_str() {
+                    return from_self.then(|| HistoryMessage {
+                        id: message.id.clone(),
+                        sender_is_ai: true,
+                        from_self,
+                        sender_name: None,
+                        content: message.content.clone(),
+                        quoted: None,
+                        tool_calls: Vec::new(),
+                        tool_call_id: message.tool_call_id.clone(),
+                    });
+                }
+                if message.content.is_empty() {
+                    return None;
+                }
+                Some(HistoryMessage {
+                    id: message.id.clone(),
+                    sender_is_ai: sender_is_ai(&message.sender_id),
+                    from_self,
+                    sender_name: if from_self { None } else { sender_name(&message.sender_id) },
+                    content: message.content.clone(),
+                    quoted: message
+                        .reply_to_id
+                        .as_deref()
+                        .and_then(|id| by_id.get(id))
+                        .filter(|quoted| !quoted.content.is_empty())
+                        .map(|quoted| QuotedMessage {
+                            sender_is_ai: sender_is_ai(&quoted.sender_id),
+                            sender_name: sender_name(&quoted.sender_id),
+                            content: truncate_chars(&quoted.content, MAX_QUOTE_CHARS),
+                        }),
+                    tool_calls: Vec::new(),
+                    tool_call_id: None,
+                })
+            })
+            .collect())
+    }
+
+    // 读取触发回复的消息作为回复目标
+    //
+    // 触发消息就是聊天的最新消息时按正常流程回复，返回空；否则返回该消息，回复应针对这条较早的消息。
+    // attribute_names 为 true 时标注该消息的发送者名称
+    pub fn load_reply_target(
+        pool: &DbPool,
+        chat_id: &str,
+        message_id: &str,
+        attribute_names: bool,
+    ) -> ServiceResult<Option<QuotedMessage>> {
+        let latest = MessageRepository::get_page_before(pool, chat_id, None, 1)
+            .map_err(|e| anyhow!("获取聊天记录失败: {}", e))?;
+        if latest.first().is_some_and(|latest| latest.id == message_id) {
+            return Ok(None);
+        }
+
+        let message = MessageRepository::get(pool, message_id)
+            .map_err(|e| anyhow!("获取触发消息失败: {}", e))?;
+        if message.content.is_empty() || message.deleted_at.is_some() {
+            return Ok(None);
+        }
+        let sender = UserRepository::get(pool, &message.sender_id)
+            .map_err(|e| anyhow!("获取消息发送者失败: {}", e))?;
+        Ok(Some(QuotedMessage {
+            sender_is_ai: sender.is_ai,
+            sender_name: attribute_names.then_some(sender.name),
+            content: truncate_chars(&message.content, MAX_QUOTE_CHARS),
+        }))
+    }
+
+    // 知识库资料消息，按相关度放入不超过 knowledge_max_tokens 的片段，一个都放不下时返回 None
+    fn knowledge_message_content(&self) -> Option<String> {
+        let max_tokens = self.knowledge_max_tokens();
+        let mut content = KNOWLEDGE_HEADER.to_string();
+        let mut count = 0;
+        for chunk in &self.knowledge {
+            let entry = format!(
+                "\n\n[资源:{}]《{}》第 {} 段\n{}",
+                chunk.resource_id,
+                chunk.resource_name,
+                chunk.chunk_index + 1,
+                chunk.content
+            );
+            if self.estimator.estimate(&content) + self.estimator.estimate(&entry) > max_tokens {
+                continue;
+            }
+            content.push_str(&entry);
+            count += 1;
+        }
+        (count > 0).then_some(content)
+    }
+
+    // 长期记忆消息，按顺序放入不超过 memory_max_tokens 的记忆，一条都放不下时返回 None
+    fn memory_message_content(&self) -> Option<String> {
+        let max_tokens = self.memory_max_tokens();
+        let mut content = MEMORY_HEADER.to_string();
+        let mut count = 0;
+        for memory in &self.memories {
+            let entry = format!("\n- {}", memory.content);
+            if self.estimator.estimate(&content) + self.estimator.estimate(&entry) > max_tokens {
+                continue;
+            }
+            content.push_str(&entry);
+            count += 1;
+        }
+        (count > 0).then_some(content)
+    }
+
+    fn message_cost(&self, content: &str) -> usize {
+        self.estimator.estimate(content) + MESSAGE_OVERHEAD_TOKENS
+    }
+
+    // 历史消息的 token 数，工具调用按其 JSON 文本估算
+    fn history_cost(&self, message: &HistoryMessage) -> usize {
+        let tool_calls_cost = if message.tool_calls.is_empty() {
+            0
+        } else {
+            self.estimator
+                .estimate(&serde_json::to_string(&message.tool_calls).unwrap_or_default())
+        };
+        self.message_cost(&message.prompt_content()) + tool_calls_cost
+    }
+
+    // 去掉开头部分，使剩余内容不超过 max_tokens
+    fn truncate_head(&self, content: &str, max_tokens: usize) -> String {
+        let boundaries: Vec<usize> = content.char_indices().map(|(index, _)| index).collect();
+        // 估算值随保留的字符减少而不增，二分查找能保留的最长后缀
+        let (mut low, mut high) = (0, boundaries.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            let candidate = &content[boundaries[mid]..];
+            if self.estimator.estimate(candidate) + self.estimator.estimate(TRUNCATED_PREFIX) <= max_tokens {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+
+        match boundaries.get(low) {
+            Some(&start) if start > 0 => format!("{}{}", TRUNCATED_PREFIX, &content[start..]),
+            Some(_) => content.to_string(),
+            None => String::new(),
+        }
+    }
+}
+
+// 工具结果作为 tool，正在回复的 AI 用户自己的消息作为 assistant，其他用户（包括其他 AI 用户）的消息作为 user
+fn role_for(message: &HistoryMessage) -> Role {
+    if message.tool_call_id.is_some() {
+        Role::Tool
+    } else if message.from_self {
+        Role::Assistant
+    } else {
+        Role::User
+    }
+}
+
+// 去掉不成对的工具调用和工具结果
+//
+// 历史消息按预算截断时，工具结果可能缺少对应的调用，调用也可能缺少结果，服务商会拒绝这样的请求
+fn pair_tool_messages(messages: Vec<LlmMessage>) -> Vec<LlmMessage> {
+    let result_ids: HashSet<String> = messages
+        .iter()
+        .filter_map(|message| message.tool_call_id.clone())
+        .collect();
+    let mut call_ids: HashSet<String> = HashSet::new();
+
+    messages
+        .into_iter()
+        .filter_map(|mut message| {
+            if let Some(tool_call_id) = &message.tool_call_id {
+                return call_ids.contains(tool_call_id).then_some(message);
+            }
+            if !message.tool_calls.is_empty() {
+                message.tool_calls.retain(|call| result_ids.contains(&call.id));
+                call_ids.extend(message.tool_calls.iter().map(|call| call.id.clone()));
+                if message.tool_calls.is_empty() && message.content.is_empty() {
+                    return None;
+                }
+            }
+            Some(message)
+        })
+        .collect()
+}
+
+fn speaker_label(sender_is_ai: bool) -> &'static str {
+    if sender_is_ai { "助手" } else { "用户" }
+}
+
+fn group_chat_message_content(speaker_name: &str) -> String {
+    format!(
+        "这是一个多人聊天，你是其中的「{}」。其他成员的发言以「名字：」开头，回复时直接给出你的发言内容，不要加名字前缀。",
+        speaker_name
+    )
+}
+
+const MEMORY_HEADER: &str = "以下是你在过往对话中记住的信息，回复时自然地参考，不要逐条复述：";
+
+const KNOWLEDGE_HEADER: &str =
+    "以下是知识库中与当前对话相关的资料，回答时可以参考。引用资料时在相应内容后注明来源，格式为 [资源:资源ID]。";
+
+fn summary_message_content(summary: &str) -> String {
+    format!("以下是更早对话的摘要：\n{}", summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 每个字符计 1 个 token，便于按字符数计算预算
+    struct CharEstimator;
+
+    impl TokenEstimator for CharEstimator {
+        fn estimate(&self, text: &str) -> usize {
+            text.chars().count()
+        }
+    }
+
+    fn builder(context_window: usize, strategy: ContextStrategy) -> ContextBuilder {
+        ContextBuilder::new(context_window, 0)
+            .with_estimator(Arc::new(CharEstimator))
+            .with_strategy(strategy)
+            .with_system_prompt(Some("0123456789".to_string()))
+    }
+
+    fn message(id: usize, from_self: bool, content: String) -> HistoryMessage {
+        HistoryMessage {
+            id: format!("m{}", id),
+            sender_is_ai: from_self,
+            from_self,
+            sender_name: None,
+            content,
+            quoted: None,
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    // 六条内容为 16 个字符的消息，每条计 20 个 token
+    fn history() -> Vec<HistoryMessage> {
+        (0..6).map(|i| message(i, i % 2 == 1, format!("{:0>16}", i))).collect()
+    }
+
+    fn omitted_ids(context: &BuiltContext) -> Vec<&str> {
+        context.omitted.iter().map(|message| message.id.as_str()).collect()
+    }
+
+    #[test]
+    fn budget_reserves_output_tokens() {
+        assert_eq!(ContextBuilder::new(4096, 512).budget(), 3584);
+        assert_eq!(ContextBuilder::new(256, 512).budget(), 0);
+    }
+
+    #[test]
+    fn sliding_window_keeps_newest_messages() {
+        // 系统提示词 14，最新四条 80，共 94
+        let context = builder(100, ContextStrategy::SlidingWindow).build(&history());
+
+        assert_eq!(omitted_ids(&context), vec!["m0", "m1"]);
+        assert_eq!(context.estimated_tokens, 94);
+        assert_eq!(context.messages.len(), 5);
+        assert_eq!(context.messages[0].role, Role::System);
+        assert_eq!(context.messages[1].role, Role::User);
+        assert_eq!(context.messages[2].role, Role::Assistant);
+    }
+
+    #[test]
+    fn keep_first_and_recent_keeps_earliest_messages() {
+        let context = builder(100, ContextStrategy::KeepFirstAndRecent { keep_first: 1 }).build(&history());
+
+        assert_eq!(omitted_ids(&context), vec!["m1", "m2"]);
+        assert_eq!(context.messages[1].content, format!("{:0>16}", 0));
+        assert_eq!(context.estimated_tokens, 94);
+    }
+
+    #[test]
+    fn summary_and_recent_reserves_room_for_summary() {
+        // 尚未生成摘要时预留 min(512, 100 / 4) + 4 = 29，剩余 71
+        let builder = builder(100, ContextStrategy::SummaryAndRecent);
+        let without_summary = builder.build(&history());
+        assert_eq!(omitted_ids(&without_summary), vec!["m0", "m1", "m2", "m3"]);
+
+        let builder = builder.with_summary(Some("s".repeat(10)));
+        let with_summary = builder.build(&history());
+        assert_eq!(omitted_ids(&with_summary), vec!["m0", "m1", "m2"]);
+        assert_eq!(with_summary.messages[1].content, summary_message_content(&"s".repeat(10)));
+        assert!(with_summary.estimated_tokens <= builder.budget());
+    }
+
+    #[test]
+    fn long_summary_is_clamped_to_reserved_budget() {
+        // 摘要消息最多占预留的 25，其中前缀占 12
+        let builder = builder(100, ContextStrategy::SummaryAndRecent).with_summary(Some("s".repeat(100)));
+        let context = builder.build(&history());
+
+        assert_eq!(omitted_ids(&context), vec!["m0", "m1", "m2", "m3"]);
+        assert_eq!(context.messages[1].content, summary_message_content(&format!("{}{}", TRUNCATED_PREFIX, "s".repeat(12))));
+        assert!(context.estimated_tokens <= builder.budget());
+    }
+
+    #[test]
+    fn latest_message_is_truncated_from_head() {
+        let builder = ContextBuilder::new(30, 0).with_estimator(Arc::new(CharEstimator));
+        let history = vec![message(0, false, "早".repeat(10)), message(1, false, "a".repeat(75) + &"b".repeat(25))];
+
+        let context = builder.build(&history);
+        assert_eq!(omitted_ids(&context), vec!["m0"]);
+        assert_eq!(context.messages[0].content, format!("{}{}", TRUNCATED_PREFIX, "b".repeat(25)));
+        assert_eq!(context.estimated_tokens, 30);
+    }
+
+    #[test]
+    fn truncated_latest_message_stays_within_remaining_budget() {
+        // 系统提示词占去 44，最新一条消息只剩 2 个 token
+        let builder = ContextBuilder::new(50, 0)
+            .with_estimator(Arc::new(CharEstimator))
+            .with_system_prompt(Some("p".repeat(40)));
+        let context = builder.build(&[message(0, false, "x".repeat(200))]);
+
+        assert_eq!(context.messages[1].content, format!("{}x", TRUNCATED_PREFIX));
+        assert!(context.estimated_tokens <= builder.budget());
+    }
+
+    #[test]
+    fn unpaired_tool_messages_are_dropped() {
+        let call = |id: &str| ToolCall { id: id.to_string(), name: "get_time".to_string(), arguments: serde_json::json!({}) };
+        let messages = vec![
+            LlmMessage::tool_result("call_0", "早于窗口的调用的结果"),
+            LlmMessage::tool_calls("", vec![call("call_1"), call("call_2")]),
+            LlmMessage::tool_result("call_1", "12:00"),
+            LlmMessage::tool_calls("", vec![call("call_3")]),
+            LlmMessage::new(Role::Assistant, "现在是 12 点"),
+        ];
+
+        let paired = pair_tool_messages(messages);
+        assert_eq!(paired.len(), 3);
+        assert_eq!(paired[0].tool_calls, vec![call("call_1")]);
+        assert_eq!(paired[1].tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(paired[2].content, "现在是 12 点");
+    }
+
+    #[test]
+    fn strategy_parse() {
+        assert_eq!(ContextStrategy::parse("sliding_window", None), Some(ContextStrategy::SlidingWindow));
+        assert_eq!(
+            ContextStrategy::parse("keep_first_and_recent", None),
+            Some(ContextStrategy::KeepFirstAndRecent { keep_first: DEFAULT_KEEP_FIRST })
+        );
+        assert_eq!(
+            ContextStrategy::parse("keep_first_and_recent", Some(-1)),
+            Some(ContextStrategy::KeepFirstAndRecent { keep_first: DEFAULT_KEEP_FIRST })
+        );
+        assert_eq!(ContextStrategy::parse("unknown", None), None);
+    }
+}
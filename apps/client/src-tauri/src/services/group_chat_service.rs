@@ -0,0 +1,423 @@
+// 群聊回复编排服务
+//
+// 用户发言后按聊天的回复策略决定由哪些 AI 参与者回复，消息中 @ 到的 AI 参与者总会回复，
+// 但一条用户消息触发的回复不超过聊天的 max_ai_turns 条；AI 的回复只会触发其中 @ 到的其他 AI 参与者，
+// 用户发言之后 AI 连续回复的条数（包括尚未结束的任务）达到 max_ai_turns 后不再触发新的回复
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+use diesel::connection::Connection;
+use serde::Deserialize;
+
+use crate::db::DbPool;
+use crate::llm::{LlmMessage, Role};
+use crate::models::{ChatReplyPolicyChangeset, Message, MessageKind, MessageStatus, ReplyPolicy, User};
+use crate::repositories::ai_job_repository::AiJobRepository;
+use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+use crate::repositories::chat_repository::ChatRepository;
+use crate::repositories::error::RepositoryError;
+use crate::repositories::message_repository::MessageRepository;
+use crate::repositories::user_repository::UserRepository;
+use super::context_builder::HistoryMessage;
+use super::ServiceResult;
+
+// max_ai_turns 取值范围
+const MAX_AI_TURNS_RANGE: (i32, i32) = (1, 50);
+// 主持人挑选回复者时参考的最近消息条数
+pub const MODERATOR_HISTORY_LIMIT: i64 = 20;
+// 主持人挑选回复者时的最大输出 token 数
+pub const MODERATOR_MAX_TOKENS: i32 = 200;
+
+// 由哪些 AI 参与者回复
+#[derive(Debug)]
+pub enum ReplyDecision {
+    // 由这些 AI 参与者按顺序依次回复，为空时不回复
+    Speakers(Vec<User>),
+    // 由主持人从候选者中挑选，最多 max_speakers 名；挑选失败或没有选出任何人时由 fallback 回复
+    Moderate {
+        moderator: User,
+        candidates: Vec<User>,
+        fallback: Vec<User>,
+        max_speakers: usize,
+    },
+}
+
+// 一条消息的回复安排
+#[derive(Debug)]
+pub struct ReplyPlan {
+    pub chat_id: String,
+    pub decision: ReplyDecision,
+}
+
+// 主持人输出的 JSON
+#[derive(Debug, Default, Deserialize)]
+struct ModeratorChoice {
+    #[serde(default)]
+    speakers: Vec<String>,
+}
+
+pub struct GroupChatService;
+
+impl GroupChatService {
+    // 按聊天的回复策略为消息安排回复的 AI 参与者
+    //
+    // 已有针对该消息的未结束任务的 AI 参与者不再重复安排，连同这些任务不超过 max_ai_turns 条；
+    // 消息由 AI 发送时，只安排其中 @ 到的、没有未结束任务的其他 AI 参与者，且不超过剩余的连续回复条数
+    pub fn plan_replies(pool: &DbPool, message_id: &str) -> ServiceResult<ReplyPlan> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        // 1. 校验消息和聊天
+        let message = MessageRepository::get_with_conn(&mut conn, message_id).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("消息不存在"),
+            e => anyhow!("获取消息失败: {}", e),
+        })?;
+        if message.deleted_at.is_some() {
+            return Err(anyhow!("消息已删除"));
+        }
+        if message.status == MessageStatus::Streaming.as_str() {
+            return Err(anyhow!("消息正在生成中"));
+        }
+        if message.kind != MessageKind::Text.as_str() {
+            return Err(anyhow!("工具调用和工具结果不会触发回复"));
+        }
+        let chat = ChatRepository::get_with_conn(&mut conn, &message.chat_id)
+            .map_err(|e| anyhow!("获取聊天信息失败: {}", e))?;
+        if chat.deleted_at.is_some() {
+            return Err(anyhow!("聊天已删除"));
+        }
+
+        // 2. 读取 AI 参与者、消息所在的对话路径和未结束的任务
+        let agents: Vec<User> = ChatParticipantRepository::get_users_by_chat_id_with_conn(&mut conn, &chat.id)
+            .map_err(|e| anyhow!("获取聊天参与者失败: {}", e))?
+            .into_iter()
+            .filter(|user| user.is_ai)
+            .collect();
+        let sender = UserRepository::get_with_conn(&mut conn, &message.sender_id)
+            .map_err(|e| anyhow!("获取消息发送者失败: {}", e))?;
+        // 工具调用和工具结果不算作发言
+        let path: Vec<Message> = MessageRepository::get_ancestors_with_conn(&mut conn, &message.id)
+            .map_err(|e| anyhow!("获取消息历史失败: {}", e))?
+            .into_iter()
+            .filter(|m| m.deleted_at.is_none() && m.kind == MessageKind::Text.as_str())
+            .collect();
+        let active_jobs = AiJobRepository::get_by_chat_id(pool, &chat.id, true)
+            .map_err(|e| anyhow!("获取AI回复任务失败: {}", e))?;
+
+        let mentioned: Vec<User> = Self::parse_mentions(&message.content, &agents)
+            .into_iter()
+            .filter(|agent| agent.id != sender.id)
+            .collect();
+
+        // 3. 按发送者和回复策略决定回复者
+        let decision = if sender.is_ai {
+            let busy: HashSet<&str> = active_jobs.iter().map(|job| job.agent_user_id.as_str()).collect();
+            let remaining = usize::try_from(chat.max_ai_turns)
+                .unwrap_or(0)
+                .saturating_sub(Self::consecutive_ai_turns(&path, &agents) + active_jobs.len());
+            ReplyDecision::Speakers(
+                mentioned
+                    .into_iter()
+                    .filter(|agent| !busy.contains(agent.id.as_str()))
+                    .take(remaining)
+                    .collect(),
+            )
+        } else {
+            let busy: HashSet<&str> = active_jobs
+                .iter()
+                .filter(|job| job.trigger_message_id.as_deref() == Some(message.id.as_str()))
+                .map(|job| job.agent_user_id.as_str())
+                .collect();
+            let remaining = usize::try_from(chat.max_ai_turns).unwrap_or(0).saturating_sub(busy.len());
+            let idle = |users: Vec<User>| -> Vec<User> {
+                users.into_iter().filter(|user| !busy.contains(user.id.as_str())).collect()
+            };
+            let speakers = |users: Vec<User>| ReplyDecision::Speakers(idle(users).into_iter().take(remaining).collect());
+
+            if !mentioned.is_empty() {
+                speakers(mentioned)
+            } else {
+                match ReplyPolicy::parse(&chat.reply_policy).unwrap_or_default() {
+                    ReplyPolicy::Mention => ReplyDecision::Speakers(Vec::new()),
+                    ReplyPolicy::Everyone => speakers(agents),
+                    ReplyPolicy::RoundRobin => speakers(Self::next_in_turn(&agents, &path)),
+                    ReplyPolicy::Moderator => {
+                        let fallback: Vec<User> =
+                            idle(Self::next_in_turn(&agents, &path)).into_iter().take(remaining).collect();
+                        let moderator = chat
+                            .moderator_user_id
+                            .as_deref()
+                            .and_then(|id| agents.iter().find(|agent| agent.id == id))
+                            .cloned();
+                        // 只有一名 AI 参与者或主持人已不在聊天中时无需挑选
+                        match moderator {
+                            Some(moderator) if agents.len() > 1 => {
+                                let candidates = idle(agents);
+                                if candidates.is_empty() || remaining == 0 {
+                                    ReplyDecision::Speakers(Vec::new())
+                                } else {
+                                    ReplyDecision::Moderate { moderator, candidates, fallback, max_speakers: remaining }
+                                }
+                            }
+                            _ => ReplyDecision::Speakers(fallback),
+                        }
+                    }
+                }
+            }
+        };
+
+        Ok(ReplyPlan { chat_id: chat.id, decision })
+    }
+
+    // 修改聊天的回复策略，max_ai_turns 为空时保持不变
+    //
+    // moderator 策略需要指定主持人，主持人必须是该聊天中的 AI 参与者
+    pub fn update_reply_policy(
+        pool: &DbPool,
+        user_id: &str,
+        chat_id: &str,
+        policy: ReplyPolicy,
+        moderator_user_id: Option<&str>,
+        max_ai_turns: Option<i32>,
+    ) -> ServiceResult<()> {
+        if let Some(max_ai_turns) = max_ai_turns {
+            let (min, max) = MAX_AI_TURNS_RANGE;
+            if !(min..=max).contains(&max_ai_turns) {
+                return Err(anyhow!("max_ai_turns 必须在 {} 到 {} 之间，当前为 {}", min, max, max_ai_turns));
+            }
+        }
+        if policy == ReplyPolicy::Moderator && moderator_user_id.is_none() {
+            return Err(anyhow!("主持人模式需要指定主持人"));
+        }
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            let is_participant = ChatParticipantRepository::exists_with_conn(conn, chat_id, user_id)
+                .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+            if !is_participant {
+                return Err(anyhow!("聊天不存在或您不是该聊天的参与者"));
+            }
+            let chat = ChatRepository::get_with_conn(conn, chat_id).map_err(|e| anyhow!("获取聊天信息失败: {}", e))?;
+            if chat.deleted_at.is_some() {
+                return Err(anyhow!("聊天已删除"));
+            }
+
+            if let Some(moderator_user_id) = moderator_user_id {
+                let moderator = UserRepository::get_with_conn(conn, moderator_user_id).map_err(|e| match e {
+                    RepositoryError::NotFound => anyhow!("主持人不存在"),
+                    e => anyhow!("获取用户信息失败: {}", e),
+                })?;
+                let moderator_is_participant = ChatParticipantRepository::exists_with_conn(conn, chat_id, moderator_user_id)
+                    .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+                if !moderator.is_ai || !moderator_is_participant {
+                    return Err(anyhow!("主持人必须是该聊天中的AI用户"));
+                }
+            }
+
+            let changeset = ChatReplyPolicyChangeset {
+                reply_policy: policy.as_str().to_string(),
+                moderator_user_id: moderator_user_id.map(str::to_string),
+                max_ai_turns: max_ai_turns.unwrap_or(chat.max_ai_turns),
+            };
+            ChatRepository::update_reply_policy_with_conn(conn, chat_id, &changeset)
+                .map_err(|e| anyhow!("更新回复策略失败: {}", e))
+        })
+    }
+
+    // 按出现顺序找出消息中 @ 到的 AI 参与者，名称有包含关系时匹配最长的名称
+    pub fn parse_mentions(content: &str, agents: &[User]) -> Vec<User> {
+        let mut mentioned: Vec<User> = Vec::new();
+        for (index, c) in content.char_indices().filter(|(_, c)| *c == '@' || *c == '＠') {
+            let rest = &content[index + c.len_utf8()..];
+            let agent = agents
+                .iter()
+                .filter(|agent| !agent.name.is_empty() && rest.starts_with(agent.name.as_str()))
+                .max_by_key(|agent| agent.name.len());
+            if let Some(agent) = agent {
+                if !mentioned.iter().any(|m| m.id == agent.id) {
+                    mentioned.push(agent.clone());
+                }
+            }
+        }
+        mentioned
+    }
+
+    // 请求主持人挑选回复者的消息
+    pub fn moderator_messages(candidates: &[User], history: &[HistoryMessage]) -> Vec<LlmMessage> {
+        let members = candidates
+            .iter()
+            .map(|user| match user.description.as_deref().filter(|d| !d.trim().is_empty()) {
+                Some(description) => format!("- {}：{}", user.name, description),
+                None => format!("- {}", user.name),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let instruction = format!(
+            "你是多人聊天的主持人，负责根据最新的发言决定接下来由哪些成员回复。可选的成员：\n{}\n\
+             只输出 JSON，格式为 {{\"speakers\": [\"成员名字\"]}}，按发言顺序列出最适合回复的成员，不需要任何成员回复时输出空数组。",
+            members
+        );
+        let transcript = history
+            .iter()
+            .map(HistoryMessage::transcript_line)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        vec![
+            LlmMessage::new(Role::System, instruction),
+            LlmMessage::new(Role::User, transcript),
+        ]
+    }
+
+    // 解析主持人的输出，按输出顺序返回选中的候选者；输出无法解析时返回空
+    pub fn parse_moderator_choice(content: &str, candidates: &[User]) -> Vec<User> {
+        // 模型可能在 JSON 前后附带说明文字，只取最外层的花括号部分
+        let json = match (content.find('{'), content.rfind('}')) {
+            (Some(start), Some(end)) if start < end => &content[start..=end],
+            _ => content,
+        };
+        let choice: ModeratorChoice = serde_json::from_str(json).unwrap_or_default();
+
+        let mut chosen: Vec<User> = Vec::new();
+        for name in &choice.speakers {
+            let name = name.trim().trim_start_matches(['@', '＠']);
+            if let Some(user) = candidates.iter().find(|user| user.name == name) {
+                if !chosen.iter().any(|c| c.id == user.id) {
+                    chosen.push(user.clone());
+                }
+            }
+        }
+        chosen
+    }
+
+    // 对话路径末尾连续由 AI 参与者发送的消息条数
+    fn consecutive_ai_turns(path: &[Message], agents: &[User]) -> usize {
+        path.iter()
+            .rev()
+            .take_while(|message| agents.iter().any(|agent| agent.id == message.sender_id))
+            .count()
+    }
+
+    // 轮流回复时下一个回复的 AI 参与者：对话路径上最后发言的 AI 参与者按加入顺序的下一位
+    fn next_in_turn(agents: &[User], path: &[Message]) -> Vec<User> {
+        if agents.is_empty() {
+            return Vec::new();
+        }
+        let last_index = path
+            .iter()
+            .rev()
+            .find_map(|message| agents.iter().position(|agent| agent.id == message.sender_id));
+        let next_index = last_index.map_or(0, |index| (index + 1) % agents.len());
+        vec![agents[next_index].clone()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    use crate::models::ChatType;
+    use crate::test_support::create_test_pool;
+
+    fn user(id: &str, name: &str) -> User {
+        User {
+            id: id.to_string(),
+            name: name.to_string(),
+            avatar_url: None,
+            description: None,
+            is_ai: true,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+        }
+    }
+
+    fn message(sender_id: &str) -> Message {
+        Message {
+            id: sender_id.to_string(),
+            content: String::new(),
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            chat_id: "chat".to_string(),
+            sender_id: sender_id.to_string(),
+            status: MessageStatus::Complete.as_str().to_string(),
+            error_message: None,
+            reply_to_id: None,
+            parent_id: None,
+            active_child_id: None,
+            is_active: true,
+            deleted_at: None,
+            kind: MessageKind::Text.as_str().to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+
+    fn ids(users: &[User]) -> Vec<&str> {
+        users.iter().map(|user| user.id.as_str()).collect()
+    }
+
+    #[test]
+    fn parse_mentions_keeps_order_and_prefers_longest_name() {
+        let agents = vec![user("a", "小明"), user("b", "小明同学"), user("c", "Bob")];
+
+        let mentioned = GroupChatService::parse_mentions("@Bob 你好，＠小明同学 和 @小明 呢？@Bob", &agents);
+        assert_eq!(ids(&mentioned), ["c", "b", "a"]);
+
+        assert!(GroupChatService::parse_mentions("没有提到任何人 @ 某人", &agents).is_empty());
+    }
+
+    #[test]
+    fn parse_moderator_choice_extracts_json_and_ignores_unknown_names() {
+        let candidates = vec![user("a", "甲"), user("b", "乙")];
+
+        let chosen = GroupChatService::parse_moderator_choice(
+            "好的，结果如下：{\"speakers\": [\"@乙\", \"丙\", \" 甲 \", \"乙\"]} 以上",
+            &candidates,
+        );
+        assert_eq!(ids(&chosen), ["b", "a"]);
+
+        assert!(GroupChatService::parse_moderator_choice("无法决定", &candidates).is_empty());
+        assert!(GroupChatService::parse_moderator_choice("{\"speakers\": []}", &candidates).is_empty());
+    }
+
+    #[test]
+    fn next_in_turn_follows_last_ai_speaker() {
+        let agents = vec![user("a", "甲"), user("b", "乙"), user("c", "丙")];
+
+        assert_eq!(ids(&GroupChatService::next_in_turn(&agents, &[message("human")])), ["a"]);
+        assert_eq!(
+            ids(&GroupChatService::next_in_turn(&agents, &[message("b"), message("human")])),
+            ["c"]
+        );
+        assert_eq!(ids(&GroupChatService::next_in_turn(&agents, &[message("c")])), ["a"]);
+        assert!(GroupChatService::next_in_turn(&[], &[message("a")]).is_empty());
+    }
+
+    #[test]
+    fn user_message_replies_are_capped_by_max_ai_turns() {
+        let pool = create_test_pool();
+        let human = UserRepository::create(&pool, "用户".to_string(), None, false).unwrap();
+        let chat = ChatRepository::create(&pool, "群聊", "", ChatType::Group).unwrap();
+        ChatParticipantRepository::create(&pool, &chat.id, &human.id).unwrap();
+        for name in ["甲", "乙", "丙"] {
+            let agent = UserRepository::create(&pool, name.to_string(), None, true).unwrap();
+            ChatParticipantRepository::create(&pool, &chat.id, &agent.id).unwrap();
+        }
+        GroupChatService::update_reply_policy(&pool, &human.id, &chat.id, ReplyPolicy::Everyone, None, Some(2)).unwrap();
+
+        let everyone = MessageRepository::create(&pool, "大家好".to_string(), &chat.id, &human.id).unwrap();
+        let plan = GroupChatService::plan_replies(&pool, &everyone.id).unwrap();
+        assert!(matches!(plan.decision, ReplyDecision::Speakers(speakers) if speakers.len() == 2));
+
+        let mention = MessageRepository::create(&pool, "@甲 @乙 @丙".to_string(), &chat.id, &human.id).unwrap();
+        let plan = GroupChatService::plan_replies(&pool, &mention.id).unwrap();
+        match plan.decision {
+            ReplyDecision::Speakers(speakers) => {
+                let names: Vec<&str> = speakers.iter().map(|user| user.name.as_str()).collect();
+                assert_eq!(names, ["甲", "乙"]);
+            }
+            decision => panic!("unexpected decision: {:?}", decision),
+        }
+    }
+}
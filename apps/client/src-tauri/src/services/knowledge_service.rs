@@ -0,0 +1,507 @@
+// 知识库检索服务
+//
+// AI 用户可以关联若干文本资源作为知识库：资源内容切分为片段，由 Agent 的服务商生成向量后保存在 resource_chunks 表中，
+// 回复时对触发消息生成向量，在关联资源的全部片段中按余弦相似度暴力检索，相关片段连同资源 ID 放入上下文供模型引用。
+// 片段记录切分时资源内容的哈希，内容变化或更换向量模型后在下次检索时重新生成；
+// 检索时资源在切分后未修改过则不再读取内容计算哈希
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::anyhow;
+use chrono::Utc;
+use diesel::connection::Connection;
+use uuid::Uuid;
+
+use crate::db::DbPool;
+use crate::llm::LlmProvider;
+use crate::models::{Agent, NewResourceChunk, Resource};
+use crate::repositories::error::RepositoryError;
+use crate::repositories::knowledge_repository::KnowledgeRepository;
+use crate::repositories::resource_repository::ResourceRepository;
+use super::agent_service::AgentService;
+use super::resource_service::ResourceService;
+use super::ServiceResult;
+
+// 每个片段的目标字符数
+const CHUNK_CHARS: usize = 800;
+// 相邻片段重叠的字符数，避免语句在切分处被割裂后无法检索
+const CHUNK_OVERLAP_CHARS: usize = 100;
+// 每次请求生成向量的片段数
+const EMBED_BATCH_SIZE: usize = 16;
+// 单个 AI 用户最多关联的资源数
+const MAX_AGENT_RESOURCES: usize = 32;
+// 每次回复最多放入上下文的片段数
+const TOP_K: usize = 4;
+// 相似度低于该值的片段视为无关
+const MIN_SIMILARITY: f32 = 0.2;
+
+// 检索到的片段
+#[derive(Debug, Clone)]
+pub struct RetrievedChunk {
+    pub resource_id: String,
+    pub resource_name: String,
+    pub chunk_index: i32,
+    pub content: String,
+}
+
+// AI 用户关联的知识库资源，chunk_count 为按其当前向量模型已生成的片段数，为 0 时尚未索引
+#[derive(Debug)]
+pub struct KnowledgeResource {
+    pub resource: Resource,
+    pub chunk_count: i64,
+}
+
+pub struct KnowledgeService;
+
+impl KnowledgeService {
+    // 获取 AI 用户关联的知识库资源
+    pub fn get_agent_resources(pool: &DbPool, agent_user_id: &str) -> ServiceResult<Vec<KnowledgeResource>> {
+        let agent = AgentService::get_agent_by_user(pool, agent_user_id)?;
+        let resources = {
+            let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+            KnowledgeRepository::get_agent_resources_with_conn(&mut conn, agent_user_id)
+                .map_err(|e| anyhow!("获取AI用户的知识库失败: {}", e))?
+        };
+        Self::with_chunk_counts(pool, &agent, resources)
+    }
+
+    // 将 AI 用户关联的知识库资源整体替换为给定的资源，只能关联文本资源
+    //
+    // 只保存关联，片段在下次回复或重新索引时生成
+    pub fn set_agent_resources(
+        pool: &DbPool,
+        agent_user_id: &str,
+        resource_ids: &[String],
+    ) -> ServiceResult<Vec<KnowledgeResource>> {
+        let mut resource_ids_dedup: Vec<String> = Vec::new();
+        for id in resource_ids {
+            if !resource_ids_dedup.contains(id) {
+                resource_ids_dedup.push(id.clone());
+            }
+        }
+        if resource_ids_dedup.len() > MAX_AGENT_RESOURCES {
+            return Err(anyhow!("每个AI用户最多关联{}个知识库资源", MAX_AGENT_RESOURCES));
+        }
+
+        let agent = AgentService::get_agent_by_user(pool, agent_user_id)?;
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        let resources = conn.transaction(|conn| {
+            for id in &resource_ids_dedup {
+                let resource = ResourceRepository::get_with_conn(conn, id).map_err(|e| match e {
+                    RepositoryError::NotFound => anyhow!("资源不存在: {}", id),
+                    e => anyhow!("获取资源失败: {}", e),
+                })?;
+                if resource.type_ != "text" {
+                    return Err(anyhow!("只能关联文本资源: {}", resource.name));
+                }
+            }
+            KnowledgeRepository::set_agent_resources_with_conn(conn, agent_user_id, &resource_ids_dedup)
+                .map_err(|e| anyhow!("保存AI用户的知识库失败: {}", e))?;
+            KnowledgeRepository::get_agent_resources_with_conn(conn, agent_user_id)
+                .map_err(|e| anyhow!("获取AI用户的知识库失败: {}", e))
+        })?;
+        drop(conn);
+        Self::with_chunk_counts(pool, &agent, resources)
+    }
+
+    // 为 AI 用户关联的全部资源生成片段，内容未变化的资源跳过，任一资源失败时返回错误
+    pub async fn index_agent_resources(
+        pool: &DbPool,
+        provider: &dyn LlmProvider,
+        agent: &Agent,
+        app_resource_path: &Path,
+    ) -> ServiceResult<Vec<KnowledgeResource>> {
+        let resources = {
+            let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+            KnowledgeRepository::get_agent_resources_with_conn(&mut conn, &agent.user_id)
+                .map_err(|e| anyhow!("获取AI用户的知识库失败: {}", e))?
+        };
+
+        let model = AgentService::embedding_model(agent);
+        let mut indexed = Vec::with_capacity(resources.len());
+        for resource in resources {
+            let chunk_count = Self::index_resource(pool, provider, model, &resource, app_resource_path)
+                .await
+                .map_err(|e| anyhow!("索引资源 {} 失败: {}", resource.name, e))?;
+            indexed.push(KnowledgeResource { resource, chunk_count });
+        }
+        Ok(indexed)
+    }
+
+    // 按指定模型为资源生成片段，返回片段数；内容与上次切分时相同则不重新生成
+    pub async fn index_resource(
+        pool: &DbPool,
+        provider: &dyn LlmProvider,
+        model: &str,
+        resource: &Resource,
+        app_resource_path: &Path,
+    ) -> ServiceResult<i64> {
+        let content = ResourceService::read_text_resource_content(pool, &resource.id, app_resource_path)?;
+        let hash = content_hash(&content);
+        {
+            let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+            let indexed_hash =
+                KnowledgeRepository::get_content_hash_with_conn(&mut conn, &resource.id, provider.name(), model)
+                    .map_err(|e| anyhow!("获取资源片段失败: {}", e))?;
+            if indexed_hash.as_deref() == Some(hash.as_str()) {
+                return KnowledgeRepository::count_chunks_with_conn(&mut conn, &resource.id, provider.name(), model)
+                    .map_err(|e| anyhow!("获取资源片段失败: {}", e));
+            }
+        }
+
+        let pieces = chunk_text(&content);
+        let mut embeddings = Vec::with_capacity(pieces.len());
+        for batch in pieces.chunks(EMBED_BATCH_SIZE) {
+            embeddings.extend(provider.embed(model, batch).await?);
+        }
+
+        let now = Utc::now().naive_utc();
+        let rows: Vec<NewResourceChunk> = pieces
+            .into_iter()
+            .zip(embeddings)
+            .enumerate()
+            .map(|(index, (content, embedding))| NewResourceChunk {
+                id: Uuid::new_v4().to_string(),
+                resource_id: resource.id.clone(),
+                embedding_provider: provider.name().to_string(),
+                embedding_model: model.to_string(),
+                chunk_index: index as i32,
+                content,
+                embedding: encode_embedding(&embedding),
+                content_hash: hash.clone(),
+                created_at: now,
+            })
+            .collect();
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+        conn.transaction(|conn| {
+            KnowledgeRepository::replace_chunks_with_conn(conn, &resource.id, provider.name(), model, &rows)
+        })
+        .map_err(|e| anyhow!("保存资源片段失败: {}", e))?;
+        Ok(rows.len() as i64)
+    }
+
+    // 在 AI 用户关联的知识库中检索与 query 相关的片段，按相似度降序
+    //
+    // 检索前为内容有变化或尚未索引的资源生成片段，单个资源索引失败时跳过该资源的新内容
+    pub async fn retrieve(
+        pool: &DbPool,
+        provider: &dyn LlmProvider,
+        agent: &Agent,
+        app_resource_path: &Path,
+        query: &str,
+    ) -> ServiceResult<Vec<RetrievedChunk>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let resources = {
+            let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+            KnowledgeRepository::get_agent_resources_with_conn(&mut conn, &agent.user_id)
+                .map_err(|e| anyhow!("获取AI用户的知识库失败: {}", e))?
+        };
+        if resources.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let model = AgentService::embedding_model(agent);
+        for resource in &resources {
+            match Self::is_index_fresh(pool, provider.name(), model, resource) {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(e) => eprintln!("获取资源片段失败 resource_id={}: {}", resource.id, e),
+            }
+            if let Err(e) = Self::index_resource(pool, provider, model, resource, app_resource_path).await {
+                eprintln!("索引知识库资源失败 resource_id={}: {}", resource.id, e);
+            }
+        }
+
+        let query_embedding = provider
+            .embed(model, &[query.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("模型未返回检索向量"))?;
+
+        let resource_ids: Vec<String> = resources.iter().map(|resource| resource.id.clone()).collect();
+        let names: HashMap<&str, &str> = resources
+            .iter()
+            .map(|resource| (resource.id.as_str(), resource.name.as_str()))
+            .collect();
+        let chunks = {
+            let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+            KnowledgeRepository::get_chunks_with_conn(&mut conn, &resource_ids, provider.name(), model)
+                .map_err(|e| anyhow!("获取资源片段失败: {}", e))?
+        };
+
+        let mut scored: Vec<(f32, RetrievedChunk)> = chunks
+            .into_iter()
+            .filter_map(|chunk| {
+                let score = cosine_similarity(&query_embedding, &decode_embedding(&chunk.embedding))?;
+                (score >= MIN_SIMILARITY).then(|| {
+                    let retrieved = RetrievedChunk {
+                        resource_name: names.get(chunk.resource_id.as_str()).copied().unwrap_or_default().to_string(),
+                        resource_id: chunk.resource_id,
+                        chunk_index: chunk.chunk_index,
+                        content: chunk.content,
+                    };
+                    (score, retrieved)
+                })
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(scored.into_iter().take(TOP_K).map(|(_, chunk)| chunk).collect())
+    }
+
+    // 资源在按指定模型切分后是否未再修改过，修改资源内容时会更新其修改时间
+    fn is_index_fresh(
+        pool: &DbPool,
+        embedding_provider: &str,
+        model: &str,
+        resource: &Resource,
+    ) -> ServiceResult<bool> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+        let indexed_at = KnowledgeRepository::get_indexed_at_with_conn(&mut conn, &resource.id, embedding_provider, model)
+            .map_err(|e| anyhow!("获取资源片段失败: {}", e))?;
+        Ok(indexed_at.is_some_and(|indexed_at| resource.updated_at < indexed_at))
+    }
+
+    // 附上各资源按 AI 用户当前服务商和向量模型已生成的片段数
+    fn with_chunk_counts(
+        pool: &DbPool,
+        agent: &Agent,
+        resources: Vec<Resource>,
+    ) -> ServiceResult<Vec<KnowledgeResource>> {
+        let model = AgentService::embedding_model(agent);
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+        resources
+            .into_iter()
+            .map(|resource| {
+                let chunk_count = KnowledgeRepository::count_chunks_with_conn(&mut conn, &resource.id, &agent.provider, model)
+                    .map_err(|e| anyhow!("获取资源片段失败: {}", e))?;
+                Ok(KnowledgeResource { resource, chunk_count })
+            })
+            .collect()
+    }
+}
+
+// 将文本切分为约 CHUNK_CHARS 个字符的片段，相邻片段重叠 CHUNK_OVERLAP_CHARS 个字符
+//
+// 切分处优先选在片段后半部分的换行或句末，找不到时直接按字符数切分
+pub fn chunk_text(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = (start + CHUNK_CHARS).min(chars.len());
+        if end < chars.len() {
+            if let Some(boundary) = (start + CHUNK_CHARS / 2..end).rev().find(|&index| is_boundary(chars[index])) {
+                end = boundary + 1;
+            }
+        }
+
+        let chunk: String = chars[start..end].iter().collect();
+        let chunk = chunk.trim();
+        if !chunk.is_empty() {
+            chunks.push(chunk.to_string());
+        }
+        if end >= chars.len() {
+            break;
+        }
+        start = end.saturating_sub(CHUNK_OVERLAP_CHARS).max(start + 1);
+    }
+    chunks
+}
+
+fn is_boundary(c: char) -> bool {
+    matches!(c, '\n' | '。' | '！' | '？' | '；' | '.' | '!' | '?' | ';')
+}
+
+// 向量以小端序 f32 数组保存
+fn encode_embedding(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+fn decode_embedding(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+// 维数不同或存在零向量时无法比较，返回 None
+fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() || a.is_empty() {
+        return None;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return None;
+    }
+    Some(dot / (norm_a * norm_b))
+}
+
+// 资源内容的 FNV-1a 哈希，用于判断内容是否变化
+fn content_hash(content: &str) -> String {
+    let hash = content.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TEXTS_DIR_NAME;
+    use crate::llm::mock::{MockProvider, MOCK_MODEL};
+    use crate::services::agent_service::AgentConfig;
+    use crate::test_support::{create_test_users, mock_agent_config, TempDir, TestUsers};
+
+    #[test]
+    fn short_text_is_a_single_trimmed_chunk() {
+        assert_eq!(chunk_text("  你好，世界  "), vec!["你好，世界".to_string()]);
+        assert!(chunk_text("   ").is_empty());
+    }
+
+    #[test]
+    fn chunks_overlap_when_no_boundary_is_found() {
+        let text: String = (0..2000).map(|index| char::from(b'a' + (index % 26) as u8)).collect();
+        let chunks = chunk_text(&text);
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].chars().count(), CHUNK_CHARS);
+        // 第二个片段从第一个片段末尾往前 CHUNK_OVERLAP_CHARS 个字符开始
+        let overlap: String = chunks[0].chars().skip(CHUNK_CHARS - CHUNK_OVERLAP_CHARS).collect();
+        assert!(chunks[1].starts_with(&overlap));
+        assert!(text.ends_with(chunks[2].as_str()));
+    }
+
+    #[test]
+    fn chunks_end_at_last_boundary_in_second_half() {
+        // 多字节字符按字符计数，句号分别位于第 300 和 600 个字符，只有后者在片段后半部分
+        let text = format!("{}。{}。{}", "甲".repeat(299), "乙".repeat(299), "丙".repeat(1000));
+        let chunks = chunk_text(&text);
+
+        assert_eq!(chunks[0].chars().count(), 600);
+        assert!(chunks[0].ends_with("乙。"));
+        assert!(chunks[1].starts_with(&"乙".repeat(CHUNK_OVERLAP_CHARS - 1)));
+        assert!(text.ends_with(chunks.last().unwrap().as_str()));
+    }
+
+    #[test]
+    fn embeddings_round_trip_through_bytes() {
+        let embedding = vec![0.0, 1.5, -2.25, f32::MIN_POSITIVE];
+        let bytes = encode_embedding(&embedding);
+        assert_eq!(bytes.len(), embedding.len() * 4);
+        assert_eq!(decode_embedding(&bytes), embedding);
+    }
+
+    #[test]
+    fn cosine_similarity_handles_mismatched_and_zero_vectors() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), Some(1.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), Some(0.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]), Some(-1.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]), None);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), None);
+        assert_eq!(cosine_similarity(&[], &[]), None);
+    }
+
+    struct Fixture {
+        pool: DbPool,
+        agent: Agent,
+        app_resource_dir: TempDir,
+        fruit: Resource,
+        train: Resource,
+    }
+
+    // 使用模拟模型的 AI 用户关联两个内容无关的文本资源
+    fn fixture() -> Fixture {
+        let TestUsers { pool, human: user, ai } = create_test_users();
+        let config = AgentConfig { embedding_model: Some(MOCK_MODEL.to_string()), ..mock_agent_config() };
+        let agent = AgentService::update_agent_config(&pool, &ai.id, config).unwrap();
+
+        let app_resource_dir = TempDir::new();
+        let app_resource_path = app_resource_dir.path();
+        std::fs::create_dir_all(app_resource_path.join(TEXTS_DIR_NAME)).unwrap();
+        let fruit = ResourceService::create_text_resource(
+            &pool, &user.id, "水果", "苹果和香蕉的价格今天上涨了", None, app_resource_path,
+        )
+        .unwrap();
+        let train = ResourceService::create_text_resource(
+            &pool, &user.id, "列车", "明早八点的高铁从北京南站出发", None, app_resource_path,
+        )
+        .unwrap();
+        KnowledgeService::set_agent_resources(&pool, &ai.id, &[fruit.id.clone(), train.id.clone()]).unwrap();
+
+        Fixture { pool, agent, app_resource_dir, fruit, train }
+    }
+
+    fn chunk_contents(pool: &DbPool, resource_id: &str) -> Vec<String> {
+        let mut conn = pool.get().unwrap();
+        KnowledgeRepository::get_chunks_with_conn(&mut conn, &[resource_id.to_string()], "mock", MOCK_MODEL)
+            .unwrap()
+            .into_iter()
+            .map(|chunk| chunk.content)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn retrieve_indexes_resources_and_ranks_relevant_chunk_first() {
+        let Fixture { pool, agent, app_resource_dir, fruit, train } = fixture();
+        let app_resource_path = app_resource_dir.path();
+        let provider = MockProvider::new();
+
+        let chunks = KnowledgeService::retrieve(&pool, &provider, &agent, app_resource_path, "香蕉的价格")
+            .await
+            .unwrap();
+        assert_eq!(chunks[0].resource_id, fruit.id);
+        assert_eq!(chunks[0].resource_name, "水果");
+        assert_eq!(chunks[0].content, "苹果和香蕉的价格今天上涨了");
+        assert_eq!(chunk_contents(&pool, &train.id).len(), 1);
+
+        let empty = KnowledgeService::retrieve(&pool, &provider, &agent, app_resource_path, "  ").await.unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[tokio::test]
+    async fn index_resource_re_embeds_when_content_hash_changes() {
+        let Fixture { pool, agent, app_resource_dir, fruit, .. } = fixture();
+        let app_resource_path = app_resource_dir.path();
+        let provider = MockProvider::new();
+        let model = AgentService::embedding_model(&agent);
+
+        KnowledgeService::index_resource(&pool, &provider, model, &fruit, app_resource_path).await.unwrap();
+        assert_eq!(chunk_contents(&pool, &fruit.id), vec!["苹果和香蕉的价格今天上涨了".to_string()]);
+
+        // 内容未变化时保留原片段
+        KnowledgeService::index_resource(&pool, &provider, model, &fruit, app_resource_path).await.unwrap();
+        assert_eq!(chunk_contents(&pool, &fruit.id), vec!["苹果和香蕉的价格今天上涨了".to_string()]);
+
+        let path = app_resource_path.join(TEXTS_DIR_NAME).join(&fruit.file_name);
+        std::fs::write(&path, "葡萄降价了").unwrap();
+        KnowledgeService::index_resource(&pool, &provider, model, &fruit, app_resource_path).await.unwrap();
+        assert_eq!(chunk_contents(&pool, &fruit.id), vec!["葡萄降价了".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn retrieve_skips_resources_not_modified_since_indexing() {
+        let Fixture { pool, agent, app_resource_dir, fruit, .. } = fixture();
+        let app_resource_path = app_resource_dir.path();
+        let provider = MockProvider::new();
+        KnowledgeService::retrieve(&pool, &provider, &agent, app_resource_path, "价格").await.unwrap();
+
+        // 绕过应用直接改写文件，资源的修改时间不变，检索时不会重新读取
+        let path = app_resource_path.join(TEXTS_DIR_NAME).join(&fruit.file_name);
+        std::fs::write(&path, "葡萄降价了").unwrap();
+        KnowledgeService::retrieve(&pool, &provider, &agent, app_resource_path, "价格").await.unwrap();
+        assert_eq!(chunk_contents(&pool, &fruit.id), vec!["苹果和香蕉的价格今天上涨了".to_string()]);
+
+        // 通过应用修改内容后重新生成
+        ResourceService::update_text_resource_content(&pool, &fruit.id, "葡萄降价了", app_resource_path).unwrap();
+        KnowledgeService::retrieve(&pool, &provider, &agent, app_resource_path, "价格").await.unwrap();
+        assert_eq!(chunk_contents(&pool, &fruit.id), vec!["葡萄降价了".to_string()]);
+    }
+}
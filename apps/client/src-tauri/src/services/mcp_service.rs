@@ -0,0 +1,256 @@
+// MCP 服务器配置服务
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use chrono::Utc;
+use diesel::connection::Connection;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{DbConnection, DbPool};
+use crate::models::{McpServer, McpServerChangeset, NewMcpServer};
+use crate::repositories::error::RepositoryError;
+use crate::repositories::mcp_server_repository::McpServerRepository;
+use crate::repositories::user_repository::UserRepository;
+use super::ServiceResult;
+
+// 服务器名称的最大长度，名称作为工具名称的前缀，需为工具名称留出长度
+const MAX_SERVER_NAME_CHARS: usize = 32;
+// 单个 AI 用户最多启用的服务器数
+const MAX_AGENT_SERVERS: usize = 16;
+
+// MCP 服务器的启动配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct McpServerConfig {
+    pub name: String,                   // 只能包含字母、数字、下划线和连字符
+    pub command: String,                // 可执行文件，不经过 shell 解析
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,  // 在应用环境变量的基础上追加
+    pub cwd: Option<String>,
+    pub enabled: bool,
+}
+
+impl Default for McpServerConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            command: String::new(),
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            cwd: None,
+            enabled: true,
+        }
+    }
+}
+
+impl From<&McpServer> for McpServerConfig {
+    fn from(server: &McpServer) -> Self {
+        Self {
+            name: server.name.clone(),
+            command: server.command.clone(),
+            args: McpService::decode_args(&server.args),
+            env: McpService::decode_env(&server.env),
+            cwd: server.cwd.clone(),
+            enabled: server.enabled,
+        }
+    }
+}
+
+pub struct McpService;
+
+impl McpService {
+    // 获取全部 MCP 服务器配置
+    pub fn get_servers(pool: &DbPool) -> ServiceResult<Vec<McpServer>> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+        McpServerRepository::get_all_with_conn(&mut conn).map_err(|e| anyhow!("获取MCP服务器列表失败: {}", e))
+    }
+
+    // 获取 MCP 服务器配置
+    pub fn get_server(pool: &DbPool, id: &str) -> ServiceResult<McpServer> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+        Self::get_server_with_conn(&mut conn, id)
+    }
+
+    // 添加 MCP 服务器，名称不能与已有服务器重复
+    pub fn create_server(pool: &DbPool, config: McpServerConfig) -> ServiceResult<McpServer> {
+        let config = Self::normalize(config);
+        Self::validate(&config)?;
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            Self::check_name_available_with_conn(conn, &config.name, None)?;
+
+            let now = Utc::now().naive_utc();
+            let new_server = NewMcpServer {
+                id: Uuid::new_v4().to_string(),
+                args: Self::encode_args(&config.args),
+                env: Self::encode_env(&config.env),
+                name: config.name,
+                command: config.command,
+                cwd: config.cwd,
+                enabled: config.enabled,
+                created_at: now,
+                updated_at: now,
+            };
+            McpServerRepository::create_with_conn(conn, &new_server).map_err(|e| anyhow!("添加MCP服务器失败: {}", e))
+        })
+    }
+
+    // 整体替换 MCP 服务器配置
+    pub fn update_server(pool: &DbPool, id: &str, config: McpServerConfig) -> ServiceResult<McpServer> {
+        let config = Self::normalize(config);
+        Self::validate(&config)?;
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            Self::get_server_with_conn(conn, id)?;
+            Self::check_name_available_with_conn(conn, &config.name, Some(id))?;
+
+            let changeset = McpServerChangeset {
+                args: Self::encode_args(&config.args),
+                env: Self::encode_env(&config.env),
+                name: config.name,
+                command: config.command,
+                cwd: config.cwd,
+                enabled: config.enabled,
+                updated_at: Utc::now().naive_utc(),
+            };
+            McpServerRepository::update_with_conn(conn, id, &changeset).map_err(|e| anyhow!("更新MCP服务器失败: {}", e))
+        })
+    }
+
+    // 删除 MCP 服务器，各 AI 用户对其的启用一并删除
+    pub fn delete_server(pool: &DbPool, id: &str) -> ServiceResult<()> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            McpServerRepository::delete_with_conn(conn, id).map_err(|e| match e {
+                RepositoryError::NotFound => anyhow!("MCP服务器不存在"),
+                e => anyhow!("删除MCP服务器失败: {}", e),
+            })
+        })
+    }
+
+    // 获取 AI 用户启用的 MCP 服务器，包括已停用的服务器
+    pub fn get_agent_servers(pool: &DbPool, agent_user_id: &str) -> ServiceResult<Vec<McpServer>> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+        Self::check_ai_user_with_conn(&mut conn, agent_user_id)?;
+        McpServerRepository::get_by_agent_with_conn(&mut conn, agent_user_id, false)
+            .map_err(|e| anyhow!("获取AI用户的MCP服务器失败: {}", e))
+    }
+
+    // 获取 AI 用户回复时可以使用的 MCP 服务器，即 AI 用户启用且服务器本身未停用的
+    pub fn get_active_agent_servers(pool: &DbPool, agent_user_id: &str) -> ServiceResult<Vec<McpServer>> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+        McpServerRepository::get_by_agent_with_conn(&mut conn, agent_user_id, true)
+            .map_err(|e| anyhow!("获取AI用户的MCP服务器失败: {}", e))
+    }
+
+    // 将 AI 用户启用的 MCP 服务器整体替换为给定的服务器
+    pub fn set_agent_servers(pool: &DbPool, agent_user_id: &str, server_ids: &[String]) -> ServiceResult<Vec<McpServer>> {
+        let mut server_ids_dedup: Vec<String> = Vec::new();
+        for id in server_ids {
+            if !server_ids_dedup.contains(id) {
+                server_ids_dedup.push(id.clone());
+            }
+        }
+        if server_ids_dedup.len() > MAX_AGENT_SERVERS {
+            return Err(anyhow!("每个AI用户最多启用{}个MCP服务器", MAX_AGENT_SERVERS));
+        }
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        conn.transaction(|conn| {
+            Self::check_ai_user_with_conn(conn, agent_user_id)?;
+            for id in &server_ids_dedup {
+                Self::get_server_with_conn(conn, id)?;
+            }
+            McpServerRepository::set_agent_servers_with_conn(conn, agent_user_id, &server_ids_dedup)
+                .map_err(|e| anyhow!("保存AI用户的MCP服务器失败: {}", e))?;
+            McpServerRepository::get_by_agent_with_conn(conn, agent_user_id, false)
+                .map_err(|e| anyhow!("获取AI用户的MCP服务器失败: {}", e))
+        })
+    }
+
+    // 解析 JSON 数组格式的启动参数，无法解析时视为没有参数
+    pub fn decode_args(raw: &str) -> Vec<String> {
+        serde_json::from_str(raw).unwrap_or_default()
+    }
+
+    // 解析 JSON 对象格式的环境变量，无法解析时视为没有环境变量
+    pub fn decode_env(raw: &str) -> BTreeMap<String, String> {
+        serde_json::from_str(raw).unwrap_or_default()
+    }
+
+    fn encode_args(args: &[String]) -> String {
+        serde_json::to_string(args).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    fn encode_env(env: &BTreeMap<String, String>) -> String {
+        serde_json::to_string(env).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    fn get_server_with_conn(conn: &mut DbConnection, id: &str) -> ServiceResult<McpServer> {
+        McpServerRepository::get_with_conn(conn, id).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("MCP服务器不存在: {}", id),
+            e => anyhow!("获取MCP服务器失败: {}", e),
+        })
+    }
+
+    // 使用已有连接检查名称未被其他服务器使用
+    fn check_name_available_with_conn(conn: &mut DbConnection, name: &str, except_id: Option<&str>) -> ServiceResult<()> {
+        let existing = McpServerRepository::get_by_name_with_conn(conn, name)
+            .map_err(|e| anyhow!("检查MCP服务器名称失败: {}", e))?;
+        match existing {
+            Some(server) if Some(server.id.as_str()) != except_id => Err(anyhow!("MCP服务器名称已存在: {}", name)),
+            _ => Ok(()),
+        }
+    }
+
+    // 使用已有连接检查用户存在且为 AI 用户
+    fn check_ai_user_with_conn(conn: &mut DbConnection, user_id: &str) -> ServiceResult<()> {
+        let user = UserRepository::get_with_conn(conn, user_id).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("用户不存在"),
+            e => anyhow!("获取用户信息失败: {}", e),
+        })?;
+        if !user.is_ai {
+            return Err(anyhow!("该用户不是AI用户"));
+        }
+        Ok(())
+    }
+
+    // 去除首尾空白，空的工作目录视为未设置
+    fn normalize(mut config: McpServerConfig) -> McpServerConfig {
+        config.name = config.name.trim().to_string();
+        config.command = config.command.trim().to_string();
+        config.cwd = config.cwd.map(|cwd| cwd.trim().to_string()).filter(|cwd| !cwd.is_empty());
+        config.env = config
+            .env
+            .into_iter()
+            .map(|(key, value)| (key.trim().to_string(), value))
+            .collect();
+        config
+    }
+
+    fn validate(config: &McpServerConfig) -> ServiceResult<()> {
+        if config.name.is_empty() {
+            return Err(anyhow!("MCP服务器名称不能为空"));
+        }
+        if config.name.chars().count() > MAX_SERVER_NAME_CHARS {
+            return Err(anyhow!("MCP服务器名称不能超过{}个字符", MAX_SERVER_NAME_CHARS));
+        }
+        if !config.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(anyhow!("MCP服务器名称只能包含字母、数字、下划线和连字符"));
+        }
+        if config.command.is_empty() {
+            return Err(anyhow!("启动命令不能为空"));
+        }
+        if let Some(key) = config.env.keys().find(|key| key.is_empty() || key.contains('=')) {
+            return Err(anyhow!("环境变量名称不合法: {:?}", key));
+        }
+        Ok(())
+    }
+}
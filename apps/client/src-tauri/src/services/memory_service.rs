@@ -0,0 +1,584 @@
+// AI 用户长期记忆服务
+//
+// 开启记忆的 AI 用户在对话后由自己的模型按提取提示词从新消息中提取值得长期记住的事实，保存在 agent_memories 表中；
+// 每个聊天记录已提取到的位置，只处理之后的消息。回复时置顶的记忆总是放入系统提示词，
+// 其余记忆按与当前消息的字面相关度和重要程度挑选，由上下文组装在预算内放入
+use std::collections::{HashMap, HashSet};
+
+use anyhow::anyhow;
+use chrono::Utc;
+use diesel::connection::Connection;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::db::DbPool;
+use crate::llm::{CompletionRequest, LlmMessage, LlmProvider, Role, SamplingOptions};
+use crate::models::{Agent, AgentMemory, Message, MessageKind, MessageStatus, NewAgentMemory};
+use crate::repositories::agent_memory_repository::AgentMemoryRepository;
+use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+use crate::repositories::error::RepositoryError;
+use crate::repositories::message_repository::MessageRepository;
+use crate::repositories::user_repository::UserRepository;
+use super::ServiceResult;
+
+// 未配置提取提示词时使用的默认提示词
+pub const DEFAULT_EXTRACTION_PROMPT: &str = "你负责为 AI 助手整理长期记忆。请从对话中找出以后的对话仍然有用的事实，\
+     例如用户的身份、偏好、习惯、计划、重要的约定和结论；忽略寒暄、一次性的问题和已有记忆中已经包含的内容。\
+     每条记忆用一句完整的中文陈述，不依赖上下文也能看懂。";
+// 对话后自动提取时至少需要的新消息条数
+pub const MIN_EXTRACTION_MESSAGES: usize = 6;
+
+// 每次提取最多处理的消息条数，更多的消息留到下次提取
+const MAX_EXTRACTION_MESSAGES: i64 = 50;
+// 提取时每条消息最多保留的字符数
+const MAX_TRANSCRIPT_MESSAGE_CHARS: usize = 1000;
+// 提取时提供给模型用于去重的已有记忆条数
+const MAX_EXISTING_IN_PROMPT: usize = 50;
+// 提取请求的 max_tokens
+const EXTRACTION_MAX_TOKENS: i32 = 1024;
+// 单个 AI 用户最多保存的记忆条数，超出时清理未置顶的记忆中重要程度最低、最久未更新的
+const MAX_AGENT_MEMORIES: usize = 500;
+// 单条记忆的最大字符数
+const MAX_MEMORY_CHARS: usize = 500;
+// 重要程度的取值范围和默认值
+const IMPORTANCE_RANGE: (i32, i32) = (1, 5);
+const DEFAULT_IMPORTANCE: i32 = 3;
+// 重要程度不低于该值的记忆即使与当前消息无关也可以放入上下文
+const HIGH_IMPORTANCE: i32 = 4;
+// 每次回复最多放入上下文的未置顶记忆条数
+const MAX_RETRIEVED_MEMORIES: usize = 20;
+// 排序时重要程度每级相当的相关度
+const IMPORTANCE_WEIGHT: f32 = 0.05;
+
+// 模型提取结果中的一条记忆，source 为对话记录中的行号
+#[derive(Debug, Deserialize)]
+struct ExtractedMemory {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    importance: Option<i32>,
+    #[serde(default)]
+    source: Option<usize>,
+}
+
+// 模型输出的 JSON
+#[derive(Debug, Default, Deserialize)]
+struct ExtractionResult {
+    #[serde(default)]
+    memories: Vec<ExtractedMemory>,
+}
+
+pub struct MemoryService;
+
+impl MemoryService {
+    // 获取 AI 用户的全部记忆，置顶的在前，其余按重要程度和更新时间降序
+    pub fn get_agent_memories(pool: &DbPool, agent_user_id: &str) -> ServiceResult<Vec<AgentMemory>> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        AgentMemoryRepository::get_by_agent_with_conn(&mut conn, agent_user_id)
+            .map_err(|e| anyhow!("获取AI用户的记忆失败: {}", e))
+    }
+
+    // 修改记忆的内容和重要程度
+    pub fn update_memory(pool: &DbPool, id: &str, content: &str, importance: i32) -> ServiceResult<AgentMemory> {
+        let content = content.trim();
+        if content.is_empty() {
+            return Err(anyhow!("记忆内容不能为空"));
+        }
+        if content.chars().count() > MAX_MEMORY_CHARS {
+            return Err(anyhow!("记忆内容不能超过{}个字符", MAX_MEMORY_CHARS));
+        }
+        let (min, max) = IMPORTANCE_RANGE;
+        if !(min..=max).contains(&importance) {
+            return Err(anyhow!("重要程度必须在 {} 到 {} 之间，当前为 {}", min, max, importance));
+        }
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        AgentMemoryRepository::update_with_conn(&mut conn, id, content, importance).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("记忆不存在"),
+            e => anyhow!("修改记忆失败: {}", e),
+        })
+    }
+
+    // 置顶或取消置顶记忆
+    pub fn set_memory_pinned(pool: &DbPool, id: &str, is_pinned: bool) -> ServiceResult<AgentMemory> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        AgentMemoryRepository::set_pinned_with_conn(&mut conn, id, is_pinned).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("记忆不存在"),
+            e => anyhow!("更新记忆失败: {}", e),
+        })
+    }
+
+    // 删除记忆
+    pub fn delete_memory(pool: &DbPool, id: &str) -> ServiceResult<()> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        let deleted = AgentMemoryRepository::delete_by_ids_with_conn(&mut conn, &[id.to_string()])
+            .map_err(|e| anyhow!("删除记忆失败: {}", e))?;
+        if deleted == 0 {
+            return Err(anyhow!("记忆不存在"));
+        }
+        Ok(())
+    }
+
+    // 从聊天中上次提取位置之后的消息提取记忆，返回新保存的记忆
+    //
+    // 新消息少于 min_messages 条时不提取；遇到生成中的消息时只处理它之前的消息。
+    // 模型的输出无法解析时返回错误且不推进提取位置，与已有记忆重复的内容跳过
+    pub async fn extract_from_chat(
+        pool: &DbPool,
+        provider: &dyn LlmProvider,
+        agent: &Agent,
+        chat_id: &str,
+        min_messages: usize,
+    ) -> ServiceResult<Vec<AgentMemory>> {
+        // 1. 读取提取位置之后的消息和已有记忆
+        let (cursor, existing) = {
+            let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+            let is_participant = ChatParticipantRepository::exists_with_conn(&mut conn, chat_id, &agent.user_id)
+                .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+            if !is_participant {
+                return Err(anyhow!("该AI用户不是该聊天的参与者"));
+            }
+            let cursor = ChatParticipantRepository::get_memory_cursor_with_conn(&mut conn, chat_id, &agent.user_id)
+                .map_err(|e| anyhow!("获取记忆提取位置失败: {}", e))?;
+            let existing = AgentMemoryRepository::get_by_agent_with_conn(&mut conn, &agent.user_id)
+                .map_err(|e| anyhow!("获取AI用户的记忆失败: {}", e))?;
+            (cursor, existing)
+        };
+
+        let page = MessageRepository::get_page_after(
+            pool,
+            chat_id,
+            cursor.as_ref().map(|(created_at, id)| (*created_at, id.as_str())),
+            MAX_EXTRACTION_MESSAGES,
+        )
+        .map_err(|e| anyhow!("获取聊天记录失败: {}", e))?;
+        let page: Vec<Message> = page
+            .into_iter()
+            .take_while(|message| message.status != MessageStatus::Streaming.as_str())
+            .collect();
+        let Some(last) = page.last() else {
+            return Ok(Vec::new());
+        };
+        let last = (last.id.clone(), last.created_at);
+
+        // 工具调用和工具结果不参与提取
+        let messages: Vec<&Message> = page
+            .iter()
+            .filter(|message| message.kind == MessageKind::Text.as_str() && !message.content.trim().is_empty())
+            .collect();
+        if messages.len() < min_messages.max(1) {
+            return Ok(Vec::new());
+        }
+
+        // 2. 请求模型提取
+        let transcript = Self::transcript(pool, &messages)?;
+        let request = CompletionRequest {
+            model: agent.model_name.clone(),
+            messages: Self::extraction_messages(agent, &existing, &transcript),
+            options: SamplingOptions {
+                temperature: Some(0.0),
+                max_tokens: Some(EXTRACTION_MAX_TOKENS),
+                ..Default::default()
+            },
+            json_mode: true,
+            tools: Vec::new(),
+        };
+        let response = provider.complete(&request).await?;
+        let extracted = Self::parse_extraction(&response.content)
+            .ok_or_else(|| anyhow!("无法解析模型提取的记忆: {}", truncate_chars(&response.content, 200)))?;
+
+        // 3. 去重后保存，清理超出上限的记忆并推进提取位置
+        let mut seen: HashSet<String> = existing.iter().map(|memory| dedup_key(&memory.content)).collect();
+        let now = Utc::now().naive_utc();
+        let mut new_memories = Vec::new();
+        for item in extracted {
+            let content = truncate_chars(item.content.trim(), MAX_MEMORY_CHARS);
+            if content.is_empty() || !seen.insert(dedup_key(&content)) {
+                continue;
+            }
+            let source = item
+                .source
+                .and_then(|line| line.checked_sub(1))
+                .and_then(|index| messages.get(index))
+                .or(messages.last());
+            new_memories.push(NewAgentMemory {
+                id: Uuid::new_v4().to_string(),
+                agent_user_id: agent.user_id.clone(),
+                content,
+                source_chat_id: Some(chat_id.to_string()),
+                source_message_id: source.map(|message| message.id.clone()),
+                importance: item
+                    .importance
+                    .unwrap_or(DEFAULT_IMPORTANCE)
+                    .clamp(IMPORTANCE_RANGE.0, IMPORTANCE_RANGE.1),
+                is_pinned: false,
+                created_at: now,
+                updated_at: now,
+            });
+        }
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+        conn.transaction(|conn| {
+            if !new_memories.is_empty() {
+                AgentMemoryRepository::create_with_conn(conn, &new_memories)
+                    .map_err(|e| anyhow!("保存AI记忆失败: {}", e))?;
+            }
+            ChatParticipantRepository::advance_memory_cursor_with_conn(conn, chat_id, &agent.user_id, &last.0, last.1)
+                .map_err(|e| anyhow!("更新记忆提取位置失败: {}", e))?;
+
+            let memories = AgentMemoryRepository::get_by_agent_with_conn(conn, &agent.user_id)
+                .map_err(|e| anyhow!("获取AI用户的记忆失败: {}", e))?;
+            let pruned = Self::prune_candidates(&memories);
+            if !pruned.is_empty() {
+                AgentMemoryRepository::delete_by_ids_with_conn(conn, &pruned)
+                    .map_err(|e| anyhow!("清理AI记忆失败: {}", e))?;
+            }
+
+            let new_ids: HashSet<&str> = new_memories.iter().map(|memory| memory.id.as_str()).collect();
+            Ok(memories
+                .into_iter()
+                .filter(|memory| new_ids.contains(memory.id.as_str()) && !pruned.contains(&memory.id))
+                .collect())
+        })
+    }
+
+    // 挑选回复时放入上下文的记忆：全部置顶的记忆，以及与当前消息相关或重要程度较高的其他记忆
+    //
+    // 结果按放入的优先顺序排列，上下文组装时在预算内依次放入；query 为空时只按重要程度挑选
+    pub fn retrieve(pool: &DbPool, agent_user_id: &str, query: Option<&str>) -> ServiceResult<Vec<AgentMemory>> {
+        let memories = Self::get_agent_memories(pool, agent_user_id)?;
+        let query_bigrams = query.map(bigrams).unwrap_or_default();
+
+        let (pinned, others): (Vec<AgentMemory>, Vec<AgentMemory>) =
+            memories.into_iter().partition(|memory| memory.is_pinned);
+        let mut scored: Vec<(f32, AgentMemory)> = others
+            .into_iter()
+            .filter_map(|memory| {
+                let relevance = overlap(&bigrams(&memory.content), &query_bigrams);
+                if relevance <= 0.0 && memory.importance < HIGH_IMPORTANCE {
+                    return None;
+                }
+                Some((relevance + memory.importance as f32 * IMPORTANCE_WEIGHT, memory))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        Ok(pinned
+            .into_iter()
+            .chain(scored.into_iter().take(MAX_RETRIEVED_MEMORIES).map(|(_, memory)| memory))
+            .collect())
+    }
+
+    // 带行号的对话记录，行号从 1 开始，模型以行号注明记忆的来源
+    fn transcript(pool: &DbPool, messages: &[&Message]) -> ServiceResult<String> {
+        let sender_ids: Vec<String> = messages
+            .iter()
+            .map(|message| message.sender_id.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let names: HashMap<String, String> = UserRepository::get_by_ids(pool, &sender_ids)
+            .map_err(|e| anyhow!("获取消息发送者失败: {}", e))?
+            .into_iter()
+            .map(|user| (user.id, user.name))
+            .collect();
+
+        Ok(messages
+            .iter()
+            .enumerate()
+            .map(|(index, message)| {
+                let name = names.get(&message.sender_id).map(String::as_str).unwrap_or("未知用户");
+                format!(
+                    "[{}] {}：{}",
+                    index + 1,
+                    name,
+                    truncate_chars(&message.content, MAX_TRANSCRIPT_MESSAGE_CHARS)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    // 请求模型提取记忆的消息：提取提示词和输出格式说明，已有记忆和对话记录
+    fn extraction_messages(agent: &Agent, existing: &[AgentMemory], transcript: &str) -> Vec<LlmMessage> {
+        let prompt = agent
+            .memory_extraction_prompt
+            .as_deref()
+            .filter(|prompt| !prompt.trim().is_empty())
+            .unwrap_or(DEFAULT_EXTRACTION_PROMPT);
+        let instruction = format!(
+            "{}\n只输出 JSON，格式为 {{\"memories\": [{{\"content\": \"记忆内容\", \"importance\": 3, \"source\": 1}}]}}，\
+             importance 为 1 到 5 的重要程度，source 为记忆所依据的对话行号；没有值得记住的内容时输出空数组。",
+            prompt
+        );
+
+        let existing = existing
+            .iter()
+            .take(MAX_EXISTING_IN_PROMPT)
+            .map(|memory| format!("- {}", memory.content))
+            .collect::<Vec<_>>();
+        let content = if existing.is_empty() {
+            format!("对话：\n{}", transcript)
+        } else {
+            format!("已有记忆：\n{}\n\n对话：\n{}", existing.join("\n"), transcript)
+        };
+
+        vec![
+            LlmMessage::new(Role::System, instruction),
+            LlmMessage::new(Role::User, content),
+        ]
+    }
+
+    // 解析模型的输出，无法解析时返回 None
+    fn parse_extraction(content: &str) -> Option<Vec<ExtractedMemory>> {
+        // 模型可能在 JSON 前后附带说明文字，只取最外层的花括号部分
+        let json = match (content.find('{'), content.rfind('}')) {
+            (Some(start), Some(end)) if start < end => &content[start..=end],
+            _ => content,
+        };
+        serde_json::from_str::<ExtractionResult>(json).ok().map(|result| result.memories)
+    }
+
+    // 超出上限时需要清理的记忆：未置顶的记忆中重要程度最低、最久未更新的
+    fn prune_candidates(memories: &[AgentMemory]) -> Vec<String> {
+        let excess = memories.len().saturating_sub(MAX_AGENT_MEMORIES);
+        if excess == 0 {
+            return Vec::new();
+        }
+        let mut candidates: Vec<&AgentMemory> = memories.iter().filter(|memory| !memory.is_pinned).collect();
+        candidates.sort_by_key(|memory| (memory.importance, memory.updated_at));
+        candidates.into_iter().take(excess).map(|memory| memory.id.clone()).collect()
+    }
+}
+
+// 截取开头的 max_chars 个字符
+fn truncate_chars(content: &str, max_chars: usize) -> String {
+    match content.char_indices().nth(max_chars) {
+        Some((end, _)) => content[..end].to_string(),
+        None => content.to_string(),
+    }
+}
+
+// 判断重复的键：去掉空白和标点并统一大小写
+fn dedup_key(content: &str) -> String {
+    content
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// 文本中相邻两个字符组成的词元，按非字母数字的字符分隔，单个字符的词单独作为一个词元
+fn bigrams(text: &str) -> HashSet<(char, char)> {
+    let mut result = HashSet::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        let chars: Vec<char> = word.chars().flat_map(char::to_lowercase).collect();
+        if chars.len() == 1 {
+            result.insert((chars[0], ' '));
+        }
+        for pair in chars.windows(2) {
+            result.insert((pair[0], pair[1]));
+        }
+    }
+    result
+}
+
+// 记忆的词元在当前消息中出现的比例
+fn overlap(memory: &HashSet<(char, char)>, query: &HashSet<(char, char)>) -> f32 {
+    if memory.is_empty() || query.is_empty() {
+        return 0.0;
+    }
+    memory.intersection(query).count() as f32 / memory.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDateTime, TimeDelta};
+
+    use super::*;
+    use crate::llm::mock::{MockProvider, MockReply};
+    use crate::services::agent_service::{AgentConfig, AgentService};
+    use crate::test_support::{create_test_chat, create_test_pool, mock_agent_config, TestChat};
+
+    fn memory(id: &str, content: &str, importance: i32, is_pinned: bool, updated_at: NaiveDateTime) -> AgentMemory {
+        AgentMemory {
+            id: id.to_string(),
+            agent_user_id: "agent".to_string(),
+            content: content.to_string(),
+            source_chat_id: None,
+            source_message_id: None,
+            importance,
+            is_pinned,
+            created_at: updated_at,
+            updated_at,
+        }
+    }
+
+    fn new_memory(agent_user_id: &str, content: &str, importance: i32, is_pinned: bool) -> NewAgentMemory {
+        let now = Utc::now().naive_utc();
+        NewAgentMemory {
+            id: Uuid::new_v4().to_string(),
+            agent_user_id: agent_user_id.to_string(),
+            content: content.to_string(),
+            source_chat_id: None,
+            source_message_id: None,
+            importance,
+            is_pinned,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn parses_extraction_with_surrounding_text() {
+        let memories = MemoryService::parse_extraction(
+            "好的，结果如下：\n{\"memories\": [{\"content\": \"用户喜欢咖啡\", \"importance\": 4, \"source\": 2}]}\n以上。",
+        )
+        .unwrap();
+        assert_eq!(memories.len(), 1);
+        assert_eq!(memories[0].content, "用户喜欢咖啡");
+        assert_eq!(memories[0].importance, Some(4));
+        assert_eq!(memories[0].source, Some(2));
+
+        assert!(MemoryService::parse_extraction("{}").unwrap().is_empty());
+        assert!(MemoryService::parse_extraction("没有值得记住的内容").is_none());
+        assert!(MemoryService::parse_extraction("{\"memories\": \"无\"}").is_none());
+    }
+
+    #[test]
+    fn dedup_key_ignores_whitespace_punctuation_and_case() {
+        assert_eq!(dedup_key("用户 喜欢 Coffee！"), dedup_key("用户喜欢coffee"));
+        assert_ne!(dedup_key("用户喜欢咖啡"), dedup_key("用户喜欢茶"));
+    }
+
+    #[test]
+    fn prunes_least_important_and_oldest_unpinned_memories() {
+        let now = Utc::now().naive_utc();
+        let mut memories: Vec<AgentMemory> = (0..MAX_AGENT_MEMORIES - 1)
+            .map(|index| memory(&format!("keep-{}", index), "记忆", 3, false, now))
+            .collect();
+        memories.push(memory("pinned", "置顶", 1, true, now - TimeDelta::days(10)));
+        memories.push(memory("old", "旧的", 2, false, now - TimeDelta::days(5)));
+        memories.push(memory("new", "新的", 2, false, now));
+        assert!(MemoryService::prune_candidates(&memories[..MAX_AGENT_MEMORIES]).is_empty());
+
+        assert_eq!(MemoryService::prune_candidates(&memories), vec!["old".to_string(), "new".to_string()]);
+    }
+
+    #[test]
+    fn retrieve_puts_pinned_first_then_relevant_and_important() {
+        let pool = create_test_pool();
+        let agent = UserRepository::create(&pool, "助手".to_string(), None, true).unwrap();
+        {
+            let mut conn = pool.get().unwrap();
+            AgentMemoryRepository::create_with_conn(
+                &mut conn,
+                &[
+                    new_memory(&agent.id, "用户的名字是小王", 1, true),
+                    new_memory(&agent.id, "用户住在上海", 5, false),
+                    new_memory(&agent.id, "用户每天早上喝咖啡", 2, false),
+                    new_memory(&agent.id, "用户养了一只猫", 2, false),
+                ],
+            )
+            .unwrap();
+        }
+
+        let contents = |query: Option<&str>| -> Vec<String> {
+            MemoryService::retrieve(&pool, &agent.id, query)
+                .unwrap()
+                .into_iter()
+                .map(|memory| memory.content)
+                .collect()
+        };
+        assert_eq!(
+            contents(Some("早上喝咖啡好吗")),
+            vec!["用户的名字是小王", "用户每天早上喝咖啡", "用户住在上海"]
+        );
+        // 没有当前消息时只挑选重要程度较高的记忆
+        assert_eq!(contents(None), vec!["用户的名字是小王", "用户住在上海"]);
+    }
+
+    struct Fixture {
+        pool: DbPool,
+        agent: Agent,
+        chat_id: String,
+        messages: Vec<Message>,
+    }
+
+    // 真人用户与 AI 用户的单聊中有三条消息，AI 用户已有一条记忆
+    fn fixture() -> Fixture {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let config = AgentConfig { memory_enabled: true, ..mock_agent_config() };
+        let agent = AgentService::update_agent_config(&pool, &ai.id, config).unwrap();
+
+        let messages = ["我住在上海", "好的，记住了", "我对花生过敏"]
+            .iter()
+            .enumerate()
+            .map(|(index, content)| {
+                let sender_id = if index % 2 == 0 { &human.id } else { &ai.id };
+                MessageRepository::create(&pool, content.to_string(), &chat.id, sender_id).unwrap()
+            })
+            .collect();
+        let mut conn = pool.get().unwrap();
+        AgentMemoryRepository::create_with_conn(&mut conn, &[new_memory(&ai.id, "用户住在上海", 3, false)]).unwrap();
+
+        Fixture { pool, agent, chat_id: chat.id, messages }
+    }
+
+    #[tokio::test]
+    async fn extracts_new_memories_and_advances_cursor() {
+        let Fixture { pool, agent, chat_id, messages } = fixture();
+        let provider = MockProvider::new();
+        provider.push_reply(MockReply::Text(
+            r#"{"memories": [
+                {"content": "用户住在 上海。", "importance": 3, "source": 1},
+                {"content": "用户对花生过敏", "importance": 9, "source": 3},
+                {"content": "用户喜欢简短的回答"},
+                {"content": "  "}
+            ]}"#
+            .to_string(),
+        ));
+
+        let extracted = MemoryService::extract_from_chat(&pool, &provider, &agent, &chat_id, 1).await.unwrap();
+        assert_eq!(extracted.len(), 2);
+        let allergy = extracted.iter().find(|memory| memory.content == "用户对花生过敏").unwrap();
+        assert_eq!(allergy.importance, 5);
+        assert_eq!(allergy.source_message_id.as_deref(), Some(messages[2].id.as_str()));
+        assert_eq!(allergy.source_chat_id.as_deref(), Some(chat_id.as_str()));
+        // 未注明来源时使用最后一条消息
+        let brief = extracted.iter().find(|memory| memory.content == "用户喜欢简短的回答").unwrap();
+        assert_eq!(brief.importance, DEFAULT_IMPORTANCE);
+        assert_eq!(brief.source_message_id.as_deref(), Some(messages[2].id.as_str()));
+        assert_eq!(MemoryService::get_agent_memories(&pool, &agent.user_id).unwrap().len(), 3);
+
+        // 没有新消息时不请求模型，脚本已用完，请求模型会得到无法解析的回声
+        let extracted = MemoryService::extract_from_chat(&pool, &provider, &agent, &chat_id, 1).await.unwrap();
+        assert!(extracted.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unparsable_output_keeps_cursor() {
+        let Fixture { pool, agent, chat_id, .. } = fixture();
+        let provider = MockProvider::new();
+        provider.push_reply(MockReply::Text("抱歉，我无法完成".to_string()));
+        assert!(MemoryService::extract_from_chat(&pool, &provider, &agent, &chat_id, 1).await.is_err());
+
+        provider.push_reply(MockReply::Text(r#"{"memories": [{"content": "用户对花生过敏"}]}"#.to_string()));
+        let extracted = MemoryService::extract_from_chat(&pool, &provider, &agent, &chat_id, 1).await.unwrap();
+        assert_eq!(extracted.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn skips_extraction_below_min_messages() {
+        let Fixture { pool, agent, chat_id, .. } = fixture();
+        let provider = MockProvider::new();
+        let extracted = MemoryService::extract_from_chat(&pool, &provider, &agent, &chat_id, MIN_EXTRACTION_MESSAGES)
+            .await
+            .unwrap();
+        assert!(extracted.is_empty());
+    }
+}
@@ -1 +1,1341 @@
 // 消息相关服务
+use std::collections::{HashMap, HashSet};
+
+use anyhow::anyhow;
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{self, DbConnection, DbPool};
+use crate::llm::ToolCall;
+use crate::models::{
+    Message, MessageKind, MessageRevision, MessageSearchFilter, MessageSearchRow, MessageStatus,
+};
+use crate::repositories::agent_memory_repository::AgentMemoryRepository;
+use crate::repositories::ai_job_repository::AiJobRepository;
+use crate::repositories::error::RepositoryError;
+use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+use crate::repositories::chat_repository::ChatRepository;
+use crate::repositories::message_repository::MessageRepository;
+use crate::repositories::message_revision_repository::MessageRevisionRepository;
+use crate::repositories::user_repository::UserRepository;
+use super::ServiceResult;
+
+// 分页查询的默认和最大条数
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+// 分页锚点
+pub enum MessagePageAnchor {
+    // 最新的一页
+    Latest,
+    // 指定消息之前的一页
+    Before(String),
+    // 指定消息之后的一页
+    After(String),
+    // 以指定消息为中心的上下文窗口（包含该消息）
+    Around(String),
+}
+
+// 一页消息及其两端是否还有更多
+pub struct MessagePage {
+    pub messages: Vec<Message>,
+    pub has_more_before: bool,
+    pub has_more_after: bool,
+}
+
+// 检索的默认和最大条数
+pub const DEFAULT_SEARCH_LIMIT: i64 = 20;
+pub const MAX_SEARCH_LIMIT: i64 = 100;
+// 检索词的最大个数和单个检索词的最大长度
+const MAX_SEARCH_TERMS: usize = 8;
+const MAX_SEARCH_TERM_CHARS: usize = 100;
+// 摘录中命中位置前后保留的字符数
+const SNIPPET_CONTEXT_CHARS: usize = 30;
+// 摘录被截断时的省略号
+const SNIPPET_ELLIPSIS: &str = "…";
+
+// 检索结果摘录的一段文本，highlighted 为 true 时是命中的检索词
+#[derive(Debug, Clone, Serialize)]
+pub struct SnippetSegment {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+// 检索命中的一条消息及其摘录
+pub struct MessageSearchHit {
+    pub row: MessageSearchRow,
+    pub snippet: Vec<SnippetSegment>,
+}
+
+// 一页检索结果
+pub struct MessageSearchPage {
+    pub hits: Vec<MessageSearchHit>,
+    pub has_more: bool,
+}
+
+// 回复预览的最大字符数
+pub const REPLY_PREVIEW_CHARS: usize = 80;
+
+// 被回复消息的简要预览
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplyPreview {
+    pub id: String,
+    pub sender_id: String,
+    pub sender_name: String,
+    pub content: String, // 超出 REPLY_PREVIEW_CHARS 时截断并以省略号结尾
+    pub status: String,
+}
+
+// 消息及其回复的消息的预览、在兄弟节点中的位置
+pub struct MessageDetail {
+    pub message: Message,
+    pub reply_to: Option<ReplyPreview>,
+    pub sibling_index: i64,
+    pub sibling_count: i64, // 大于 1 时该消息有重新生成的其他版本
+    pub revision_count: i64, // 大于 0 时该消息被编辑过
+}
+
+// 编辑自己发送的消息时对其后续消息的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageEditMode {
+    // 只修改内容，后续消息保持不变
+    #[default]
+    InPlace,
+    // 修改内容并删除其后的所有消息，以便重新获取 AI 回复
+    Truncate,
+    // 以新内容创建一个新版本，原消息及其后续消息保留为另一个分支
+    Branch,
+}
+
+pub struct MessageService;
+
+impl MessageService {
+    // 发送消息
+    //
+    // 校验发送者是否为聊天参与者、写入消息并将发送者的已读位置推进到该消息，三步在同一事务中完成；
+    // reply_to_id 为回复的消息，必须属于同一聊天
+    pub fn send_message(
+        pool: &DbPool,
+        chat_id: &str,
+        sender_id: &str,
+        content: &str,
+        reply_to_id: Option<&str>,
+    ) -> ServiceResult<Message> {
+        if content.trim().is_empty() {
+            return Err(anyhow!("消息内容不能为空"));
+        }
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        db::immediate_transaction(&mut conn, |conn| {
+            // 1. 检查发送者是否在聊天中
+            let is_participant = ChatParticipantRepository::exists_with_conn(conn, chat_id, sender_id)
+                .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+            if !is_participant {
+                return Err(anyhow!("发送者不是该聊天的参与者"));
+            }
+            Self::check_chat_available_with_conn(conn, chat_id)?;
+
+            // 2. 创建消息
+            if let Some(reply_to_id) = reply_to_id {
+                Self::check_reply_target_with_conn(conn, chat_id, reply_to_id)?;
+            }
+            let message = MessageRepository::create_with_conn(conn, content.to_string(), chat_id, sender_id, reply_to_id)
+                .map_err(|e| anyhow!("创建消息失败: {}", e))?;
+
+            // 3. 发送者已读自己的消息
+            ChatParticipantRepository::mark_read_with_conn(conn, chat_id, sender_id, &message.id, message.created_at)
+                .map_err(|e| anyhow!("更新已读位置失败: {}", e))?;
+
+            Ok(message)
+        })
+    }
+
+    // 开始一条流式消息
+    //
+    // 与 send_message 相同，校验参与者、写入消息并推进发送者的已读位置；消息内容为空，状态为 streaming。
+    // parent_id 为重新生成的回复所接的消息，必须仍在当前分支上，其原来选中的后续消息移出当前分支；
+    // 为空时消息接在当前分支的最后一条消息之后
+    pub fn start_streaming_message(
+        pool: &DbPool,
+        chat_id: &str,
+        sender_id: &str,
+        reply_to_id: Option<&str>,
+        parent_id: Option<&str>,
+    ) -> ServiceResult<Message> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        db::immediate_transaction(&mut conn, |conn| {
+            let is_participant = ChatParticipantRepository::exists_with_conn(conn, chat_id, sender_id)
+                .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+            if !is_participant {
+                return Err(anyhow!("发送者不是该聊天的参与者"));
+            }
+            Self::check_chat_available_with_conn(conn, chat_id)?;
+
+            if let Some(reply_to_id) = reply_to_id {
+                Self::check_reply_target_with_conn(conn, chat_id, reply_to_id)?;
+            }
+            if let Some(parent_id) = parent_id {
+                let parent = MessageRepository::get_with_conn(conn, parent_id).map_err(|e| match e {
+                    RepositoryError::NotFound => anyhow!("上一条消息不存在"),
+                    e => anyhow!("获取上一条消息失败: {}", e),
+                })?;
+                if parent.chat_id != chat_id || !parent.is_active || parent.deleted_at.is_some() {
+                    return Err(anyhow!("上一条消息已不在当前分支上"));
+                }
+            }
+            let message = MessageRepository::create_streaming_with_conn(conn, chat_id, sender_id, reply_to_id, parent_id)
+                .map_err(|e| anyhow!("创建消息失败: {}", e))?;
+            if parent_id.is_some() {
+                ChatRepository::refresh_last_message_with_conn(conn, chat_id)
+                    .map_err(|e| anyhow!("更新聊天最后消息失败: {}", e))?;
+            }
+
+            ChatParticipantRepository::mark_read_with_conn(conn, chat_id, sender_id, &message.id, message.created_at)
+                .map_err(|e| anyhow!("更新已读位置失败: {}", e))?;
+
+            Ok(message)
+        })
+    }
+
+    // 校验聊天未被删除
+    fn check_chat_available_with_conn(conn: &mut DbConnection, chat_id: &str) -> ServiceResult<()> {
+        let chat = ChatRepository::get_with_conn(conn, chat_id)
+            .map_err(|e| anyhow!("获取聊天信息失败: {}", e))?;
+        if chat.deleted_at.is_some() {
+            return Err(anyhow!("聊天已删除"));
+        }
+        Ok(())
+    }
+
+    // 校验回复的消息存在、未删除且属于同一聊天
+    fn check_reply_target_with_conn(conn: &mut DbConnection, chat_id: &str, reply_to_id: &str) -> ServiceResult<()> {
+        let target = MessageRepository::get_with_conn(conn, reply_to_id).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("回复的消息不存在"),
+            e => anyhow!("获取回复的消息失败: {}", e),
+        })?;
+        if target.deleted_at.is_some() {
+            return Err(anyhow!("回复的消息已删除"));
+        }
+        if target.chat_id != chat_id {
+            return Err(anyhow!("回复的消息不属于该聊天"));
+        }
+        Ok(())
+    }
+
+    // 写入流式消息目前已生成的完整内容
+    //
+    // 消息已结束或被删除时返回错误，生成方应停止生成
+    pub fn flush_streaming_message(pool: &DbPool, id: &str, content: &str) -> ServiceResult<()> {
+        let updated = MessageRepository::update_streaming_content(pool, id, content)
+            .map_err(|e| anyhow!("写入流式消息失败: {}", e))?;
+        if !updated {
+            return Err(anyhow!("流式消息已结束或已被删除"));
+        }
+        Ok(())
+    }
+
+    // 结束流式消息，写入最终内容和状态
+    pub fn finish_streaming_message(
+        pool: &DbPool,
+        id: &str,
+        content: &str,
+        status: MessageStatus,
+        error_message: Option<&str>,
+    ) -> ServiceResult<Message> {
+        MessageRepository::finish_streaming(pool, id, content, status, error_message).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("流式消息已结束或已被删除"),
+            e => anyhow!("结束流式消息失败: {}", e),
+        })
+    }
+
+    // 将流式消息结束为工具调用消息，content 为模型在调用前输出的文本
+    pub fn finish_tool_call_message(
+        pool: &DbPool,
+        id: &str,
+        content: &str,
+        tool_calls: &[ToolCall],
+    ) -> ServiceResult<Message> {
+        let tool_calls = serde_json::to_string(tool_calls).map_err(|e| anyhow!("序列化工具调用失败: {}", e))?;
+        MessageRepository::finish_as_tool_call(pool, id, content, &tool_calls).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("流式消息已结束或已被删除"),
+            e => anyhow!("保存工具调用失败: {}", e),
+        })
+    }
+
+    // 以调用工具的 AI 用户身份写入工具结果消息
+    //
+    // 与 send_message 相同，校验参与者、写入消息并推进发送者的已读位置
+    pub fn add_tool_result(
+        pool: &DbPool,
+        chat_id: &str,
+        sender_id: &str,
+        tool_call_id: &str,
+        content: &str,
+    ) -> ServiceResult<Message> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        db::immediate_transaction(&mut conn, |conn| {
+            let is_participant = ChatParticipantRepository::exists_with_conn(conn, chat_id, sender_id)
+                .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+            if !is_participant {
+                return Err(anyhow!("发送者不是该聊天的参与者"));
+            }
+            Self::check_chat_available_with_conn(conn, chat_id)?;
+
+            let message = MessageRepository::create_tool_result_with_conn(
+                conn,
+                content.to_string(),
+                chat_id,
+                sender_id,
+                tool_call_id,
+            )
+            .map_err(|e| anyhow!("保存工具结果失败: {}", e))?;
+
+            ChatParticipantRepository::mark_read_with_conn(conn, chat_id, sender_id, &message.id, message.created_at)
+                .map_err(|e| anyhow!("更新已读位置失败: {}", e))?;
+
+            Ok(message)
+        })
+    }
+
+    // 解析 tool_call 消息中 JSON 数组格式的工具调用，无法解析时返回空
+    pub fn decode_tool_calls(raw: Option<&str>) -> Vec<ToolCall> {
+        raw.and_then(|raw| serde_json::from_str(raw).ok()).unwrap_or_default()
+    }
+
+    // 将上次退出时未结束的流式消息标记为失败，已写入的内容保留
+    pub fn reconcile_streaming_messages(pool: &DbPool, error_message: &str) -> ServiceResult<Vec<Message>> {
+        MessageRepository::fail_all_streaming(pool, error_message)
+            .map_err(|e| anyhow!("清理未结束的流式消息失败: {}", e))
+    }
+
+    // 获取聊天的历史消息
+    pub fn get_chat_messages(pool: &DbPool, chat_id: &str) -> ServiceResult<Vec<Message>> {
+        let messages = MessageRepository::get_by_chat_id(pool, chat_id)
+            .map_err(|e| anyhow!("获取聊天消息失败: {}", e))?;
+        Ok(messages)
+    }
+
+    // 获取消息及其下当前分支上的全部回复（包括回复的回复），按时间升序排列，第一条为该消息本身
+    pub fn get_reply_thread(pool: &DbPool, message_id: &str) -> ServiceResult<Vec<Message>> {
+        let thread = MessageRepository::get_reply_thread(pool, message_id)
+            .map_err(|e| anyhow!("获取回复串失败: {}", e))?;
+        if thread.is_empty() {
+            return Err(anyhow!("消息不存在"));
+        }
+        Ok(thread)
+    }
+
+    // 为消息附上所回复消息的预览和在兄弟节点中的位置，各通过一次批量查询获得
+    pub fn attach_details(pool: &DbPool, messages: Vec<Message>) -> ServiceResult<Vec<MessageDetail>> {
+        let ids: Vec<String> = messages.iter().map(|message| message.id.clone()).collect();
+        let positions: HashMap<String, (i64, i64)> = MessageRepository::get_sibling_positions(pool, &ids)
+            .map_err(|e| anyhow!("获取消息分支失败: {}", e))?
+            .into_iter()
+            .map(|position| (position.id, (position.sibling_index, position.sibling_count)))
+            .collect();
+        let revision_counts: HashMap<String, i64> = MessageRevisionRepository::count_by_message_ids(pool, &ids)
+            .map_err(|e| anyhow!("获取消息修订记录失败: {}", e))?
+            .into_iter()
+            .collect();
+
+        let reply_to_ids: Vec<String> = messages
+            .iter()
+            .filter_map(|message| message.reply_to_id.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let targets = if reply_to_ids.is_empty() {
+            Vec::new()
+        } else {
+            MessageRepository::get_by_ids(pool, &reply_to_ids)
+                .map_err(|e| anyhow!("获取回复的消息失败: {}", e))?
+        };
+        let sender_ids: Vec<String> = targets
+            .iter()
+            .map(|target| target.sender_id.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let sender_names: HashMap<String, String> = UserRepository::get_by_ids(pool, &sender_ids)
+            .map_err(|e| anyhow!("获取消息发送者失败: {}", e))?
+            .into_iter()
+            .map(|user| (user.id, user.name))
+            .collect();
+
+        let previews: HashMap<String, ReplyPreview> = targets
+            .into_iter()
+            .map(|target| {
+                let preview = ReplyPreview {
+                    id: target.id.clone(),
+                    sender_id: target.sender_id.clone(),
+                    sender_name: sender_names.get(&target.sender_id).cloned().unwrap_or_default(),
+                    content: truncate_chars(&target.content, REPLY_PREVIEW_CHARS),
+                    status: target.status,
+                };
+                (target.id, preview)
+            })
+            .collect();
+
+        Ok(messages
+            .into_iter()
+            .map(|message| {
+                let reply_to = message.reply_to_id.as_ref().and_then(|id| previews.get(id).cloned());
+                let (sibling_index, sibling_count) = positions.get(&message.id).copied().unwrap_or((0, 1));
+                let revision_count = revision_counts.get(&message.id).copied().unwrap_or(0);
+                MessageDetail { message, reply_to, sibling_index, sibling_count, revision_count }
+            })
+            .collect())
+    }
+
+    // 获取消息的所有版本（兄弟节点），按创建时间升序，同时返回当前选中的版本的位置
+    pub fn get_siblings(pool: &DbPool, message_id: &str) -> ServiceResult<(Vec<Message>, Option<usize>)> {
+        let message = MessageRepository::get(pool, message_id)
+            .map_err(|e| anyhow!("获取消息失败: {}", e))?;
+        let siblings = MessageRepository::get_siblings(pool, &message)
+            .map_err(|e| anyhow!("获取消息分支失败: {}", e))?;
+        let active_index = siblings.iter().position(|sibling| sibling.is_active);
+        Ok((siblings, active_index))
+    }
+
+    // 切换到指定消息所在的分支
+    //
+    // 原来选中的兄弟节点及其后续消息移出当前分支，指定消息及其之后沿选中的下一条消息延伸的消息成为当前分支；
+    // 返回从指定消息开始的新分支，按时间升序
+    pub fn switch_branch(pool: &DbPool, message_id: &str) -> ServiceResult<Vec<Message>> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        db::immediate_transaction(&mut conn, |conn| {
+            let message = MessageRepository::get_with_conn(conn, message_id).map_err(|e| match e {
+                RepositoryError::NotFound => anyhow!("消息不存在"),
+                e => anyhow!("获取消息失败: {}", e),
+            })?;
+            if message.deleted_at.is_some() {
+                return Err(anyhow!("消息已删除"));
+            }
+            if message.is_active {
+                return MessageRepository::get_branch_with_conn(conn, &message.id)
+                    .map_err(|e| anyhow!("获取消息分支失败: {}", e));
+            }
+            Self::check_no_streaming_with_conn(conn, &message.chat_id, "回复生成中，无法切换分支")?;
+
+            // 1. 移出原来选中的分支；父消息不在当前分支上时，需先切换到父消息所在的分支
+            if let Some(parent_id) = &message.parent_id {
+                let parent = MessageRepository::get_with_conn(conn, parent_id)
+                    .map_err(|e| anyhow!("获取上一条消息失败: {}", e))?;
+                if !parent.is_active {
+                    return Err(anyhow!("该消息的上一条消息不在当前分支上，请先切换上一条消息的分支"));
+                }
+            }
+            let current = MessageRepository::get_active_sibling_with_conn(conn, &message)
+                .map_err(|e| anyhow!("获取消息分支失败: {}", e))?;
+            if let Some(current) = current {
+                MessageRepository::set_branch_active_with_conn(conn, &current.id, false)
+                    .map_err(|e| anyhow!("更新消息分支失败: {}", e))?;
+            }
+
+            // 2. 选中新的分支
+            if let Some(parent_id) = &message.parent_id {
+                MessageRepository::set_active_child_with_conn(conn, parent_id, Some(&message.id))
+                    .map_err(|e| anyhow!("更新消息分支失败: {}", e))?;
+            }
+            MessageRepository::set_branch_active_with_conn(conn, &message.id, true)
+                .map_err(|e| anyhow!("更新消息分支失败: {}", e))?;
+            let branch = MessageRepository::get_branch_with_conn(conn, &message.id)
+                .map_err(|e| anyhow!("获取消息分支失败: {}", e))?;
+
+            ChatRepository::refresh_last_message_with_conn(conn, &message.chat_id)
+                .map_err(|e| anyhow!("更新聊天最后消息失败: {}", e))?;
+            Ok(branch)
+        })
+    }
+
+    // 为重新生成 AI 回复做准备
+    //
+    // 指定的 AI 回复及其后续消息移出当前分支但保留，之后生成的新回复接在它的上一条消息之后，成为它的兄弟节点；
+    // 返回原回复
+    pub fn start_regeneration(pool: &DbPool, message_id: &str) -> ServiceResult<Message> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        db::immediate_transaction(&mut conn, |conn| {
+            let message = MessageRepository::get_with_conn(conn, message_id).map_err(|e| match e {
+                RepositoryError::NotFound => anyhow!("消息不存在"),
+                e => anyhow!("获取消息失败: {}", e),
+            })?;
+            let sender = UserRepository::get_with_conn(conn, &message.sender_id)
+                .map_err(|e| anyhow!("获取消息发送者失败: {}", e))?;
+            if !sender.is_ai {
+                return Err(anyhow!("只能重新生成AI用户的回复"));
+            }
+            if message.kind != MessageKind::Text.as_str() {
+                return Err(anyhow!("工具调用和工具结果不能重新生成"));
+            }
+            if !message.is_active {
+                return Err(anyhow!("只能重新生成当前分支上的回复"));
+            }
+            if message.deleted_at.is_some() {
+                return Err(anyhow!("消息已删除"));
+            }
+            Self::check_no_streaming_with_conn(conn, &message.chat_id, "回复生成中，无法重新生成")?;
+
+            MessageRepository::set_branch_active_with_conn(conn, &message.id, false)
+                .map_err(|e| anyhow!("更新消息分支失败: {}", e))?;
+            if let Some(parent_id) = &message.parent_id {
+                MessageRepository::set_active_child_with_conn(conn, parent_id, None)
+                    .map_err(|e| anyhow!("更新消息分支失败: {}", e))?;
+            }
+            ChatRepository::refresh_last_message_with_conn(conn, &message.chat_id)
+                .map_err(|e| anyhow!("更新聊天最后消息失败: {}", e))?;
+            Ok(message)
+        })
+    }
+
+    // 当前分支上有正在生成的回复时返回错误
+    fn check_no_streaming_with_conn(conn: &mut DbConnection, chat_id: &str, error: &str) -> ServiceResult<()> {
+        let streaming = MessageRepository::has_active_streaming_with_conn(conn, chat_id)
+            .map_err(|e| anyhow!("检查生成中的回复失败: {}", e))?;
+        if streaming {
+            return Err(anyhow!("{}", error));
+        }
+        Ok(())
+    }
+
+    // 按游标分页获取聊天消息，返回结果按时间升序排列
+    pub fn get_messages_page(
+        pool: &DbPool,
+        chat_id: &str,
+        anchor: MessagePageAnchor,
+        limit: i64,
+    ) -> ServiceResult<MessagePage> {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+
+        let page = match anchor {
+            MessagePageAnchor::Latest => {
+                let (messages, has_more_before) = Self::load_before(pool, chat_id, None, limit)?;
+                MessagePage { messages, has_more_before, has_more_after: false }
+            }
+            MessagePageAnchor::Before(message_id) => {
+                let anchor = Self::get_anchor(pool, chat_id, &message_id)?;
+                let cursor = Some((anchor.created_at, anchor.id.as_str()));
+                let (messages, has_more_before) = Self::load_before(pool, chat_id, cursor, limit)?;
+                // 锚点本身位于这一页之后
+                MessagePage { messages, has_more_before, has_more_after: true }
+            }
+            MessagePageAnchor::After(message_id) => {
+                let anchor = Self::get_anchor(pool, chat_id, &message_id)?;
+                let cursor = Some((anchor.created_at, anchor.id.as_str()));
+                let (messages, has_more_after) = Self::load_after(pool, chat_id, cursor, limit)?;
+                // 锚点本身位于这一页之前
+                MessagePage { messages, has_more_before: true, has_more_after }
+            }
+            MessagePageAnchor::Around(message_id) => {
+                let anchor = Self::get_anchor(pool, chat_id, &message_id)?;
+                let before_limit = (limit - 1) / 2;
+                let after_limit = limit - 1 - before_limit;
+                let cursor = Some((anchor.created_at, anchor.id.as_str()));
+
+                // before_limit 为 0 时仍会多取一条，用于判断锚点之前是否还有消息
+                let (mut messages, has_more_before) = Self::load_before(pool, chat_id, cursor, before_limit)?;
+                let (after, has_more_after) = Self::load_after(pool, chat_id, cursor, after_limit)?;
+
+                messages.push(anchor);
+                messages.extend(after);
+                MessagePage { messages, has_more_before, has_more_after }
+            }
+        };
+
+        Ok(page)
+    }
+
+    // 多取一条用于判断游标之前是否还有更多消息
+    fn load_before(
+        pool: &DbPool,
+        chat_id: &str,
+        cursor: Option<(NaiveDateTime, &str)>,
+        limit: i64,
+    ) -> ServiceResult<(Vec<Message>, bool)> {
+        let mut messages = MessageRepository::get_page_before(pool, chat_id, cursor, limit + 1)
+            .map_err(|e| anyhow!("获取聊天消息失败: {}", e))?;
+        let has_more = messages.len() as i64 > limit;
+        if has_more {
+            // 结果为升序，多出的一条在最前面
+            messages.remove(0);
+        }
+        Ok((messages, has_more))
+    }
+
+    // 多取一条用于判断游标之后是否还有更多消息
+    fn load_after(
+        pool: &DbPool,
+        chat_id: &str,
+        cursor: Option<(NaiveDateTime, &str)>,
+        limit: i64,
+    ) -> ServiceResult<(Vec<Message>, bool)> {
+        let mut messages = MessageRepository::get_page_after(pool, chat_id, cursor, limit + 1)
+            .map_err(|e| anyhow!("获取聊天消息失败: {}", e))?;
+        let has_more = messages.len() as i64 > limit;
+        if has_more {
+            messages.pop();
+        }
+        Ok((messages, has_more))
+    }
+
+    // 获取分页锚点消息并确认其属于该聊天
+    fn get_anchor(pool: &DbPool, chat_id: &str, message_id: &str) -> ServiceResult<Message> {
+        let message = MessageRepository::get(pool, message_id)
+            .map_err(|e| anyhow!("获取锚点消息失败: {}", e))?;
+        if message.chat_id != chat_id {
+            return Err(anyhow!("锚点消息不属于该聊天"));
+        }
+        Ok(message)
+    }
+
+    // 全文检索消息
+    //
+    // 检索内容按空白拆分为多个检索词，消息需包含全部检索词（不区分大小写）
+    pub fn search_messages(
+        pool: &DbPool,
+        query: &str,
+        filter: &MessageSearchFilter,
+        limit: i64,
+        offset: i64,
+    ) -> ServiceResult<MessageSearchPage> {
+        let terms = Self::parse_search_terms(query)?;
+        let limit = limit.clamp(1, MAX_SEARCH_LIMIT);
+        let offset = offset.max(0);
+
+        // 多取一条用于判断是否还有更多结果
+        let mut rows = MessageRepository::search(pool, &terms, filter, limit + 1, offset)
+            .map_err(|e| anyhow!("检索消息失败: {}", e))?;
+        let has_more = rows.len() as i64 > limit;
+        if has_more {
+            rows.pop();
+        }
+
+        let hits = rows
+            .into_iter()
+            .map(|row| MessageSearchHit {
+                snippet: build_snippet(&row.content, &terms),
+                row,
+            })
+            .collect();
+
+        Ok(MessageSearchPage { hits, has_more })
+    }
+
+    fn parse_search_terms(query: &str) -> ServiceResult<Vec<String>> {
+        let mut terms: Vec<String> = Vec::new();
+        for term in query.split_whitespace() {
+            if term.chars().count() > MAX_SEARCH_TERM_CHARS {
+                return Err(anyhow!("单个检索词不能超过{}个字符", MAX_SEARCH_TERM_CHARS));
+            }
+            if !terms.iter().any(|existing| existing.to_lowercase() == term.to_lowercase()) {
+                terms.push(term.to_string());
+            }
+        }
+
+        if terms.is_empty() {
+            return Err(anyhow!("检索内容不能为空"));
+        }
+        if terms.len() > MAX_SEARCH_TERMS {
+            return Err(anyhow!("检索词不能超过{}个", MAX_SEARCH_TERMS));
+        }
+        Ok(terms)
+    }
+
+    // 编辑消息
+    //
+    // 原内容作为修订记录保存，内容没有变化时不做修改；编辑者必须是聊天参与者，且只能编辑自己或 AI 用户发送的消息。
+    // 编辑自己发送的消息时，mode 可以选择删除其后的所有消息，或者以新内容创建新版本、
+    // 将原消息及其后续消息保留为另一个分支，此时返回新版本的消息
+    pub fn edit_message(
+        pool: &DbPool,
+        editor_id: &str,
+        id: &str,
+        content: &str,
+        mode: MessageEditMode,
+    ) -> ServiceResult<Message> {
+        if content.trim().is_empty() {
+            return Err(anyhow!("消息内容不能为空"));
+        }
+
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        db::immediate_transaction(&mut conn, |conn| {
+            let message = Self::get_editable_with_conn(conn, editor_id, id)?;
+            if mode != MessageEditMode::InPlace {
+                if message.sender_id != editor_id {
+                    return Err(anyhow!("只能对自己发送的消息重新生成后续回复"));
+                }
+                if !message.is_active {
+                    return Err(anyhow!("该消息不在当前分支上"));
+                }
+                let streaming = MessageRepository::has_active_streaming_with_conn(conn, &message.chat_id)
+                    .map_err(|e| anyhow!("检查生成中的消息失败: {}", e))?;
+                if streaming {
+                    return Err(anyhow!("AI 回复正在生成中，请稍后再试"));
+                }
+            }
+
+            match mode {
+                MessageEditMode::InPlace => Self::revise_with_conn(conn, editor_id, message, content),
+                MessageEditMode::Truncate => {
+                    Self::truncate_after_with_conn(conn, &message)?;
+                    let message = Self::revise_with_conn(conn, editor_id, message, content)?;
+                    ChatRepository::refresh_last_message_with_conn(conn, &message.chat_id)
+                        .map_err(|e| anyhow!("更新聊天最后消息失败: {}", e))?;
+                    Ok(message)
+                }
+                MessageEditMode::Branch => {
+                    // 原消息所在的分支整体移出当前分支，新版本接在原消息的上一条消息之后
+                    MessageRepository::set_branch_active_with_conn(conn, &message.id, false)
+                        .map_err(|e| anyhow!("更新消息分支失败: {}", e))?;
+                    let new_message = MessageRepository::create_with_conn(
+                        conn,
+                        content.to_string(),
+                        &message.chat_id,
+                        editor_id,
+                        message.reply_to_id.as_deref(),
+                    )
+                    .map_err(|e| anyhow!("创建消息失败: {}", e))?;
+                    ChatParticipantRepository::mark_read_with_conn(
+                        conn,
+                        &new_message.chat_id,
+                        editor_id,
+                        &new_message.id,
+                        new_message.created_at,
+                    )
+                    .map_err(|e| anyhow!("更新已读位置失败: {}", e))?;
+                    Ok(new_message)
+                }
+            }
+        })
+    }
+
+    // 获取消息的修订记录，按修改时间倒序；用户必须是消息所在聊天的参与者
+    pub fn get_revisions(pool: &DbPool, user_id: &str, message_id: &str) -> ServiceResult<Vec<MessageRevision>> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+        Self::get_participant_message_with_conn(&mut conn, user_id, message_id)?;
+
+        MessageRevisionRepository::get_by_message_id(pool, message_id)
+            .map_err(|e| anyhow!("获取消息修订记录失败: {}", e))
+    }
+
+    // 将消息内容恢复为某条修订记录中的内容，恢复前的内容同样保存为修订记录
+    pub fn restore_revision(pool: &DbPool, editor_id: &str, revision_id: &str) -> ServiceResult<Message> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        db::immediate_transaction(&mut conn, |conn| {
+            let revision = MessageRevisionRepository::get_with_conn(conn, revision_id).map_err(|e| match e {
+                RepositoryError::NotFound => anyhow!("修订记录不存在"),
+                e => anyhow!("获取修订记录失败: {}", e),
+            })?;
+            let message = Self::get_editable_with_conn(conn, editor_id, &revision.message_id)?;
+            Self::revise_with_conn(conn, editor_id, message, &revision.content)
+        })
+    }
+
+    // 使用已有连接获取可编辑的消息：消息存在、未删除、不在生成中，编辑者是聊天参与者，
+    // 且消息由编辑者本人或 AI 用户发送
+    fn get_editable_with_conn(conn: &mut DbConnection, editor_id: &str, id: &str) -> ServiceResult<Message> {
+        let message = MessageRepository::get_with_conn(conn, id).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("消息不存在"),
+            e => anyhow!("获取消息失败: {}", e),
+        })?;
+        if message.deleted_at.is_some() {
+            return Err(anyhow!("消息已删除"));
+        }
+        if message.status == MessageStatus::Streaming.as_str() {
+            return Err(anyhow!("消息正在生成中，无法编辑"));
+        }
+        let is_participant = ChatParticipantRepository::exists_with_conn(conn, &message.chat_id, editor_id)
+            .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+        if !is_participant {
+            return Err(anyhow!("您不是该聊天的参与者"));
+        }
+        if message.sender_id != editor_id {
+            let sender = UserRepository::get_with_conn(conn, &message.sender_id)
+                .map_err(|e| anyhow!("获取消息发送者失败: {}", e))?;
+            if !sender.is_ai {
+                return Err(anyhow!("只能编辑自己或AI用户发送的消息"));
+            }
+        }
+        Ok(message)
+    }
+
+    // 使用已有连接修改消息内容，并将原内容保存为修订记录
+    fn revise_with_conn(
+        conn: &mut DbConnection,
+        editor_id: &str,
+        message: Message,
+        content: &str,
+    ) -> ServiceResult<Message> {
+        if message.content == content {
+            return Ok(message);
+        }
+
+        MessageRevisionRepository::create_with_conn(conn, &message.id, message.content, editor_id)
+            .map_err(|e| anyhow!("保存修订记录失败: {}", e))?;
+        MessageRepository::update_with_conn(conn, &message.id, content.to_string())
+            .map_err(|e| anyhow!("更新消息失败: {}", e))
+    }
+
+    // 使用已有连接删除接在消息之后的所有消息，包括其他分支，这些消息的修订记录一并删除，其他消息对它们的回复引用被清除
+    fn truncate_after_with_conn(conn: &mut DbConnection, message: &Message) -> ServiceResult<()> {
+        let descendants = MessageRepository::get_descendants_with_conn(conn, &message.id)
+            .map_err(|e| anyhow!("获取后续消息失败: {}", e))?;
+        if descendants.iter().any(|m| m.status == MessageStatus::Streaming.as_str()) {
+            return Err(anyhow!("AI 回复正在生成中，请稍后再试"));
+        }
+
+        let ids: Vec<String> = descendants.into_iter().map(|m| m.id).collect();
+        for id in &ids {
+            AiJobRepository::clear_message_refs_with_conn(conn, id)
+                .map_err(|e| anyhow!("更新AI回复任务失败: {}", e))?;
+            AgentMemoryRepository::clear_message_refs_with_conn(conn, id)
+                .map_err(|e| anyhow!("更新AI记忆失败: {}", e))?;
+        }
+        MessageRepository::clear_reply_refs_with_conn(conn, &ids)
+            .map_err(|e| anyhow!("更新回复消息失败: {}", e))?;
+        MessageRevisionRepository::delete_by_message_ids_with_conn(conn, &ids)
+            .map_err(|e| anyhow!("删除消息修订记录失败: {}", e))?;
+        MessageRepository::delete_by_ids_with_conn(conn, &ids)
+            .map_err(|e| anyhow!("删除后续消息失败: {}", e))?;
+        MessageRepository::set_active_child_with_conn(conn, &message.id, None)
+            .map_err(|e| anyhow!("更新消息分支失败: {}", e))?;
+        Ok(())
+    }
+
+    // 删除消息，消息移入回收站，超过保留期限后由 TrashService 彻底清除
+    //
+    // 消息仍留在分支结构中，只是不再显示，其后续消息保持不变
+    pub fn delete_message(pool: &DbPool, user_id: &str, id: &str) -> ServiceResult<()> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        db::immediate_transaction(&mut conn, |conn| {
+            let message = Self::get_participant_message_with_conn(conn, user_id, id)?;
+            if message.deleted_at.is_some() {
+                return Ok(());
+            }
+            MessageRepository::set_deleted_with_conn(conn, id, Some(Utc::now().naive_utc()))
+                .map_err(|e| anyhow!("删除消息失败: {}", e))?;
+            ChatRepository::refresh_last_message_with_conn(conn, &message.chat_id)
+                .map_err(|e| anyhow!("更新聊天最后消息失败: {}", e))?;
+            Ok(())
+        })
+    }
+
+    // 从回收站恢复消息，所在聊天已删除时需先恢复聊天
+    pub fn restore_message(pool: &DbPool, user_id: &str, id: &str) -> ServiceResult<Message> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+
+        db::immediate_transaction(&mut conn, |conn| {
+            let message = Self::get_participant_message_with_conn(conn, user_id, id)?;
+            Self::check_chat_available_with_conn(conn, &message.chat_id)
+                .map_err(|e| anyhow!("{}，请先恢复聊天", e))?;
+            MessageRepository::set_deleted_with_conn(conn, id, None)
+                .map_err(|e| anyhow!("恢复消息失败: {}", e))?;
+            ChatRepository::refresh_last_message_with_conn(conn, &message.chat_id)
+                .map_err(|e| anyhow!("更新聊天最后消息失败: {}", e))?;
+            MessageRepository::get_with_conn(conn, id).map_err(|e| anyhow!("获取消息失败: {}", e))
+        })
+    }
+
+    // 获取用户参与的聊天中已删除的消息，按删除时间倒序；已删除聊天中的消息随聊天一起列出，不在此返回
+    pub fn get_deleted_messages(pool: &DbPool, user_id: &str) -> ServiceResult<Vec<Message>> {
+        MessageRepository::get_deleted_by_user(pool, user_id)
+            .map_err(|e| anyhow!("获取已删除的消息失败: {}", e))
+    }
+
+    // 使用已有连接获取消息并校验用户是该聊天的参与者
+    fn get_participant_message_with_conn(conn: &mut DbConnection, user_id: &str, id: &str) -> ServiceResult<Message> {
+        let message = MessageRepository::get_with_conn(conn, id).map_err(|e| match e {
+            RepositoryError::NotFound => anyhow!("消息不存在"),
+            e => anyhow!("获取消息失败: {}", e),
+        })?;
+        let is_participant = ChatParticipantRepository::exists_with_conn(conn, &message.chat_id, user_id)
+            .map_err(|e| anyhow!("检查聊天参与者失败: {}", e))?;
+        if !is_participant {
+            return Err(anyhow!("您不是该聊天的参与者"));
+        }
+        Ok(message)
+    }
+
+    // 使用已有连接彻底删除消息
+    //
+    // 消息的后续消息改为接在它的上一条消息之后，分支的其余部分保持不变；AI 回复任务和 AI 记忆对该消息的引用被清除，
+    // 回复它的消息和从它分出的聊天不再引用它，它的修订记录一并删除；ai_jobs 的外键没有声明删除动作，
+    // 其余引用虽由外键的 ON DELETE 动作处理，也在这里一并显式清除，不依赖外键动作
+    pub fn purge_message_with_conn(conn: &mut DbConnection, message: &Message) -> ServiceResult<()> {
+        let message_ids = std::slice::from_ref(&message.id);
+        MessageRepository::reparent_children_with_conn(conn, message)
+            .map_err(|e| anyhow!("更新消息分支失败: {}", e))?;
+        MessageRepository::clear_reply_refs_with_conn(conn, message_ids)
+            .map_err(|e| anyhow!("更新回复消息失败: {}", e))?;
+        ChatRepository::clear_forked_message_refs_with_conn(conn, &message.id)
+            .map_err(|e| anyhow!("更新分出的聊天失败: {}", e))?;
+        MessageRevisionRepository::delete_by_message_ids_with_conn(conn, message_ids)
+            .map_err(|e| anyhow!("删除消息修订记录失败: {}", e))?;
+        AiJobRepository::clear_message_refs_with_conn(conn, &message.id)
+            .map_err(|e| anyhow!("更新AI回复任务失败: {}", e))?;
+        AgentMemoryRepository::clear_message_refs_with_conn(conn, &message.id)
+            .map_err(|e| anyhow!("更新AI记忆失败: {}", e))?;
+        MessageRepository::delete_with_conn(conn, &message.id)
+            .map_err(|e| anyhow!("删除消息失败: {}", e))
+    }
+}
+
+// 截取开头的 max_chars 个字符，超出时以省略号结尾
+pub(crate) fn truncate_chars(content: &str, max_chars: usize) -> String {
+    match content.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}{}", &content[..end], SNIPPET_ELLIPSIS),
+        None => content.to_string(),
+    }
+}
+
+// 截取第一个命中位置前后的内容作为摘录，并标出摘录中所有命中的检索词
+fn build_snippet(content: &str, terms: &[String]) -> Vec<SnippetSegment> {
+    let chars: Vec<char> = content.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| fold_case(*c)).collect();
+    let terms: Vec<Vec<char>> = terms.iter().map(|term| term.chars().map(fold_case).collect()).collect();
+
+    // 每个位置上命中的最长检索词
+    let match_at = |index: usize| {
+        terms
+            .iter()
+            .filter(|term| lower[index..].starts_with(term))
+            .map(|term| term.len())
+            .max()
+    };
+
+    let first = (0..chars.len()).find(|&index| match_at(index).is_some()).unwrap_or(0);
+    let start = first.saturating_sub(SNIPPET_CONTEXT_CHARS);
+    let end = chars.len().min(first + SNIPPET_CONTEXT_CHARS * 2);
+
+    let mut segments = Vec::new();
+    let mut plain = String::new();
+    if start > 0 {
+        plain.push_str(SNIPPET_ELLIPSIS);
+    }
+
+    let mut index = start;
+    while index < end {
+        match match_at(index) {
+            Some(len) => {
+                if !plain.is_empty() {
+                    segments.push(SnippetSegment { text: std::mem::take(&mut plain), highlighted: false });
+                }
+                let match_end = (index + len).min(chars.len());
+                segments.push(SnippetSegment {
+                    text: chars[index..match_end].iter().collect(),
+                    highlighted: true,
+                });
+                index = match_end;
+            }
+            None => {
+                plain.push(chars[index]);
+                index += 1;
+            }
+        }
+    }
+
+    if index < chars.len() {
+        plain.push_str(SNIPPET_ELLIPSIS);
+    }
+    if !plain.is_empty() {
+        segments.push(SnippetSegment { text: plain, highlighted: false });
+    }
+    segments
+}
+
+// 逐字符忽略大小写比较，保持字符位置不变
+fn fold_case(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(folded), None) => folded,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use diesel::prelude::*;
+
+    use super::*;
+    use crate::models::ChatType;
+    use crate::schema::messages;
+    use crate::services::ai_job_service::AiJobService;
+    use crate::test_support::{create_test_chat, TestChat};
+
+    // 发送 count 条消息，创建时间依次间隔一秒
+    fn send_messages(pool: &DbPool, chat_id: &str, sender_id: &str, count: usize) -> Vec<Message> {
+        let start = Utc::now().naive_utc();
+        let mut conn = pool.get().unwrap();
+        (0..count)
+            .map(|index| {
+                let message = MessageService::send_message(pool, chat_id, sender_id, &format!("消息{}", index), None)
+                    .unwrap();
+                diesel::update(messages::table.filter(messages::id.eq(&message.id)))
+                    .set(messages::created_at.eq(start + TimeDelta::seconds(index as i64)))
+                    .execute(&mut conn)
+                    .unwrap();
+                MessageRepository::get(pool, &message.id).unwrap()
+            })
+            .collect()
+    }
+
+    fn page_ids(page: &MessagePage) -> Vec<&str> {
+        page.messages.iter().map(|message| message.id.as_str()).collect()
+    }
+
+    // 用户在聊天中的已读位置
+    fn last_read_message_id(pool: &DbPool, chat_id: &str, user_id: &str) -> Option<String> {
+        ChatParticipantRepository::get_by_chat_id(pool, chat_id)
+            .unwrap()
+            .into_iter()
+            .find(|participant| participant.user_id == user_id)
+            .and_then(|participant| participant.last_read_message_id)
+    }
+
+    fn unread(pool: &DbPool, chat_id: &str, user_id: &str) -> i64 {
+        let mut conn = pool.get().unwrap();
+        ChatParticipantRepository::count_unread_with_conn(&mut conn, user_id, Some(&[chat_id]))
+            .unwrap()
+            .first()
+            .map(|row| row.unread)
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn send_message_requires_participant() {
+        let TestChat { pool, chat, .. } = create_test_chat();
+        let stranger = UserRepository::create(&pool, "陌生人".to_string(), None, false).unwrap();
+
+        let error = MessageService::send_message(&pool, &chat.id, &stranger.id, "你好", None).unwrap_err();
+        assert!(error.to_string().contains("不是该聊天的参与者"));
+        let error = MessageService::send_message(&pool, &chat.id, &stranger.id, "  ", None).unwrap_err();
+        assert!(error.to_string().contains("不能为空"));
+        assert!(MessageService::get_chat_messages(&pool, &chat.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn send_message_marks_own_message_read() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+
+        let first = MessageService::send_message(&pool, &chat.id, &human.id, "第一条", None).unwrap();
+        assert_eq!(last_read_message_id(&pool, &chat.id, &human.id), Some(first.id.clone()));
+        assert_eq!(last_read_message_id(&pool, &chat.id, &ai.id), None);
+        assert_eq!(unread(&pool, &chat.id, &human.id), 0);
+        assert_eq!(unread(&pool, &chat.id, &ai.id), 1);
+
+        // 回复后对方的消息仍计为未读，自己的消息不计入
+        let reply = MessageService::send_message(&pool, &chat.id, &ai.id, "收到", Some(&first.id)).unwrap();
+        assert_eq!(reply.reply_to_id.as_deref(), Some(first.id.as_str()));
+        assert_eq!(last_read_message_id(&pool, &chat.id, &ai.id), Some(reply.id));
+        assert_eq!(unread(&pool, &chat.id, &ai.id), 0);
+        assert_eq!(unread(&pool, &chat.id, &human.id), 1);
+    }
+
+    #[test]
+    fn edit_message_rejects_non_participant_editor() {
+        let TestChat { pool, human, chat, .. } = create_test_chat();
+        let stranger = UserRepository::create(&pool, "陌生人".to_string(), None, false).unwrap();
+        let message = MessageService::send_message(&pool, &chat.id, &human.id, "原内容", None).unwrap();
+
+        let error =
+            MessageService::edit_message(&pool, &stranger.id, &message.id, "改过的内容", MessageEditMode::InPlace)
+                .unwrap_err();
+        assert!(error.to_string().contains("不是该聊天的参与者"));
+        assert_eq!(MessageRepository::get(&pool, &message.id).unwrap().content, "原内容");
+        assert!(MessageService::get_revisions(&pool, &human.id, &message.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn messages_page_anchors_and_has_more_flags() {
+        let TestChat { pool, human, chat, .. } = create_test_chat();
+        let sent = send_messages(&pool, &chat.id, &human.id, 7);
+        let ids: Vec<&str> = sent.iter().map(|message| message.id.as_str()).collect();
+        let page = |anchor: MessagePageAnchor, limit: i64| {
+            MessageService::get_messages_page(&pool, &chat.id, anchor, limit).unwrap()
+        };
+        let anchor = |index: usize| ids[index].to_string();
+
+        let latest = page(MessagePageAnchor::Latest, 3);
+        assert_eq!(page_ids(&latest), ids[4..]);
+        assert!(latest.has_more_before && !latest.has_more_after);
+
+        let before = page(MessagePageAnchor::Before(anchor(4)), 3);
+        assert_eq!(page_ids(&before), ids[1..4]);
+        assert!(before.has_more_before && before.has_more_after);
+        let first = page(MessagePageAnchor::Before(anchor(3)), 3);
+        assert_eq!(page_ids(&first), ids[..3]);
+        assert!(!first.has_more_before && first.has_more_after);
+
+        let after = page(MessagePageAnchor::After(anchor(2)), 3);
+        assert_eq!(page_ids(&after), ids[3..6]);
+        assert!(after.has_more_before && after.has_more_after);
+        let last = page(MessagePageAnchor::After(anchor(3)), 3);
+        assert_eq!(page_ids(&last), ids[4..]);
+        assert!(last.has_more_before && !last.has_more_after);
+
+        let around = page(MessagePageAnchor::Around(anchor(3)), 3);
+        assert_eq!(page_ids(&around), ids[2..5]);
+        assert!(around.has_more_before && around.has_more_after);
+        let around = page(MessagePageAnchor::Around(anchor(0)), 5);
+        assert_eq!(page_ids(&around), ids[..3]);
+        assert!(!around.has_more_before && around.has_more_after);
+    }
+
+    #[test]
+    fn around_with_limit_one_returns_only_anchor() {
+        let TestChat { pool, human, chat, .. } = create_test_chat();
+        let sent = send_messages(&pool, &chat.id, &human.id, 3);
+        let around = |index: usize| {
+            let anchor = MessagePageAnchor::Around(sent[index].id.clone());
+            MessageService::get_messages_page(&pool, &chat.id, anchor, 1).unwrap()
+        };
+
+        let middle = around(1);
+        assert_eq!(page_ids(&middle), [sent[1].id.as_str()]);
+        assert!(middle.has_more_before && middle.has_more_after);
+        let first = around(0);
+        assert_eq!(page_ids(&first), [sent[0].id.as_str()]);
+        assert!(!first.has_more_before && first.has_more_after);
+        let last = around(2);
+        assert!(last.has_more_before && !last.has_more_after);
+
+        // 锚点必须属于该聊天
+        let other = create_test_chat();
+        let anchor = MessagePageAnchor::Around(sent[0].id.clone());
+        assert!(MessageService::get_messages_page(&pool, &other.chat.id, anchor, 1).is_err());
+    }
+
+    #[test]
+    fn search_returns_snippets_around_first_match() {
+        let TestChat { pool, human, chat, .. } = create_test_chat();
+        let content = format!("{}明天的会议改到下午{}", "前".repeat(40), "后".repeat(80));
+        MessageService::send_message(&pool, &chat.id, &human.id, &content, None).unwrap();
+        MessageService::send_message(&pool, &chat.id, &human.id, "会议室已预订", None).unwrap();
+
+        let page = MessageService::search_messages(&pool, "会议 下午", &MessageSearchFilter::default(), 1, 0).unwrap();
+        assert_eq!(page.hits.len(), 1);
+        assert!(!page.has_more);
+        let snippet: Vec<(&str, bool)> =
+            page.hits[0].snippet.iter().map(|segment| (segment.text.as_str(), segment.highlighted)).collect();
+        let before = format!("{}{}明天的", SNIPPET_ELLIPSIS, "前".repeat(SNIPPET_CONTEXT_CHARS - 3));
+        // 摘录从第一个命中位置往后共保留 SNIPPET_CONTEXT_CHARS * 2 个字符
+        let after = format!("{}{}", "后".repeat(SNIPPET_CONTEXT_CHARS * 2 - 6), SNIPPET_ELLIPSIS);
+        assert_eq!(snippet, [(before.as_str(), false), ("会议", true), ("改到", false), ("下午", true), (after.as_str(), false)]);
+
+        let page = MessageService::search_messages(&pool, "会议", &MessageSearchFilter::default(), 1, 0).unwrap();
+        assert!(page.has_more);
+        let page = MessageService::search_messages(&pool, "会议", &MessageSearchFilter::default(), 1, 1).unwrap();
+        assert_eq!(page.hits.len(), 1);
+        assert!(!page.has_more);
+    }
+
+    #[test]
+    fn search_terms_are_deduplicated_and_validated() {
+        assert_eq!(MessageService::parse_search_terms(" 会议  Rust rust ").unwrap(), ["会议", "Rust"]);
+        assert!(MessageService::parse_search_terms("   ").is_err());
+        let too_many = (0..=MAX_SEARCH_TERMS).map(|index| index.to_string()).collect::<Vec<_>>().join(" ");
+        assert!(MessageService::parse_search_terms(&too_many).is_err());
+        assert!(MessageService::parse_search_terms(&"长".repeat(MAX_SEARCH_TERM_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn details_embed_truncated_reply_preview() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let long = "长".repeat(REPLY_PREVIEW_CHARS + 10);
+        let target = MessageService::send_message(&pool, &chat.id, &human.id, &long, None).unwrap();
+        let reply = MessageService::send_message(&pool, &chat.id, &ai.id, "收到", Some(&target.id)).unwrap();
+
+        let (target_id, target_status, reply_id) = (target.id.clone(), target.status.clone(), reply.id.clone());
+
+        let details = MessageService::attach_details(&pool, vec![target, reply]).unwrap();
+        assert!(details[0].reply_to.is_none());
+        let preview = details[1].reply_to.as_ref().unwrap();
+        assert_eq!(preview.id, target_id);
+        assert_eq!(preview.sender_id, human.id);
+        assert_eq!(preview.sender_name, "用户");
+        assert_eq!(preview.content, format!("{}{}", "长".repeat(REPLY_PREVIEW_CHARS), SNIPPET_ELLIPSIS));
+        assert_eq!(preview.status, target_status);
+
+        // 被回复的消息移入回收站后不再显示预览，恢复后预览重新出现
+        MessageService::delete_message(&pool, &human.id, &target_id).unwrap();
+        let reply = MessageRepository::get(&pool, &reply_id).unwrap();
+        assert_eq!(reply.reply_to_id.as_deref(), Some(target_id.as_str()));
+        let details = MessageService::attach_details(&pool, vec![reply]).unwrap();
+        assert!(details[0].reply_to.is_none());
+        MessageService::restore_message(&pool, &human.id, &target_id).unwrap();
+        let reply = MessageRepository::get(&pool, &reply_id).unwrap();
+        let details = MessageService::attach_details(&pool, vec![reply]).unwrap();
+        assert_eq!(details[0].reply_to.as_ref().map(|preview| preview.id.as_str()), Some(target_id.as_str()));
+    }
+
+    #[test]
+    fn reply_target_must_be_in_the_same_chat() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let other = ChatRepository::create(&pool, "其他", "", ChatType::Group).unwrap();
+        ChatParticipantRepository::create(&pool, &other.id, &human.id).unwrap();
+        let foreign = MessageService::send_message(&pool, &other.id, &human.id, "其他聊天", None).unwrap();
+
+        let error = MessageService::send_message(&pool, &chat.id, &human.id, "回复", Some(&foreign.id)).unwrap_err();
+        assert!(error.to_string().contains("回复的消息不属于该聊天"));
+        let error = MessageService::send_message(&pool, &chat.id, &ai.id, "回复", Some("不存在")).unwrap_err();
+        assert!(error.to_string().contains("回复的消息不存在"));
+    }
+
+    #[test]
+    fn reply_thread_follows_nested_replies_on_active_branch() {
+        let TestChat { pool, human, chat, .. } = create_test_chat();
+        let messages = send_messages(&pool, &chat.id, &human.id, 6);
+        let root = &messages[0];
+        let mut conn = pool.get().unwrap();
+        let set_reply_to = |conn: &mut DbConnection, id: &str, reply_to_id: &str| {
+            diesel::update(messages::table.filter(messages::id.eq(id)))
+                .set(messages::reply_to_id.eq(reply_to_id))
+                .execute(conn)
+                .unwrap();
+        };
+        set_reply_to(&mut conn, &messages[1].id, &root.id);
+        set_reply_to(&mut conn, &messages[3].id, &messages[1].id);
+        set_reply_to(&mut conn, &messages[4].id, &root.id);
+        set_reply_to(&mut conn, &messages[5].id, &messages[4].id);
+        // 不在当前分支上的回复及其下的回复都不计入
+        diesel::update(messages::table.filter(messages::id.eq(&messages[4].id)))
+            .set(messages::is_active.eq(false))
+            .execute(&mut conn)
+            .unwrap();
+
+        let thread: Vec<String> = MessageService::get_reply_thread(&pool, &root.id)
+            .unwrap()
+            .into_iter()
+            .map(|message| message.id)
+            .collect();
+        assert_eq!(thread, [root.id.clone(), messages[1].id.clone(), messages[3].id.clone()]);
+
+        let thread = MessageService::get_reply_thread(&pool, &messages[3].id).unwrap();
+        assert_eq!(thread.len(), 1);
+        MessageService::delete_message(&pool, &human.id, &root.id).unwrap();
+        assert!(MessageService::get_reply_thread(&pool, &root.id).is_err());
+    }
+
+    fn contents(pool: &DbPool, chat_id: &str) -> Vec<String> {
+        MessageService::get_chat_messages(pool, chat_id)
+            .unwrap()
+            .into_iter()
+            .map(|message| message.content)
+            .collect()
+    }
+
+    fn revision_contents(pool: &DbPool, user_id: &str, message_id: &str) -> Vec<String> {
+        MessageService::get_revisions(pool, user_id, message_id)
+            .unwrap()
+            .into_iter()
+            .map(|revision| revision.content)
+            .collect()
+    }
+
+    #[test]
+    fn edits_keep_revisions_that_can_be_restored() {
+        let TestChat { pool, human, chat, .. } = create_test_chat();
+        let message = MessageService::send_message(&pool, &chat.id, &human.id, "原内容", None).unwrap();
+
+        MessageService::edit_message(&pool, &human.id, &message.id, "第一次修改", MessageEditMode::InPlace).unwrap();
+        MessageService::edit_message(&pool, &human.id, &message.id, "第二次修改", MessageEditMode::InPlace).unwrap();
+        // 内容没有变化时不产生修订记录
+        MessageService::edit_message(&pool, &human.id, &message.id, "第二次修改", MessageEditMode::InPlace).unwrap();
+        assert_eq!(revision_contents(&pool, &human.id, &message.id), ["第一次修改", "原内容"]);
+        let revisions = MessageService::get_revisions(&pool, &human.id, &message.id).unwrap();
+        assert!(revisions.iter().all(|revision| revision.editor_id.as_deref() == Some(human.id.as_str())));
+
+        let restored = MessageService::restore_revision(&pool, &human.id, &revisions[1].id).unwrap();
+        assert_eq!(restored.content, "原内容");
+        assert_eq!(revision_contents(&pool, &human.id, &message.id), ["第二次修改", "第一次修改", "原内容"]);
+        assert_eq!(ChatRepository::get(&pool, &chat.id).unwrap().last_message.as_deref(), Some("原内容"));
+        let details = MessageService::attach_details(&pool, vec![restored]).unwrap();
+        assert_eq!(details[0].revision_count, 3);
+    }
+
+    #[test]
+    fn only_own_and_ai_messages_can_be_edited() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let other = UserRepository::create(&pool, "同事".to_string(), None, false).unwrap();
+        ChatParticipantRepository::create(&pool, &chat.id, &other.id).unwrap();
+        let stranger = UserRepository::create(&pool, "陌生人".to_string(), None, false).unwrap();
+        let others = MessageService::send_message(&pool, &chat.id, &other.id, "同事的消息", None).unwrap();
+        let reply = MessageService::send_message(&pool, &chat.id, &ai.id, "AI的回复", None).unwrap();
+
+        let error = MessageService::edit_message(&pool, &human.id, &others.id, "改过的内容", MessageEditMode::InPlace)
+            .unwrap_err();
+        assert!(error.to_string().contains("只能编辑自己或AI用户发送的消息"));
+        MessageService::edit_message(&pool, &other.id, &others.id, "同事改过的消息", MessageEditMode::InPlace).unwrap();
+        let revision_id = MessageService::get_revisions(&pool, &human.id, &others.id).unwrap()[0].id.clone();
+        assert!(MessageService::restore_revision(&pool, &human.id, &revision_id).is_err());
+
+        // AI 的回复可以直接修改，但不能以它为起点重新生成后续回复
+        MessageService::edit_message(&pool, &human.id, &reply.id, "修正后的回复", MessageEditMode::InPlace).unwrap();
+        let error = MessageService::edit_message(&pool, &human.id, &reply.id, "再次修正", MessageEditMode::Truncate)
+            .unwrap_err();
+        assert!(error.to_string().contains("只能对自己发送的消息"));
+
+        // 修订记录只对聊天参与者可见
+        let error = MessageService::get_revisions(&pool, &stranger.id, &reply.id).unwrap_err();
+        assert!(error.to_string().contains("不是该聊天的参与者"));
+        assert_eq!(revision_contents(&pool, &human.id, &reply.id), ["AI的回复"]);
+    }
+
+    #[test]
+    fn truncate_edit_deletes_every_later_message() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        let question = MessageService::send_message(&pool, &chat.id, &human.id, "原来的问题", None).unwrap();
+        let answer = MessageService::send_message(&pool, &chat.id, &ai.id, "回答", None).unwrap();
+        let follow_up = MessageService::send_message(&pool, &chat.id, &human.id, "追问", Some(&answer.id)).unwrap();
+        MessageService::edit_message(&pool, &human.id, &follow_up.id, "修改过的追问", MessageEditMode::InPlace).unwrap();
+        let job = AiJobService::create_job(&pool, &chat.id, &ai.id, Some(&follow_up.id), None).unwrap();
+
+        let edited =
+            MessageService::edit_message(&pool, &human.id, &question.id, "新的问题", MessageEditMode::Truncate).unwrap();
+        assert_eq!(edited.id, question.id);
+        assert!(edited.active_child_id.is_none());
+        assert_eq!(contents(&pool, &chat.id), ["新的问题"]);
+        assert_eq!(revision_contents(&pool, &human.id, &question.id), ["原来的问题"]);
+        for id in [&answer.id, &follow_up.id] {
+            assert!(MessageRepository::get(&pool, id).is_err());
+            assert!(MessageRevisionRepository::get_by_message_id(&pool, id).unwrap().is_empty());
+        }
+        assert!(AiJobService::get_job(&pool, &job.id).unwrap().trigger_message_id.is_none());
+        assert_eq!(ChatRepository::get(&pool, &chat.id).unwrap().last_message.as_deref(), Some("新的问题"));
+    }
+
+    #[test]
+    fn branch_edit_keeps_original_as_another_branch() {
+        let TestChat { pool, human, ai, chat } = create_test_chat();
+        MessageService::send_message(&pool, &chat.id, &human.id, "你好", None).unwrap();
+        let answer = MessageService::send_message(&pool, &chat.id, &ai.id, "你好，有什么可以帮忙的", None).unwrap();
+        let question = MessageService::send_message(&pool, &chat.id, &human.id, "原来的问题", None).unwrap();
+        MessageService::send_message(&pool, &chat.id, &ai.id, "原来的回答", None).unwrap();
+
+        let edited =
+            MessageService::edit_message(&pool, &human.id, &question.id, "新的问题", MessageEditMode::Branch).unwrap();
+        assert_ne!(edited.id, question.id);
+        assert_eq!(edited.parent_id.as_deref(), Some(answer.id.as_str()));
+        assert_eq!(contents(&pool, &chat.id), ["你好", "你好，有什么可以帮忙的", "新的问题"]);
+        let (siblings, active) = MessageService::get_siblings(&pool, &edited.id).unwrap();
+        let sibling_ids: Vec<&str> = siblings.iter().map(|sibling| sibling.id.as_str()).collect();
+        assert_eq!(sibling_ids, [question.id.as_str(), edited.id.as_str()]);
+        assert_eq!(active, Some(1));
+        // 原消息保持原样，不产生修订记录
+        assert_eq!(MessageRepository::get(&pool, &question.id).unwrap().content, "原来的问题");
+        assert!(revision_contents(&pool, &human.id, &question.id).is_empty());
+        assert_eq!(last_read_message_id(&pool, &chat.id, &human.id), Some(edited.id));
+
+        MessageService::switch_branch(&pool, &question.id).unwrap();
+        assert_eq!(contents(&pool, &chat.id), ["你好", "你好，有什么可以帮忙的", "原来的问题", "原来的回答"]);
+    }
+}
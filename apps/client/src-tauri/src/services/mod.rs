@@ -2,5 +2,13 @@ pub mod chat_service;
 pub mod message_service;
 pub mod user_service;
 pub mod resource_service;
+pub mod agent_service;
+pub mod ai_job_service;
+pub mod context_builder;
+pub mod trash_service;
+pub mod group_chat_service;
+pub mod mcp_service;
+pub mod knowledge_service;
+pub mod memory_service;
 
 pub type ServiceResult<T> = Result<T, anyhow::Error>;
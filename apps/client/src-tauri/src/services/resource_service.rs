@@ -8,6 +8,7 @@ use diesel::connection::Connection;
 use crate::db::{DbPool, RESOURCES_DIR_NAME, IMAGES_DIR_NAME, TEXTS_DIR_NAME};
 use crate::models::Resource;
 use crate::repositories::resource_repository::ResourceRepository;
+use crate::repositories::knowledge_repository::KnowledgeRepository;
 use crate::repositories::error::RepositoryError;
 use super::ServiceResult;
 
@@ -150,6 +151,40 @@ impl ResourceService {
         Ok(resource)
     }
     
+    // 替换文本资源的内容
+    //
+    // 删除原内容生成的知识库片段，关联该资源的 AI 用户在下次检索时重新生成
+    pub fn update_text_resource_content(
+        pool: &DbPool,
+        id: &str,
+        content: &str,
+        app_resource_path: &Path,
+    ) -> ServiceResult<Resource> {
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+        
+        conn.transaction(|conn| {
+            let resource = ResourceRepository::get_with_conn(conn, id).map_err(|e| match e {
+                RepositoryError::NotFound => anyhow!("资源不存在"),
+                e => anyhow!("获取资源信息失败: {}", e),
+            })?;
+            if resource.type_ != "text" {
+                return Err(anyhow!("不是文本资源"));
+            }
+            
+            KnowledgeRepository::delete_chunks_by_resource_with_conn(conn, id)
+                .map_err(|e| anyhow!("删除资源片段失败: {}", e))?;
+            let resource = ResourceRepository::touch_with_conn(conn, id)
+                .map_err(|e| anyhow!("更新资源失败: {}", e))?;
+            
+            // 最后写入文件，写入失败时回滚数据库的修改
+            let file_path = app_resource_path.join(TEXTS_DIR_NAME).join(&resource.file_name);
+            fs::write(&file_path, content)
+                .map_err(|e| anyhow!("保存文本失败: {}", e))?;
+            
+            Ok(resource)
+        })
+    }
+    
     // 删除资源
     pub fn delete_resource(pool: &DbPool, id: &str, app_resource_path: &Path) -> ServiceResult<()> {
         // 获取资源信息
@@ -165,9 +200,13 @@ impl ResourceService {
         // 开始事务
         let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
         
-        conn.transaction(|_conn| {
-            // 1. 删除数据库记录
-            ResourceRepository::delete(pool, id)
+        conn.transaction(|conn| {
+            // 1. 删除数据库记录，连同知识库片段和各 AI 用户对其的关联
+            KnowledgeRepository::delete_chunks_by_resource_with_conn(conn, id)
+                .map_err(|e| anyhow!("删除资源片段失败: {}", e))?;
+            KnowledgeRepository::delete_agent_links_by_resource_with_conn(conn, id)
+                .map_err(|e| anyhow!("删除知识库关联失败: {}", e))?;
+            ResourceRepository::delete_with_conn(conn, id)
                 .map_err(|e| anyhow!("删除资源记录失败: {}", e))?;
             
             // 2. 删除文件
@@ -0,0 +1,208 @@
+// 回收站服务
+//
+// 删除的聊天和消息先移入回收站，超过保留期限后由后台任务彻底清除
+use std::time::Duration;
+
+use anyhow::anyhow;
+use chrono::{TimeDelta, Utc};
+
+use crate::db::{self, DbConnection, DbPool};
+use crate::repositories::agent_memory_repository::AgentMemoryRepository;
+use crate::repositories::ai_job_repository::AiJobRepository;
+use crate::repositories::chat_agent_override_repository::ChatAgentOverrideRepository;
+use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+use crate::repositories::chat_repository::ChatRepository;
+use crate::repositories::message_repository::MessageRepository;
+use crate::repositories::message_revision_repository::MessageRevisionRepository;
+use super::message_service::MessageService;
+use super::ServiceResult;
+
+// 设置回收站保留天数的环境变量
+const RETENTION_DAYS_ENV: &str = "GUIXIN_TRASH_RETENTION_DAYS";
+// 默认保留天数
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+// 后台清除的间隔
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// 一次清除彻底删除的数量
+#[derive(Debug, Default)]
+pub struct PurgeSummary {
+    pub chats: usize,
+    pub messages: usize,
+}
+
+pub struct TrashService;
+
+impl TrashService {
+    // 回收站保留天数，可通过环境变量 GUIXIN_TRASH_RETENTION_DAYS 设置，无效时使用默认值
+    pub fn retention_days() -> i64 {
+        std::env::var(RETENTION_DAYS_ENV)
+            .ok()
+            .and_then(|days| days.trim().parse::<i64>().ok())
+            .filter(|days| *days >= 0)
+            .unwrap_or(DEFAULT_RETENTION_DAYS)
+    }
+
+    // 彻底清除删除时间超过保留天数的聊天和消息
+    //
+    // 每个聊天在单独的事务中清除，超期的消息在同一事务中清除
+    pub fn purge_expired(pool: &DbPool, retention_days: i64) -> ServiceResult<PurgeSummary> {
+        let cutoff = Utc::now().naive_utc() - TimeDelta::days(retention_days);
+        let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
+        let mut summary = PurgeSummary::default();
+
+        let chat_ids = ChatRepository::get_expired_ids_with_conn(&mut conn, cutoff)
+            .map_err(|e| anyhow!("获取待清除的聊天失败: {}", e))?;
+        for chat_id in &chat_ids {
+            db::immediate_transaction(&mut conn, |conn| Self::purge_chat_with_conn(conn, chat_id))?;
+            summary.chats += 1;
+        }
+
+        summary.messages = db::immediate_transaction(&mut conn, |conn| {
+            let expired = MessageRepository::get_expired_with_conn(conn, cutoff)
+                .map_err(|e| anyhow!("获取待清除的消息失败: {}", e))?;
+            // 前面的清除会修改后续消息的分支关系，每条消息都重新读取
+            for expired_message in &expired {
+                let message = MessageRepository::get_with_conn(conn, &expired_message.id)
+                    .map_err(|e| anyhow!("获取消息失败: {}", e))?;
+                MessageService::purge_message_with_conn(conn, &message)?;
+            }
+            Ok::<_, anyhow::Error>(expired.len())
+        })?;
+
+        Ok(summary)
+    }
+
+    // 使用已有连接彻底删除聊天及其 AI 回复任务、AI 配置覆盖、消息修订记录、消息和参与者
+    //
+    // 消息、参与者和 AI 回复任务的外键没有声明删除动作，其他记录对该聊天的引用都显式清除：分出的聊天保留但不再指向来源，
+    // 其他聊天中回复该聊天消息的引用置空，从该聊天提取的 AI 记忆保留，只清除对聊天的引用；
+    // 资源只关联上传的用户，不随聊天删除；必须在事务中调用
+    pub fn purge_chat_with_conn(conn: &mut DbConnection, chat_id: &str) -> ServiceResult<()> {
+        AgentMemoryRepository::clear_chat_refs_with_conn(conn, chat_id)
+            .map_err(|e| anyhow!("更新AI记忆失败: {}", e))?;
+        ChatRepository::clear_fork_refs_with_conn(conn, chat_id)
+            .map_err(|e| anyhow!("更新分出的聊天失败: {}", e))?;
+        MessageRepository::clear_chat_reply_refs_with_conn(conn, chat_id)
+            .map_err(|e| anyhow!("更新回复消息失败: {}", e))?;
+        AiJobRepository::delete_by_chat_id_with_conn(conn, chat_id)
+            .map_err(|e| anyhow!("删除AI回复任务失败: {}", e))?;
+        ChatAgentOverrideRepository::delete_by_chat_id_with_conn(conn, chat_id)
+            .map_err(|e| anyhow!("删除聊天配置覆盖失败: {}", e))?;
+        MessageRevisionRepository::delete_by_chat_id_with_conn(conn, chat_id)
+            .map_err(|e| anyhow!("删除消息修订记录失败: {}", e))?;
+        MessageRepository::delete_by_chat_id_with_conn(conn, chat_id)
+            .map_err(|e| anyhow!("删除聊天消息失败: {}", e))?;
+        ChatParticipantRepository::delete_by_chat_id_with_conn(conn, chat_id)
+            .map_err(|e| anyhow!("删除聊天参与者失败: {}", e))?;
+        ChatRepository::delete_with_conn(conn, chat_id).map_err(|e| anyhow!("删除聊天失败: {}", e))
+    }
+
+    // 后台定期清除回收站，应用运行期间一直执行
+    pub async fn run_purge_loop(pool: DbPool, retention_days: i64) {
+        loop {
+            if let Err(e) = Self::purge_expired(&pool, retention_days) {
+                eprintln!("清除回收站失败: {}", e);
+            }
+            tokio::time::sleep(PURGE_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ChatType, Message};
+    use crate::repositories::user_repository::UserRepository;
+    use crate::test_support::create_test_pool;
+
+    fn expired() -> chrono::NaiveDateTime {
+        Utc::now().naive_utc() - TimeDelta::days(31)
+    }
+
+    fn send(pool: &DbPool, chat_id: &str, sender_id: &str, content: &str, reply_to_id: Option<&str>) -> Message {
+        let mut conn = pool.get().unwrap();
+        MessageRepository::create_with_conn(&mut conn, content.to_string(), chat_id, sender_id, reply_to_id).unwrap()
+    }
+
+    #[test]
+    fn purges_expired_chat_and_clears_refs_to_it() {
+        let pool = create_test_pool();
+        let user = UserRepository::create(&pool, "用户".to_string(), None, false).unwrap();
+        let chat = ChatRepository::create(&pool, "来源", "", ChatType::Group).unwrap();
+        ChatParticipantRepository::create(&pool, &chat.id, &user.id).unwrap();
+        let first = send(&pool, &chat.id, &user.id, "第一条", None);
+        let mut conn = pool.get().unwrap();
+        MessageRevisionRepository::create_with_conn(&mut conn, &first.id, "旧内容".to_string(), &user.id).unwrap();
+
+        // 分出的聊天中回复来源聊天的消息
+        let fork = ChatRepository::create_fork_with_conn(&mut conn, &chat, &first.id).unwrap();
+        let reply = MessageRepository::create_with_conn(&mut conn, "回复".to_string(), &fork.id, &user.id, Some(&first.id))
+            .unwrap();
+        ChatRepository::set_deleted_with_conn(&mut conn, &chat.id, Some(expired())).unwrap();
+
+        let summary = TrashService::purge_expired(&pool, 30).unwrap();
+        assert_eq!(summary.chats, 1);
+
+        assert!(ChatRepository::get_with_conn(&mut conn, &chat.id).is_err());
+        assert!(MessageRepository::get_by_chat_id(&pool, &chat.id).unwrap().is_empty());
+        assert!(ChatParticipantRepository::get_by_chat_id(&pool, &chat.id).unwrap().is_empty());
+        assert!(MessageRevisionRepository::get_by_message_id(&pool, &first.id).unwrap().is_empty());
+
+        let fork = ChatRepository::get_with_conn(&mut conn, &fork.id).unwrap();
+        assert_eq!(fork.forked_from_chat_id, None);
+        assert_eq!(fork.forked_from_message_id, None);
+        assert_eq!(MessageRepository::get_with_conn(&mut conn, &reply.id).unwrap().reply_to_id, None);
+    }
+
+    #[test]
+    fn purges_expired_message_and_clears_refs_to_it() {
+        let pool = create_test_pool();
+        let user = UserRepository::create(&pool, "用户".to_string(), None, false).unwrap();
+        let chat = ChatRepository::create(&pool, "聊天", "", ChatType::Group).unwrap();
+        ChatParticipantRepository::create(&pool, &chat.id, &user.id).unwrap();
+        let first = send(&pool, &chat.id, &user.id, "第一条", None);
+        let second = send(&pool, &chat.id, &user.id, "第二条", None);
+        let third = send(&pool, &chat.id, &user.id, "第三条", Some(&second.id));
+
+        let mut conn = pool.get().unwrap();
+        MessageRevisionRepository::create_with_conn(&mut conn, &second.id, "旧内容".to_string(), &user.id).unwrap();
+        let fork = ChatRepository::create_fork_with_conn(&mut conn, &chat, &second.id).unwrap();
+        MessageRepository::set_deleted_with_conn(&mut conn, &second.id, Some(expired())).unwrap();
+
+        let summary = TrashService::purge_expired(&pool, 30).unwrap();
+        assert_eq!(summary.chats, 0);
+        assert_eq!(summary.messages, 1);
+
+        assert!(MessageRepository::get_with_conn(&mut conn, &second.id).is_err());
+        assert!(MessageRevisionRepository::get_by_message_id(&pool, &second.id).unwrap().is_empty());
+
+        let third = MessageRepository::get_with_conn(&mut conn, &third.id).unwrap();
+        assert_eq!(third.reply_to_id, None);
+        assert_eq!(third.parent_id.as_deref(), Some(first.id.as_str()));
+
+        let fork = ChatRepository::get_with_conn(&mut conn, &fork.id).unwrap();
+        assert_eq!(fork.forked_from_chat_id.as_deref(), Some(chat.id.as_str()));
+        assert_eq!(fork.forked_from_message_id, None);
+    }
+
+    #[test]
+    fn keeps_items_within_retention() {
+        let pool = create_test_pool();
+        let user = UserRepository::create(&pool, "用户".to_string(), None, false).unwrap();
+        let chat = ChatRepository::create(&pool, "聊天", "", ChatType::Group).unwrap();
+        let other = ChatRepository::create(&pool, "另一个聊天", "", ChatType::Group).unwrap();
+        let message = send(&pool, &other.id, &user.id, "消息", None);
+
+        let mut conn = pool.get().unwrap();
+        let recent = Utc::now().naive_utc() - TimeDelta::days(29);
+        ChatRepository::set_deleted_with_conn(&mut conn, &chat.id, Some(recent)).unwrap();
+        MessageRepository::set_deleted_with_conn(&mut conn, &message.id, Some(recent)).unwrap();
+
+        let summary = TrashService::purge_expired(&pool, 30).unwrap();
+        assert_eq!(summary.chats, 0);
+        assert_eq!(summary.messages, 0);
+        assert!(ChatRepository::get_with_conn(&mut conn, &chat.id).is_ok());
+        assert!(MessageRepository::get_with_conn(&mut conn, &message.id).is_ok());
+    }
+}
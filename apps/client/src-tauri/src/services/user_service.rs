@@ -8,6 +8,7 @@ use crate::models::{User, NewUser};
 use crate::schema::users;
 use crate::repositories::user_repository::UserRepository;
 use crate::repositories::user_contact_repository::UserContactRepository;
+use crate::services::agent_service::{AgentConfig, AgentService};
 
 use super::ServiceResult;
 
@@ -73,6 +74,7 @@ impl UserService {
         let new_user = NewUser {
             id: Uuid::new_v4().to_string(),
             name: "默认用户".to_string(),
+            avatar_url: None,
             description: Some("系统创建的默认用户".to_string()),
             is_ai: false,
             created_at: Utc::now().naive_utc(),
@@ -101,6 +103,7 @@ impl UserService {
         let new_user = NewUser {
             id: Uuid::new_v4().to_string(),
             name: name.to_string(),
+            avatar_url: None,
             description: description.map(|desc| desc.to_string()),
             is_ai: true,
             created_at: Utc::now().naive_utc(),
@@ -119,12 +122,13 @@ impl UserService {
             .map_err(|e| anyhow!("获取新创建的AI用户失败: {}", e))
     }
 
-    // 创建AI用户并添加为联系人
+    // 创建AI用户并添加为联系人，同时创建其 Agent 配置
     pub fn create_ai_user_and_add_as_contact(
         pool: &DbPool,
         user_id: &str,
         name: &str,
         description: Option<&str>,
+        agent_config: Option<AgentConfig>,
     ) -> Result<User> {
         // 获取连接
         let mut conn = pool.get().map_err(|e| anyhow!("获取数据库连接失败: {}", e))?;
@@ -134,7 +138,10 @@ impl UserService {
             // 1. 创建AI用户
             let ai_user = Self::create_ai_user(conn, name, description)?;
             
-            // 2. 添加为联系人 - 使用同一个事务连接
+            // 2. 创建 Agent 配置
+            AgentService::create_with_conn(conn, &ai_user.id, agent_config)?;
+
+            // 3. 添加为联系人 - 使用同一个事务连接
             UserContactRepository::create_with_conn(conn, user_id, &ai_user.id)
                 .map_err(|e| anyhow!("添加联系人失败: {}", e))?;
             
@@ -0,0 +1,193 @@
+// 测试共用的辅助函数
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use diesel_migrations::MigrationHarness;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+use crate::ai_queue::events::{AiJobDeltaEvent, AiJobStatusEvent, JobEventSink};
+use crate::ai_queue::AiJobQueue;
+use crate::db::{self, DbPool};
+use crate::llm::mock::{MockProvider, MOCK_MODEL};
+use crate::llm::ProviderRegistry;
+use crate::mcp::McpManager;
+use crate::models::{Chat, ChatType, User};
+use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+use crate::repositories::chat_repository::ChatRepository;
+use crate::repositories::user_contact_repository::UserContactRepository;
+use crate::repositories::user_repository::UserRepository;
+use crate::services::agent_service::{AgentConfig, AgentService};
+use crate::tools::ToolRegistry;
+
+// 在临时目录中创建数据库并运行全部迁移，每次调用得到一个独立的数据库
+//
+// 连接池中的每个连接各自打开文件，不能使用 :memory: 数据库
+pub fn create_test_pool() -> DbPool {
+    create_test_database().1
+}
+
+// 同 create_test_pool，同时返回数据库文件路径，用于再打开只读的连接池
+pub fn create_test_database() -> (String, DbPool) {
+    let path = std::env::temp_dir().join(format!("guixin-test-{}.db", Uuid::new_v4()));
+    let path = path.to_str().expect("临时目录路径无效").to_string();
+    let pool = db::create_pool(&path, false).expect("无法创建测试数据库");
+    let mut conn = pool.get().expect("无法获取数据库连接");
+    conn.run_pending_migrations(db::MIGRATIONS).expect("无法运行数据库迁移");
+    (path, pool)
+}
+
+// 使用模拟模型的 Agent 配置，其余为默认值
+pub fn mock_agent_config() -> AgentConfig {
+    AgentConfig {
+        provider: "mock".to_string(),
+        model_name: MOCK_MODEL.to_string(),
+        ..Default::default()
+    }
+}
+
+// 真人用户与使用模拟模型的 AI 用户
+pub struct TestUsers {
+    pub pool: DbPool,
+    pub human: User,
+    pub ai: User,
+}
+
+// 在新数据库中创建真人用户和使用模拟模型的 AI 用户，AI 用户是真人用户的联系人，尚无聊天
+pub fn create_test_users() -> TestUsers {
+    create_test_users_with_pool(create_test_pool())
+}
+
+// 同 create_test_users，在给定的数据库中创建
+pub fn create_test_users_with_pool(pool: DbPool) -> TestUsers {
+    let human = UserRepository::create(&pool, "用户".to_string(), None, false).expect("无法创建用户");
+    let ai = UserRepository::create(&pool, "助手".to_string(), None, true).expect("无法创建AI用户");
+    UserContactRepository::create(&pool, &human.id, &ai.id).expect("无法添加联系人");
+    AgentService::update_agent_config(&pool, &ai.id, mock_agent_config()).expect("无法保存Agent配置");
+    TestUsers { pool, human, ai }
+}
+
+// 真人用户与 AI 用户的单聊
+pub struct TestChat {
+    pub pool: DbPool,
+    pub human: User,
+    pub ai: User,
+    pub chat: Chat,
+}
+
+// 在新数据库中创建真人用户与使用模拟模型的 AI 用户的单聊，尚无消息
+pub fn create_test_chat() -> TestChat {
+    create_test_chat_with_pool(create_test_pool())
+}
+
+// 同 create_test_chat，在给定的数据库中创建
+pub fn create_test_chat_with_pool(pool: DbPool) -> TestChat {
+    let TestUsers { pool, human, ai } = create_test_users_with_pool(pool);
+    let chat = ChatRepository::create(&pool, "助手", "", ChatType::Direct).expect("无法创建聊天");
+    ChatParticipantRepository::create(&pool, &chat.id, &human.id).expect("无法添加参与者");
+    ChatParticipantRepository::create(&pool, &chat.id, &ai.id).expect("无法添加参与者");
+    TestChat { pool, human, ai, chat }
+}
+
+// 使用模拟服务商的任务队列，任务事件被丢弃
+pub fn create_test_queue(pool: &DbPool, provider: Arc<MockProvider>) -> AiJobQueue {
+    let mut providers = ProviderRegistry::new();
+    providers.register(provider);
+    let app_resource_path = std::env::temp_dir();
+    AiJobQueue::new(
+        pool.clone(),
+        Arc::new(providers),
+        Arc::new(ToolRegistry::with_defaults(app_resource_path.clone())),
+        Arc::new(McpManager::default()),
+        Arc::new(NoopJobEventSink),
+        app_resource_path,
+    )
+}
+
+// 临时目录，离开作用域时连同其中的文件一并删除
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("guixin-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path).expect("无法创建临时目录");
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+// 丢弃全部任务事件
+pub struct NoopJobEventSink;
+
+impl JobEventSink for NoopJobEventSink {
+    fn status_changed(&self, _event: AiJobStatusEvent) {}
+
+    fn delta(&self, _event: AiJobDeltaEvent) {}
+}
+
+// 启动只处理一次请求的本地 HTTP 服务，返回形如 http://127.0.0.1:port 的地址
+//
+// 响应体按 chunks 分多次写出，每次之间稍作停顿，用于模拟流式响应被拆成多个数据块
+pub async fn serve_once(status: u16, content_type: &'static str, chunks: Vec<&'static str>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("无法绑定本地端口");
+    let address = listener.local_addr().expect("无法获取本地地址");
+
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.expect("接受连接失败");
+        read_request(&mut socket).await;
+
+        let length: usize = chunks.iter().map(|chunk| chunk.len()).sum();
+        let head = format!(
+            "HTTP/1.1 {} \r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            status, content_type, length
+        );
+        socket.write_all(head.as_bytes()).await.expect("写出响应头失败");
+        for chunk in chunks {
+            socket.write_all(chunk.as_bytes()).await.expect("写出响应体失败");
+            socket.flush().await.expect("写出响应体失败");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let _ = socket.shutdown().await;
+    });
+
+    format!("http://{}", address)
+}
+
+// 读取完整的请求头和按 content-length 给出的请求体
+async fn read_request(socket: &mut tokio::net::TcpStream) {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 4096];
+    loop {
+        let read = socket.read(&mut buffer).await.expect("读取请求失败");
+        if read == 0 {
+            return;
+        }
+        request.extend_from_slice(&buffer[..read]);
+
+        let Some(header_end) = request.windows(4).position(|window| window == b"\r\n\r\n") else {
+            continue;
+        };
+        let headers = String::from_utf8_lossy(&request[..header_end]).to_ascii_lowercase();
+        let body_length = headers
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length:"))
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        if request.len() >= header_end + 4 + body_length {
+            return;
+        }
+    }
+}
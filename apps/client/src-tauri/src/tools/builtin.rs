@@ -0,0 +1,369 @@
+// 内置工具，封装已有的服务，结果以 JSON 文本返回给模型
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chrono::{Datelike, Local, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::error::{ToolError, ToolResult};
+use super::{parse_arguments, Tool, ToolContext};
+use crate::models::MessageSearchFilter;
+use crate::repositories::user_contact_repository::UserContactRepository;
+use crate::repositories::user_repository::UserRepository;
+use crate::services::message_service::MessageService;
+use crate::services::resource_service::ResourceService;
+
+pub const CURRENT_TIME: &str = "current_time";
+pub const LIST_CONTACTS: &str = "list_contacts";
+pub const SEARCH_MESSAGES: &str = "search_messages";
+pub const LIST_TEXT_RESOURCES: &str = "list_text_resources";
+pub const READ_TEXT_RESOURCE: &str = "read_text_resource";
+
+// 所有内置工具的名称，Agent 配置中的工具名称需在其中
+pub const BUILTIN_TOOL_NAMES: [&str; 5] = [
+    CURRENT_TIME,
+    LIST_CONTACTS,
+    SEARCH_MESSAGES,
+    LIST_TEXT_RESOURCES,
+    READ_TEXT_RESOURCE,
+];
+
+// 检索消息时默认和最多返回的条数
+const DEFAULT_SEARCH_RESULTS: i64 = 10;
+const MAX_SEARCH_RESULTS: i64 = 20;
+// 检索结果中每条消息内容的最大字符数
+const MAX_SEARCH_CONTENT_CHARS: usize = 300;
+
+const WEEKDAYS: [&str; 7] = ["星期一", "星期二", "星期三", "星期四", "星期五", "星期六", "星期日"];
+
+fn execution_error(e: impl std::fmt::Display) -> ToolError {
+    ToolError::Execution(e.to_string())
+}
+
+fn empty_parameters() -> Value {
+    json!({ "type": "object", "properties": {} })
+}
+
+// 当前时间
+pub struct CurrentTimeTool;
+
+#[async_trait]
+impl Tool for CurrentTimeTool {
+    fn name(&self) -> &str {
+        CURRENT_TIME
+    }
+
+    fn description(&self) -> &str {
+        "获取当前的本地日期、时间和星期"
+    }
+
+    fn parameters(&self) -> Value {
+        empty_parameters()
+    }
+
+    async fn call(&self, _context: &ToolContext, _arguments: Value) -> ToolResult<String> {
+        let now = Local::now();
+        Ok(json!({
+            "local_time": now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            "utc_offset": now.format("%:z").to_string(),
+            "weekday": WEEKDAYS[now.weekday().num_days_from_monday() as usize],
+            "utc_time": Utc::now().to_rfc3339(),
+        })
+        .to_string())
+    }
+}
+
+// 用户的联系人列表
+pub struct ListContactsTool;
+
+#[async_trait]
+impl Tool for ListContactsTool {
+    fn name(&self) -> &str {
+        LIST_CONTACTS
+    }
+
+    fn description(&self) -> &str {
+        "列出用户的联系人，包括名称、简介以及是否为AI"
+    }
+
+    fn parameters(&self) -> Value {
+        empty_parameters()
+    }
+
+    async fn call(&self, context: &ToolContext, _arguments: Value) -> ToolResult<String> {
+        let contact_ids: Vec<String> = UserContactRepository::get_by_user_id(&context.pool, &context.user_id)
+            .map_err(execution_error)?
+            .into_iter()
+            .map(|relation| relation.contact_id)
+            .collect();
+        let contacts: Vec<Value> = UserRepository::get_by_ids(&context.pool, &contact_ids)
+            .map_err(execution_error)?
+            .into_iter()
+            .map(|user| {
+                json!({
+                    "id": user.id,
+                    "name": user.name,
+                    "description": user.description,
+                    "is_ai": user.is_ai,
+                })
+            })
+            .collect();
+        Ok(Value::Array(contacts).to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct SearchMessagesArguments {
+    query: String,
+    #[serde(default)]
+    current_chat_only: bool,
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+// 全文检索聊天记录
+pub struct SearchMessagesTool;
+
+#[async_trait]
+impl Tool for SearchMessagesTool {
+    fn name(&self) -> &str {
+        SEARCH_MESSAGES
+    }
+
+    fn description(&self) -> &str {
+        "按关键词检索聊天记录，多个关键词用空格分隔，返回同时包含全部关键词的消息"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "检索关键词，多个关键词用空格分隔" },
+                "current_chat_only": { "type": "boolean", "description": "是否只检索当前聊天，默认检索所有聊天" },
+                "limit": {
+                    "type": "integer",
+                    "description": format!("返回的最大条数，默认 {}", DEFAULT_SEARCH_RESULTS),
+                    "minimum": 1,
+                    "maximum": MAX_SEARCH_RESULTS,
+                },
+            },
+            "required": ["query"],
+        })
+    }
+
+    async fn call(&self, context: &ToolContext, arguments: Value) -> ToolResult<String> {
+        let arguments: SearchMessagesArguments = parse_arguments(arguments)?;
+        let filter = MessageSearchFilter {
+            chat_id: arguments.current_chat_only.then(|| context.chat_id.clone()),
+            ..Default::default()
+        };
+        let limit = arguments
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_RESULTS)
+            .clamp(1, MAX_SEARCH_RESULTS);
+
+        let page = MessageService::search_messages(&context.pool, &arguments.query, &filter, limit, 0)
+            .map_err(execution_error)?;
+        let hits: Vec<Value> = page
+            .hits
+            .into_iter()
+            .map(|hit| {
+                let row = hit.row;
+                let content = match row.content.char_indices().nth(MAX_SEARCH_CONTENT_CHARS) {
+                    Some((end, _)) => format!("{}…", &row.content[..end]),
+                    None => row.content,
+                };
+                json!({
+                    "message_id": row.id,
+                    "chat_name": row.chat_name,
+                    "sender_name": row.sender_name,
+                    "sender_is_ai": row.sender_is_ai,
+                    "created_at": row.created_at.to_string(),
+                    "content": content,
+                })
+            })
+            .collect();
+        Ok(json!({ "results": hits, "has_more": page.has_more }).to_string())
+    }
+}
+
+// 用户上传的文本资源列表
+pub struct ListTextResourcesTool;
+
+#[async_trait]
+impl Tool for ListTextResourcesTool {
+    fn name(&self) -> &str {
+        LIST_TEXT_RESOURCES
+    }
+
+    fn description(&self) -> &str {
+        "列出用户上传的文本资源，返回资源ID、名称和描述，可再通过 read_text_resource 读取内容"
+    }
+
+    fn parameters(&self) -> Value {
+        empty_parameters()
+    }
+
+    async fn call(&self, context: &ToolContext, _arguments: Value) -> ToolResult<String> {
+        let resources: Vec<Value> = ResourceService::get_user_text_resources(&context.pool, &context.user_id)
+            .map_err(execution_error)?
+            .into_iter()
+            .map(|resource| {
+                json!({
+                    "id": resource.id,
+                    "name": resource.name,
+                    "description": resource.description,
+                    "updated_at": resource.updated_at.to_string(),
+                })
+            })
+            .collect();
+        Ok(Value::Array(resources).to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct ReadTextResourceArguments {
+    resource_id: String,
+}
+
+// 读取文本资源的内容，只能读取用户自己的资源
+pub struct ReadTextResourceTool {
+    app_resource_path: PathBuf,
+}
+
+impl ReadTextResourceTool {
+    pub fn new(app_resource_path: PathBuf) -> Self {
+        Self { app_resource_path }
+    }
+}
+
+#[async_trait]
+impl Tool for ReadTextResourceTool {
+    fn name(&self) -> &str {
+        READ_TEXT_RESOURCE
+    }
+
+    fn description(&self) -> &str {
+        "读取用户的一个文本资源的内容"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "resource_id": { "type": "string", "description": "文本资源的ID" },
+            },
+            "required": ["resource_id"],
+        })
+    }
+
+    async fn call(&self, context: &ToolContext, arguments: Value) -> ToolResult<String> {
+        let arguments: ReadTextResourceArguments = parse_arguments(arguments)?;
+        let resource = ResourceService::get_resource(&context.pool, &arguments.resource_id)
+            .map_err(|_| ToolError::InvalidArguments(format!("资源不存在: {}", arguments.resource_id)))?;
+        if resource.user_id != context.user_id {
+            return Err(ToolError::PermissionDenied(format!("资源 {}", arguments.resource_id)));
+        }
+        ResourceService::read_text_resource_content(&context.pool, &resource.id, &self.app_resource_path)
+            .map_err(execution_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DbPool;
+    use crate::models::ChatType;
+    use crate::repositories::chat_participant_repository::ChatParticipantRepository;
+    use crate::repositories::chat_repository::ChatRepository;
+    use crate::repositories::message_repository::MessageRepository;
+    use crate::test_support::{create_test_pool, TempDir};
+
+    fn context(pool: &DbPool, user_id: &str) -> ToolContext {
+        ToolContext { pool: pool.clone(), chat_id: String::new(), user_id: user_id.to_string() }
+    }
+
+    fn parse(output: &str) -> Value {
+        serde_json::from_str(output).expect("工具结果不是合法的 JSON")
+    }
+
+    #[tokio::test]
+    async fn current_time_reports_local_and_utc_time() {
+        let pool = create_test_pool();
+        let output = CurrentTimeTool.call(&context(&pool, "user"), Value::Null).await.unwrap();
+        let value = parse(&output);
+        assert!(WEEKDAYS.contains(&value["weekday"].as_str().unwrap()));
+        assert!(value["local_time"].is_string());
+        assert!(value["utc_time"].is_string());
+    }
+
+    #[tokio::test]
+    async fn list_contacts_returns_only_own_contacts() {
+        let pool = create_test_pool();
+        let user = UserRepository::create(&pool, "用户".to_string(), None, false).unwrap();
+        let friend = UserRepository::create(&pool, "朋友".to_string(), Some("大学同学".to_string()), false).unwrap();
+        let stranger = UserRepository::create(&pool, "陌生人".to_string(), None, true).unwrap();
+        UserContactRepository::create(&pool, &user.id, &friend.id).unwrap();
+        UserContactRepository::create(&pool, &stranger.id, &user.id).unwrap();
+
+        let value = parse(&ListContactsTool.call(&context(&pool, &user.id), Value::Null).await.unwrap());
+        let contacts = value.as_array().unwrap();
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0]["name"], "朋友");
+        assert_eq!(contacts[0]["description"], "大学同学");
+        assert_eq!(contacts[0]["is_ai"], false);
+    }
+
+    #[tokio::test]
+    async fn search_messages_filters_by_current_chat() {
+        let pool = create_test_pool();
+        let user = UserRepository::create(&pool, "用户".to_string(), None, false).unwrap();
+        let mut chat_ids = Vec::new();
+        for name in ["工作", "生活"] {
+            let chat = ChatRepository::create(&pool, name, "", ChatType::Group).unwrap();
+            ChatParticipantRepository::create(&pool, &chat.id, &user.id).unwrap();
+            MessageRepository::create(&pool, format!("{}群里的周报", name), &chat.id, &user.id).unwrap();
+            chat_ids.push(chat.id);
+        }
+
+        let tool_context = ToolContext { chat_id: chat_ids[0].clone(), ..context(&pool, &user.id) };
+        let value = parse(&SearchMessagesTool.call(&tool_context, json!({ "query": "周报" })).await.unwrap());
+        assert_eq!(value["results"].as_array().unwrap().len(), 2);
+
+        let arguments = json!({ "query": "周报", "current_chat_only": true });
+        let value = parse(&SearchMessagesTool.call(&tool_context, arguments).await.unwrap());
+        let results = value["results"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["chat_name"], "工作");
+
+        let error = SearchMessagesTool.call(&tool_context, Value::Null).await.unwrap_err();
+        assert!(matches!(error, ToolError::InvalidArguments(_)));
+    }
+
+    #[tokio::test]
+    async fn text_resources_are_readable_only_by_owner() {
+        let pool = create_test_pool();
+        let app_resource_dir = TempDir::new();
+        let app_resource_path = app_resource_dir.path();
+        let owner = UserRepository::create(&pool, "用户".to_string(), None, false).unwrap();
+        let other = UserRepository::create(&pool, "他人".to_string(), None, false).unwrap();
+        let resource =
+            ResourceService::create_text_resource(&pool, &owner.id, "笔记", "会议纪要", Some("周一"), app_resource_path)
+                .unwrap();
+
+        let value = parse(&ListTextResourcesTool.call(&context(&pool, &owner.id), Value::Null).await.unwrap());
+        assert_eq!(value[0]["id"], resource.id.as_str());
+        assert_eq!(value[0]["name"], "笔记");
+
+        let tool = ReadTextResourceTool::new(app_resource_path.to_path_buf());
+        let arguments = json!({ "resource_id": resource.id });
+        let content = tool.call(&context(&pool, &owner.id), arguments.clone()).await.unwrap();
+        assert_eq!(content, "会议纪要");
+
+        let error = tool.call(&context(&pool, &other.id), arguments).await.unwrap_err();
+        assert!(matches!(error, ToolError::PermissionDenied(_)));
+        let error = tool.call(&context(&pool, &owner.id), json!({ "resource_id": "missing" })).await.unwrap_err();
+        assert!(matches!(error, ToolError::InvalidArguments(_)));
+    }
+}
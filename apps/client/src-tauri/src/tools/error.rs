@@ -0,0 +1,22 @@
+use thiserror::Error;
+
+/// 工具调用错误类型
+///
+/// 错误信息会作为工具结果返回给模型，由模型决定如何继续
+#[derive(Debug, Error)]
+pub enum ToolError {
+    #[error("未知的工具: {0}")]
+    UnknownTool(String),
+
+    #[error("工具参数错误: {0}")]
+    InvalidArguments(String),
+
+    #[error("无权访问: {0}")]
+    PermissionDenied(String),
+
+    #[error("工具执行失败: {0}")]
+    Execution(String),
+}
+
+/// 工具调用结果类型
+pub type ToolResult<T> = Result<T, ToolError>;
@@ -0,0 +1,181 @@
+// Agent 可以调用的工具
+//
+// 模型在回复过程中请求调用工具，执行结果作为 tool 消息返回给模型，模型据此继续生成，
+// 直到给出最终回复或达到 Agent 配置的最大轮数
+pub mod builtin;
+pub mod error;
+pub mod registry;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::db::DbPool;
+use crate::llm::{ToolCall, ToolDefinition};
+
+pub use error::{ToolError, ToolResult};
+pub use registry::ToolRegistry;
+
+// 返回给模型的工具结果的最大字符数，超出部分被截断
+pub const MAX_TOOL_RESULT_CHARS: usize = 8000;
+// 截断工具结果时添加的后缀
+const TRUNCATED_SUFFIX: &str = "…（内容过长，已截断）";
+
+// 工具执行时的上下文
+#[derive(Clone)]
+pub struct ToolContext {
+    pub pool: DbPool,
+    pub chat_id: String,
+    // AI 代为操作的真人用户，资源、联系人等按该用户的权限读取
+    pub user_id: String,
+}
+
+/// 可由模型调用的工具
+#[async_trait]
+pub trait Tool: Send + Sync {
+    // 工具名称，只包含字母、数字、下划线和连字符，在注册表中唯一
+    fn name(&self) -> &str;
+
+    // 提供给模型的工具说明
+    fn description(&self) -> &str;
+
+    // 参数的 JSON Schema
+    fn parameters(&self) -> Value;
+
+    // 执行工具，返回提供给模型的文本结果
+    async fn call(&self, context: &ToolContext, arguments: Value) -> ToolResult<String>;
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+            parameters: self.parameters(),
+        }
+    }
+}
+
+// 一次回复中可以调用的工具
+#[derive(Clone, Default)]
+pub struct ToolSet {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolSet {
+    pub fn new(tools: Vec<Arc<dyn Tool>>) -> Self {
+        Self { tools }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.iter().map(|tool| tool.definition()).collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools.iter().any(|tool| tool.name() == name)
+    }
+
+    // 按名称执行工具，结果超出 MAX_TOOL_RESULT_CHARS 时截断
+    pub async fn call(&self, context: &ToolContext, name: &str, arguments: Value) -> ToolResult<String> {
+        match self.tools.iter().find(|tool| tool.name() == name) {
+            Some(tool) => tool.call(context, arguments).await.map(truncate_result),
+            None => Err(ToolError::UnknownTool(name.to_string())),
+        }
+    }
+
+    // 执行一次工具调用，失败时返回错误信息，由模型决定如何继续
+    pub async fn execute(&self, context: &ToolContext, call: &ToolCall) -> String {
+        match self.call(context, &call.name, call.arguments.clone()).await {
+            Ok(output) => output,
+            Err(e) => format!("错误：{}", e),
+        }
+    }
+}
+
+// 将调用参数解析为工具的参数结构，模型未提供参数时视为空对象
+pub fn parse_arguments<T: DeserializeOwned>(arguments: Value) -> ToolResult<T> {
+    let arguments = match arguments {
+        Value::Null => Value::Object(Map::new()),
+        Value::String(raw) => {
+            return Err(ToolError::InvalidArguments(format!("参数不是合法的 JSON 对象: {}", raw)));
+        }
+        arguments => arguments,
+    };
+    serde_json::from_value(arguments).map_err(|e| ToolError::InvalidArguments(e.to_string()))
+}
+
+fn truncate_result(output: String) -> String {
+    match output.char_indices().nth(MAX_TOOL_RESULT_CHARS) {
+        Some((end, _)) => format!("{}{}", &output[..end], TRUNCATED_SUFFIX),
+        None => output,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::create_test_pool;
+
+    #[derive(Debug, Deserialize)]
+    struct Arguments {
+        #[serde(default)]
+        limit: Option<i64>,
+    }
+
+    // 返回固定长度输出的工具
+    struct LongOutputTool;
+
+    #[async_trait]
+    impl Tool for LongOutputTool {
+        fn name(&self) -> &str {
+            "long_output"
+        }
+
+        fn description(&self) -> &str {
+            "返回超长的结果"
+        }
+
+        fn parameters(&self) -> Value {
+            json!({ "type": "object", "properties": {} })
+        }
+
+        async fn call(&self, _context: &ToolContext, _arguments: Value) -> ToolResult<String> {
+            Ok("字".repeat(MAX_TOOL_RESULT_CHARS + 1))
+        }
+    }
+
+    #[test]
+    fn parse_arguments_accepts_missing_and_rejects_raw_strings() {
+        let parsed: Arguments = parse_arguments(Value::Null).unwrap();
+        assert_eq!(parsed.limit, None);
+        let parsed: Arguments = parse_arguments(json!({ "limit": 3 })).unwrap();
+        assert_eq!(parsed.limit, Some(3));
+
+        let raw = parse_arguments::<Arguments>(Value::String("{limit: 3".to_string())).unwrap_err();
+        assert!(matches!(raw, ToolError::InvalidArguments(_)));
+        let wrong_type = parse_arguments::<Arguments>(json!({ "limit": "三" })).unwrap_err();
+        assert!(matches!(wrong_type, ToolError::InvalidArguments(_)));
+    }
+
+    #[tokio::test]
+    async fn tool_set_truncates_output_and_reports_unknown_tools() {
+        let context = ToolContext { pool: create_test_pool(), chat_id: String::new(), user_id: String::new() };
+        let tools = ToolSet::new(vec![Arc::new(LongOutputTool)]);
+        assert!(tools.contains("long_output"));
+        assert_eq!(tools.definitions()[0].name, "long_output");
+
+        let output = tools.call(&context, "long_output", Value::Null).await.unwrap();
+        assert!(output.ends_with(TRUNCATED_SUFFIX));
+        assert_eq!(output.chars().count(), MAX_TOOL_RESULT_CHARS + TRUNCATED_SUFFIX.chars().count());
+
+        let call = ToolCall { id: "call-1".to_string(), name: "missing".to_string(), arguments: Value::Null };
+        assert_eq!(tools.execute(&context, &call).await, "错误：未知的工具: missing");
+    }
+}
@@ -0,0 +1,49 @@
+// 工具注册表，按名称查找 Agent 可以调用的工具
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::builtin::{
+    CurrentTimeTool, ListContactsTool, ListTextResourcesTool, ReadTextResourceTool, SearchMessagesTool,
+};
+use super::Tool;
+use crate::llm::ToolDefinition;
+
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: HashMap<String, Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 注册内置工具，读取文本资源需要应用资源目录
+    pub fn with_defaults(app_resource_path: PathBuf) -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(CurrentTimeTool));
+        registry.register(Arc::new(ListContactsTool));
+        registry.register(Arc::new(SearchMessagesTool));
+        registry.register(Arc::new(ListTextResourcesTool));
+        registry.register(Arc::new(ReadTextResourceTool::new(app_resource_path)));
+        registry
+    }
+
+    // 注册工具，同名工具会被替换
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        self.tools.insert(tool.name().to_string(), tool);
+    }
+
+    // 按名称挑选工具，未注册的名称被忽略
+    pub fn select(&self, names: &[String]) -> Vec<Arc<dyn Tool>> {
+        names.iter().filter_map(|name| self.tools.get(name).cloned()).collect()
+    }
+
+    // 已注册工具的定义，按名称排序
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        let mut definitions: Vec<ToolDefinition> = self.tools.values().map(|tool| tool.definition()).collect();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        definitions
+    }
+}
@@ -0,0 +1,105 @@
+// 通过标准输入输出与 mcp_echo_server 交互，校验握手、列出工具和调用工具的应答
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde_json::{json, Value};
+
+struct EchoServer {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: i64,
+}
+
+impl EchoServer {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_mcp_echo_server"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("无法启动 mcp_echo_server");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Self { child, stdin, stdout, next_id: 1 }
+    }
+
+    // 发送请求并读取一行应答
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        writeln!(self.stdin, "{}", request).unwrap();
+        self.stdin.flush().unwrap();
+
+        let mut line = String::new();
+        self.stdout.read_line(&mut line).unwrap();
+        let response: Value = serde_json::from_str(&line).expect("应答不是合法的 JSON");
+        assert_eq!(response["id"], id);
+        response
+    }
+
+    fn notify(&mut self, method: &str) {
+        writeln!(self.stdin, "{}", json!({ "jsonrpc": "2.0", "method": method })).unwrap();
+        self.stdin.flush().unwrap();
+    }
+}
+
+impl Drop for EchoServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn initialized() -> EchoServer {
+    let mut server = EchoServer::start();
+    let response = server.request(
+        "initialize",
+        json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": { "name": "test", "version": "0" },
+        }),
+    );
+    let result = &response["result"];
+    assert_eq!(result["protocolVersion"], "2024-11-05");
+    assert_eq!(result["serverInfo"]["name"], "mcp-echo-server");
+    assert!(result["capabilities"]["tools"].is_object());
+    // 通知没有应答，下一个请求读到的仍是它自己的应答
+    server.notify("notifications/initialized");
+    server
+}
+
+#[test]
+fn initialize_and_list_tools() {
+    let mut server = initialized();
+    let response = server.request("tools/list", json!({}));
+    let names: Vec<&str> = response["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["echo", "add", "fail"]);
+    assert_eq!(response["result"]["tools"][0]["inputSchema"]["required"], json!(["text"]));
+}
+
+#[test]
+fn call_tools() {
+    let mut server = initialized();
+
+    let response = server.request("tools/call", json!({ "name": "echo", "arguments": { "text": "你好" } }));
+    assert_eq!(response["result"]["content"][0]["text"], "你好");
+    assert!(response["result"]["isError"].is_null());
+
+    let response = server.request("tools/call", json!({ "name": "add", "arguments": { "a": 1.5, "b": 2 } }));
+    assert_eq!(response["result"]["content"][0]["text"], "3.5");
+
+    // 工具执行失败通过 isError 返回，未知工具返回 JSON-RPC 错误
+    let response = server.request("tools/call", json!({ "name": "fail", "arguments": {} }));
+    assert_eq!(response["result"]["isError"], true);
+
+    let response = server.request("tools/call", json!({ "name": "missing", "arguments": {} }));
+    assert_eq!(response["error"]["code"], -32602);
+}